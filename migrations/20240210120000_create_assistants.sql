@@ -0,0 +1,63 @@
+-- Add migration script here
+create table if not exists assistants (
+  id uuid default gen_random_uuid() primary key,
+  user_id uuid not null references users(id) on delete cascade,
+  model text not null,
+  name text,
+  description text,
+  instructions text,
+  tools jsonb not null default '[]',
+  file_ids text[] not null default '{}',
+  metadata jsonb,
+  created_at timestamptz not null default now(),
+  updated_at timestamptz not null default now()
+);
+
+create table if not exists threads (
+  id uuid default gen_random_uuid() primary key,
+  user_id uuid not null references users(id) on delete cascade,
+  metadata jsonb,
+  created_at timestamptz not null default now(),
+  updated_at timestamptz not null default now()
+);
+
+create table if not exists runs (
+  id uuid default gen_random_uuid() primary key,
+  user_id uuid not null references users(id) on delete cascade,
+  thread_id uuid not null references threads(id) on delete cascade,
+  assistant_id uuid references assistants(id) on delete set null,
+  status text not null default 'queued',
+  required_action jsonb,
+  last_error jsonb,
+  model text not null,
+  instructions text not null default '',
+  tools jsonb not null default '[]',
+  file_ids text[] not null default '{}',
+  metadata jsonb,
+  -- the times the app ran tools for the run and queued it again
+  tool_steps integer not null default 0,
+  expires_at timestamptz,
+  started_at timestamptz,
+  cancelled_at timestamptz,
+  failed_at timestamptz,
+  completed_at timestamptz,
+  created_at timestamptz not null default now(),
+  updated_at timestamptz not null default now()
+);
+
+create index if not exists assistants_user_id_idx on assistants(user_id, created_at);
+create index if not exists threads_user_id_idx on threads(user_id);
+create index if not exists runs_thread_id_idx on runs(thread_id, created_at);
+
+-- thread messages share the messages table with chats, a message belongs to exactly one of them
+alter table messages alter column chat_id drop not null;
+alter table messages add column if not exists thread_id uuid references threads(id) on delete cascade;
+alter table messages add column if not exists assistant_id uuid references assistants(id) on delete set null;
+alter table messages add column if not exists run_id uuid references runs(id) on delete set null;
+alter table messages add column if not exists file_ids text[] not null default '{}';
+alter table messages add column if not exists metadata jsonb;
+alter table messages add constraint messages_owner_check check (
+  (chat_id is not null and thread_id is null) or (chat_id is null and thread_id is not null)
+);
+
+create index if not exists messages_thread_id_idx on messages(thread_id, created_at);
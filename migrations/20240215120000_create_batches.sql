@@ -0,0 +1,45 @@
+-- Add migration script here
+create table if not exists batches (
+  id uuid default gen_random_uuid() primary key,
+  user_id uuid not null references users(id) on delete cascade,
+  endpoint text not null,
+  input_file_id text not null,
+  completion_window text not null,
+  status text not null default 'validating',
+  errors jsonb,
+  output_file_id text,
+  error_file_id text,
+  metadata jsonb,
+  -- the models the API key that created the batch is limited to, empty allows all models
+  models text[] not null default '{}',
+  expires_at timestamptz not null,
+  in_progress_at timestamptz,
+  finalizing_at timestamptz,
+  completed_at timestamptz,
+  failed_at timestamptz,
+  expired_at timestamptz,
+  cancelling_at timestamptz,
+  cancelled_at timestamptz,
+  created_at timestamptz not null default now(),
+  updated_at timestamptz not null default now()
+);
+
+create index if not exists batches_status_idx on batches(status);
+create index if not exists batches_user_id_idx on batches(user_id, created_at);
+
+-- one row per line of the input file, the worker picks up pending rows after a restart
+create table if not exists batch_requests (
+  id uuid default gen_random_uuid() primary key,
+  batch_id uuid not null references batches(id) on delete cascade,
+  line bigint not null,
+  custom_id text not null,
+  body jsonb not null,
+  status text not null default 'pending',
+  response jsonb,
+  error jsonb,
+  created_at timestamptz not null default now(),
+  updated_at timestamptz not null default now(),
+  unique (batch_id, custom_id)
+);
+
+create index if not exists batch_requests_batch_id_idx on batch_requests(batch_id, status, line);
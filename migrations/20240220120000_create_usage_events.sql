@@ -0,0 +1,67 @@
+-- no-transaction
+-- continuous aggregates can not be created inside a transaction
+create table if not exists usage_events (
+  time timestamptz not null default now(),
+  user_id uuid references users(id) on delete set null,
+  chat_id uuid references chats(id) on delete set null,
+  endpoint text not null,
+  model text not null,
+  prompt_tokens integer not null default 0,
+  completion_tokens integer not null default 0,
+  total_tokens integer not null default 0,
+  latency_ms integer not null,
+  status text not null,
+  error text
+);
+
+select create_hypertable('usage_events', 'time', if_not_exists => true);
+
+create index if not exists usage_events_user_id_idx on usage_events(user_id, time desc);
+create index if not exists usage_events_model_idx on usage_events(model, time desc);
+
+create materialized view if not exists usage_hourly
+-- real-time aggregation adds the raw events that are not materialized yet
+with (timescaledb.continuous, timescaledb.materialized_only = false) as
+select
+  time_bucket('1 hour', time) as bucket,
+  user_id,
+  model,
+  endpoint,
+  count(*) as requests,
+  count(*) filter (where status = 'error') as errors,
+  sum(prompt_tokens) as prompt_tokens,
+  sum(completion_tokens) as completion_tokens,
+  sum(total_tokens) as total_tokens,
+  avg(latency_ms) as avg_latency_ms
+from usage_events
+group by bucket, user_id, model, endpoint
+with no data;
+
+select add_continuous_aggregate_policy('usage_hourly',
+  start_offset => interval '3 hours',
+  end_offset => interval '1 hour',
+  schedule_interval => interval '30 minutes',
+  if_not_exists => true);
+
+create materialized view if not exists usage_daily
+with (timescaledb.continuous, timescaledb.materialized_only = false) as
+select
+  time_bucket('1 day', time) as bucket,
+  user_id,
+  model,
+  endpoint,
+  count(*) as requests,
+  count(*) filter (where status = 'error') as errors,
+  sum(prompt_tokens) as prompt_tokens,
+  sum(completion_tokens) as completion_tokens,
+  sum(total_tokens) as total_tokens,
+  avg(latency_ms) as avg_latency_ms
+from usage_events
+group by bucket, user_id, model, endpoint
+with no data;
+
+select add_continuous_aggregate_policy('usage_daily',
+  start_offset => interval '3 days',
+  end_offset => interval '1 hour',
+  schedule_interval => interval '1 hour',
+  if_not_exists => true);
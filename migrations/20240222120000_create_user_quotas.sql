@@ -0,0 +1,18 @@
+-- Add migration script here
+-- per user overrides of the default limits, a null column falls back to the default
+create table if not exists user_quotas (
+  user_id uuid primary key references users(id) on delete cascade,
+  daily_requests bigint,
+  monthly_requests bigint,
+  daily_tokens bigint,
+  monthly_tokens bigint,
+  requests_per_minute bigint,
+  created_at timestamptz not null default now(),
+  updated_at timestamptz not null default now()
+);
+
+create index if not exists prompts_user_id_date_idx on prompts(user_id, submission_date);
+-- the requests of a user are counted in a single row without a prompt per day
+create unique index if not exists prompts_user_id_date_count_idx on prompts(user_id, submission_date)
+  where prompt = '';
+create index if not exists goals_user_id_subsidized_idx on goals(user_id) where subsidized;
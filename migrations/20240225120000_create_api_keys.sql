@@ -0,0 +1,18 @@
+-- Add migration script here
+-- only the sha256 of a key is stored, the key itself is shown once when it is created
+create table if not exists api_keys (
+  id uuid default gen_random_uuid() primary key,
+  user_id uuid not null references users(id) on delete cascade,
+  name text not null,
+  prefix text not null,
+  key_hash text not null unique,
+  models text[] not null default '{}',
+  endpoints text[] not null default '{}',
+  expires_at timestamptz,
+  last_used_at timestamptz,
+  revoked_at timestamptz,
+  created_at timestamptz not null default now(),
+  updated_at timestamptz not null default now()
+);
+
+create index if not exists api_keys_user_id_idx on api_keys(user_id, created_at);
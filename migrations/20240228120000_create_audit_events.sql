@@ -0,0 +1,21 @@
+-- Add migration script here
+-- who sent what to which model, bodies are stored redacted and truncated
+create table if not exists audit_events (
+  id uuid primary key default gen_random_uuid(),
+  created_at timestamptz not null default now(),
+  -- 'proxy' for the OpenAI compatible API, 'agent' for calls made by the app itself
+  source text not null,
+  user_id uuid references users(id) on delete set null,
+  api_key_id uuid references api_keys(id) on delete set null,
+  method text not null,
+  endpoint text not null,
+  model text,
+  status integer not null,
+  latency_ms integer not null default 0,
+  request_body text,
+  response_body text,
+  truncated boolean not null default false
+);
+
+create index if not exists audit_events_created_at_idx on audit_events(created_at desc);
+create index if not exists audit_events_user_id_created_at_idx on audit_events(user_id, created_at desc);
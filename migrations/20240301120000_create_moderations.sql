@@ -0,0 +1,16 @@
+-- Add migration script here
+-- moderation results of user content, kept with the chat they belong to when there is one
+create table if not exists moderations (
+  id uuid default gen_random_uuid() primary key,
+  chat_id uuid references chats(id) on delete cascade,
+  user_id uuid references users(id) on delete set null,
+  -- 'goal' or 'chat'
+  source text not null,
+  -- 'allow', 'flag' or 'block'
+  action text not null,
+  categories text[] not null default '{}',
+  response jsonb not null,
+  created_at timestamptz not null default now()
+);
+
+create index if not exists moderations_chat_id_idx on moderations(chat_id, created_at);
@@ -0,0 +1,16 @@
+-- Add migration script here
+-- chats opt in to having personal data replaced before prompts are sent to the provider
+alter table chats add column if not exists redact_pii boolean not null default false;
+
+-- what was redacted, the original values are never stored
+create table if not exists pii_redactions (
+  id uuid default gen_random_uuid() primary key,
+  chat_id uuid references chats(id) on delete cascade,
+  user_id uuid references users(id) on delete set null,
+  endpoint text not null,
+  kind text not null,
+  placeholder text not null,
+  created_at timestamptz not null default now()
+);
+
+create index if not exists pii_redactions_chat_id_idx on pii_redactions(chat_id, created_at);
@@ -0,0 +1,7 @@
+-- the estimated cost in USD of each call, from the pricing table at the time of the call
+alter table usage_events add column if not exists cost double precision not null default 0;
+
+create index if not exists usage_events_chat_id_idx on usage_events(chat_id, time desc);
+
+-- a goal stops making calls once it spent its budget, null means no budget
+alter table goals add column if not exists budget_usd double precision;
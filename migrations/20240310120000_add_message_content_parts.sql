@@ -0,0 +1,2 @@
+-- user messages with images keep all their parts, `content` holds the text for search and display
+alter table messages add column if not exists content_parts jsonb;
@@ -0,0 +1,6 @@
+create table if not exists user_settings (
+  user_id uuid primary key references users(id) on delete cascade,
+  voice text not null default 'alloy',
+  speech_speed real not null default 1.0,
+  updated_at timestamptz not null default now()
+);
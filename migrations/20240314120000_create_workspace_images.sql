@@ -0,0 +1,13 @@
+-- images generated into a chat workspace, the files live in the upload store
+create table if not exists workspace_images (
+  chat_id uuid not null references chats(id) on delete cascade,
+  file_name text not null,
+  user_id uuid references users(id) on delete set null,
+  prompt text not null,
+  revised_prompt text,
+  model text not null,
+  size text not null,
+  source text,
+  created_at timestamptz not null default now(),
+  primary key (chat_id, file_name)
+);
@@ -0,0 +1,30 @@
+-- fine-tuning jobs created through the proxy, refreshed from the provider until they finish
+create table if not exists fine_tuning_jobs (
+  id text primary key,
+  user_id uuid references users(id) on delete set null,
+  model text not null,
+  training_file text not null,
+  validation_file text,
+  status text not null,
+  fine_tuned_model text,
+  error text,
+  trained_tokens bigint,
+  created_at timestamptz not null default now(),
+  finished_at timestamptz,
+  -- set once the fine-tuned model is deleted, it is no longer listed in the catalog
+  model_deleted_at timestamptz,
+  updated_at timestamptz not null default now()
+);
+
+create index if not exists fine_tuning_jobs_user_id_idx on fine_tuning_jobs(user_id, created_at);
+create index if not exists fine_tuning_jobs_status_idx on fine_tuning_jobs(status);
+
+create table if not exists fine_tuning_events (
+  id text primary key,
+  job_id text not null references fine_tuning_jobs(id) on delete cascade,
+  level text not null,
+  message text not null,
+  created_at timestamptz not null
+);
+
+create index if not exists fine_tuning_events_job_id_idx on fine_tuning_events(job_id, created_at);
@@ -0,0 +1,11 @@
+-- files of the Files API when it is served locally, the contents live in the file store directory
+create table if not exists files (
+  id text primary key,
+  user_id uuid references users(id) on delete cascade,
+  filename text not null,
+  purpose text not null,
+  bytes bigint not null,
+  created_at timestamptz not null default now()
+);
+
+create index if not exists files_user_id_idx on files(user_id, purpose, created_at);
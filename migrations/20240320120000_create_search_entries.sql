@@ -0,0 +1,25 @@
+-- the searchable text of chats, one row per title, goal, log entry and message. Rows are synced
+-- from their sources in the background and embedded with the model in `embedding_model`, chats
+-- that redact personal data are only searched by their words
+create extension if not exists vector;
+
+create table if not exists search_entries (
+  id uuid default gen_random_uuid() primary key,
+  user_id uuid not null references users(id) on delete cascade,
+  chat_id uuid not null references chats(id) on delete cascade,
+  kind text not null,
+  -- the chat, goal, log or message the text is from
+  source_id uuid not null,
+  content text not null,
+  content_tsv tsvector generated always as (to_tsvector('english', content)) stored,
+  embedding vector,
+  embedding_model text,
+  created_at timestamptz not null,
+  indexed_at timestamptz not null default now(),
+  unique (kind, source_id)
+);
+
+create index if not exists search_entries_user_id_idx on search_entries(user_id);
+create index if not exists search_entries_content_tsv_idx on search_entries using gin(content_tsv);
+create index if not exists search_entries_pending_idx on search_entries(indexed_at)
+  where embedding is null;
@@ -0,0 +1,25 @@
+-- the text of workspace files in chunks of lines, retrieved for the prompts of their chat. Files
+-- are chunked again when they change on disk, chats that redact personal data keep their chunks
+-- without embeddings and are only searched by their words
+create table if not exists workspace_chunks (
+  id uuid default gen_random_uuid() primary key,
+  chat_id uuid not null references chats(id) on delete cascade,
+  file_name text not null,
+  -- when the file was modified as it was chunked
+  file_modified_at timestamptz not null,
+  chunk_index int not null,
+  start_line int not null,
+  end_line int not null,
+  content text not null,
+  content_tsv tsvector generated always as (to_tsvector('english', content)) stored,
+  -- the dimension of text-embedding-3-small and text-embedding-ada-002, which the HNSW index
+  -- needs to be fixed
+  embedding vector(1536),
+  embedding_model text,
+  created_at timestamptz not null default now(),
+  unique (chat_id, file_name, chunk_index)
+);
+
+create index if not exists workspace_chunks_content_tsv_idx on workspace_chunks using gin(content_tsv);
+create index if not exists workspace_chunks_embedding_idx on workspace_chunks
+  using hnsw (embedding vector_cosine_ops);
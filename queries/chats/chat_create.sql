@@ -5,6 +5,7 @@ INSERT INTO chats(id, user_id)
     *)
   SELECT
     inserted.*,
+    0::float8 AS "cost!",
     users.email AS user_email
   FROM
     inserted
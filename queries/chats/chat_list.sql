@@ -1,6 +1,14 @@
 SELECT
   chats.id,
   title,
+  chats.redact_pii,
+  (
+    SELECT
+      COALESCE(sum(cost), 0)
+    FROM
+      usage_events
+    WHERE
+      usage_events.chat_id = chats.id)::float8 AS "cost!",
   chats.user_id,
   users.email AS user_email,
   chats.created_at,
@@ -10,6 +10,13 @@ WITH updated AS (
 )
 SELECT
   updated.*,
+  (
+    SELECT
+      COALESCE(sum(cost), 0)
+    FROM
+      usage_events
+    WHERE
+      usage_events.chat_id = updated.id)::float8 AS "cost!",
   users.email AS user_email
 FROM
   updated
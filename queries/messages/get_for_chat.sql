@@ -1,8 +1,9 @@
 SELECT
   m.id,
-  m.chat_id,
+  m.chat_id AS "chat_id!",
   c.user_id,
   m.content,
+  m.content_parts,
   m.name,
   m.tool_calls,
   m.temporary,
@@ -43,8 +43,15 @@ cfg_if! {
     }
 
     pub async fn get_text_file(chat_id: String, file_name: String) -> Option<String> {
-      let uri = format!("/api/v1/workspace/{}/files/{}", chat_id, file_name);
+      get_text(format!("/api/v1/workspace/{}/files/{}", chat_id, file_name)).await
+    }
 
+    /// The text of a workspace file, as markdown for documents like PDFs.
+    pub async fn get_extracted_text(chat_id: String, file_name: String) -> Option<String> {
+      get_text(format!("/api/v1/workspace/{}/files/{}/text", chat_id, file_name)).await
+    }
+
+    async fn get_text(uri: String) -> Option<String> {
       let abort_controller = web_sys::AbortController::new().ok();
       let abort_signal = abort_controller.as_ref().map(|a| a.signal());
 
@@ -55,11 +62,15 @@ cfg_if! {
         }
       });
 
-      let file = gloo_net::http::Request::get(&uri)
+      let response = gloo_net::http::Request::get(&uri)
         .abort_signal(abort_signal.as_ref())
         .send()
         .await
-        .ok()?
+        .ok()?;
+      if !response.ok() {
+        return None;
+      }
+      let file = response
         .text()
         .await
         .ok()?;
@@ -67,6 +78,124 @@ cfg_if! {
       Some(file)
     }
 
+    /// A recording from the microphone, running until it is stopped.
+    pub struct Recorder {
+      recorder: web_sys::MediaRecorder,
+      stream: web_sys::MediaStream,
+      chunks: std::rc::Rc<std::cell::RefCell<Vec<web_sys::Blob>>>,
+      _on_data: wasm_bindgen::closure::Closure<dyn FnMut(web_sys::BlobEvent)>,
+    }
+
+    impl Recorder {
+      /// Asks for the microphone and starts recording, `None` when the user declines.
+      pub async fn start() -> Option<Self> {
+        use wasm_bindgen::{closure::Closure, JsCast};
+
+        let mut constraints = web_sys::MediaStreamConstraints::new();
+        constraints.audio(&true.into());
+        let stream = web_sys::window()?
+          .navigator()
+          .media_devices()
+          .ok()?
+          .get_user_media_with_constraints(&constraints)
+          .ok()?;
+        let stream: web_sys::MediaStream = wasm_bindgen_futures::JsFuture::from(stream)
+          .await
+          .ok()?
+          .dyn_into()
+          .ok()?;
+
+        let recorder = web_sys::MediaRecorder::new_with_media_stream(&stream).ok()?;
+        let chunks = std::rc::Rc::new(std::cell::RefCell::new(vec![]));
+        let on_data = {
+          let chunks = chunks.clone();
+          Closure::<dyn FnMut(web_sys::BlobEvent)>::new(move |event: web_sys::BlobEvent| {
+            if let Some(data) = event.data() {
+              chunks.borrow_mut().push(data);
+            }
+          })
+        };
+        recorder.set_ondataavailable(Some(on_data.as_ref().unchecked_ref()));
+        recorder.start().ok()?;
+        Some(Self {
+          recorder,
+          stream,
+          chunks,
+          _on_data: on_data,
+        })
+      }
+
+      /// Stops the recording and releases the microphone, `None` when nothing was recorded.
+      pub async fn stop(self) -> Option<web_sys::Blob> {
+        use wasm_bindgen::{closure::Closure, JsCast};
+
+        let stopped = js_sys::Promise::new(&mut |resolve, _| {
+          let on_stop = Closure::once_into_js(move || {
+            let _ = resolve.call0(&wasm_bindgen::JsValue::NULL);
+          });
+          self.recorder.set_onstop(Some(on_stop.unchecked_ref()));
+        });
+        self.recorder.stop().ok()?;
+        wasm_bindgen_futures::JsFuture::from(stopped).await.ok()?;
+        for track in self.stream.get_tracks().iter() {
+          if let Ok(track) = track.dyn_into::<web_sys::MediaStreamTrack>() {
+            track.stop();
+          }
+        }
+
+        let chunks = self.chunks.borrow();
+        if chunks.is_empty() {
+          return None;
+        }
+        let parts: js_sys::Array = chunks.iter().collect();
+        let mut options = web_sys::BlobPropertyBag::new();
+        options.type_(&self.recorder.mime_type());
+        web_sys::Blob::new_with_blob_sequence_and_options(&parts, &options).ok()
+      }
+    }
+
+    /// The text spoken in a recording.
+    pub async fn transcribe(recording: web_sys::Blob) -> Option<String> {
+      #[derive(serde::Deserialize)]
+      struct Transcription {
+        text: String,
+      }
+
+      let data = FormData::new().ok()?;
+      data
+        .append_with_blob_and_filename("file", &recording, "speech.webm")
+        .ok()?;
+      let transcription: Transcription = gloo_net::http::Request::post("/api/v1/voice/transcriptions")
+        .body(data)
+        .unwrap()
+        .send()
+        .await
+        .ok()?
+        .json()
+        .await
+        .ok()?;
+      Some(transcription.text)
+    }
+
+    /// An object URL of `text` read aloud with the voice of the user, to be revoked after playing.
+    pub async fn speak(text: String) -> Option<String> {
+      let response = gloo_net::http::Request::post("/api/v1/voice/speech")
+        .json(&serde_json::json!({ "input": text }))
+        .ok()?
+        .send()
+        .await
+        .ok()?;
+      if !response.ok() {
+        return None;
+      }
+      let bytes = response.binary().await.ok()?;
+      let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes.as_slice()));
+      let mut options = web_sys::BlobPropertyBag::new();
+      options.type_("audio/mpeg");
+      let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options).ok()?;
+      web_sys::Url::create_object_url_with_blob(&blob).ok()
+    }
+
 
   } else if #[cfg(feature = "ssr")]{
     pub async fn upload_file<T>(_chat_id: uuid::Uuid, _files: Vec<web_sys::File>) -> Option<T>
@@ -83,6 +212,32 @@ cfg_if! {
       Some(text)
 
     }
+
+    pub async fn get_extracted_text(chat_id: String, file_name: String) -> Option<String> {
+      let uri = format!("/api/v1/workspace/{}/files/{}/text", chat_id, file_name);
+      let res = reqwest::get(uri).await.ok()?.error_for_status().ok()?;
+      res.text().await.ok()
+    }
+
+    pub struct Recorder;
+
+    impl Recorder {
+      pub async fn start() -> Option<Self> {
+        None
+      }
+
+      pub async fn stop(self) -> Option<web_sys::Blob> {
+        None
+      }
+    }
+
+    pub async fn transcribe(_recording: web_sys::Blob) -> Option<String> {
+      None
+    }
+
+    pub async fn speak(_text: String) -> Option<String> {
+      None
+    }
   } else {
     pub async fn upload_file<T>(_chat_id: uuid::Uuid, _files: Vec<web_sys::File>) -> Option<T>
     where
@@ -94,6 +249,30 @@ cfg_if! {
     pub async fn get_text_file(_chat_id: String, _file_name: String) -> Option<String> {
       None
     }
+
+    pub async fn get_extracted_text(_chat_id: String, _file_name: String) -> Option<String> {
+      None
+    }
+
+    pub struct Recorder;
+
+    impl Recorder {
+      pub async fn start() -> Option<Self> {
+        None
+      }
+
+      pub async fn stop(self) -> Option<web_sys::Blob> {
+        None
+      }
+    }
+
+    pub async fn transcribe(_recording: web_sys::Blob) -> Option<String> {
+      None
+    }
+
+    pub async fn speak(_text: String) -> Option<String> {
+      None
+    }
   }
 }
 // #[cfg(feature = "ssr")]
@@ -216,6 +216,9 @@ fn MainContent(set_chat_id: WriteSignal<Option<Uuid>>) -> impl IntoView {
       >
 
         <Route path="about" view=AboutPage/>
+        <Route path="settings" view=SettingsPage/>
+        <Route path="fine-tuning" view=FineTuningPage/>
+        <Route path="settings/api-keys" view=ApiKeysPage/>
         <Route path="" view=move || view! { <ChatPage set_chat_id/> }/>
         <Route path="chat/:id" view=move || view! { <ChatPage set_chat_id/> }/>
       </Route>
@@ -16,6 +16,7 @@ if #[cfg(feature="ssr")] {
   use async_openai::config::OpenAIConfig;
   use std::path::{PathBuf};
   use std::fmt::Formatter;
+  use crate::server::{audit::AuditPolicy, catalog::ModelCatalog, file_store::FileStore, moderation::ModerationPolicy, pii::PiiPolicy, pricing::Pricing, quota::Quotas, retrieval::RetrievalPolicy, search::SearchPolicy};
 
   /// This takes advantage of Axum's SubStates feature by deriving FromRef. This is the only way to have more than one
   /// item in Axum's State. Leptos requires you to have leptosOptions in your State struct for the leptos route handlers
@@ -29,6 +30,15 @@ if #[cfg(feature="ssr")] {
     secrets: Arc<RwLock<ttl_cache::TtlCache<String, PkceCodeVerifier>>>,
     openai_client: Arc<Client<OpenAIConfig>>,
     pub upload_store: PathBuf,
+    quotas: Arc<Quotas>,
+    audit: Arc<AuditPolicy>,
+    moderation: Arc<ModerationPolicy>,
+    pii: Arc<PiiPolicy>,
+    pricing: Arc<Pricing>,
+    catalog: Arc<ModelCatalog>,
+    file_store: Arc<FileStore>,
+    search: Arc<SearchPolicy>,
+    retrieval: Arc<RetrievalPolicy>,
   }
 
   impl std::fmt::Debug for AppState {
@@ -40,6 +50,15 @@ if #[cfg(feature="ssr")] {
         .field("auth_client", &self.auth_client)
         .field("openai_client", &self.openai_client)
         .field("upload_store", &self.upload_store)
+        .field("quotas", &self.quotas)
+        .field("audit", &self.audit)
+        .field("moderation", &self.moderation)
+        .field("pii", &self.pii)
+        .field("pricing", &self.pricing)
+        .field("catalog", &self.catalog)
+        .field("file_store", &self.file_store)
+        .field("search", &self.search)
+        .field("retrieval", &self.retrieval)
         .finish()
     }
   }
@@ -56,8 +75,11 @@ if #[cfg(feature="ssr")] {
       if let Ok(api_base) = dotenvy::var("OPENAI_API_BASE") {
         openai_config = openai_config.with_api_base(api_base);
       }
-      let upload_store = dotenvy::var("MIKO_FILE_STORAGE").as_deref().unwrap_or("uploads").into();
+      let upload_store: PathBuf = dotenvy::var("MIKO_FILE_STORAGE").as_deref().unwrap_or("uploads").into();
       tokio::fs::create_dir_all(&upload_store).await?;
+      let file_store = Arc::new(FileStore::from_env(&upload_store)?);
+      let catalog = Arc::new(ModelCatalog::from_env()?);
+      let retrieval = Arc::new(RetrievalPolicy::from_env(&catalog)?);
 
       Ok(Self {
         leptos_options,
@@ -66,6 +88,15 @@ if #[cfg(feature="ssr")] {
         openai_client: Arc::new(Client::with_config(openai_config)),
         secrets: Arc::new(RwLock::new(ttl_cache::TtlCache::new(100_000))),
         upload_store,
+        quotas: Arc::new(Quotas::from_env()),
+        audit: Arc::new(AuditPolicy::from_env()),
+        moderation: Arc::new(ModerationPolicy::from_env()?),
+        pii: Arc::new(PiiPolicy::from_env()?),
+        pricing: Arc::new(Pricing::from_env()?),
+        catalog,
+        file_store,
+        search: Arc::new(SearchPolicy::from_env()),
+        retrieval,
         auth_client: BasicClient::new(
           ClientId::new(client_id.into()),
           None,
@@ -97,6 +128,42 @@ if #[cfg(feature="ssr")] {
       self.openai_client.clone()
     }
 
+    pub(crate) fn quotas(&self) -> Arc<Quotas> {
+      self.quotas.clone()
+    }
+
+    pub(crate) fn audit(&self) -> Arc<AuditPolicy> {
+      self.audit.clone()
+    }
+
+    pub(crate) fn moderation(&self) -> Arc<ModerationPolicy> {
+      self.moderation.clone()
+    }
+
+    pub(crate) fn pii(&self) -> Arc<PiiPolicy> {
+      self.pii.clone()
+    }
+
+    pub(crate) fn pricing(&self) -> Arc<Pricing> {
+      self.pricing.clone()
+    }
+
+    pub(crate) fn catalog(&self) -> Arc<ModelCatalog> {
+      self.catalog.clone()
+    }
+
+    pub(crate) fn file_store(&self) -> Arc<FileStore> {
+      self.file_store.clone()
+    }
+
+    pub(crate) fn search(&self) -> Arc<SearchPolicy> {
+      self.search.clone()
+    }
+
+    pub(crate) fn retrieval(&self) -> Arc<RetrievalPolicy> {
+      self.retrieval.clone()
+    }
+
     pub(crate) async fn remember_verifier(&self, token: &CsrfToken, verifier: PkceCodeVerifier) {
       self.secrets.write().await.insert(
         token.secret().to_string(),
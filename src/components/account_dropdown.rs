@@ -1,5 +1,5 @@
 use leptos::*;
-use phosphor_leptos::{GearSix, IconWeight, SignOut, UserCirclePlus};
+use phosphor_leptos::{Brain, GearSix, IconWeight, SignOut, UserCirclePlus};
 
 use crate::models::CurrentUser;
 
@@ -47,8 +47,16 @@ fn UserDropdown(user: ReadSignal<CurrentUser>, show_logout: RwSignal<bool>) -> i
       </div>
       <ul tabindex="0" class="dropdown-content z-[1] menu p-2 shadow bg-base-300 rounded-box w-52">
         <li>
-          <GearSix size="16" weight=IconWeight::Bold/>
-          <div class="leading-none">"Account Settings"</div>
+          <a href="/settings/api-keys">
+            <GearSix size="16" weight=IconWeight::Bold/>
+            <div class="leading-none">"API Keys"</div>
+          </a>
+        </li>
+        <li>
+          <a href="/fine-tuning">
+            <Brain size="16" weight=IconWeight::Bold/>
+            <div class="leading-none">"Fine-tuning"</div>
+          </a>
         </li>
         <li on:click=move |_| { show_logout.set(true) }>
           <SignOut size="16" weight=IconWeight::Bold/>
@@ -4,9 +4,13 @@ use phosphor_leptos::{ArrowRight, IconWeight, UploadSimple};
 use uuid::Uuid;
 
 use crate::{
-  components::{chat_logs::ChatLogs, example_prompts::ExamplePrompts, logo::Logo},
+  components::{
+    chat_logs::ChatLogs, example_prompts::ExamplePrompts, logo::Logo, model_picker::ModelPicker,
+    voice::PushToTalk,
+  },
   models::ChatLog,
-  ChatResourceContext, OnGoalSubmit,
+  routes::chats::{generate_title, UpdateChatTitle},
+  ChatResourceContext, ChatState, OnGoalSubmit,
 };
 
 #[component]
@@ -20,8 +24,10 @@ pub fn Chat(
     resource: chat_resource,
     create_chat: create_action,
     submit_goal: on_goal_submit,
+    update_title,
     ..
   } = expect_context::<ChatResourceContext>();
+  let ChatState { model, .. } = expect_context::<ChatState>();
   let should_show_example_prompts = move || id().is_none();
 
   let container_class = move || {
@@ -52,11 +58,13 @@ pub fn Chat(
   };
 
   let (chat_name, set_chat_name) = create_signal("".to_string());
+  let (is_untitled, set_is_untitled) = create_signal(false);
   let (chat_logs, set_chat_logs) = create_signal(Vec::new());
   create_effect(move |_| {
     if let Some(id) = id() {
       if let Some(Ok(chat)) = chat_resource.get() {
         let active_chat = chat.iter().find(|chat| chat.id == id).map(|chat| {
+          set_is_untitled.set(chat.title.is_none());
           let name = chat
             .title
             .clone()
@@ -81,6 +89,18 @@ pub fn Chat(
       return;
     }
     message.update(|msg| msg.clear());
+    // untitled chats are named after their goal, by the picked model
+    if let Some(chat_id) = id.get_untracked().filter(|_| is_untitled.get_untracked()) {
+      set_is_untitled.set(false);
+      let prompt = prompt.clone();
+      let model = model.get_untracked();
+      spawn_local(async move {
+        match generate_title(chat_id, prompt, model).await {
+          Ok(title) => update_title.dispatch(UpdateChatTitle { id: chat_id, title }),
+          Err(e) => log!("failed to generate a title: {e}"),
+        }
+      });
+    }
     on_goal_submit.dispatch(prompt);
   };
 
@@ -107,13 +127,14 @@ pub fn Chat(
         }
       >
 
-        <ChatLogs chat_name chat_logs is_running/>
+        <ChatLogs chat_id=id chat_name chat_logs is_running/>
       </Show>
       <div class=container_class>
         <Show when=should_show_example_prompts>
           <ExamplePrompts on_click=handle_goal_submit/>
         </Show>
         <div class=form_class>
+          <ModelPicker selected=model/>
           <ActionForm action=create_action>
             <TextInput
               name="content"
@@ -141,6 +162,15 @@ pub fn TextInput<KDF: Fn(web_sys::KeyboardEvent) + 'static>(
   on_keydown: KDF,
 ) -> impl IntoView {
   let can_send = move || !value.get().is_empty();
+  // the transcript is appended so speaking can continue what was typed
+  let append_transcript = move |text: String| {
+    value.update(|value| {
+      if !value.is_empty() && !value.ends_with(' ') {
+        value.push(' ');
+      }
+      value.push_str(text.trim());
+    })
+  };
 
   view! {
     <div class="form-control w-full space-y-1">
@@ -161,6 +191,7 @@ pub fn TextInput<KDF: Fn(web_sys::KeyboardEvent) + 'static>(
           class:opacity-50=disabled
           class:cursor-text=move || !disabled()
         />
+        <PushToTalk on_text=append_transcript disabled=disabled/>
         <ChatSendButton is_running enabled=Signal::derive(can_send) />
       </div>
     </div>
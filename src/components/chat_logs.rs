@@ -1,15 +1,19 @@
 use leptos::{html::Div, *};
+use leptos_router::use_query_map;
 use leptos_use::{use_scroll_with_options, ScrollBehavior, UseScrollOptions, UseScrollReturn};
 use phosphor_leptos::ArrowSquareRight;
+use uuid::Uuid;
+use wasm_bindgen::JsCast as _;
 
 use crate::{
-  components::{logo::Logo, mdown::Markdown},
-  models::{ChatLog, CurrentUser},
-  ShowChatDetailsModal, UiMessage,
+  components::{logo::Logo, mdown::Markdown, voice::ReadAloud},
+  models::{resolve_workspace_links, workspace_link_target, ChatLog, CurrentUser, UploadedFile},
+  ShowChatDetailsModal, ShowFileModal, UiMessage,
 };
 
 #[component]
 pub fn ChatLogs(
+  #[prop(into)] chat_id: Signal<Option<Uuid>>,
   chat_name: ReadSignal<String>,
   chat_logs: ReadSignal<Vec<ChatLog>>,
   is_running: ReadSignal<bool>,
@@ -17,6 +21,21 @@ pub fn ChatLogs(
   let list_container_ref = create_node_ref::<Div>();
   let messages = create_memo(move |_| chat_logs_for_ui(chat_logs()));
   let enumerated_messages = move || messages().into_iter().enumerate();
+  // a search result links to the message written at `at`, in milliseconds since the epoch
+  let query = use_query_map();
+  let highlighted = create_memo(move |_| {
+    let at = query.with(|q| q.get("at").and_then(|at| at.parse::<i64>().ok()))?;
+    let logs = chat_logs()
+      .into_iter()
+      .filter(|log| log.created_at.timestamp_millis() <= at)
+      .collect::<Vec<_>>();
+    chat_logs_for_ui(logs).len().checked_sub(1)
+  });
+  // workspace images embedded in messages are shown as thumbnails
+  let with_images = move |text: String| match chat_id() {
+    Some(chat_id) => resolve_workspace_links(&text, chat_id),
+    None => text,
+  };
   let user = use_context::<CurrentUser>();
   let has_image_and_email = if let Some(user) = user.as_ref() {
     user.picture().is_some() && user.email().is_some()
@@ -26,6 +45,30 @@ pub fn ChatLogs(
 
   let ShowChatDetailsModal(show_details_modal, chat_details_message, chat_status) =
     expect_context();
+  let ShowFileModal(show_file_modal, set_selected_file) = expect_context();
+
+  // citations of workspace files open the file instead of leaving the chat
+  let open_workspace_link = move |ev: ev::MouseEvent| {
+    let Some(chat_id) = chat_id.get_untracked() else {
+      return;
+    };
+    let Some(href) = ev
+      .target()
+      .and_then(|target| target.dyn_into::<web_sys::Element>().ok())
+      .and_then(|element| element.closest("a").ok().flatten())
+      .and_then(|link| link.get_attribute("href"))
+    else {
+      return;
+    };
+    let Some(file_name) = workspace_link_target(&href, chat_id)
+      .and_then(|file_name| js_sys::decode_uri_component(file_name).ok())
+    else {
+      return;
+    };
+    ev.prevent_default();
+    set_selected_file(Some(UploadedFile::in_workspace(chat_id, file_name.into())));
+    show_file_modal.set(true);
+  };
 
   let (log_details, set_log_details) = create_signal(ChatLogDetails::default());
 
@@ -89,6 +132,17 @@ pub fn ChatLogs(
     }
   });
 
+  create_effect(move |_| {
+    if let Some(idx) = highlighted() {
+      // waits for the messages to be rendered before scrolling to the one that matched
+      request_animation_frame(move || {
+        if let Some(element) = document().get_element_by_id(&message_element_id(idx)) {
+          element.scroll_into_view();
+        }
+      });
+    }
+  });
+
   use leptos::logging::log;
   create_effect(move |_| {
     log!("is_at_bottom: {}", is_at_bottom());
@@ -105,6 +159,7 @@ pub fn ChatLogs(
       on:scroll=move |_| {
           handle_scroll();
       }
+      on:click=open_workspace_link
     >
 
       <For
@@ -115,8 +170,15 @@ pub fn ChatLogs(
             let user_name = user.as_ref().and_then(|user| user.name_opt()).unwrap_or_else(|| "User".to_string());
             let picture = user.as_ref().and_then(|user| user.picture());
             let has_miko_message = message.miko_message.as_ref().is_some();
+            let spoken_message = store_value(message.miko_message.clone().unwrap_or_default());
+            let is_highlighted = move || highlighted() == Some(idx);
             view! {
-              <div class="m-auto w-full max-w-[56rem] self-center">
+              <div
+                id=message_element_id(idx)
+                class="m-auto w-full max-w-[56rem] self-center rounded-lg transition-shadow duration-300"
+                class:ring-2=is_highlighted
+                class:ring-accent=is_highlighted
+              >
                 <div class="group relative flex w-full animate-slide-down items-start space-x-3 rounded-lg p-2 pb-10 opacity-0 transition-colors duration-300">
                   <Show
                     when=move || has_image_and_email
@@ -128,8 +190,8 @@ pub fn ChatLogs(
                     <div class="flex items-center justify-between">
                       <span class="font-medium">{user_name}</span>
                     </div>
-                    <div class="prose prose-invert w-full max-w-none">
-                      <Markdown content=message.user_message/>
+                    <div class="prose prose-invert w-full max-w-none prose-img:inline-block prose-img:max-h-48 prose-img:rounded-lg prose-img:my-2">
+                      <Markdown content=with_images(message.user_message.clone())/>
                     </div>
                   </div>
                 </div>
@@ -140,25 +202,30 @@ pub fn ChatLogs(
                       <div class="flex items-center justify-between">
                         <span class="font-medium">{"Miko"}</span>
                         <Show when=move || { !is_running() }>
-                          <button
-                            class="group/button flex items-center space-x-2 text-cyan-500 hover:text-cyan-400"
-                            on:click=move |_| {
-                                set_log_details(ChatLogDetails {
-                                    index: idx,
-                                    open: true,
-                                });
-                            }
-                          >
+                          <div class="flex items-center space-x-4">
+                            <Show when=move || { has_miko_message }>
+                              <ReadAloud text=spoken_message.get_value()/>
+                            </Show>
+                            <button
+                              class="group/button flex items-center space-x-2 text-cyan-500 hover:text-cyan-400"
+                              on:click=move |_| {
+                                  set_log_details(ChatLogDetails {
+                                      index: idx,
+                                      open: true,
+                                  });
+                              }
+                            >
 
-                            <div class="font-regular text-xs group-hover/button:underline">{"View Details"}</div>
-                            <ArrowSquareRight size="24"/>
-                          </button>
+                              <div class="font-regular text-xs group-hover/button:underline">{"View Details"}</div>
+                              <ArrowSquareRight size="24"/>
+                            </button>
+                          </div>
                         </Show>
                       </div>
                       <Show when=move || { has_miko_message }>
                         <Markdown
-                          content=message.miko_message.clone().unwrap_or_default()
-                          class="prose prose-invert w-full max-w-none"
+                          content=with_images(message.miko_message.clone().unwrap_or_default())
+                          class="prose prose-invert w-full max-w-none prose-img:max-h-48 prose-img:rounded-lg"
                         />
                       </Show>
                       <Show when=move || { !has_miko_message && is_running() && messages().len() - 1 == idx }>
@@ -204,6 +271,10 @@ pub(super) struct ChatLogDetails {
   pub open: bool,
 }
 
+fn message_element_id(idx: usize) -> String {
+  format!("chat-message-{idx}")
+}
+
 fn chat_logs_for_ui(mut value: Vec<ChatLog>) -> Vec<UiMessage> {
   value.sort_by(|a, b| a.created_at.cmp(&b.created_at));
 
@@ -9,5 +9,7 @@ pub mod layout;
 mod logo;
 mod mdown;
 pub mod modals;
+mod model_picker;
 pub mod sidebar;
+mod voice;
 mod workspace;
@@ -1,8 +1,11 @@
 use leptos::*;
+use leptos_router::ActionForm;
 
 use crate::{
   components::{mdown::Markdown, modals::Modal},
-  UiMessage,
+  models::pricing::format_usd,
+  routes::chats::{get_goal_budget, SetGoalBudget},
+  ChatResourceContext, ChatState, UiMessage,
 };
 
 #[component]
@@ -12,8 +15,86 @@ pub fn ChatDetailsModal(
   status: RwSignal<String>,
 ) -> impl IntoView {
   let details = move || message().details.into_iter().enumerate();
+  let ChatState { active_chat, .. } = expect_context();
+  let ChatResourceContext {
+    resource: chats, ..
+  } = expect_context();
+  let set_budget = create_server_action::<SetGoalBudget>();
+  let budget = create_resource(
+    move || (active_chat(), set_budget.version().get()),
+    |(id, _)| async move {
+      match id {
+        Some(id) => get_goal_budget(id).await.ok().flatten(),
+        None => None,
+      }
+    },
+  );
+  let cost = move || {
+    chats
+      .get()
+      .and_then(Result::ok)
+      .and_then(|chats| {
+        chats
+          .into_iter()
+          .find(|chat| Some(chat.id) == active_chat())
+      })
+      .map(|chat| chat.cost)
+      .unwrap_or_default()
+  };
+  let spent = move || {
+    budget
+      .get()
+      .flatten()
+      .and_then(|budget| {
+        let limit = budget.budget_usd?;
+        Some(format!(
+          "Goal spent {} of {}",
+          format_usd(budget.spent_usd),
+          format_usd(limit)
+        ))
+      })
+      .unwrap_or_default()
+  };
+  let is_exceeded = move || {
+    budget
+      .get()
+      .flatten()
+      .is_some_and(|budget| budget.is_exceeded())
+  };
+
   view! {
     <Modal id="chatDetails" show_modal=show_modal>
+      <Show when=move || active_chat().is_some()>
+        <div class="flex items-center justify-between space-x-4 px-4 pb-2 text-sm">
+          <div>"Cost: " {move || format_usd(cost())}</div>
+          <div class:text-error=is_exceeded>{spent}</div>
+          <ActionForm action=set_budget class="flex items-center space-x-2">
+            <input
+              type="hidden"
+              name="id"
+              prop:value=move || active_chat().map(|id| id.to_string()).unwrap_or_default()
+            />
+            <input
+              type="text"
+              name="budget"
+              placeholder="No budget"
+              class="input input-sm input-bordered w-24"
+              prop:value=move || {
+                  budget
+                      .get()
+                      .flatten()
+                      .and_then(|budget| budget.budget_usd)
+                      .map(|budget| budget.to_string())
+                      .unwrap_or_default()
+              }
+            />
+
+            <button class="btn btn-sm" type="submit">
+              "Set budget"
+            </button>
+          </ActionForm>
+        </div>
+      </Show>
       <For
         each=details
         key=move |(_, (id, _))| { id.clone() }
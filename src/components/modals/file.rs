@@ -31,20 +31,37 @@ pub fn FileModal(
           let chat_id = file.workspace.clone();
 
           spawn_local(async move {
-            let content = api::get_text_file(chat_id, file_name).await.unwrap();
+            let content = api::get_text_file(chat_id, file_name)
+              .await
+              .unwrap_or_default();
             set_markdown_content.update(|v| *v = content);
           })
         }
         ("application", "application/pdf") => {
           set_file_type.update(|v| *v = "pdf".to_string());
         }
+        // documents show the markdown extracted from them
+        ("application", _) | ("text", "text/html") => {
+          set_file_type.update(|v| *v = "markdown".to_string());
+          let file_name = file.file_name.clone();
+          let chat_id = file.workspace.clone();
+
+          spawn_local(async move {
+            let content = api::get_extracted_text(chat_id, file_name)
+              .await
+              .unwrap_or_else(|| "This file has no text to show.".to_string());
+            set_markdown_content.update(|v| *v = content);
+          })
+        }
         ("text", _) => {
           set_file_type.update(|v| *v = prefix.to_string());
           let file_name = file.file_name.clone();
           let chat_id = file.workspace.clone();
 
           spawn_local(async move {
-            let content = api::get_text_file(chat_id, file_name).await.unwrap();
+            let content = api::get_text_file(chat_id, file_name)
+              .await
+              .unwrap_or_default();
             set_text_content.update(|v| *v = content);
           })
         }
@@ -98,7 +115,7 @@ pub fn FileModal(
               </object>
             </div>
           </Show>
-          <Show when=move || { file_type() == "image" }>
+          <Show when=move || { file_type() == "text" }>
             <div class="w-full h-full">
               <pre>{text_content()}</pre>
             </div>
@@ -0,0 +1,66 @@
+use leptos::*;
+
+use crate::{models::catalog::CatalogModel, routes::models::get_models};
+
+/// A short description of what a model can do, shown next to its name.
+fn describe(model: &CatalogModel) -> String {
+  let capabilities = &model.capabilities;
+  let mut details = vec![];
+  if let Some(context_window) = capabilities.context_window {
+    details.push(format!("{}k context", context_window / 1000));
+  }
+  if capabilities.vision {
+    details.push("vision".to_string());
+  }
+  if capabilities.tools {
+    details.push("tools".to_string());
+  }
+  if let Some(price) = &model.pricing {
+    details.push(format!("${}/1K in", price.input_per_1k));
+  }
+  if details.is_empty() {
+    model.id.clone()
+  } else {
+    format!("{} ({})", model.id, details.join(", "))
+  }
+}
+
+/// Selects one of the available chat models of the catalog.
+#[component]
+pub fn ModelPicker(selected: RwSignal<String>) -> impl IntoView {
+  let models = create_resource(|| (), |_| get_models());
+  let chat_models = move || {
+    models
+      .get()
+      .and_then(Result::ok)
+      .unwrap_or_default()
+      .into_iter()
+      .filter(|model| model.available && model.capabilities.chat)
+      .collect::<Vec<_>>()
+  };
+
+  view! {
+    <Transition fallback=|| ()>
+      <select
+        class="select select-sm select-bordered max-w-xs"
+        title="Model"
+        on:change=move |ev| selected.set(event_target_value(&ev))
+      >
+        <For
+          each=chat_models
+          key=|model| model.id.clone()
+          children=move |model| {
+              let id = model.id.clone();
+              let is_selected = move || selected() == id;
+              view! {
+                <option value=model.id.clone() selected=is_selected>
+                  {describe(&model)}
+                </option>
+              }
+          }
+        />
+
+      </select>
+    </Transition>
+  }
+}
@@ -1,15 +1,22 @@
 #[cfg(feature = "hydrate")] use gloo_events::EventListener;
 use leptos::{html::Input, logging::log, *};
 use leptos_router::*;
-use phosphor_leptos::{GithubLogo, IconWeight, NotePencil, PencilSimple, TrashSimple};
+use leptos_use::signal_debounced;
+use phosphor_leptos::{
+  GithubLogo, IconWeight, MagnifyingGlass, NotePencil, PencilSimple, Shield, ShieldCheck,
+  TrashSimple,
+};
 use uuid::Uuid;
 use wasm_bindgen::JsCast as _;
 use web_sys::{Event, Node, SubmitEvent};
 
 use crate::{
   components::{account_dropdown::AccountDropdown, logo::Logo, workspace::Workspace},
-  models::{ChatInfo, CurrentUser, EditChat},
-  routes::chats::UpdateChatTitle,
+  models::{pricing::format_usd, search::SearchHit, ChatInfo, CurrentUser, EditChat},
+  routes::{
+    chats::{SetChatRedaction, UpdateChatTitle},
+    search::search_chats,
+  },
   ChatDeleteAction, ChatResourceContext, ChatState, ChatUpdateTitleAction,
 };
 
@@ -39,6 +46,8 @@ pub fn Sidebar(
   let chats_loading = chats.loading();
 
   let is_authenticated = move || user().is_authenticated();
+  let search_query = create_rw_signal(String::new());
+  let is_searching = move || !search_query().trim().is_empty();
 
   create_effect(move |_| {
     let cid = chat_id();
@@ -84,7 +93,13 @@ pub fn Sidebar(
                     </Show>
                   </div>
 
-                  <ChatList edit_chat active_chat />
+                  <SearchBox query=search_query />
+                  <Show
+                    when=is_searching
+                    fallback=move || view! { <ChatList edit_chat active_chat /> }
+                  >
+                    <SearchResults query=search_query />
+                  </Show>
                 </div>
                 <Workspace chats chat_id />
             </Show>
@@ -139,6 +154,108 @@ fn ChatList(
   }
 }
 
+#[component]
+fn SearchBox(query: RwSignal<String>) -> impl IntoView {
+  view! {
+    <label class="input input-sm input-bordered flex items-center gap-2 mx-2 bg-neutral text-neutral-content">
+      <MagnifyingGlass size="16" weight=IconWeight::Bold />
+      <input
+        type="search"
+        class="grow"
+        placeholder="Search chats"
+        prop:value=query
+        on:input=move |ev| query.set(event_target_value(&ev))
+        on:keydown=move |ev| {
+            if ev.key() == "Escape" {
+                query.set(String::new());
+            }
+        }
+      />
+    </label>
+  }
+}
+
+#[component]
+fn SearchResults(query: RwSignal<String>) -> impl IntoView {
+  // searching embeds the query, so it waits until the user stops typing
+  let debounced = signal_debounced(query, 300.0);
+  let hits = create_resource(debounced, |query| async move {
+    if query.trim().is_empty() {
+      Ok(vec![])
+    } else {
+      search_chats(query).await
+    }
+  });
+
+  view! {
+    <div class="h-full max-h-[30vh] space-y-0.5 overflow-y-auto px-2 [scrollbar-gutter:stable]">
+      <Suspense fallback=move || {
+          view! { <div class="skeleton mx-2 h-[20vh] w-[calc(100%-1rem)]"></div> }
+      }>
+        {move || {
+            hits
+                .get()
+                .map(|hits| match hits {
+                    Ok(hits) if hits.is_empty() => {
+                        view! {
+                          <p class="p-2 text-center text-xs text-neutral-content">"No chats match"</p>
+                        }
+                            .into_view()
+                    }
+                    Ok(hits) => {
+                        hits
+                            .into_iter()
+                            .map(|hit| view! { <SearchResult hit query /> })
+                            .collect_view()
+                    }
+                    Err(e) => {
+                        view! { <p class="p-2 text-xs text-error">{e.to_string()}</p> }.into_view()
+                    }
+                })
+        }}
+
+      </Suspense>
+    </div>
+  }
+}
+
+#[component]
+fn SearchResult(hit: SearchHit, query: RwSignal<String>) -> impl IntoView {
+  let navigate = use_navigate();
+  let url = hit.url();
+  let title = hit
+    .chat_title
+    .clone()
+    .unwrap_or_else(|| "New Session".to_string());
+  let snippet = hit
+    .snippet_parts()
+    .into_iter()
+    .map(|(text, matched)| {
+      if matched {
+        view! { <mark class="bg-accent text-accent-content rounded-sm">{text}</mark> }.into_view()
+      } else {
+        text.into_view()
+      }
+    })
+    .collect_view();
+
+  view! {
+    <div
+      class="w-full cursor-pointer rounded p-1 text-sm transition-colors duration-300 hover:bg-secondary hover:text-secondary-content"
+      on:click=move |_| {
+          navigate(&url, Default::default());
+          query.set(String::new());
+      }
+    >
+      <div class="flex items-center justify-between space-x-2">
+        <span class="overflow-x-hidden text-ellipsis whitespace-nowrap">{title}</span>
+        <span class="badge badge-ghost badge-sm">{hit.source.as_str()}</span>
+      </div>
+      <p class="line-clamp-2 text-xs opacity-70">{snippet}</p>
+    </div>
+  }
+}
+
 #[component]
 fn ChatListItems(
   edit_chat: RwSignal<Option<EditChat>>,
@@ -177,6 +294,15 @@ fn ChatListItem(
 ) -> impl IntoView {
   let (title, _) = create_signal(chat.name);
   let id = chat.id;
+  let cost = format_usd(chat.cost);
+  let redact_pii = create_rw_signal(chat.redact_pii);
+  let set_redaction = create_server_action::<SetChatRedaction>();
+  let toggle_redaction = move |ev: ev::MouseEvent| {
+    ev.prevent_default();
+    let enabled = !redact_pii.get_untracked();
+    set_redaction.dispatch(SetChatRedaction { id, enabled });
+    redact_pii.set(enabled);
+  };
   let is_active_selected_chat = move || Some(chat.id) == active_chat();
   let is_selected_chat = move || edit_chat().as_ref().map_or(false, |chat| chat.id == id);
   let (is_hovering, set_is_hovering) = create_signal(false);
@@ -225,6 +351,7 @@ fn ChatListItem(
         when=is_selected_chat
         fallback={
           let navigate = navigate.clone();
+          let cost = cost.clone();
           move || {
             let navigate = navigate.clone();
             let detail_url = format!("/chat/{}", id);
@@ -241,6 +368,9 @@ fn ChatListItem(
               >
 
                 {title()}
+                <span class="ml-2 text-xs opacity-60" title="Estimated cost">
+                  {cost.clone()}
+                </span>
               </div>
             }
         }}
@@ -263,6 +393,25 @@ fn ChatListItem(
       </Show>
       <Show when=move || !is_selected_chat()>
         <div class="absolute right-1 top-1/2 -translate-y-1/2 transform animate-fade-in items-center flex flex-row">
+          <button
+            class="btn-link px-1 text-neutral-content hover:text-accent"
+            title=move || {
+                if redact_pii() {
+                    "Personal data is redacted before prompts leave the server"
+                } else {
+                    "Redact personal data in prompts"
+                }
+            }
+
+            on:click=toggle_redaction
+          >
+            <Show
+              when=redact_pii
+              fallback=|| view! { <Shield size="16" weight=IconWeight::Bold/> }
+            >
+              <ShieldCheck size="16" weight=IconWeight::Bold/>
+            </Show>
+          </button>
           <form class="px-1">
             <button
               class="btn-link text-neutral-content hover:text-accent"
@@ -0,0 +1,124 @@
+use leptos::*;
+use phosphor_leptos::{Microphone, SpeakerHigh};
+
+use crate::api::{self, Recorder};
+
+/// Records while pressed and hands the transcript of the recording to `on_text`.
+#[component]
+pub fn PushToTalk(
+  #[prop(into)] on_text: Callback<String>,
+  #[prop(optional, into)] disabled: MaybeSignal<bool>,
+) -> impl IntoView {
+  let recorder = store_value(None::<Recorder>);
+  let (is_recording, set_is_recording) = create_signal(false);
+  let (is_transcribing, set_is_transcribing) = create_signal(false);
+
+  let start = move |_| {
+    if disabled() || is_recording() || is_transcribing() {
+      return;
+    }
+    set_is_recording(true);
+    spawn_local(async move {
+      match Recorder::start().await {
+        // released before the microphone was ready
+        Some(started) if !is_recording.get_untracked() => {
+          let _ = started.stop().await;
+        }
+        started => recorder.set_value(started),
+      }
+      if recorder.with_value(Option::is_none) {
+        set_is_recording(false);
+      }
+    });
+  };
+  let stop = move |_| {
+    if !is_recording() {
+      return;
+    }
+    set_is_recording(false);
+    let Some(started) = recorder.try_update_value(Option::take).flatten() else {
+      return;
+    };
+    set_is_transcribing(true);
+    spawn_local(async move {
+      if let Some(recording) = started.stop().await {
+        if let Some(text) = api::transcribe(recording).await {
+          if !text.trim().is_empty() {
+            on_text(text);
+          }
+        }
+      }
+      set_is_transcribing(false);
+    });
+  };
+
+  view! {
+    <Show
+      when=move || !is_transcribing()
+      fallback=move || view! { <span class="loading loading-dots text-accent"></span> }
+    >
+      <button
+        type="button"
+        title="Hold to talk"
+        class="hover:btn-accent btn btn-neutral btn-square flex-1"
+        class:btn-error=is_recording
+        prop:disabled=disabled
+        on:pointerdown=start
+        on:pointerup=stop
+        on:pointerleave=stop
+      >
+        <Microphone size="20" class="text-[currentColor]"/>
+      </button>
+    </Show>
+  }
+}
+
+/// Plays `text` with the voice and speed of the user.
+#[component]
+pub fn ReadAloud(#[prop(into)] text: String) -> impl IntoView {
+  let (is_loading, set_is_loading) = create_signal(false);
+
+  let on_click = move |_| {
+    if is_loading() {
+      return;
+    }
+    set_is_loading(true);
+    let text = text.clone();
+    spawn_local(async move {
+      if let Some(url) = api::speak(text).await {
+        play(url);
+      }
+      set_is_loading(false);
+    });
+  };
+
+  view! {
+    <button
+      class="group/button flex items-center space-x-2 text-cyan-500 hover:text-cyan-400"
+      title="Read aloud"
+      prop:disabled=is_loading
+      on:click=on_click
+    >
+      <div class="font-regular text-xs group-hover/button:underline">{"Read Aloud"}</div>
+      <SpeakerHigh size="24"/>
+    </button>
+  }
+}
+
+#[cfg(feature = "hydrate")]
+fn play(url: String) {
+  use wasm_bindgen::{closure::Closure, JsCast};
+
+  let Ok(audio) = web_sys::HtmlAudioElement::new_with_src(&url) else {
+    return;
+  };
+  // the object URL is only needed until the audio has been played
+  let on_ended = Closure::once_into_js(move || {
+    let _ = web_sys::Url::revoke_object_url(&url);
+  });
+  audio.set_onended(Some(on_ended.unchecked_ref()));
+  let _ = audio.play();
+}
+
+#[cfg(not(feature = "hydrate"))]
+fn play(_url: String) {}
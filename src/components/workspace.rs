@@ -9,7 +9,12 @@ use phosphor_leptos::{File as PhosphorFile, *};
 use uuid::Uuid;
 use web_sys::{js_sys, Event, File, HtmlInputElement};
 
-use crate::{api, models::UploadedFile, routes::files::get_files, ChatResource, ShowFileModal};
+use crate::{
+  api,
+  models::{workspace_url, UploadedFile},
+  routes::files::{get_files, get_workspace_images},
+  ChatResource, ShowFileModal,
+};
 
 #[component]
 #[allow(unused_variables)]
@@ -131,6 +136,7 @@ pub fn Workspace(chat_id: ReadSignal<Option<Uuid>>, chats: ChatResource) -> impl
             </Show>
           </Suspense>
         </div>
+        <ImageGallery chat_id files select_file/>
 
       </div>
     </Show>
@@ -235,6 +241,56 @@ fn WorkspaceFiles(
   }
 }
 
+/// Thumbnails of the images generated into the workspace, with their prompts as titles.
+#[component]
+fn ImageGallery(
+  chat_id: ReadSignal<Option<Uuid>>,
+  files: ReadSignal<Vec<UploadedFile>>,
+  #[prop(into)] select_file: Callback<UploadedFile>,
+) -> impl IntoView {
+  // new files may be generated images, so the gallery reloads with the file list
+  let images = create_resource(
+    move || (chat_id(), files().len()),
+    |(chat_id, _)| async move {
+      match chat_id {
+        Some(chat_id) => get_workspace_images(chat_id).await.unwrap_or_default(),
+        None => vec![],
+      }
+    },
+  );
+  let has_images = move || images.get().is_some_and(|images| !images.is_empty());
+
+  view! {
+    <Transition fallback=|| ()>
+      <Show when=has_images>
+        <div class="mt-2 flex w-full items-center px-2 text-neutral-content p-1">
+          <div class="text-xs uppercase tracking-widest text-[currentColor]">"Generated images"</div>
+        </div>
+        <div class="grid max-h-[24vh] grid-cols-3 gap-1 overflow-y-auto p-1 [scrollbar-gutter:stable]">
+          <For each=move || images.get().unwrap_or_default() key=|image| image.file_name.clone() let:image>
+            <img
+              class="aspect-square w-full cursor-pointer rounded object-cover transition-opacity duration-300 hover:opacity-75"
+              src=workspace_url(image.chat_id, &image.file_name)
+              title=image.revised_prompt.clone().unwrap_or_else(|| image.prompt.clone())
+              alt=image.prompt.clone()
+              on:click={
+                  let image = image.clone();
+                  move |_| {
+                      select_file(UploadedFile {
+                          file_name: image.file_name.clone(),
+                          mime_type: "image/png".to_string(),
+                          workspace: image.chat_id.to_string(),
+                      });
+                  }
+              }
+            />
+          </For>
+        </div>
+      </Show>
+    </Transition>
+  }
+}
+
 #[component]
 pub(super) fn FileDialogOpener(
   id: &'static str,
@@ -44,12 +44,15 @@ pub fn create_chat_details_state() -> ShowChatDetailsModal {
 pub struct ChatState {
   pub active_chat: RwSignal<Option<Uuid>>,
   pub edit_chat: RwSignal<Option<EditChat>>,
+  /// The chat model picked from the catalog, which chat titles are generated with.
+  pub model: RwSignal<String>,
 }
 
 pub fn create_chat_state() {
   let res = ChatState {
     active_chat: create_rw_signal(None),
     edit_chat: create_rw_signal(None),
+    model: create_rw_signal("gpt-3.5-turbo".to_string()),
   };
   provide_context(res);
 }
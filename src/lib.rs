@@ -38,6 +38,7 @@ cfg_if! { if #[cfg(feature = "ssr")]{
   use axum::Json;
   use serde_json::json;
   use async_openai::error::OpenAIError;
+  use crate::models::ApiError;
 
   pub type Result<T, E = Error> = core::result::Result<T, E>;
 
@@ -67,6 +68,20 @@ cfg_if! { if #[cfg(feature = "ssr")]{
     NotFound(String),
     #[error("watcher: {0}")]
     Watcher(#[from] notify::Error),
+    #[error("forbidden: {0}")]
+    Forbidden(String),
+    #[error("quota exceeded: {0}")]
+    QuotaExceeded(crate::models::quota::QuotaExceeded),
+    #[error("{0}")]
+    ContentBlocked(crate::models::moderation::ContentBlocked),
+    #[error("{0}")]
+    BudgetExceeded(crate::models::pricing::BudgetExceeded),
+    #[error("invalid {param}: {message}")]
+    InvalidParam { param: String, message: String },
+    #[error("{param} is larger than {limit} bytes")]
+    PayloadTooLarge { param: String, limit: u64 },
+    #[error("cannot extract the text of {file}: {message}")]
+    Extraction { file: String, message: String },
     // #[error("uninitialized field: {0}")]
     // UninitializedField(#[from] UninitializedFieldError),
   }
@@ -85,25 +100,160 @@ cfg_if! { if #[cfg(feature = "ssr")]{
         Error::Pgx(sqlx::Error::RowNotFound) => StatusCode::NOT_FOUND,
         Error::Pgx(_e) => StatusCode::INTERNAL_SERVER_ERROR,
         Error::Serde(_e) => StatusCode::BAD_REQUEST,
-        Error::OpenAI(_e) => StatusCode::INTERNAL_SERVER_ERROR,
+        Error::OpenAI(e) => upstream_status(e),
         Error::InvalidArgument(_e) => StatusCode::BAD_REQUEST,
         Error::Io(_e) => StatusCode::INTERNAL_SERVER_ERROR,
         Error::NotFound(_e) => StatusCode::NOT_FOUND,
         Error::Watcher(notify::Error{kind: notify::ErrorKind::PathNotFound,..}) => StatusCode::NOT_FOUND,
         Error::Watcher(notify::Error{kind: notify::ErrorKind::WatchNotFound,..}) => StatusCode::NOT_FOUND,
         Error::Watcher(_e) => StatusCode::INTERNAL_SERVER_ERROR,
+        Error::Forbidden(_e) => StatusCode::FORBIDDEN,
+        Error::QuotaExceeded(_e) => StatusCode::TOO_MANY_REQUESTS,
+        Error::ContentBlocked(_e) => StatusCode::BAD_REQUEST,
+        Error::BudgetExceeded(_e) => StatusCode::PAYMENT_REQUIRED,
+        Error::InvalidParam { .. } => StatusCode::BAD_REQUEST,
+        Error::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+        Error::Extraction { .. } => StatusCode::UNPROCESSABLE_ENTITY,
         // Error::UninitializedField(_e) => StatusCode::BAD_REQUEST,
       }
     }
   }
 
+  /// The status the provider answered with. `async-openai` drops it, so it is told from the
+  /// type and code of the error, which map to statuses one to one.
+  fn upstream_status(error: &OpenAIError) -> StatusCode {
+    match error {
+      OpenAIError::ApiError(e) => {
+        let code = e.code.as_ref().and_then(|code| code.as_str());
+        match (e.r#type.as_deref(), code) {
+          (_, Some("invalid_api_key")) | (Some("authentication_error"), _) => StatusCode::UNAUTHORIZED,
+          (_, Some("model_not_found")) | (Some("not_found_error"), _) => StatusCode::NOT_FOUND,
+          (Some("permission_error"), _) => StatusCode::FORBIDDEN,
+          (_, Some("rate_limit_exceeded" | "insufficient_quota"))
+          | (Some("requests" | "tokens" | "rate_limit_error" | "insufficient_quota"), _) => {
+            StatusCode::TOO_MANY_REQUESTS
+          }
+          (Some("invalid_request_error"), _) => StatusCode::BAD_REQUEST,
+          (Some("overloaded_error" | "engine_overloaded"), _) => StatusCode::SERVICE_UNAVAILABLE,
+          (Some("server_error"), _) => StatusCode::INTERNAL_SERVER_ERROR,
+          _ => StatusCode::BAD_GATEWAY,
+        }
+      }
+      // reqwest is on an older `http`, the status crosses over as its number
+      OpenAIError::Reqwest(e) => e
+        .status()
+        .and_then(|status| StatusCode::from_u16(status.as_u16()).ok())
+        .unwrap_or(StatusCode::BAD_GATEWAY),
+      OpenAIError::InvalidArgument(_) => StatusCode::BAD_REQUEST,
+      OpenAIError::JSONDeserialize(_) | OpenAIError::StreamError(_) => StatusCode::BAD_GATEWAY,
+      OpenAIError::FileSaveError(_) | OpenAIError::FileReadError(_) => {
+        StatusCode::INTERNAL_SERVER_ERROR
+      }
+    }
+  }
+
+  static RETRY_AFTER: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+    regex::Regex::new(r"try again in (\d+(?:\.\d+)?)(ms|s)").expect("retry after pattern")
+  });
+
+  /// The seconds the provider asks to wait in messages like "Please try again in 6ms.". Its
+  /// `x-ratelimit-*` headers are not passed on, `async-openai` drops the headers of errors.
+  fn upstream_retry_after(message: &str) -> Option<u64> {
+    let captures = RETRY_AFTER.captures(message)?;
+    let value: f64 = captures[1].parse().ok()?;
+    let seconds = if &captures[2] == "ms" { value / 1000.0 } else { value };
+    Some(seconds.ceil().max(1.0) as u64)
+  }
+
+  impl Error {
+    /// The error in the shape of the OpenAI API, for the proxy.
+    pub fn api_error(&self) -> ApiError {
+      let (r#type, param, code) = match self {
+        Error::OpenAI(OpenAIError::ApiError(e)) => {
+          return ApiError {
+            message: e.message.clone(),
+            r#type: e.r#type.clone(),
+            param: e.param.clone(),
+            code: e.code.clone(),
+          }
+        }
+        Error::UserNotAuthenticated => ("invalid_request_error", None, Some("invalid_api_key")),
+        Error::Forbidden(_) => ("permission_error", None, None),
+        Error::NotFound(_) => ("invalid_request_error", None, Some("not_found")),
+        Error::InvalidParam { param, .. } => ("invalid_request_error", Some(param.as_str()), None),
+        Error::PayloadTooLarge { param, .. } => {
+          ("invalid_request_error", Some(param.as_str()), Some("payload_too_large"))
+        }
+        Error::QuotaExceeded(quota) if quota.limit == "requests_per_minute" => {
+          ("requests", None, Some("rate_limit_exceeded"))
+        }
+        Error::QuotaExceeded(_) => ("insufficient_quota", None, Some("insufficient_quota")),
+        Error::BudgetExceeded(_) => ("insufficient_quota", None, Some("budget_exceeded")),
+        Error::ContentBlocked(_) => ("invalid_request_error", None, Some("content_policy_violation")),
+        _ if self.status_code().is_server_error() => ("server_error", None, None),
+        _ => ("invalid_request_error", None, None),
+      };
+      ApiError {
+        message: self.to_string(),
+        r#type: Some(r#type.to_string()),
+        param: param.map(Into::into),
+        code: code.map(Into::into),
+      }
+    }
+  }
+
   impl IntoResponse for Error {
+    /// The internal shape with a `message`, the proxy rewrites it to the OpenAI shape from the
+    /// [ApiError] left in the extensions.
     fn into_response(self) -> axum::response::Response {
-      (
-        self.status_code(),
-        Json(json!({"message": self.to_string()})),
-      )
-        .into_response()
+      let api_error = self.api_error();
+      let mut response = if let Error::QuotaExceeded(quota) = &self {
+        let retry_after = (quota.reset_at - chrono::Utc::now()).num_seconds().max(1);
+        let unit = if quota.limit.ends_with("tokens") { "tokens" } else { "requests" };
+        (
+          self.status_code(),
+          [
+            (http::header::RETRY_AFTER.to_string(), retry_after.to_string()),
+            (format!("x-ratelimit-limit-{unit}"), quota.max.to_string()),
+            (format!("x-ratelimit-remaining-{unit}"), (quota.max - quota.used).max(0).to_string()),
+            (format!("x-ratelimit-reset-{unit}"), format!("{retry_after}s")),
+          ],
+          Json(json!({"message": self.to_string(), "quota": quota})),
+        )
+          .into_response()
+      } else if let Error::ContentBlocked(blocked) = &self {
+        (
+          self.status_code(),
+          Json(json!({"message": self.to_string(), "moderation": blocked})),
+        )
+          .into_response()
+      } else if let Error::BudgetExceeded(budget) = &self {
+        (
+          self.status_code(),
+          Json(json!({"message": self.to_string(), "budget": budget})),
+        )
+          .into_response()
+      } else if let Some(retry_after) = match &self {
+        Error::OpenAI(OpenAIError::ApiError(e)) if self.status_code() == StatusCode::TOO_MANY_REQUESTS => {
+          upstream_retry_after(&e.message)
+        }
+        _ => None,
+      } {
+        (
+          self.status_code(),
+          [(http::header::RETRY_AFTER, retry_after.to_string())],
+          Json(json!({"message": self.to_string()})),
+        )
+          .into_response()
+      } else {
+        (
+          self.status_code(),
+          Json(json!({"message": self.to_string()})),
+        )
+          .into_response()
+      };
+      response.extensions_mut().insert(api_error);
+      response
     }
   }
 
@@ -49,6 +49,10 @@ async fn main() -> anyhow::Result<()> {
   let state = AppState::new(database_url, client_id, leptos_options)
     .await?
     .with_redirect_url("http://localhost:3000/oauth/finish");
+  miko::server::localai::resume(&state).await?;
+  miko::server::audit::spawn_retention(state.clone());
+  miko::server::search::spawn_indexer(state.clone());
+  miko::server::retrieval::spawn_indexer(state.clone());
 
   let session_config = SessionConfig::default().with_table_name("axum_sessions");
   let auth_config = AuthConfig::<Uuid>::default();
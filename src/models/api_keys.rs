@@ -0,0 +1,56 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// An API key for the OpenAI compatible API, without its secret.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApiKey {
+  pub id: Uuid,
+  pub user_id: Uuid,
+  pub name: String,
+  /// The first characters of the key, so users can tell their keys apart.
+  pub prefix: String,
+  /// The models the key may use, empty allows all models.
+  pub models: Vec<String>,
+  /// The endpoints the key may call, e.g. `/chat/completions`, empty allows all endpoints.
+  pub endpoints: Vec<String>,
+  pub expires_at: Option<DateTime<Utc>>,
+  pub last_used_at: Option<DateTime<Utc>>,
+  pub revoked_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+}
+
+impl ApiKey {
+  pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+    self.revoked_at.is_none() && self.expires_at.map_or(true, |expires_at| expires_at > now)
+  }
+
+  pub fn allows_endpoint(&self, path: &str) -> bool {
+    self.endpoints.is_empty()
+      || self
+        .endpoints
+        .iter()
+        .any(|endpoint| path == endpoint || path.starts_with(&format!("{endpoint}/")))
+  }
+
+  pub fn allows_model(&self, model: &str) -> bool {
+    self.models.is_empty() || self.models.iter().any(|m| m == model)
+  }
+}
+
+/// A freshly created key, the only time the secret is available.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CreatedApiKey {
+  pub key: ApiKey,
+  pub secret: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CreateApiKeyRequest {
+  pub name: String,
+  #[serde(default)]
+  pub models: Vec<String>,
+  #[serde(default)]
+  pub endpoints: Vec<String>,
+  pub expires_at: Option<DateTime<Utc>>,
+}
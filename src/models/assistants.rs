@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+
+use super::{ChatCompletionMessageToolCall, ChatError, FunctionCall};
+
+pub type Metadata = HashMap<String, serde_json::Value>;
+
+/// A tool enabled on an assistant.
+///
+/// Only `function` tools are executed locally, `code_interpreter` and `retrieval` are accepted
+/// and stored so existing clients keep working, but the model never sees them.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AssistantTools {
+  CodeInterpreter,
+  Retrieval,
+  Function { function: FunctionObject },
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct FunctionObject {
+  /// The name of the function to be called. Must be a-z, A-Z, 0-9, or contain underscores and dashes, with a maximum length of 64.
+  pub name: String,
+  /// A description of what the function does, used by the model to choose when and how to call the function.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub description: Option<String>,
+  /// The parameters the functions accepts, described as a JSON Schema object.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub parameters: Option<serde_json::Value>,
+}
+
+#[cfg(feature = "ssr")]
+impl From<FunctionObject> for async_openai::types::FunctionObject {
+  fn from(value: FunctionObject) -> Self {
+    Self {
+      name: value.name,
+      description: value.description,
+      parameters: value.parameters,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AssistantObject {
+  /// The identifier, which can be referenced in API endpoints.
+  pub id: String,
+  /// The object type, which is always `assistant`.
+  pub object: String,
+  /// The Unix timestamp (in seconds) for when the assistant was created.
+  pub created_at: i64,
+  pub name: Option<String>,
+  pub description: Option<String>,
+  /// ID of the model to use.
+  pub model: String,
+  /// The system instructions that the assistant uses.
+  pub instructions: Option<String>,
+  pub tools: Vec<AssistantTools>,
+  pub file_ids: Vec<String>,
+  pub metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Builder, PartialEq)]
+#[builder(name = "CreateAssistantRequestArgs")]
+#[builder(pattern = "mutable")]
+#[builder(setter(into, strip_option), default)]
+#[builder(derive(Debug))]
+#[builder(build_fn(error = "ChatError"))]
+pub struct CreateAssistantRequest {
+  /// ID of the model to use.
+  pub model: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub name: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub description: Option<String>,
+  /// The system instructions that the assistant uses. The maximum length is 32768 characters.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub instructions: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub tools: Option<Vec<AssistantTools>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub file_ids: Option<Vec<String>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct ModifyAssistantRequest {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub model: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub name: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub description: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub instructions: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub tools: Option<Vec<AssistantTools>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub file_ids: Option<Vec<String>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ThreadObject {
+  pub id: String,
+  /// The object type, which is always `thread`.
+  pub object: String,
+  pub created_at: i64,
+  pub metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Builder, PartialEq)]
+#[builder(name = "CreateThreadRequestArgs")]
+#[builder(pattern = "mutable")]
+#[builder(setter(into, strip_option), default)]
+#[builder(derive(Debug))]
+#[builder(build_fn(error = "ChatError"))]
+pub struct CreateThreadRequest {
+  /// A list of messages to start the thread with.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub messages: Option<Vec<CreateMessageRequest>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct ModifyMetadataRequest {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageRole {
+  #[default]
+  User,
+  Assistant,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MessageObject {
+  pub id: String,
+  /// The object type, which is always `thread.message`.
+  pub object: String,
+  pub created_at: i64,
+  pub thread_id: String,
+  pub role: MessageRole,
+  pub content: Vec<MessageContent>,
+  /// If applicable, the ID of the assistant that authored this message.
+  pub assistant_id: Option<String>,
+  /// If applicable, the ID of the run associated with the authoring of this message.
+  pub run_id: Option<String>,
+  pub file_ids: Vec<String>,
+  pub metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MessageContent {
+  Text { text: TextData },
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct TextData {
+  pub value: String,
+  pub annotations: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Builder, PartialEq)]
+#[builder(name = "CreateMessageRequestArgs")]
+#[builder(pattern = "mutable")]
+#[builder(setter(into, strip_option), default)]
+#[builder(derive(Debug))]
+#[builder(build_fn(error = "ChatError"))]
+pub struct CreateMessageRequest {
+  /// The role of the entity that is creating the message. Currently only `user` is supported.
+  #[serde(default)]
+  pub role: MessageRole,
+  /// The content of the message.
+  pub content: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub file_ids: Option<Vec<String>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+  #[default]
+  Queued,
+  InProgress,
+  RequiresAction,
+  Cancelling,
+  Cancelled,
+  Failed,
+  Completed,
+  Expired,
+}
+
+impl RunStatus {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      RunStatus::Queued => "queued",
+      RunStatus::InProgress => "in_progress",
+      RunStatus::RequiresAction => "requires_action",
+      RunStatus::Cancelling => "cancelling",
+      RunStatus::Cancelled => "cancelled",
+      RunStatus::Failed => "failed",
+      RunStatus::Completed => "completed",
+      RunStatus::Expired => "expired",
+    }
+  }
+
+  /// Whether the run has finished and will not make any more progress.
+  pub fn is_terminal(&self) -> bool {
+    matches!(
+      self,
+      RunStatus::Cancelled | RunStatus::Failed | RunStatus::Completed | RunStatus::Expired
+    )
+  }
+}
+
+impl std::str::FromStr for RunStatus {
+  type Err = ChatError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "queued" => Ok(RunStatus::Queued),
+      "in_progress" => Ok(RunStatus::InProgress),
+      "requires_action" => Ok(RunStatus::RequiresAction),
+      "cancelling" => Ok(RunStatus::Cancelling),
+      "cancelled" => Ok(RunStatus::Cancelled),
+      "failed" => Ok(RunStatus::Failed),
+      "completed" => Ok(RunStatus::Completed),
+      "expired" => Ok(RunStatus::Expired),
+      other => Err(ChatError::InvalidArgument(format!(
+        "unknown run status: {other}"
+      ))),
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RequiredAction {
+  /// For now, this is always `submit_tool_outputs`.
+  pub r#type: String,
+  pub submit_tool_outputs: SubmitToolOutputs,
+}
+
+impl From<Vec<ChatCompletionMessageToolCall>> for RequiredAction {
+  fn from(tool_calls: Vec<ChatCompletionMessageToolCall>) -> Self {
+    Self {
+      r#type: "submit_tool_outputs".to_string(),
+      submit_tool_outputs: SubmitToolOutputs {
+        tool_calls: tool_calls
+          .into_iter()
+          .map(|call| RunToolCallObject {
+            id: call.id,
+            r#type: "function".to_string(),
+            function: call.function,
+          })
+          .collect(),
+      },
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SubmitToolOutputs {
+  pub tool_calls: Vec<RunToolCallObject>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RunToolCallObject {
+  /// The ID of the tool call. This ID must be referenced when you submit the tool outputs.
+  pub id: String,
+  /// The type of tool call the output is required for. For now, this is always `function`.
+  pub r#type: String,
+  pub function: FunctionCall,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LastError {
+  /// One of `server_error` or `rate_limit_exceeded`.
+  pub code: String,
+  pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RunObject {
+  pub id: String,
+  /// The object type, which is always `thread.run`.
+  pub object: String,
+  pub created_at: i64,
+  pub thread_id: String,
+  pub assistant_id: Option<String>,
+  pub status: RunStatus,
+  /// Details on the action required to continue the run. Will be `null` if no action is required.
+  pub required_action: Option<RequiredAction>,
+  pub last_error: Option<LastError>,
+  pub expires_at: Option<i64>,
+  pub started_at: Option<i64>,
+  pub cancelled_at: Option<i64>,
+  pub failed_at: Option<i64>,
+  pub completed_at: Option<i64>,
+  pub model: String,
+  pub instructions: String,
+  pub tools: Vec<AssistantTools>,
+  pub file_ids: Vec<String>,
+  pub metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Builder, PartialEq)]
+#[builder(name = "CreateRunRequestArgs")]
+#[builder(pattern = "mutable")]
+#[builder(setter(into, strip_option), default)]
+#[builder(derive(Debug))]
+#[builder(build_fn(error = "ChatError"))]
+pub struct CreateRunRequest {
+  /// The ID of the assistant to use to execute this run.
+  pub assistant_id: String,
+  /// Overrides the model of the assistant.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub model: Option<String>,
+  /// Overrides the instructions of the assistant.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub instructions: Option<String>,
+  /// Appends additional instructions at the end of the instructions for the run.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub additional_instructions: Option<String>,
+  /// Overrides the tools the assistant can use for this run.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub tools: Option<Vec<AssistantTools>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct CreateThreadAndRunRequest {
+  pub assistant_id: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub thread: Option<CreateThreadRequest>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub model: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub instructions: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub tools: Option<Vec<AssistantTools>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct SubmitToolOutputsRunRequest {
+  pub tool_outputs: Vec<ToolOutput>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct ToolOutput {
+  /// The ID of the tool call in the `required_action` object within the run object the output is being submitted for.
+  pub tool_call_id: Option<String>,
+  /// The output of the tool call to be submitted to continue the run.
+  pub output: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DeletedObject {
+  pub id: String,
+  pub object: String,
+  pub deleted: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ListResponse<T> {
+  /// The object type, which is always `list`.
+  pub object: String,
+  pub data: Vec<T>,
+  pub first_id: Option<String>,
+  pub last_id: Option<String>,
+  pub has_more: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+  Asc,
+  #[default]
+  Desc,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct ListQuery {
+  /// A limit on the number of objects to be returned. Limit can range between 1 and 100, and the default is 20.
+  pub limit: Option<i64>,
+  #[serde(default)]
+  pub order: SortOrder,
+  /// A cursor for use in pagination, the ID of the last object of the previous page.
+  pub after: Option<String>,
+}
+
+impl ListQuery {
+  pub fn page_size(&self) -> i64 {
+    self.limit.unwrap_or(20).clamp(1, 100)
+  }
+
+  pub fn is_ascending(&self) -> bool {
+    self.order == SortOrder::Asc
+  }
+}
@@ -102,6 +102,39 @@ pub enum Voice {
   Other(String),
 }
 
+impl Voice {
+  /// The voices every provider of the speech endpoint offers.
+  pub const BUILTIN: [Voice; 6] = [
+    Voice::Alloy,
+    Voice::Echo,
+    Voice::Fable,
+    Voice::Onyx,
+    Voice::Nova,
+    Voice::Shimmer,
+  ];
+
+  pub fn as_str(&self) -> &str {
+    match self {
+      Voice::Alloy => "alloy",
+      Voice::Echo => "echo",
+      Voice::Fable => "fable",
+      Voice::Onyx => "onyx",
+      Voice::Nova => "nova",
+      Voice::Shimmer => "shimmer",
+      Voice::Other(voice) => voice,
+    }
+  }
+}
+
+impl From<&str> for Voice {
+  fn from(value: &str) -> Self {
+    Voice::BUILTIN
+      .into_iter()
+      .find(|voice| voice.as_str() == value)
+      .unwrap_or_else(|| Voice::Other(value.to_string()))
+  }
+}
+
 #[cfg(feature = "ssr")]
 impl From<async_openai::types::Voice> for Voice {
   fn from(v: async_openai::types::Voice) -> Self {
@@ -165,3 +198,41 @@ impl From<SpeechResponseFormat> for async_openai::types::SpeechResponseFormat {
     }
   }
 }
+
+/// The format of a transcription or translation.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioResponseFormat {
+  #[default]
+  Json,
+  Text,
+  Srt,
+  VerboseJson,
+  Vtt,
+}
+
+#[cfg(feature = "ssr")]
+impl From<async_openai::types::AudioResponseFormat> for AudioResponseFormat {
+  fn from(v: async_openai::types::AudioResponseFormat) -> Self {
+    match v {
+      async_openai::types::AudioResponseFormat::Json => Self::Json,
+      async_openai::types::AudioResponseFormat::Text => Self::Text,
+      async_openai::types::AudioResponseFormat::Srt => Self::Srt,
+      async_openai::types::AudioResponseFormat::VerboseJson => Self::VerboseJson,
+      async_openai::types::AudioResponseFormat::Vtt => Self::Vtt,
+    }
+  }
+}
+
+#[cfg(feature = "ssr")]
+impl From<AudioResponseFormat> for async_openai::types::AudioResponseFormat {
+  fn from(v: AudioResponseFormat) -> Self {
+    match v {
+      AudioResponseFormat::Json => Self::Json,
+      AudioResponseFormat::Text => Self::Text,
+      AudioResponseFormat::Srt => Self::Srt,
+      AudioResponseFormat::VerboseJson => Self::VerboseJson,
+      AudioResponseFormat::Vtt => Self::Vtt,
+    }
+  }
+}
@@ -0,0 +1,53 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Where an audited call came from.
+pub const AUDIT_SOURCE_PROXY: &str = "proxy";
+pub const AUDIT_SOURCE_AGENT: &str = "agent";
+
+/// A single audited call to a model, the bodies are redacted and truncated according to the
+/// audit policy at the time the call was made.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
+pub struct AuditEvent {
+  pub id: Uuid,
+  pub created_at: DateTime<Utc>,
+  /// `proxy` for the OpenAI compatible API, `agent` for calls made by the app itself.
+  pub source: String,
+  pub user_id: Option<Uuid>,
+  pub api_key_id: Option<Uuid>,
+  pub method: String,
+  pub endpoint: String,
+  pub model: Option<String>,
+  pub status: i32,
+  pub latency_ms: i32,
+  pub request_body: Option<String>,
+  pub response_body: Option<String>,
+  /// Set when either body was cut off at the configured limit.
+  pub truncated: bool,
+}
+
+/// Filters for the audit log, results are returned newest first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AuditQuery {
+  #[serde(default)]
+  pub user_id: Option<Uuid>,
+  #[serde(default)]
+  pub api_key_id: Option<Uuid>,
+  #[serde(default)]
+  pub source: Option<String>,
+  #[serde(default)]
+  pub endpoint: Option<String>,
+  #[serde(default)]
+  pub model: Option<String>,
+  #[serde(default)]
+  pub since: Option<DateTime<Utc>>,
+  #[serde(default)]
+  pub until: Option<DateTime<Utc>>,
+  /// Only events older than this one, for paging.
+  #[serde(default)]
+  pub before: Option<Uuid>,
+  #[serde(default)]
+  pub limit: Option<i64>,
+}
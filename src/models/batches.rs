@@ -0,0 +1,186 @@
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+
+use super::{assistants::Metadata, ChatError};
+
+/// The endpoints a batch can target.
+pub const BATCH_ENDPOINTS: &[&str] = &["/v1/chat/completions", "/v1/embeddings"];
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+  #[default]
+  Validating,
+  Failed,
+  InProgress,
+  Finalizing,
+  Completed,
+  Expired,
+  Cancelling,
+  Cancelled,
+}
+
+impl BatchStatus {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      BatchStatus::Validating => "validating",
+      BatchStatus::Failed => "failed",
+      BatchStatus::InProgress => "in_progress",
+      BatchStatus::Finalizing => "finalizing",
+      BatchStatus::Completed => "completed",
+      BatchStatus::Expired => "expired",
+      BatchStatus::Cancelling => "cancelling",
+      BatchStatus::Cancelled => "cancelled",
+    }
+  }
+
+  /// Whether the batch has finished and the worker is done with it.
+  pub fn is_terminal(&self) -> bool {
+    matches!(
+      self,
+      BatchStatus::Failed | BatchStatus::Completed | BatchStatus::Expired | BatchStatus::Cancelled
+    )
+  }
+}
+
+impl std::str::FromStr for BatchStatus {
+  type Err = ChatError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "validating" => Ok(BatchStatus::Validating),
+      "failed" => Ok(BatchStatus::Failed),
+      "in_progress" => Ok(BatchStatus::InProgress),
+      "finalizing" => Ok(BatchStatus::Finalizing),
+      "completed" => Ok(BatchStatus::Completed),
+      "expired" => Ok(BatchStatus::Expired),
+      "cancelling" => Ok(BatchStatus::Cancelling),
+      "cancelled" => Ok(BatchStatus::Cancelled),
+      other => Err(ChatError::InvalidArgument(format!(
+        "unknown batch status: {other}"
+      ))),
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct BatchRequestCounts {
+  /// Total number of requests in the batch.
+  pub total: i64,
+  /// Number of requests that have been completed successfully.
+  pub completed: i64,
+  /// Number of requests that have failed.
+  pub failed: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BatchError {
+  /// An error code identifying the error type.
+  pub code: String,
+  /// A human-readable message providing more details about the error.
+  pub message: String,
+  /// The name of the parameter that caused the error, if applicable.
+  pub param: Option<String>,
+  /// The line number of the input file where the error occurred, if applicable.
+  pub line: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BatchErrors {
+  /// The object type, which is always `list`.
+  pub object: String,
+  pub data: Vec<BatchError>,
+}
+
+impl From<Vec<BatchError>> for BatchErrors {
+  fn from(data: Vec<BatchError>) -> Self {
+    Self {
+      object: "list".to_string(),
+      data,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BatchObject {
+  pub id: String,
+  /// The object type, which is always `batch`.
+  pub object: String,
+  /// The OpenAI API endpoint used by the batch.
+  pub endpoint: String,
+  pub errors: Option<BatchErrors>,
+  /// The ID of the input file for the batch.
+  pub input_file_id: String,
+  /// The time frame within which the batch should be processed.
+  pub completion_window: String,
+  pub status: BatchStatus,
+  /// The ID of the file containing the outputs of successfully executed requests.
+  pub output_file_id: Option<String>,
+  /// The ID of the file containing the outputs of requests with errors.
+  pub error_file_id: Option<String>,
+  pub created_at: i64,
+  pub in_progress_at: Option<i64>,
+  pub expires_at: Option<i64>,
+  pub finalizing_at: Option<i64>,
+  pub completed_at: Option<i64>,
+  pub failed_at: Option<i64>,
+  pub expired_at: Option<i64>,
+  pub cancelling_at: Option<i64>,
+  pub cancelled_at: Option<i64>,
+  pub request_counts: BatchRequestCounts,
+  pub metadata: Option<Metadata>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Builder, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
+#[builder(name = "CreateBatchRequestArgs")]
+#[builder(pattern = "mutable")]
+#[builder(setter(into, strip_option), default)]
+#[builder(derive(Debug))]
+#[builder(build_fn(error = "ChatError"))]
+pub struct CreateBatchRequest {
+  /// The ID of an uploaded file that contains requests for the new batch, as JSONL.
+  pub input_file_id: String,
+  /// The endpoint to be used for all requests in the batch.
+  pub endpoint: String,
+  /// The time frame within which the batch should be processed. Currently only `24h` is supported.
+  pub completion_window: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub metadata: Option<Metadata>,
+}
+
+/// A single line of a batch input file.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BatchRequestInput {
+  /// A developer-provided per-request id that will be used to match outputs to inputs. Must be unique within a batch.
+  pub custom_id: String,
+  /// The HTTP method to be used for the request. Currently only `POST` is supported.
+  pub method: String,
+  /// The relative URL to be used for the request, must match the endpoint of the batch.
+  pub url: String,
+  pub body: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BatchResponse {
+  /// The HTTP status code of the response.
+  pub status_code: u16,
+  pub request_id: String,
+  /// The JSON body of the response.
+  pub body: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BatchRequestError {
+  pub code: String,
+  pub message: String,
+}
+
+/// A single line of a batch output or error file.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BatchRequestOutput {
+  pub id: String,
+  pub custom_id: String,
+  pub response: Option<BatchResponse>,
+  pub error: Option<BatchRequestError>,
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+use super::pricing::ModelPrice;
+
+/// What a model can do, unknown limits are `None`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct ModelCapabilities {
+  pub chat: bool,
+  pub tools: bool,
+  pub vision: bool,
+  pub json_mode: bool,
+  /// The length of the vectors of embedding models.
+  pub embedding_dimensions: Option<u32>,
+  /// The most tokens of prompt and completion together.
+  pub context_window: Option<u32>,
+  pub max_output_tokens: Option<u32>,
+}
+
+/// A model of the catalog, shaped like an OpenAI `Model` with the metadata of the catalog added.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
+pub struct CatalogModel {
+  pub id: String,
+  pub object: String,
+  pub created: u32,
+  pub owned_by: String,
+  /// The configured provider that serves the model.
+  pub provider: String,
+  /// Whether the provider currently lists the model.
+  pub available: bool,
+  pub capabilities: ModelCapabilities,
+  pub pricing: Option<ModelPrice>,
+}
+
+/// The response of `GET /models`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
+pub struct ModelCatalogList {
+  pub object: String,
+  pub data: Vec<CatalogModel>,
+}
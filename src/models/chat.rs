@@ -15,7 +15,7 @@ pub enum ChatError {
 }
 
 /// OpenAI API returns error object on failure
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiError {
   pub message: String,
   pub r#type: Option<String>,
@@ -27,6 +27,9 @@ pub struct ApiError {
 pub struct ChatInfo {
   pub id: Uuid,
   pub name: Option<String>,
+  pub redact_pii: bool,
+  /// What the chat cost so far in USD.
+  pub cost: f64,
 }
 
 impl From<Chat> for ChatInfo {
@@ -34,6 +37,8 @@ impl From<Chat> for ChatInfo {
     Self {
       id: chat.id,
       name: chat.title.or_else(|| Some("New Session".to_string())),
+      redact_pii: chat.redact_pii,
+      cost: chat.cost,
     }
   }
 }
@@ -46,6 +51,8 @@ impl From<&Chat> for ChatInfo {
         .title
         .clone()
         .or_else(|| Some("New Session".to_string())),
+      redact_pii: chat.redact_pii,
+      cost: chat.cost,
     }
   }
 }
@@ -66,6 +73,10 @@ pub struct SubmittedGoal {
 pub struct Chat {
   pub id: Uuid,
   pub title: Option<String>,
+  /// Personal data is replaced with placeholders before prompts leave the server.
+  pub redact_pii: bool,
+  /// The estimated cost of every model call of the chat in USD.
+  pub cost: f64,
   pub user_id: Uuid,
   pub email: String,
   pub messages: Vec<SavedMessage>,
@@ -176,6 +187,102 @@ impl Default for ChatCompletionRequestUserMessageContent {
   }
 }
 
+/// Links to a file in the workspace of the chat, e.g. `![chart](workspace://chart.png)`.
+pub const WORKSPACE_SCHEME: &str = "workspace://";
+
+/// The names of the workspace files a markdown text embeds as images.
+pub fn workspace_images(text: &str) -> Vec<String> {
+  let marker = format!("]({WORKSPACE_SCHEME}");
+  text
+    .match_indices(&marker)
+    // only images, plain links to workspace files are left alone
+    .filter(|(start, _)| {
+      text[..*start]
+        .rfind("![")
+        .is_some_and(|open| !text[open..*start].contains("]("))
+    })
+    .filter_map(|(start, _)| {
+      let rest = &text[start + marker.len()..];
+      let end = rest.find(')')?;
+      let name = rest[..end].trim();
+      (!name.is_empty()).then(|| name.to_string())
+    })
+    .collect()
+}
+
+/// Where a workspace file is served.
+pub fn workspace_url(chat_id: Uuid, file_name: &str) -> String {
+  format!("/api/v1/workspace/{chat_id}/files/{file_name}")
+}
+
+/// The name of a workspace file as it is written in a link, with the characters that would end
+/// the link or the path escaped.
+pub fn workspace_link(file_name: &str) -> String {
+  file_name
+    .chars()
+    .map(|c| match c {
+      '%' | ' ' | '(' | ')' | '#' | '?' | '<' | '>' => format!("%{:02X}", c as u32),
+      c => c.to_string(),
+    })
+    .collect()
+}
+
+/// The escaped name of the workspace file a resolved link points to, without the lines it cites.
+pub fn workspace_link_target(url: &str, chat_id: Uuid) -> Option<&str> {
+  let link = url.strip_prefix(&workspace_url(chat_id, ""))?;
+  let end = link.find(['#', '?']).unwrap_or(link.len());
+  Some(&link[..end]).filter(|name| !name.is_empty())
+}
+
+/// Points the workspace links of a markdown text to where the files are served.
+pub fn resolve_workspace_links(text: &str, chat_id: Uuid) -> String {
+  text.replace(
+    &format!("]({WORKSPACE_SCHEME}"),
+    &format!("]({}", workspace_url(chat_id, "")),
+  )
+}
+
+impl ChatCompletionRequestUserMessageContent {
+  /// The text, followed by an image part for every workspace image it embeds.
+  pub fn with_workspace_images(text: String) -> Self {
+    let images = workspace_images(&text);
+    if images.is_empty() {
+      return Self::Text(text);
+    }
+    let mut parts = vec![ChatCompletionRequestMessageContentPart::Text(
+      ChatCompletionRequestMessageContentPartText {
+        r#type: "text".into(),
+        text,
+      },
+    )];
+    parts.extend(images.into_iter().map(|name| {
+      ChatCompletionRequestMessageContentPart::Image(ChatCompletionRequestMessageContentPartImage {
+        r#type: "image_url".into(),
+        image_url: ImageUrl {
+          url: format!("{WORKSPACE_SCHEME}{name}"),
+          detail: ImageUrlDetail::Auto,
+        },
+      })
+    }));
+    Self::Array(parts)
+  }
+
+  /// The text parts, joined by newlines.
+  pub fn text(&self) -> String {
+    match self {
+      Self::Text(text) => text.clone(),
+      Self::Array(parts) => parts
+        .iter()
+        .filter_map(|part| match part {
+          ChatCompletionRequestMessageContentPart::Text(part) => Some(part.text.as_str()),
+          _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n"),
+    }
+  }
+}
+
 #[derive(Debug, Serialize, Deserialize, Default, Clone, Builder, PartialEq)]
 #[builder(name = "ChatCompletionRequestUserMessageBuilder")]
 #[builder(pattern = "mutable")]
@@ -290,6 +397,155 @@ pub enum Role {
   Function,
 }
 
+#[cfg(feature = "ssr")]
+impl From<Role> for async_openai::types::Role {
+  fn from(value: Role) -> Self {
+    match value {
+      Role::System => Self::System,
+      Role::User => Self::User,
+      Role::Assistant => Self::Assistant,
+      Role::Tool => Self::Tool,
+      Role::Function => Self::Function,
+    }
+  }
+}
+
+#[cfg(feature = "ssr")]
+impl From<FunctionCall> for async_openai::types::FunctionCall {
+  fn from(value: FunctionCall) -> Self {
+    Self {
+      name: value.name,
+      arguments: value.arguments,
+    }
+  }
+}
+
+#[cfg(feature = "ssr")]
+impl From<async_openai::types::FunctionCall> for FunctionCall {
+  fn from(value: async_openai::types::FunctionCall) -> Self {
+    Self {
+      name: value.name,
+      arguments: value.arguments,
+    }
+  }
+}
+
+#[cfg(feature = "ssr")]
+impl From<ChatCompletionMessageToolCall> for async_openai::types::ChatCompletionMessageToolCall {
+  fn from(value: ChatCompletionMessageToolCall) -> Self {
+    Self {
+      id: value.id,
+      r#type: async_openai::types::ChatCompletionToolType::Function,
+      function: value.function.into(),
+    }
+  }
+}
+
+#[cfg(feature = "ssr")]
+impl From<async_openai::types::ChatCompletionMessageToolCall> for ChatCompletionMessageToolCall {
+  fn from(value: async_openai::types::ChatCompletionMessageToolCall) -> Self {
+    Self {
+      id: value.id,
+      r#type: ChatCompletionToolType::Function,
+      function: value.function.into(),
+    }
+  }
+}
+
+#[cfg(feature = "ssr")]
+impl From<ImageUrlDetail> for async_openai::types::ImageUrlDetail {
+  fn from(value: ImageUrlDetail) -> Self {
+    match value {
+      ImageUrlDetail::Auto => Self::Auto,
+      ImageUrlDetail::Low => Self::Low,
+      ImageUrlDetail::High => Self::High,
+    }
+  }
+}
+
+#[cfg(feature = "ssr")]
+impl From<ChatCompletionRequestMessageContentPart>
+  for async_openai::types::ChatCompletionRequestMessageContentPart
+{
+  fn from(value: ChatCompletionRequestMessageContentPart) -> Self {
+    match value {
+      ChatCompletionRequestMessageContentPart::Text(part) => Self::Text(
+        async_openai::types::ChatCompletionRequestMessageContentPartText {
+          r#type: part.r#type,
+          text: part.text,
+        },
+      ),
+      ChatCompletionRequestMessageContentPart::Image(part) => Self::Image(
+        async_openai::types::ChatCompletionRequestMessageContentPartImage {
+          r#type: part.r#type,
+          image_url: async_openai::types::ImageUrl {
+            url: part.image_url.url,
+            detail: part.image_url.detail.into(),
+          },
+        },
+      ),
+    }
+  }
+}
+
+#[cfg(feature = "ssr")]
+impl From<ChatCompletionRequestUserMessageContent>
+  for async_openai::types::ChatCompletionRequestUserMessageContent
+{
+  fn from(value: ChatCompletionRequestUserMessageContent) -> Self {
+    match value {
+      ChatCompletionRequestUserMessageContent::Text(text) => Self::Text(text),
+      ChatCompletionRequestUserMessageContent::Array(parts) => {
+        Self::Array(parts.into_iter().map(Into::into).collect())
+      }
+    }
+  }
+}
+
+#[cfg(feature = "ssr")]
+impl From<ChatMessage> for async_openai::types::ChatCompletionRequestMessage {
+  fn from(value: ChatMessage) -> Self {
+    match value {
+      ChatMessage::System(msg) => {
+        Self::System(async_openai::types::ChatCompletionRequestSystemMessage {
+          content: msg.content,
+          role: msg.role.into(),
+          name: msg.name,
+        })
+      }
+      ChatMessage::User(msg) => Self::User(async_openai::types::ChatCompletionRequestUserMessage {
+        content: msg.content.into(),
+        role: msg.role.into(),
+        name: msg.name,
+      }),
+      #[allow(deprecated)]
+      ChatMessage::Assistant(msg) => {
+        Self::Assistant(async_openai::types::ChatCompletionRequestAssistantMessage {
+          content: msg.content,
+          role: msg.role.into(),
+          name: msg.name,
+          tool_calls: msg
+            .tool_calls
+            .map(|calls| calls.into_iter().map(Into::into).collect()),
+          function_call: None,
+        })
+      }
+      ChatMessage::Tool(msg) => Self::Tool(async_openai::types::ChatCompletionRequestToolMessage {
+        role: msg.role.into(),
+        content: msg.content,
+        tool_call_id: msg.tool_call_id,
+      }),
+      ChatMessage::Function(msg) => {
+        Self::Function(async_openai::types::ChatCompletionRequestFunctionMessage {
+          role: msg.role.into(),
+          content: msg.content,
+          name: msg.name,
+        })
+      }
+    }
+  }
+}
+
 cfg_if! {
   if #[cfg(feature = "ssr")] {
     use sqlx::PgPool;
@@ -315,6 +571,22 @@ cfg_if! {
       pub async fn update_title(id: Uuid, title: String, pool: &PgPool) -> Result<Chat> {
         SqlChat::update_title(id, title, pool).await
       }
+
+      pub async fn add_message(id: Uuid, message: SavedMessage, pool: &PgPool) -> Result<()> {
+        SqlChat::add_message(id, message, pool).await
+      }
+
+      pub async fn is_owned_by(id: Uuid, user_id: Uuid, pool: &PgPool) -> Result<bool> {
+        SqlChat::is_owned_by(id, user_id, pool).await
+      }
+
+      pub async fn redacts_pii(id: Uuid, user_id: Uuid, pool: &PgPool) -> Result<Option<bool>> {
+        SqlChat::redacts_pii(id, user_id, pool).await
+      }
+
+      pub async fn set_redact_pii(id: Uuid, user_id: Uuid, enabled: bool, pool: &PgPool) -> Result<()> {
+        SqlChat::set_redact_pii(id, user_id, enabled, pool).await
+      }
     }
 
     impl ChatLog {
@@ -1,8 +1,74 @@
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
 pub struct UploadedFile {
   pub workspace: String,
   pub file_name: String,
   pub mime_type: String,
 }
+
+impl UploadedFile {
+  /// A file in the workspace of a chat, with the MIME type guessed from its name.
+  pub fn in_workspace(chat_id: Uuid, file_name: String) -> Self {
+    Self {
+      workspace: chat_id.to_string(),
+      mime_type: workspace_mime_type(&file_name).to_string(),
+      file_name,
+    }
+  }
+}
+
+/// The MIME type of a workspace file, guessed from its extension. Subtitles are plain text so
+/// the transcripts of recordings open as text.
+pub fn workspace_mime_type<P: AsRef<std::path::Path>>(path: P) -> mime_guess::Mime {
+  let path = path.as_ref();
+  if path.extension().is_some_and(|extension| extension == "srt") {
+    return mime_guess::mime::TEXT_PLAIN;
+  }
+  mime_guess::from_path(path).first_or_octet_stream()
+}
+
+/// A file of the Files API, shaped like an OpenAI `File` but with any `purpose`, the
+/// `async-openai` type does not know `batch` and `batch_output`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
+pub struct FileObject {
+  pub id: String,
+  pub object: String,
+  pub bytes: u64,
+  pub created_at: i64,
+  pub filename: String,
+  pub purpose: String,
+  pub status: Option<String>,
+  pub status_details: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+impl From<async_openai::types::OpenAIFile> for FileObject {
+  #[allow(deprecated)]
+  fn from(file: async_openai::types::OpenAIFile) -> Self {
+    Self {
+      purpose: serde_json::to_value(&file.purpose)
+        .ok()
+        .and_then(|purpose| purpose.as_str().map(str::to_string))
+        .unwrap_or_default(),
+      id: file.id,
+      object: file.object,
+      bytes: file.bytes as u64,
+      created_at: file.created_at as i64,
+      filename: file.filename,
+      status: file.status,
+      status_details: file.status_details,
+    }
+  }
+}
+
+/// The response of `GET /files`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
+pub struct FileList {
+  pub object: String,
+  pub data: Vec<FileObject>,
+}
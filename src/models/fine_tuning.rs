@@ -1,5 +1,7 @@
+use chrono::{DateTime, Utc};
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use super::ChatError;
 
@@ -131,3 +133,96 @@ impl From<CreateFineTuningJobRequest> for async_openai::types::CreateFineTuningJ
     }
   }
 }
+
+/// A problem found while building a fine-tuning dataset, for a chat or the whole dataset.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DatasetWarning {
+  pub chat_id: Option<Uuid>,
+  pub message: String,
+  /// Whether the chat was left out of the dataset because of it.
+  pub skipped: bool,
+}
+
+/// What a training file built from chats contains. Token counts are estimates.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct DatasetReport {
+  pub examples: usize,
+  pub skipped_chats: usize,
+  pub messages: usize,
+  pub assistant_messages: usize,
+  pub tool_calls: usize,
+  pub total_tokens: usize,
+  pub min_tokens: usize,
+  pub max_tokens: usize,
+  pub mean_tokens: usize,
+  pub warnings: Vec<DatasetWarning>,
+  /// The uploaded training file to pass to `create_job`, `None` for previews.
+  pub file_id: Option<String>,
+  pub file_name: Option<String>,
+}
+
+/// A fine-tuning job created through the proxy, as last seen at the provider.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TrackedJob {
+  pub id: String,
+  pub model: String,
+  pub training_file: String,
+  pub validation_file: Option<String>,
+  /// `validating_files`, `queued`, `running`, `succeeded`, `failed` or `cancelled`.
+  pub status: String,
+  /// The model the job created, once it succeeded.
+  pub fine_tuned_model: Option<String>,
+  pub error: Option<String>,
+  pub trained_tokens: Option<i64>,
+  pub created_at: DateTime<Utc>,
+  pub finished_at: Option<DateTime<Utc>>,
+  pub updated_at: DateTime<Utc>,
+}
+
+impl TrackedJob {
+  /// Whether the provider is done with the job.
+  pub fn is_finished(&self) -> bool {
+    matches!(self.status.as_str(), "succeeded" | "failed" | "cancelled")
+  }
+}
+
+/// The job as the provider last reported it, without what is not tracked, like its
+/// hyperparameters and result files.
+#[cfg(feature = "ssr")]
+impl From<TrackedJob> for async_openai::types::FineTuningJob {
+  fn from(job: TrackedJob) -> Self {
+    use async_openai::types::{FineTuneJobError, FineTuningJobStatus};
+
+    Self {
+      id: job.id,
+      created_at: job.created_at.timestamp() as u32,
+      error: job.error.map(|message| FineTuneJobError {
+        code: job.status.clone(),
+        message,
+        param: None,
+      }),
+      fine_tuned_model: job.fine_tuned_model,
+      finished_at: job.finished_at.map(|at| at.timestamp() as u32),
+      hyperparameters: Default::default(),
+      model: job.model,
+      object: "fine_tuning.job".to_string(),
+      organization_id: String::new(),
+      result_files: vec![],
+      status: serde_json::from_value(serde_json::Value::String(job.status))
+        .unwrap_or(FineTuningJobStatus::Queued),
+      trained_tokens: job.trained_tokens.map(|tokens| tokens as u32),
+      training_file: job.training_file,
+      validation_file: job.validation_file,
+    }
+  }
+}
+
+/// A step of a fine-tuning job reported by the provider.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct JobEvent {
+  pub id: String,
+  /// `info`, `warn` or `error`.
+  pub level: String,
+  pub message: String,
+  pub created_at: DateTime<Utc>,
+}
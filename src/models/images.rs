@@ -1,5 +1,7 @@
+use chrono::{DateTime, Utc};
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use super::ChatError;
 
@@ -271,3 +273,42 @@ impl From<ResponseFormat> for async_openai::types::ResponseFormat {
     }
   }
 }
+
+/// The arguments of the `generate_image` agent tool. Without `source` a new image is generated,
+/// with it the workspace file is edited where `mask`, or the source itself, is transparent.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct GenerateImage {
+  #[serde(flatten)]
+  pub request: CreateImageRequest,
+  /// The workspace file to edit.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub source: Option<String>,
+  /// A workspace file whose transparent areas mark what to change in `source`.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub mask: Option<String>,
+}
+
+/// An image generated into the workspace of a chat, with the prompt it was made from.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
+pub struct WorkspaceImage {
+  pub chat_id: Uuid,
+  pub file_name: String,
+  pub prompt: String,
+  /// The prompt as rewritten by the model, `dall-e-3` expands short prompts.
+  pub revised_prompt: Option<String>,
+  pub model: String,
+  pub size: String,
+  /// The workspace file that was edited, `None` for new images.
+  pub source: Option<String>,
+  pub created_at: DateTime<Utc>,
+}
+
+/// What the `generate_image` tool returns to the agent.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
+pub struct GenerateImageOutput {
+  /// Markdown that shows the images, for the agent to use in its answer.
+  pub content: String,
+  pub images: Vec<WorkspaceImage>,
+}
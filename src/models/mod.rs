@@ -1,15 +1,27 @@
+pub mod api_keys;
+pub mod assistants;
 pub mod audio;
+pub mod audit;
+pub mod batches;
+pub mod catalog;
 mod chat;
 pub mod embeddings;
 mod files;
 pub mod fine_tuning;
 pub mod images;
 pub mod moderation;
+pub mod pii;
+pub mod pricing;
+pub mod quota;
+pub mod retrieval;
+pub mod search;
+pub mod settings;
+pub mod usage;
 mod user;
 
 pub use chat::*;
 use derive_builder::UninitializedFieldError;
-pub use files::UploadedFile;
+pub use files::{workspace_mime_type, FileList, FileObject, UploadedFile};
 pub use user::User;
 
 impl From<UninitializedFieldError> for ChatError {
@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 
@@ -98,3 +100,41 @@ impl From<async_openai::types::CreateModerationRequest> for CreateModerationRequ
     }
   }
 }
+
+/// What the moderation policy decided for a piece of user content.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationAction {
+  #[default]
+  Allow,
+  /// Let through, but recorded for review.
+  Flag,
+  Block,
+}
+
+impl ModerationAction {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      ModerationAction::Allow => "allow",
+      ModerationAction::Flag => "flag",
+      ModerationAction::Block => "block",
+    }
+  }
+}
+
+/// Returned to the client when the moderation policy blocks its content.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ContentBlocked {
+  /// The categories that crossed their block threshold, e.g. `hate` or `violence/graphic`.
+  pub categories: Vec<String>,
+}
+
+impl Display for ContentBlocked {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "the content was blocked by moderation ({})",
+      self.categories.join(", ")
+    )
+  }
+}
@@ -0,0 +1,15 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A value that was replaced before a prompt was sent to the provider, the original is not kept.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PiiRedaction {
+  pub id: Uuid,
+  pub chat_id: Option<Uuid>,
+  pub endpoint: String,
+  /// `email`, `phone`, `card`, `iban` or the name of a custom pattern.
+  pub kind: String,
+  pub placeholder: String,
+  pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,59 @@
+use std::{collections::HashMap, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+/// What a model costs in USD, unused units are 0.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct ModelPrice {
+  /// Per 1K prompt tokens, or per 1K input characters for text to speech.
+  pub input_per_1k: f64,
+  /// Per 1K completion tokens.
+  pub output_per_1k: f64,
+  /// Per image by size, e.g. `1024x1024`.
+  pub per_image: HashMap<String, f64>,
+  /// Per minute of transcribed or translated audio.
+  pub per_audio_minute: f64,
+}
+
+/// The budget of the goal of a chat and what was spent on it so far.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct GoalBudget {
+  pub budget_usd: Option<f64>,
+  pub spent_usd: f64,
+}
+
+impl GoalBudget {
+  pub fn is_exceeded(&self) -> bool {
+    self
+      .budget_usd
+      .is_some_and(|budget| self.spent_usd >= budget)
+  }
+}
+
+/// Returned when a goal spent its budget.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BudgetExceeded {
+  pub budget_usd: f64,
+  pub spent_usd: f64,
+}
+
+impl Display for BudgetExceeded {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "the goal spent ${:.4} of its ${:.4} budget",
+      self.spent_usd, self.budget_usd
+    )
+  }
+}
+
+/// Formats a cost in USD for display, small amounts keep more digits.
+pub fn format_usd(cost: f64) -> String {
+  if cost > 0.0 && cost < 0.01 {
+    format!("${cost:.4}")
+  } else {
+    format!("${cost:.2}")
+  }
+}
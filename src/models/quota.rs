@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Limits of a single user, `None` means unlimited.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct QuotaLimits {
+  pub daily_requests: Option<i64>,
+  pub monthly_requests: Option<i64>,
+  pub daily_tokens: Option<i64>,
+  pub monthly_tokens: Option<i64>,
+  pub requests_per_minute: Option<i64>,
+}
+
+impl QuotaLimits {
+  /// Fills the limits that are not set with the ones from `defaults`.
+  pub fn or(self, defaults: &QuotaLimits) -> QuotaLimits {
+    QuotaLimits {
+      daily_requests: self.daily_requests.or(defaults.daily_requests),
+      monthly_requests: self.monthly_requests.or(defaults.monthly_requests),
+      daily_tokens: self.daily_tokens.or(defaults.daily_tokens),
+      monthly_tokens: self.monthly_tokens.or(defaults.monthly_tokens),
+      requests_per_minute: self.requests_per_minute.or(defaults.requests_per_minute),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct QuotaUsage {
+  pub daily_requests: i64,
+  pub monthly_requests: i64,
+  pub daily_tokens: i64,
+  pub monthly_tokens: i64,
+  /// Completion requests granted on top of the limits that are not used yet.
+  pub subsidized_completion_requests: i64,
+  /// Embedding requests granted on top of the limits that are not used yet.
+  pub subsidized_embedding_requests: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct QuotaStatus {
+  pub limits: QuotaLimits,
+  pub usage: QuotaUsage,
+}
+
+/// The limit a request ran into, returned with a `429 Too Many Requests`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct QuotaExceeded {
+  /// The name of the limit, e.g. `daily_requests` or `requests_per_minute`.
+  pub limit: String,
+  pub max: i64,
+  pub used: i64,
+  /// When the limit resets.
+  pub reset_at: DateTime<Utc>,
+}
+
+impl std::fmt::Display for QuotaExceeded {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{} limit of {} reached ({} used), resets at {}",
+      self.limit,
+      self.max,
+      self.used,
+      self.reset_at.to_rfc3339()
+    )
+  }
+}
@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{workspace_link, WORKSPACE_SCHEME};
+
+/// A chunk of a workspace file that was retrieved for a prompt.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
+pub struct WorkspaceChunk {
+  pub chat_id: Uuid,
+  pub file_name: String,
+  /// The first line of the chunk in the file, counted from 1.
+  pub start_line: i32,
+  /// The last line of the chunk in the file, inclusive.
+  pub end_line: i32,
+  pub content: String,
+  /// How well the chunk matched, higher is better. Semantic and full-text scores differ in scale.
+  pub score: f64,
+}
+
+impl WorkspaceChunk {
+  /// A markdown link to the lines of the file, which opens the file when clicked in a chat.
+  pub fn citation(&self) -> String {
+    format!(
+      "[{}, lines {}-{}]({WORKSPACE_SCHEME}{}#L{}-L{})",
+      self.file_name.replace(['[', ']'], ""),
+      self.start_line,
+      self.end_line,
+      workspace_link(&self.file_name),
+      self.start_line,
+      self.end_line
+    )
+  }
+}
+
+/// The arguments of the `search_workspace` tool.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
+pub struct SearchWorkspace {
+  /// What to look for in the files.
+  pub query: String,
+  /// How many chunks to return, the configured default when not set.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub k: Option<u32>,
+}
+
+/// What the `search_workspace` tool returns to the agent.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "ssr", derive(schemars::JsonSchema))]
+pub struct SearchWorkspaceOutput {
+  /// The chunks with their citations, ready to be put in a prompt.
+  pub content: String,
+  pub chunks: Vec<WorkspaceChunk>,
+}
+
+/// The chunks as excerpts, each headed by the citation to use when it is quoted.
+pub fn cited_excerpts(chunks: &[WorkspaceChunk]) -> String {
+  chunks
+    .iter()
+    .map(|chunk| {
+      format!(
+        "Source: {}\n\"\"\"\n{}\n\"\"\"",
+        chunk.citation(),
+        chunk.content.trim()
+      )
+    })
+    .collect::<Vec<_>>()
+    .join("\n\n")
+}
@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Marks the start of a matched word in [SearchHit::snippet].
+pub const MATCH_START: char = '\u{2}';
+/// Marks the end of a matched word in [SearchHit::snippet].
+pub const MATCH_END: char = '\u{3}';
+
+/// What part of a chat a search hit is from.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchSource {
+  Title,
+  Goal,
+  #[default]
+  Log,
+  Message,
+}
+
+impl SearchSource {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      SearchSource::Title => "title",
+      SearchSource::Goal => "goal",
+      SearchSource::Log => "log",
+      SearchSource::Message => "message",
+    }
+  }
+}
+
+impl From<&str> for SearchSource {
+  fn from(value: &str) -> Self {
+    match value {
+      "title" => SearchSource::Title,
+      "goal" => SearchSource::Goal,
+      "message" => SearchSource::Message,
+      _ => SearchSource::Log,
+    }
+  }
+}
+
+/// A chat that matched a search, with the text that matched best.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SearchHit {
+  pub chat_id: Uuid,
+  pub chat_title: Option<String>,
+  pub source: SearchSource,
+  /// An excerpt of the text with the matched words between [MATCH_START] and [MATCH_END].
+  pub snippet: String,
+  /// When the matched text was written, to find it in the logs of the chat.
+  pub created_at: DateTime<Utc>,
+  /// The fused rank of the full-text and the semantic match, higher is better.
+  pub score: f64,
+}
+
+impl SearchHit {
+  /// The snippet in parts, `true` for the matched words.
+  pub fn snippet_parts(&self) -> Vec<(String, bool)> {
+    let mut parts = vec![];
+    for (i, part) in self.snippet.split(MATCH_START).enumerate() {
+      match part.split_once(MATCH_END) {
+        Some((matched, rest)) if i > 0 => {
+          parts.push((matched.to_string(), true));
+          parts.push((rest.to_string(), false));
+        }
+        _ => parts.push((part.replace(MATCH_END, ""), false)),
+      }
+    }
+    parts.retain(|(text, _)| !text.is_empty());
+    parts
+  }
+
+  /// The page of the chat with the matched entry highlighted.
+  pub fn url(&self) -> String {
+    match self.source {
+      SearchSource::Title => format!("/chat/{}", self.chat_id),
+      _ => format!(
+        "/chat/{}?at={}",
+        self.chat_id,
+        self.created_at.timestamp_millis()
+      ),
+    }
+  }
+}
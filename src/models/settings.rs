@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+use super::audio::Voice;
+
+/// Preferences of a user that apply to all their chats.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserSettings {
+  /// The voice assistant messages are read aloud with.
+  pub voice: Voice,
+  /// From 0.25 to 4.0.
+  pub speech_speed: f32,
+}
+
+impl Default for UserSettings {
+  fn default() -> Self {
+    Self {
+      voice: Voice::default(),
+      speech_speed: 1.0,
+    }
+  }
+}
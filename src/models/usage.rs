@@ -0,0 +1,26 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// The width of the buckets usage is rolled up into.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageBucket {
+  Hour,
+  #[default]
+  Day,
+}
+
+/// Aggregated usage of one bucket, grouped either by model or by user.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UsageRollup {
+  pub bucket: DateTime<Utc>,
+  pub user_id: Option<Uuid>,
+  pub model: Option<String>,
+  pub requests: i64,
+  pub errors: i64,
+  pub prompt_tokens: i64,
+  pub completion_tokens: i64,
+  pub total_tokens: i64,
+  pub avg_latency_ms: f64,
+}
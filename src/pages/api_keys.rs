@@ -0,0 +1,201 @@
+use leptos::*;
+use uuid::Uuid;
+
+use crate::{
+  models::api_keys::{ApiKey, CreateApiKeyRequest},
+  routes::api_keys::{create_api_key, get_api_keys, RevokeApiKey},
+};
+
+fn split_list(value: &str) -> Vec<String> {
+  value
+    .split(',')
+    .map(|v| v.trim().to_string())
+    .filter(|v| !v.is_empty())
+    .collect()
+}
+
+#[component]
+pub fn ApiKeysPage() -> impl IntoView {
+  let name = create_rw_signal(String::new());
+  let models = create_rw_signal(String::new());
+  let endpoints = create_rw_signal(String::new());
+  let expires_in_days = create_rw_signal(String::new());
+
+  let create_key = create_action(move |request: &CreateApiKeyRequest| {
+    let request = request.clone();
+    async move { create_api_key(request).await }
+  });
+  let revoke_key = create_server_action::<RevokeApiKey>();
+
+  let keys = create_resource(
+    move || (create_key.version().get(), revoke_key.version().get()),
+    move |_| get_api_keys(),
+  );
+  let secret = move || {
+    create_key
+      .value()
+      .get()
+      .and_then(|r| r.ok())
+      .map(|k| k.secret)
+  };
+  let error = move || {
+    create_key
+      .value()
+      .get()
+      .and_then(|r| r.err())
+      .map(|e| e.to_string())
+  };
+
+  let on_submit = move |ev: ev::SubmitEvent| {
+    ev.prevent_default();
+    let expires_at = expires_in_days
+      .get()
+      .trim()
+      .parse::<i64>()
+      .ok()
+      .filter(|days| *days > 0)
+      .map(|days| chrono::Utc::now() + chrono::Duration::days(days));
+    create_key.dispatch(CreateApiKeyRequest {
+      name: name.get(),
+      models: split_list(&models.get()),
+      endpoints: split_list(&endpoints.get()),
+      expires_at,
+    });
+    name.set(String::new());
+  };
+
+  view! {
+    <div class="flex w-full flex-col items-center p-4">
+      <div class="w-full max-w-3xl space-y-6">
+        <h1 class="text-2xl font-bold">"API Keys"</h1>
+        <p class="text-sm text-gray-400">
+          "Keys authenticate requests to the OpenAI compatible API at /openai/v1. "
+          "Send them as a Bearer token in the Authorization header."
+        </p>
+        <form class="grid grid-cols-1 gap-2 md:grid-cols-2" on:submit=on_submit>
+          <input
+            class="input input-bordered"
+            placeholder="Name"
+            required
+            prop:value=name
+            on:input=move |ev| name.set(event_target_value(&ev))
+          />
+          <input
+            class="input input-bordered"
+            placeholder="Expires in days (optional)"
+            type="number"
+            min="1"
+            prop:value=expires_in_days
+            on:input=move |ev| expires_in_days.set(event_target_value(&ev))
+          />
+          <input
+            class="input input-bordered"
+            placeholder="Models, comma separated (optional)"
+            prop:value=models
+            on:input=move |ev| models.set(event_target_value(&ev))
+          />
+          <input
+            class="input input-bordered"
+            placeholder="Endpoints, e.g. /chat/completions (optional)"
+            prop:value=endpoints
+            on:input=move |ev| endpoints.set(event_target_value(&ev))
+          />
+          <button class="btn btn-primary md:col-span-2" type="submit" disabled=create_key.pending()>
+            "Create key"
+          </button>
+        </form>
+        {move || {
+            secret()
+                .map(|secret| {
+                    view! {
+                      <div class="alert alert-success flex-col items-start">
+                        <span>"Copy your new key now, it will not be shown again."</span>
+                        <code class="select-all break-all">{secret}</code>
+                      </div>
+                    }
+                })
+        }}
+
+        {move || error().map(|error| view! { <div class="alert alert-error">{error}</div> })}
+        <Transition fallback=|| view! { <div class="skeleton h-32 w-full"></div> }>
+          {move || {
+              keys.get()
+                  .map(|keys| match keys {
+                      Ok(keys) => view! { <ApiKeyTable keys revoke_key/> }.into_view(),
+                      Err(e) => view! { <div class="alert alert-error">{e.to_string()}</div> }.into_view(),
+                  })
+          }}
+
+        </Transition>
+      </div>
+    </div>
+  }
+}
+
+#[component]
+fn ApiKeyTable(
+  keys: Vec<ApiKey>,
+  revoke_key: Action<RevokeApiKey, Result<(), ServerFnError>>,
+) -> impl IntoView {
+  let now = chrono::Utc::now();
+  let format_date = |date: Option<chrono::DateTime<chrono::Utc>>| {
+    date.map_or("-".to_string(), |d| d.format("%Y-%m-%d").to_string())
+  };
+  let scopes = |values: &[String]| {
+    if values.is_empty() {
+      "all".to_string()
+    } else {
+      values.join(", ")
+    }
+  };
+
+  view! {
+    <table class="table w-full">
+      <thead>
+        <tr>
+          <th>"Name"</th>
+          <th>"Key"</th>
+          <th>"Models"</th>
+          <th>"Endpoints"</th>
+          <th>"Expires"</th>
+          <th>"Last used"</th>
+          <th></th>
+        </tr>
+      </thead>
+      <tbody>
+        {keys
+            .into_iter()
+            .map(|key| {
+                let id: Uuid = key.id;
+                let is_active = key.is_active(now);
+                view! {
+                  <tr class:opacity-50=!is_active>
+                    <td>{key.name.clone()}</td>
+                    <td>
+                      <code>{format!("{}...", key.prefix)}</code>
+                    </td>
+                    <td>{scopes(&key.models)}</td>
+                    <td>{scopes(&key.endpoints)}</td>
+                    <td>{format_date(key.expires_at)}</td>
+                    <td>{format_date(key.last_used_at)}</td>
+                    <td>
+                      <Show
+                        when=move || is_active
+                        fallback=|| view! { <span class="badge">"inactive"</span> }
+                      >
+                        <button
+                          class="btn btn-sm btn-error"
+                          on:click=move |_| revoke_key.dispatch(RevokeApiKey { id })
+                        >
+                          "Revoke"
+                        </button>
+                      </Show>
+                    </td>
+                  </tr>
+                }
+            })
+            .collect_view()}
+      </tbody>
+    </table>
+  }
+}
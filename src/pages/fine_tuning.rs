@@ -0,0 +1,283 @@
+use std::collections::HashSet;
+
+use leptos::*;
+use uuid::Uuid;
+
+use crate::{
+  models::fine_tuning::{DatasetReport, TrackedJob},
+  routes::{
+    chats::get_chats,
+    fine_tuning::{get_fine_tuning_events, get_fine_tuning_jobs, PreviewDataset, UploadDataset},
+  },
+};
+
+#[component]
+pub fn FineTuningPage() -> impl IntoView {
+  let chats = create_resource(|| (), |_| get_chats());
+  let selected = create_rw_signal(HashSet::<Uuid>::new());
+  let preview = create_server_action::<PreviewDataset>();
+  let upload = create_server_action::<UploadDataset>();
+
+  let chat_ids = move || selected.get().into_iter().collect::<Vec<_>>();
+  let is_pending = move || preview.pending().get() || upload.pending().get();
+  // the report of whichever action finished last
+  let report = create_rw_signal(None::<Result<DatasetReport, String>>);
+  for value in [preview.value(), upload.value()] {
+    create_effect(move |_| {
+      if let Some(result) = value.get() {
+        report.set(Some(result.map_err(|e| e.to_string())));
+      }
+    });
+  }
+  let toggle = move |id: Uuid| {
+    selected.update(|selected| {
+      if !selected.remove(&id) {
+        selected.insert(id);
+      }
+    })
+  };
+
+  view! {
+    <div class="flex w-full flex-col items-center p-4">
+      <div class="w-full max-w-3xl space-y-6">
+        <h1 class="text-2xl font-bold">"Fine-tuning dataset"</h1>
+        <p class="text-sm text-neutral-content">
+          "Select the chats to train on. Their history is converted to the chat fine-tuning format, checked and uploaded as a training file."
+        </p>
+        <Transition fallback=move || view! { <div class="skeleton h-24 w-full"></div> }>
+          <ul class="max-h-[40vh] space-y-1 overflow-y-auto">
+            {move || {
+                chats
+                    .get()
+                    .and_then(Result::ok)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|chat| {
+                        let id = chat.id;
+                        view! {
+                          <li>
+                            <label class="label cursor-pointer justify-start space-x-2">
+                              <input
+                                type="checkbox"
+                                class="checkbox checkbox-sm"
+                                prop:checked=move || selected.with(|s| s.contains(&id))
+                                on:change=move |_| toggle(id)
+                              />
+                              <span class="label-text">
+                                {chat.title.unwrap_or_else(|| "Untitled".to_string())}
+                              </span>
+                            </label>
+                          </li>
+                        }
+                    })
+                    .collect_view()
+            }}
+          </ul>
+        </Transition>
+        <div class="flex space-x-2">
+          <button
+            class="btn btn-neutral"
+            disabled=move || selected.with(HashSet::is_empty) || is_pending()
+            on:click=move |_| preview.dispatch(PreviewDataset { chat_ids: chat_ids() })
+          >
+            "Preview"
+          </button>
+          <button
+            class="btn btn-primary"
+            disabled=move || selected.with(HashSet::is_empty) || is_pending()
+            on:click=move |_| upload.dispatch(UploadDataset { chat_ids: chat_ids() })
+          >
+            "Upload training file"
+          </button>
+        </div>
+        {move || match report.get() {
+            Some(Ok(report)) => view! { <DatasetSummary report/> }.into_view(),
+            Some(Err(error)) => view! { <div class="alert alert-error">{error}</div> }.into_view(),
+            None => ().into_view(),
+        }}
+        <FineTuningJobs/>
+      </div>
+    </div>
+  }
+}
+
+/// The jobs created through the API, refreshed in the background until they finish.
+#[component]
+fn FineTuningJobs() -> impl IntoView {
+  let jobs = create_resource(|| (), |_| get_fine_tuning_jobs());
+
+  view! {
+    <div class="space-y-2">
+      <div class="flex items-center justify-between">
+        <h2 class="text-xl font-bold">"Jobs"</h2>
+        <button class="btn btn-ghost btn-sm" on:click=move |_| jobs.refetch()>
+          "Refresh"
+        </button>
+      </div>
+      <Transition fallback=move || view! { <div class="skeleton h-24 w-full"></div> }>
+        {move || match jobs.get() {
+            Some(Ok(jobs)) if jobs.is_empty() => {
+                view! {
+                  <p class="text-sm text-neutral-content">
+                    "No fine-tuning jobs yet, create one with an uploaded training file through "
+                    <code>"/api/v1/localai/fine_tuning/jobs"</code> "."
+                  </p>
+                }
+                    .into_view()
+            }
+            Some(Ok(jobs)) => {
+                jobs.into_iter().map(|job| view! { <JobCard job/> }).collect_view()
+            }
+            Some(Err(e)) => view! { <div class="alert alert-error">{e.to_string()}</div> }.into_view(),
+            None => ().into_view(),
+        }}
+      </Transition>
+    </div>
+  }
+}
+
+#[component]
+fn JobCard(job: TrackedJob) -> impl IntoView {
+  let expanded = create_rw_signal(false);
+  let job_id = job.id.clone();
+  let events = create_resource(
+    move || expanded.get(),
+    move |expanded| {
+      let job_id = job_id.clone();
+      async move {
+        if expanded {
+          get_fine_tuning_events(job_id).await
+        } else {
+          Ok(vec![])
+        }
+      }
+    },
+  );
+  let badge = match job.status.as_str() {
+    "succeeded" => "badge-success",
+    "failed" => "badge-error",
+    "cancelled" => "badge-ghost",
+    _ => "badge-info",
+  };
+  let finished = job.is_finished();
+
+  view! {
+    <div class="card card-compact bg-base-200">
+      <div class="card-body">
+        <div class="flex items-center justify-between">
+          <code class="text-sm">{job.id.clone()}</code>
+          <span class=format!("badge {badge}")>{job.status.replace('_', " ")}</span>
+        </div>
+        <div class="text-sm">
+          <div>"Base model: " {job.model.clone()}</div>
+          <div>"Training file: " <code>{job.training_file.clone()}</code></div>
+          {job
+              .fine_tuned_model
+              .clone()
+              .map(|model| view! { <div>"Fine-tuned model: " <code>{model}</code></div> })}
+          {job.trained_tokens.map(|tokens| view! { <div>"Trained tokens: " {tokens}</div> })}
+          {job.error.clone().map(|error| view! { <div class="text-error">{error}</div> })}
+          <div class="text-neutral-content">
+            "Created " {job.created_at.format("%Y-%m-%d %H:%M").to_string()}
+            {job
+                .finished_at
+                .map(|at| format!(", finished {}", at.format("%Y-%m-%d %H:%M")))}
+            {(!finished)
+                .then(|| format!(", last checked {}", job.updated_at.format("%H:%M")))}
+          </div>
+        </div>
+        <button class="btn btn-ghost btn-xs self-start" on:click=move |_| expanded.update(|e| *e = !*e)>
+          {move || if expanded.get() { "Hide events" } else { "Show events" }}
+        </button>
+        <Show when=move || expanded.get()>
+          <Transition fallback=move || view! { <div class="skeleton h-12 w-full"></div> }>
+            <ul class="timeline timeline-vertical timeline-compact">
+              {move || match events.get() {
+                  Some(Ok(events)) => {
+                      events
+                          .into_iter()
+                          .map(|event| {
+                              let level = match event.level.as_str() {
+                                  "error" => "text-error",
+                                  "warn" => "text-warning",
+                                  _ => "",
+                              };
+                              view! {
+                                <li>
+                                  <div class="timeline-start text-xs text-neutral-content">
+                                    {event.created_at.format("%m-%d %H:%M:%S").to_string()}
+                                  </div>
+                                  <div class=format!("timeline-end text-sm {level}")>{event.message}</div>
+                                  <hr/>
+                                </li>
+                              }
+                          })
+                          .collect_view()
+                  }
+                  Some(Err(e)) => view! { <li class="text-error">{e.to_string()}</li> }.into_view(),
+                  None => ().into_view(),
+              }}
+            </ul>
+          </Transition>
+        </Show>
+      </div>
+    </div>
+  }
+}
+
+#[component]
+fn DatasetSummary(report: DatasetReport) -> impl IntoView {
+  let stats = [
+    ("Examples", report.examples),
+    ("Skipped chats", report.skipped_chats),
+    ("Messages", report.messages),
+    ("Assistant messages", report.assistant_messages),
+    ("Tool calls", report.tool_calls),
+    ("Tokens (est.)", report.total_tokens),
+    ("Tokens per example", report.mean_tokens),
+    ("Largest example", report.max_tokens),
+  ];
+
+  view! {
+    <div class="space-y-4">
+      <div class="stats stats-vertical w-full shadow md:stats-horizontal md:flex-wrap">
+        {stats
+            .into_iter()
+            .map(|(title, value)| {
+                view! {
+                  <div class="stat">
+                    <div class="stat-title">{title}</div>
+                    <div class="stat-value text-2xl">{value}</div>
+                  </div>
+                }
+            })
+            .collect_view()}
+      </div>
+      {report
+          .file_id
+          .map(|file_id| {
+              view! {
+                <div class="alert alert-success">
+                  "Uploaded " {report.file_name.unwrap_or_default()} " as " <code>{file_id}</code>
+                  ", ready to create a fine-tuning job."
+                </div>
+              }
+          })}
+      <ul class="space-y-1">
+        {report
+            .warnings
+            .into_iter()
+            .map(|warning| {
+                let chat = warning.chat_id.map(|id| format!("{id}: ")).unwrap_or_default();
+                view! {
+                  <li class="alert py-2 text-sm" class:alert-warning=!warning.skipped class:alert-error=warning.skipped>
+                    {chat}
+                    {warning.message}
+                  </li>
+                }
+            })
+            .collect_view()}
+      </ul>
+    </div>
+  }
+}
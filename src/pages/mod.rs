@@ -1,6 +1,12 @@
 mod about;
+mod api_keys;
 mod chat;
+mod fine_tuning;
 mod homepage;
+mod settings;
 
 pub use about::AboutPage;
+pub use api_keys::ApiKeysPage;
 pub use chat::ChatPage;
+pub use fine_tuning::FineTuningPage;
+pub use settings::SettingsPage;
@@ -0,0 +1,97 @@
+use leptos::*;
+
+use crate::{
+  models::{audio::Voice, settings::UserSettings},
+  routes::settings::{get_user_settings, SaveUserSettings},
+};
+
+#[component]
+pub fn SettingsPage() -> impl IntoView {
+  let save_settings = create_server_action::<SaveUserSettings>();
+  let settings = create_resource(
+    move || save_settings.version().get(),
+    move |_| get_user_settings(),
+  );
+  let voice = create_rw_signal(Voice::default());
+  let speech_speed = create_rw_signal(1.0f32);
+  create_effect(move |_| {
+    if let Some(Ok(settings)) = settings.get() {
+      voice.set(settings.voice);
+      speech_speed.set(settings.speech_speed);
+    }
+  });
+  let error = move || {
+    save_settings
+      .value()
+      .get()
+      .and_then(|r| r.err())
+      .map(|e| e.to_string())
+  };
+
+  let on_submit = move |ev: ev::SubmitEvent| {
+    ev.prevent_default();
+    save_settings.dispatch(SaveUserSettings {
+      settings: UserSettings {
+        voice: voice.get(),
+        speech_speed: speech_speed.get(),
+      },
+    });
+  };
+
+  view! {
+    <div class="flex w-full flex-col items-center p-4">
+      <div class="w-full max-w-3xl space-y-6">
+        <h1 class="text-2xl font-bold">"Settings"</h1>
+        <form class="grid grid-cols-1 gap-4 md:grid-cols-2" on:submit=on_submit>
+          <label class="form-control">
+            <span class="label-text">"Voice for reading messages aloud"</span>
+            <select
+              class="select select-bordered"
+              on:change=move |ev| voice.set(event_target_value(&ev).as_str().into())
+            >
+              {Voice::BUILTIN
+                  .into_iter()
+                  .map(|option| {
+                      let value = option.as_str().to_string();
+                      let is_selected = move || voice.get() == option;
+                      view! {
+                        <option value=value.clone() selected=is_selected>
+                          {value.clone()}
+                        </option>
+                      }
+                  })
+                  .collect_view()}
+            </select>
+          </label>
+          <label class="form-control">
+            <span class="label-text">
+              "Speed " {move || format!("{:.2}x", speech_speed.get())}
+            </span>
+            <input
+              class="range range-sm"
+              type="range"
+              min="0.25"
+              max="4"
+              step="0.25"
+              prop:value=move || speech_speed.get().to_string()
+              on:input=move |ev| {
+                  if let Ok(speed) = event_target_value(&ev).parse() {
+                      speech_speed.set(speed);
+                  }
+              }
+            />
+
+          </label>
+          <button
+            class="btn btn-primary md:col-span-2"
+            type="submit"
+            disabled=save_settings.pending()
+          >
+            "Save"
+          </button>
+        </form>
+        {move || error().map(|error| view! { <div class="alert alert-error">{error}</div> })}
+      </div>
+    </div>
+  }
+}
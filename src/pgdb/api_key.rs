@@ -0,0 +1,106 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+  models::api_keys::{ApiKey, CreateApiKeyRequest, CreatedApiKey},
+  Error, Result,
+};
+
+const KEY_PREFIX: &str = "mk-";
+
+/// Generates a new secret, two v4 uuids give 244 random bits.
+fn generate_secret() -> String {
+  format!(
+    "{KEY_PREFIX}{}{}",
+    Uuid::new_v4().simple(),
+    Uuid::new_v4().simple()
+  )
+}
+
+impl ApiKey {
+  pub async fn create(
+    user_id: Uuid,
+    request: CreateApiKeyRequest,
+    pool: &PgPool,
+  ) -> Result<CreatedApiKey> {
+    let secret = generate_secret();
+    let prefix: String = secret.chars().take(KEY_PREFIX.len() + 6).collect();
+    let key = sqlx::query_as!(
+      ApiKey,
+      r#"
+        INSERT INTO api_keys(user_id, name, prefix, key_hash, models, endpoints, expires_at)
+        VALUES ($1, $2, $3, encode(sha256(convert_to($4, 'UTF8')), 'hex'), $5, $6, $7)
+        RETURNING id, user_id, name, prefix, models, endpoints, expires_at, last_used_at,
+          revoked_at, created_at
+      "#,
+      user_id,
+      request.name,
+      prefix,
+      secret,
+      &request.models,
+      &request.endpoints,
+      request.expires_at,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(CreatedApiKey { key, secret })
+  }
+
+  pub async fn list_for_user(user_id: Uuid, pool: &PgPool) -> Result<Vec<ApiKey>> {
+    let keys = sqlx::query_as!(
+      ApiKey,
+      r#"
+        SELECT id, user_id, name, prefix, models, endpoints, expires_at, last_used_at,
+          revoked_at, created_at
+        FROM api_keys
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+      "#,
+      user_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(keys)
+  }
+
+  pub async fn revoke(user_id: Uuid, id: Uuid, pool: &PgPool) -> Result<()> {
+    let result = sqlx::query!(
+      r#"
+        UPDATE api_keys
+        SET revoked_at = COALESCE(revoked_at, now()), updated_at = now()
+        WHERE user_id = $1 AND id = $2
+      "#,
+      user_id,
+      id,
+    )
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+      return Err(Error::NotFound(format!("api key {id}")));
+    }
+    Ok(())
+  }
+
+  /// Looks up a key by its secret and marks it as used, revoked and expired keys are not returned.
+  pub async fn authenticate(secret: &str, pool: &PgPool) -> Result<Option<ApiKey>> {
+    if !secret.starts_with(KEY_PREFIX) {
+      return Ok(None);
+    }
+    let key = sqlx::query_as!(
+      ApiKey,
+      r#"
+        UPDATE api_keys
+        SET last_used_at = now()
+        WHERE key_hash = encode(sha256(convert_to($1, 'UTF8')), 'hex')
+          AND revoked_at IS NULL
+          AND (expires_at IS NULL OR expires_at > now())
+        RETURNING id, user_id, name, prefix, models, endpoints, expires_at, last_used_at,
+          revoked_at, created_at
+      "#,
+      secret
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(key)
+  }
+}
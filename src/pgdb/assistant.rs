@@ -0,0 +1,813 @@
+use chrono::prelude::*;
+use sqlx::{types::Json, PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::{
+  models::{
+    assistants::{
+      AssistantObject, AssistantTools, CreateAssistantRequest, LastError, ListQuery, ListResponse,
+      MessageContent, MessageObject, MessageRole, Metadata, ModifyAssistantRequest, RequiredAction,
+      RunObject, RunStatus, TextData, ThreadObject,
+    },
+    ChatCompletionMessageToolCall, ChatCompletionRequestAssistantMessage,
+    ChatCompletionRequestSystemMessage, ChatCompletionRequestToolMessage,
+    ChatCompletionRequestUserMessage, ChatCompletionRequestUserMessageContent, ChatMessage, Role,
+  },
+  Error, Result,
+};
+
+/// Parses an object id received from a client, ids are opaque strings on the wire.
+pub fn parse_object_id(kind: &str, id: &str) -> Result<Uuid> {
+  Uuid::parse_str(id).map_err(|_| Error::NotFound(format!("{kind} {id}")))
+}
+
+pub(super) fn after_cursor(query: &ListQuery, kind: &str) -> Result<Option<Uuid>> {
+  query
+    .after
+    .as_deref()
+    .map(|id| parse_object_id(kind, id))
+    .transpose()
+}
+
+/// Turns a page fetched with `limit + 1` rows into an OpenAI list response.
+pub(super) fn into_list<R, T>(
+  mut rows: Vec<R>,
+  limit: i64,
+  id_of: fn(&T) -> String,
+) -> ListResponse<T>
+where
+  R: Into<T>,
+{
+  let has_more = rows.len() as i64 > limit;
+  rows.truncate(limit as usize);
+  let data: Vec<T> = rows.into_iter().map(Into::into).collect();
+  ListResponse {
+    object: "list".to_string(),
+    first_id: data.first().map(id_of),
+    last_id: data.last().map(id_of),
+    data,
+    has_more,
+  }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Assistant {
+  pub id: Uuid,
+  pub user_id: Uuid,
+  pub model: String,
+  pub name: Option<String>,
+  pub description: Option<String>,
+  pub instructions: Option<String>,
+  pub tools: Json<Vec<AssistantTools>>,
+  pub file_ids: Vec<String>,
+  pub metadata: Option<Json<Metadata>>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+impl From<Assistant> for AssistantObject {
+  fn from(value: Assistant) -> Self {
+    Self {
+      id: value.id.to_string(),
+      object: "assistant".to_string(),
+      created_at: value.created_at.timestamp(),
+      name: value.name,
+      description: value.description,
+      model: value.model,
+      instructions: value.instructions,
+      tools: value.tools.0,
+      file_ids: value.file_ids,
+      metadata: value.metadata.map(|m| m.0),
+    }
+  }
+}
+
+impl Assistant {
+  pub async fn create(
+    user_id: Uuid,
+    request: CreateAssistantRequest,
+    pool: &PgPool,
+  ) -> Result<Assistant> {
+    let assistant = sqlx::query_as!(
+      Assistant,
+      r#"
+        INSERT INTO assistants(
+          user_id, model, name, description, instructions, tools, file_ids, metadata
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id, user_id, model, name, description, instructions,
+          tools AS "tools: Json<Vec<AssistantTools>>", file_ids,
+          metadata AS "metadata: Json<Metadata>", created_at, updated_at
+      "#,
+      user_id,
+      request.model,
+      request.name,
+      request.description,
+      request.instructions,
+      Json(request.tools.unwrap_or_default()) as _,
+      &request.file_ids.unwrap_or_default(),
+      request.metadata.map(Json) as _,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(assistant)
+  }
+
+  /// An assistant of `user_id`, the assistants of other users are not found.
+  pub async fn get(user_id: Uuid, id: Uuid, pool: &PgPool) -> Result<Assistant> {
+    sqlx::query_as!(
+      Assistant,
+      r#"
+        SELECT id, user_id, model, name, description, instructions,
+          tools AS "tools: Json<Vec<AssistantTools>>", file_ids,
+          metadata AS "metadata: Json<Metadata>", created_at, updated_at
+        FROM assistants
+        WHERE id = $1 AND user_id = $2
+      "#,
+      id,
+      user_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| Error::NotFound(format!("assistant {id}")))
+  }
+
+  pub async fn list(
+    user_id: Uuid,
+    query: &ListQuery,
+    pool: &PgPool,
+  ) -> Result<ListResponse<AssistantObject>> {
+    let limit = query.page_size();
+    let rows = sqlx::query_as!(
+      Assistant,
+      r#"
+        SELECT id, user_id, model, name, description, instructions,
+          tools AS "tools: Json<Vec<AssistantTools>>", file_ids,
+          metadata AS "metadata: Json<Metadata>", created_at, updated_at
+        FROM assistants
+        WHERE user_id = $4
+          AND (
+            $1::uuid IS NULL
+            OR ($2 AND created_at > (SELECT created_at FROM assistants WHERE id = $1))
+            OR (NOT $2 AND created_at < (SELECT created_at FROM assistants WHERE id = $1))
+          )
+        ORDER BY
+          CASE WHEN $2 THEN created_at END ASC,
+          CASE WHEN NOT $2 THEN created_at END DESC
+        LIMIT $3
+      "#,
+      after_cursor(query, "assistant")?,
+      query.is_ascending(),
+      limit + 1,
+      user_id,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(into_list(rows, limit, |a: &AssistantObject| a.id.clone()))
+  }
+
+  pub async fn update(
+    user_id: Uuid,
+    id: Uuid,
+    request: ModifyAssistantRequest,
+    pool: &PgPool,
+  ) -> Result<Assistant> {
+    sqlx::query_as!(
+      Assistant,
+      r#"
+        UPDATE assistants
+        SET
+          model = COALESCE($2, model),
+          name = COALESCE($3, name),
+          description = COALESCE($4, description),
+          instructions = COALESCE($5, instructions),
+          tools = COALESCE($6, tools),
+          file_ids = COALESCE($7, file_ids),
+          metadata = COALESCE($8, metadata),
+          updated_at = now()
+        WHERE id = $1 AND user_id = $9
+        RETURNING id, user_id, model, name, description, instructions,
+          tools AS "tools: Json<Vec<AssistantTools>>", file_ids,
+          metadata AS "metadata: Json<Metadata>", created_at, updated_at
+      "#,
+      id,
+      request.model,
+      request.name,
+      request.description,
+      request.instructions,
+      request.tools.map(Json) as _,
+      request.file_ids.as_deref(),
+      request.metadata.map(Json) as _,
+      user_id,
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| Error::NotFound(format!("assistant {id}")))
+  }
+
+  pub async fn delete(user_id: Uuid, id: Uuid, pool: &PgPool) -> Result<bool> {
+    let result = sqlx::query!(
+      "DELETE FROM assistants WHERE id = $1 AND user_id = $2",
+      id,
+      user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+  }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Thread {
+  pub id: Uuid,
+  pub user_id: Uuid,
+  pub metadata: Option<Json<Metadata>>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+impl From<Thread> for ThreadObject {
+  fn from(value: Thread) -> Self {
+    Self {
+      id: value.id.to_string(),
+      object: "thread".to_string(),
+      created_at: value.created_at.timestamp(),
+      metadata: value.metadata.map(|m| m.0),
+    }
+  }
+}
+
+impl Thread {
+  pub async fn create<'e, E: PgExecutor<'e>>(
+    user_id: Uuid,
+    metadata: Option<Metadata>,
+    executor: E,
+  ) -> Result<Thread> {
+    let thread = sqlx::query_as!(
+      Thread,
+      r#"
+        INSERT INTO threads(user_id, metadata)
+        VALUES ($1, $2)
+        RETURNING id, user_id, metadata AS "metadata: Json<Metadata>", created_at, updated_at
+      "#,
+      user_id,
+      metadata.map(Json) as _,
+    )
+    .fetch_one(executor)
+    .await?;
+    Ok(thread)
+  }
+
+  /// A thread of `user_id`, the threads of other users are not found.
+  pub async fn get(user_id: Uuid, id: Uuid, pool: &PgPool) -> Result<Thread> {
+    sqlx::query_as!(
+      Thread,
+      r#"
+        SELECT id, user_id, metadata AS "metadata: Json<Metadata>", created_at, updated_at
+        FROM threads
+        WHERE id = $1 AND user_id = $2
+      "#,
+      id,
+      user_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| Error::NotFound(format!("thread {id}")))
+  }
+
+  pub async fn update_metadata(
+    user_id: Uuid,
+    id: Uuid,
+    metadata: Option<Metadata>,
+    pool: &PgPool,
+  ) -> Result<Thread> {
+    sqlx::query_as!(
+      Thread,
+      r#"
+        UPDATE threads
+        SET metadata = COALESCE($3, metadata), updated_at = now()
+        WHERE id = $1 AND user_id = $2
+        RETURNING id, user_id, metadata AS "metadata: Json<Metadata>", created_at, updated_at
+      "#,
+      id,
+      user_id,
+      metadata.map(Json) as _,
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| Error::NotFound(format!("thread {id}")))
+  }
+
+  pub async fn delete(user_id: Uuid, id: Uuid, pool: &PgPool) -> Result<bool> {
+    let result = sqlx::query!(
+      "DELETE FROM threads WHERE id = $1 AND user_id = $2",
+      id,
+      user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+  }
+}
+
+/// A message in an assistants thread, stored in the shared `messages` table.
+///
+/// Tool call round-trips are persisted as `temporary` messages: the model needs them to continue
+/// a run, but they are not part of the conversation the Assistants API exposes.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ThreadMessage {
+  pub id: Uuid,
+  pub thread_id: Uuid,
+  pub role: String,
+  pub content: Option<String>,
+  pub tool_calls: Option<Json<Vec<ChatCompletionMessageToolCall>>>,
+  pub tool_call_id: Option<String>,
+  pub temporary: bool,
+  pub assistant_id: Option<Uuid>,
+  pub run_id: Option<Uuid>,
+  pub file_ids: Vec<String>,
+  pub metadata: Option<Json<Metadata>>,
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NewThreadMessage {
+  pub role: String,
+  pub content: Option<String>,
+  pub tool_calls: Option<Vec<ChatCompletionMessageToolCall>>,
+  pub tool_call_id: Option<String>,
+  pub temporary: bool,
+  pub assistant_id: Option<Uuid>,
+  pub run_id: Option<Uuid>,
+  pub file_ids: Vec<String>,
+  pub metadata: Option<Metadata>,
+}
+
+impl From<ThreadMessage> for MessageObject {
+  fn from(value: ThreadMessage) -> Self {
+    Self {
+      id: value.id.to_string(),
+      object: "thread.message".to_string(),
+      created_at: value.created_at.timestamp(),
+      thread_id: value.thread_id.to_string(),
+      role: if value.role == "assistant" {
+        MessageRole::Assistant
+      } else {
+        MessageRole::User
+      },
+      content: vec![MessageContent::Text {
+        text: TextData {
+          value: value.content.unwrap_or_default(),
+          annotations: vec![],
+        },
+      }],
+      assistant_id: value.assistant_id.map(|id| id.to_string()),
+      run_id: value.run_id.map(|id| id.to_string()),
+      file_ids: value.file_ids,
+      metadata: value.metadata.map(|m| m.0),
+    }
+  }
+}
+
+impl From<ThreadMessage> for ChatMessage {
+  fn from(value: ThreadMessage) -> Self {
+    match value.role.as_str() {
+      "system" => ChatMessage::System(ChatCompletionRequestSystemMessage {
+        content: value.content.unwrap_or_default(),
+        role: Role::System,
+        name: None,
+      }),
+      "assistant" => ChatMessage::Assistant(ChatCompletionRequestAssistantMessage {
+        content: value.content,
+        role: Role::Assistant,
+        name: None,
+        tool_calls: value.tool_calls.map(|v| v.0),
+      }),
+      "tool" => ChatMessage::Tool(ChatCompletionRequestToolMessage {
+        role: Role::Tool,
+        content: value.content.unwrap_or_default(),
+        tool_call_id: value.tool_call_id.unwrap_or_default(),
+      }),
+      _ => ChatMessage::User(ChatCompletionRequestUserMessage {
+        content: ChatCompletionRequestUserMessageContent::Text(value.content.unwrap_or_default()),
+        role: Role::User,
+        name: None,
+      }),
+    }
+  }
+}
+
+impl ThreadMessage {
+  pub async fn create<'e, E: PgExecutor<'e>>(
+    thread_id: Uuid,
+    message: NewThreadMessage,
+    executor: E,
+  ) -> Result<ThreadMessage> {
+    let message = sqlx::query_as!(
+      ThreadMessage,
+      r#"
+        INSERT INTO messages(
+          thread_id, role, content, tool_calls, tool_call_id, temporary, assistant_id, run_id,
+          file_ids, metadata
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING id, thread_id AS "thread_id!", role, content,
+          tool_calls AS "tool_calls: Json<Vec<ChatCompletionMessageToolCall>>", tool_call_id,
+          temporary, assistant_id, run_id, file_ids, metadata AS "metadata: Json<Metadata>",
+          created_at
+      "#,
+      thread_id,
+      message.role,
+      message.content,
+      message.tool_calls.map(Json) as _,
+      message.tool_call_id,
+      message.temporary,
+      message.assistant_id,
+      message.run_id,
+      &message.file_ids,
+      message.metadata.map(Json) as _,
+    )
+    .fetch_one(executor)
+    .await?;
+    Ok(message)
+  }
+
+  pub async fn get(thread_id: Uuid, id: Uuid, pool: &PgPool) -> Result<ThreadMessage> {
+    sqlx::query_as!(
+      ThreadMessage,
+      r#"
+        SELECT id, thread_id AS "thread_id!", role, content,
+          tool_calls AS "tool_calls: Json<Vec<ChatCompletionMessageToolCall>>", tool_call_id,
+          temporary, assistant_id, run_id, file_ids, metadata AS "metadata: Json<Metadata>",
+          created_at
+        FROM messages
+        WHERE thread_id = $1 AND id = $2 AND NOT temporary
+      "#,
+      thread_id,
+      id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| Error::NotFound(format!("message {id}")))
+  }
+
+  pub async fn list(
+    thread_id: Uuid,
+    query: &ListQuery,
+    pool: &PgPool,
+  ) -> Result<ListResponse<MessageObject>> {
+    let limit = query.page_size();
+    let rows = sqlx::query_as!(
+      ThreadMessage,
+      r#"
+        SELECT id, thread_id AS "thread_id!", role, content,
+          tool_calls AS "tool_calls: Json<Vec<ChatCompletionMessageToolCall>>", tool_call_id,
+          temporary, assistant_id, run_id, file_ids, metadata AS "metadata: Json<Metadata>",
+          created_at
+        FROM messages
+        WHERE thread_id = $1 AND NOT temporary
+          AND (
+            $2::uuid IS NULL
+            OR ($3 AND created_at > (SELECT created_at FROM messages WHERE id = $2))
+            OR (NOT $3 AND created_at < (SELECT created_at FROM messages WHERE id = $2))
+          )
+        ORDER BY
+          CASE WHEN $3 THEN created_at END ASC,
+          CASE WHEN NOT $3 THEN created_at END DESC
+        LIMIT $4
+      "#,
+      thread_id,
+      after_cursor(query, "message")?,
+      query.is_ascending(),
+      limit + 1,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(into_list(rows, limit, |m: &MessageObject| m.id.clone()))
+  }
+
+  /// The full conversation of a thread in chronological order, including tool call round-trips.
+  pub async fn history(thread_id: Uuid, pool: &PgPool) -> Result<Vec<ChatMessage>> {
+    let rows = sqlx::query_as!(
+      ThreadMessage,
+      r#"
+        SELECT id, thread_id AS "thread_id!", role, content,
+          tool_calls AS "tool_calls: Json<Vec<ChatCompletionMessageToolCall>>", tool_call_id,
+          temporary, assistant_id, run_id, file_ids, metadata AS "metadata: Json<Metadata>",
+          created_at
+        FROM messages
+        WHERE thread_id = $1
+        ORDER BY created_at ASC
+      "#,
+      thread_id,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(Into::into).collect())
+  }
+
+  pub async fn update_metadata(
+    thread_id: Uuid,
+    id: Uuid,
+    metadata: Option<Metadata>,
+    pool: &PgPool,
+  ) -> Result<ThreadMessage> {
+    sqlx::query_as!(
+      ThreadMessage,
+      r#"
+        UPDATE messages
+        SET metadata = COALESCE($3, metadata), updated_at = now()
+        WHERE thread_id = $1 AND id = $2 AND NOT temporary
+        RETURNING id, thread_id AS "thread_id!", role, content,
+          tool_calls AS "tool_calls: Json<Vec<ChatCompletionMessageToolCall>>", tool_call_id,
+          temporary, assistant_id, run_id, file_ids, metadata AS "metadata: Json<Metadata>",
+          created_at
+      "#,
+      thread_id,
+      id,
+      metadata.map(Json) as _,
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| Error::NotFound(format!("message {id}")))
+  }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Run {
+  pub id: Uuid,
+  pub user_id: Uuid,
+  pub thread_id: Uuid,
+  pub assistant_id: Option<Uuid>,
+  pub status: String,
+  pub required_action: Option<Json<RequiredAction>>,
+  pub last_error: Option<Json<LastError>>,
+  pub model: String,
+  pub instructions: String,
+  pub tools: Json<Vec<AssistantTools>>,
+  pub file_ids: Vec<String>,
+  pub metadata: Option<Json<Metadata>>,
+  pub expires_at: Option<DateTime<Utc>>,
+  pub started_at: Option<DateTime<Utc>>,
+  pub cancelled_at: Option<DateTime<Utc>>,
+  pub failed_at: Option<DateTime<Utc>>,
+  pub completed_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NewRun {
+  pub assistant_id: Option<Uuid>,
+  pub model: String,
+  pub instructions: String,
+  pub tools: Vec<AssistantTools>,
+  pub file_ids: Vec<String>,
+  pub metadata: Option<Metadata>,
+}
+
+impl From<Run> for RunObject {
+  fn from(value: Run) -> Self {
+    Self {
+      id: value.id.to_string(),
+      object: "thread.run".to_string(),
+      created_at: value.created_at.timestamp(),
+      thread_id: value.thread_id.to_string(),
+      assistant_id: value.assistant_id.map(|id| id.to_string()),
+      status: value.status.parse().unwrap_or_default(),
+      required_action: value.required_action.map(|v| v.0),
+      last_error: value.last_error.map(|v| v.0),
+      expires_at: value.expires_at.map(|v| v.timestamp()),
+      started_at: value.started_at.map(|v| v.timestamp()),
+      cancelled_at: value.cancelled_at.map(|v| v.timestamp()),
+      failed_at: value.failed_at.map(|v| v.timestamp()),
+      completed_at: value.completed_at.map(|v| v.timestamp()),
+      model: value.model,
+      instructions: value.instructions,
+      tools: value.tools.0,
+      file_ids: value.file_ids,
+      metadata: value.metadata.map(|m| m.0),
+    }
+  }
+}
+
+impl Run {
+  pub fn status(&self) -> RunStatus {
+    self.status.parse().unwrap_or_default()
+  }
+
+  pub async fn create<'e, E: PgExecutor<'e>>(
+    user_id: Uuid,
+    thread_id: Uuid,
+    run: NewRun,
+    executor: E,
+  ) -> Result<Run> {
+    let run = sqlx::query_as!(
+      Run,
+      r#"
+        INSERT INTO runs(
+          user_id, thread_id, assistant_id, model, instructions, tools, file_ids, metadata
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id, user_id, thread_id, assistant_id, status,
+          required_action AS "required_action: Json<RequiredAction>",
+          last_error AS "last_error: Json<LastError>", model, instructions,
+          tools AS "tools: Json<Vec<AssistantTools>>", file_ids,
+          metadata AS "metadata: Json<Metadata>", expires_at, started_at, cancelled_at,
+          failed_at, completed_at, created_at, updated_at
+      "#,
+      user_id,
+      thread_id,
+      run.assistant_id,
+      run.model,
+      run.instructions,
+      Json(run.tools) as _,
+      &run.file_ids,
+      run.metadata.map(Json) as _,
+    )
+    .fetch_one(executor)
+    .await?;
+    Ok(run)
+  }
+
+  /// A run of a thread of `user_id`, the runs of other users are not found.
+  pub async fn get(user_id: Uuid, thread_id: Uuid, id: Uuid, pool: &PgPool) -> Result<Run> {
+    sqlx::query_as!(
+      Run,
+      r#"
+        SELECT id, user_id, thread_id, assistant_id, status,
+          required_action AS "required_action: Json<RequiredAction>",
+          last_error AS "last_error: Json<LastError>", model, instructions,
+          tools AS "tools: Json<Vec<AssistantTools>>", file_ids,
+          metadata AS "metadata: Json<Metadata>", expires_at, started_at, cancelled_at,
+          failed_at, completed_at, created_at, updated_at
+        FROM runs
+        WHERE thread_id = $1 AND id = $2 AND user_id = $3
+      "#,
+      thread_id,
+      id,
+      user_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| Error::NotFound(format!("run {id}")))
+  }
+
+  pub async fn list(
+    user_id: Uuid,
+    thread_id: Uuid,
+    query: &ListQuery,
+    pool: &PgPool,
+  ) -> Result<ListResponse<RunObject>> {
+    let limit = query.page_size();
+    let rows = sqlx::query_as!(
+      Run,
+      r#"
+        SELECT id, user_id, thread_id, assistant_id, status,
+          required_action AS "required_action: Json<RequiredAction>",
+          last_error AS "last_error: Json<LastError>", model, instructions,
+          tools AS "tools: Json<Vec<AssistantTools>>", file_ids,
+          metadata AS "metadata: Json<Metadata>", expires_at, started_at, cancelled_at,
+          failed_at, completed_at, created_at, updated_at
+        FROM runs
+        WHERE thread_id = $1 AND user_id = $5
+          AND (
+            $2::uuid IS NULL
+            OR ($3 AND created_at > (SELECT created_at FROM runs WHERE id = $2))
+            OR (NOT $3 AND created_at < (SELECT created_at FROM runs WHERE id = $2))
+          )
+        ORDER BY
+          CASE WHEN $3 THEN created_at END ASC,
+          CASE WHEN NOT $3 THEN created_at END DESC
+        LIMIT $4
+      "#,
+      thread_id,
+      after_cursor(query, "run")?,
+      query.is_ascending(),
+      limit + 1,
+      user_id,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(into_list(rows, limit, |r: &RunObject| r.id.clone()))
+  }
+
+  pub async fn update_metadata(
+    user_id: Uuid,
+    thread_id: Uuid,
+    id: Uuid,
+    metadata: Option<Metadata>,
+    pool: &PgPool,
+  ) -> Result<Run> {
+    sqlx::query_as!(
+      Run,
+      r#"
+        UPDATE runs
+        SET metadata = COALESCE($3, metadata), updated_at = now()
+        WHERE thread_id = $1 AND id = $2 AND user_id = $4
+        RETURNING id, user_id, thread_id, assistant_id, status,
+          required_action AS "required_action: Json<RequiredAction>",
+          last_error AS "last_error: Json<LastError>", model, instructions,
+          tools AS "tools: Json<Vec<AssistantTools>>", file_ids,
+          metadata AS "metadata: Json<Metadata>", expires_at, started_at, cancelled_at,
+          failed_at, completed_at, created_at, updated_at
+      "#,
+      thread_id,
+      id,
+      metadata.map(Json) as _,
+      user_id,
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| Error::NotFound(format!("run {id}")))
+  }
+
+  /// Moves a run to `to`, but only while it is in one of the `from` states.
+  ///
+  /// Returns `None` when the run was in another state, which is how the executor notices that a
+  /// run was cancelled underneath it.
+  /// Queues the runs that were queued or in progress when the server stopped again, and cancels
+  /// the ones that were being cancelled.
+  pub async fn requeue_unfinished(pool: &PgPool) -> Result<Vec<Run>> {
+    sqlx::query!(
+      r#"
+        UPDATE runs SET status = 'cancelled', cancelled_at = now(), updated_at = now()
+        WHERE status = 'cancelling'
+      "#
+    )
+    .execute(pool)
+    .await?;
+    let runs = sqlx::query_as!(
+      Run,
+      r#"
+        UPDATE runs SET status = 'queued', updated_at = now()
+        WHERE status IN ('queued', 'in_progress')
+        RETURNING id, user_id, thread_id, assistant_id, status,
+          required_action AS "required_action: Json<RequiredAction>",
+          last_error AS "last_error: Json<LastError>", model, instructions,
+          tools AS "tools: Json<Vec<AssistantTools>>", file_ids,
+          metadata AS "metadata: Json<Metadata>", expires_at, started_at, cancelled_at,
+          failed_at, completed_at, created_at, updated_at
+      "#
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(runs)
+  }
+
+  /// Counts a round of tools the app ran for the run, returns the rounds so far.
+  pub async fn add_tool_step(id: Uuid, pool: &PgPool) -> Result<i32> {
+    let steps = sqlx::query_scalar!(
+      r#"
+        UPDATE runs SET tool_steps = tool_steps + 1, updated_at = now()
+        WHERE id = $1
+        RETURNING tool_steps
+      "#,
+      id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(steps)
+  }
+
+  pub async fn transition<'e, E: PgExecutor<'e>>(
+    id: Uuid,
+    from: &[RunStatus],
+    to: RunStatus,
+    required_action: Option<RequiredAction>,
+    last_error: Option<LastError>,
+    executor: E,
+  ) -> Result<Option<Run>> {
+    let from: Vec<String> = from.iter().map(|s| s.as_str().to_string()).collect();
+    let run = sqlx::query_as!(
+      Run,
+      r#"
+        UPDATE runs
+        SET
+          status = $3,
+          required_action = $4,
+          last_error = $5,
+          started_at = CASE WHEN $3 = 'in_progress' THEN COALESCE(started_at, now()) ELSE started_at END,
+          cancelled_at = CASE WHEN $3 = 'cancelled' THEN now() ELSE cancelled_at END,
+          failed_at = CASE WHEN $3 = 'failed' THEN now() ELSE failed_at END,
+          completed_at = CASE WHEN $3 = 'completed' THEN now() ELSE completed_at END,
+          updated_at = now()
+        WHERE id = $1 AND status = ANY($2)
+        RETURNING id, user_id, thread_id, assistant_id, status,
+          required_action AS "required_action: Json<RequiredAction>",
+          last_error AS "last_error: Json<LastError>", model, instructions,
+          tools AS "tools: Json<Vec<AssistantTools>>", file_ids,
+          metadata AS "metadata: Json<Metadata>", expires_at, started_at, cancelled_at,
+          failed_at, completed_at, created_at, updated_at
+      "#,
+      id,
+      &from,
+      to.as_str(),
+      required_action.map(Json) as _,
+      last_error.map(Json) as _,
+    )
+    .fetch_optional(executor)
+    .await?;
+    Ok(run)
+  }
+}
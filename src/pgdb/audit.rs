@@ -0,0 +1,99 @@
+use chrono::prelude::*;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+  models::audit::{AuditEvent, AuditQuery},
+  Result,
+};
+
+/// The most events returned by a single query.
+pub const MAX_AUDIT_PAGE: i64 = 1000;
+
+/// An audited call that is about to be stored in `audit_events`.
+#[derive(Debug, Clone, Default)]
+pub struct NewAuditEvent {
+  pub source: &'static str,
+  pub user_id: Option<Uuid>,
+  pub api_key_id: Option<Uuid>,
+  pub method: String,
+  pub endpoint: String,
+  pub model: Option<String>,
+  pub status: i32,
+  pub latency_ms: i32,
+  pub request_body: Option<String>,
+  pub response_body: Option<String>,
+  pub truncated: bool,
+}
+
+impl NewAuditEvent {
+  pub async fn insert(&self, pool: &PgPool) -> Result<()> {
+    sqlx::query!(
+      r#"
+        INSERT INTO audit_events(source, user_id, api_key_id, method, endpoint, model, status,
+          latency_ms, request_body, response_body, truncated)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+      "#,
+      self.source,
+      self.user_id,
+      self.api_key_id,
+      self.method,
+      self.endpoint,
+      self.model,
+      self.status,
+      self.latency_ms,
+      self.request_body,
+      self.response_body,
+      self.truncated,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+}
+
+impl AuditEvent {
+  /// Events matching `query`, newest first.
+  pub async fn query(query: &AuditQuery, pool: &PgPool) -> Result<Vec<AuditEvent>> {
+    let limit = query.limit.unwrap_or(100).clamp(1, MAX_AUDIT_PAGE);
+    let events = sqlx::query_as!(
+      AuditEvent,
+      r#"
+        SELECT id, created_at, source, user_id, api_key_id, method, endpoint, model, status,
+          latency_ms, request_body, response_body, truncated
+        FROM audit_events
+        WHERE ($1::uuid IS NULL OR user_id = $1)
+          AND ($2::uuid IS NULL OR api_key_id = $2)
+          AND ($3::text IS NULL OR source = $3)
+          AND ($4::text IS NULL OR endpoint = $4)
+          AND ($5::text IS NULL OR model = $5)
+          AND ($6::timestamptz IS NULL OR created_at >= $6)
+          AND ($7::timestamptz IS NULL OR created_at < $7)
+          AND ($8::uuid IS NULL OR (created_at, id) <
+            (SELECT created_at, id FROM audit_events WHERE id = $8))
+        ORDER BY created_at DESC, id DESC
+        LIMIT $9
+      "#,
+      query.user_id,
+      query.api_key_id,
+      query.source,
+      query.endpoint,
+      query.model,
+      query.since,
+      query.until,
+      query.before,
+      limit,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(events)
+  }
+
+  /// Deletes the events older than `before`, returns how many were removed.
+  pub async fn prune(before: DateTime<Utc>, pool: &PgPool) -> Result<u64> {
+    let result = sqlx::query!("DELETE FROM audit_events WHERE created_at < $1", before)
+      .execute(pool)
+      .await?;
+    Ok(result.rows_affected())
+  }
+}
@@ -0,0 +1,369 @@
+use chrono::prelude::*;
+use sqlx::{types::Json, PgExecutor, PgPool};
+use uuid::Uuid;
+
+use super::assistant::{after_cursor, into_list};
+use crate::{
+  models::{
+    assistants::{ListQuery, ListResponse, Metadata},
+    batches::{
+      BatchErrors, BatchObject, BatchRequestCounts, BatchRequestError, BatchRequestInput,
+      BatchRequestOutput, BatchResponse, BatchStatus, CreateBatchRequest,
+    },
+  },
+  Error, Result,
+};
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Batch {
+  pub id: Uuid,
+  pub user_id: Uuid,
+  pub endpoint: String,
+  pub input_file_id: String,
+  pub completion_window: String,
+  pub status: String,
+  pub errors: Option<Json<BatchErrors>>,
+  pub output_file_id: Option<String>,
+  pub error_file_id: Option<String>,
+  pub metadata: Option<Json<Metadata>>,
+  /// The models the lines may use, empty allows all models.
+  pub models: Vec<String>,
+  pub expires_at: DateTime<Utc>,
+  pub in_progress_at: Option<DateTime<Utc>>,
+  pub finalizing_at: Option<DateTime<Utc>>,
+  pub completed_at: Option<DateTime<Utc>>,
+  pub failed_at: Option<DateTime<Utc>>,
+  pub expired_at: Option<DateTime<Utc>>,
+  pub cancelling_at: Option<DateTime<Utc>>,
+  pub cancelled_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+  pub updated_at: DateTime<Utc>,
+  pub total: i64,
+  pub completed: i64,
+  pub failed: i64,
+}
+
+impl From<Batch> for BatchObject {
+  fn from(value: Batch) -> Self {
+    Self {
+      id: value.id.to_string(),
+      object: "batch".to_string(),
+      endpoint: value.endpoint,
+      errors: value.errors.map(|e| e.0),
+      input_file_id: value.input_file_id,
+      completion_window: value.completion_window,
+      status: value.status.parse().unwrap_or_default(),
+      output_file_id: value.output_file_id,
+      error_file_id: value.error_file_id,
+      created_at: value.created_at.timestamp(),
+      in_progress_at: value.in_progress_at.map(|v| v.timestamp()),
+      expires_at: Some(value.expires_at.timestamp()),
+      finalizing_at: value.finalizing_at.map(|v| v.timestamp()),
+      completed_at: value.completed_at.map(|v| v.timestamp()),
+      failed_at: value.failed_at.map(|v| v.timestamp()),
+      expired_at: value.expired_at.map(|v| v.timestamp()),
+      cancelling_at: value.cancelling_at.map(|v| v.timestamp()),
+      cancelled_at: value.cancelled_at.map(|v| v.timestamp()),
+      request_counts: BatchRequestCounts {
+        total: value.total,
+        completed: value.completed,
+        failed: value.failed,
+      },
+      metadata: value.metadata.map(|m| m.0),
+    }
+  }
+}
+
+impl Batch {
+  pub fn status(&self) -> BatchStatus {
+    self.status.parse().unwrap_or_default()
+  }
+
+  pub async fn create(
+    user_id: Uuid,
+    request: CreateBatchRequest,
+    models: &[String],
+    expires_at: DateTime<Utc>,
+    pool: &PgPool,
+  ) -> Result<Batch> {
+    let batch = sqlx::query_as!(
+      Batch,
+      r#"
+        INSERT INTO batches(
+          user_id, endpoint, input_file_id, completion_window, metadata, models, expires_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id, user_id, endpoint, input_file_id, completion_window, status,
+          errors AS "errors: Json<BatchErrors>", output_file_id, error_file_id,
+          metadata AS "metadata: Json<Metadata>", models, expires_at, in_progress_at,
+          finalizing_at,
+          completed_at, failed_at, expired_at, cancelling_at, cancelled_at, created_at,
+          updated_at, 0::bigint AS "total!", 0::bigint AS "completed!", 0::bigint AS "failed!"
+      "#,
+      user_id,
+      request.endpoint,
+      request.input_file_id,
+      request.completion_window,
+      request.metadata.map(Json) as _,
+      models,
+      expires_at,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(batch)
+  }
+
+  /// A batch of `owner`, or of anyone when `owner` is `None`.
+  pub async fn get(id: Uuid, owner: Option<Uuid>, pool: &PgPool) -> Result<Batch> {
+    sqlx::query_as!(
+      Batch,
+      r#"
+        SELECT b.id, b.user_id, b.endpoint, b.input_file_id, b.completion_window, b.status,
+          b.errors AS "errors: Json<BatchErrors>", b.output_file_id, b.error_file_id,
+          b.metadata AS "metadata: Json<Metadata>", b.models, b.expires_at, b.in_progress_at,
+          b.finalizing_at, b.completed_at, b.failed_at, b.expired_at, b.cancelling_at,
+          b.cancelled_at, b.created_at, b.updated_at,
+          count(r.id) AS "total!",
+          count(r.id) FILTER (WHERE r.status = 'completed') AS "completed!",
+          count(r.id) FILTER (WHERE r.status = 'failed') AS "failed!"
+        FROM batches b
+        LEFT JOIN batch_requests r ON r.batch_id = b.id
+        WHERE b.id = $1 AND ($2::uuid IS NULL OR b.user_id = $2)
+        GROUP BY b.id
+      "#,
+      id,
+      owner
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| Error::NotFound(format!("batch {id}")))
+  }
+
+  pub async fn list(
+    user_id: Uuid,
+    query: &ListQuery,
+    pool: &PgPool,
+  ) -> Result<ListResponse<BatchObject>> {
+    let limit = query.page_size();
+    let rows = sqlx::query_as!(
+      Batch,
+      r#"
+        SELECT b.id, b.user_id, b.endpoint, b.input_file_id, b.completion_window, b.status,
+          b.errors AS "errors: Json<BatchErrors>", b.output_file_id, b.error_file_id,
+          b.metadata AS "metadata: Json<Metadata>", b.models, b.expires_at, b.in_progress_at,
+          b.finalizing_at, b.completed_at, b.failed_at, b.expired_at, b.cancelling_at,
+          b.cancelled_at, b.created_at, b.updated_at,
+          count(r.id) AS "total!",
+          count(r.id) FILTER (WHERE r.status = 'completed') AS "completed!",
+          count(r.id) FILTER (WHERE r.status = 'failed') AS "failed!"
+        FROM batches b
+        LEFT JOIN batch_requests r ON r.batch_id = b.id
+        WHERE b.user_id = $4
+          AND (
+            $1::uuid IS NULL
+            OR ($2 AND b.created_at > (SELECT created_at FROM batches WHERE id = $1))
+            OR (NOT $2 AND b.created_at < (SELECT created_at FROM batches WHERE id = $1))
+          )
+        GROUP BY b.id
+        ORDER BY
+          CASE WHEN $2 THEN b.created_at END ASC,
+          CASE WHEN NOT $2 THEN b.created_at END DESC
+        LIMIT $3
+      "#,
+      after_cursor(query, "batch")?,
+      query.is_ascending(),
+      limit + 1,
+      user_id,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(into_list(rows, limit, |b: &BatchObject| b.id.clone()))
+  }
+
+  /// Batches the worker has not finished yet, used to pick up work again after a restart.
+  pub async fn unfinished(pool: &PgPool) -> Result<Vec<Uuid>> {
+    let ids = sqlx::query_scalar!(
+      r#"
+        SELECT id FROM batches
+        WHERE status IN ('validating', 'in_progress', 'finalizing', 'cancelling')
+        ORDER BY created_at
+      "#
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(ids)
+  }
+
+  /// Moves a batch to `to`, but only while it is in one of the `from` states.
+  ///
+  /// Returns `false` when the batch was in another state, e.g. because it got cancelled.
+  pub async fn transition<'e, E: PgExecutor<'e>>(
+    id: Uuid,
+    from: &[BatchStatus],
+    to: BatchStatus,
+    errors: Option<BatchErrors>,
+    executor: E,
+  ) -> Result<bool> {
+    let from: Vec<String> = from.iter().map(|s| s.as_str().to_string()).collect();
+    let result = sqlx::query!(
+      r#"
+        UPDATE batches
+        SET
+          status = $3,
+          errors = COALESCE($4, errors),
+          in_progress_at = CASE WHEN $3 = 'in_progress' THEN now() ELSE in_progress_at END,
+          finalizing_at = CASE WHEN $3 = 'finalizing' THEN COALESCE(finalizing_at, now()) ELSE finalizing_at END,
+          completed_at = CASE WHEN $3 = 'completed' THEN now() ELSE completed_at END,
+          failed_at = CASE WHEN $3 = 'failed' THEN now() ELSE failed_at END,
+          expired_at = CASE WHEN $3 = 'expired' THEN now() ELSE expired_at END,
+          cancelling_at = CASE WHEN $3 = 'cancelling' THEN now() ELSE cancelling_at END,
+          cancelled_at = CASE WHEN $3 = 'cancelled' THEN now() ELSE cancelled_at END,
+          updated_at = now()
+        WHERE id = $1 AND status = ANY($2)
+      "#,
+      id,
+      &from,
+      to.as_str(),
+      errors.map(Json) as _,
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() > 0)
+  }
+
+  pub async fn set_files(
+    id: Uuid,
+    output_file_id: Option<String>,
+    error_file_id: Option<String>,
+    pool: &PgPool,
+  ) -> Result<()> {
+    sqlx::query!(
+      r#"
+        UPDATE batches
+        SET output_file_id = $2, error_file_id = $3, updated_at = now()
+        WHERE id = $1
+      "#,
+      id,
+      output_file_id,
+      error_file_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct BatchRequest {
+  pub id: Uuid,
+  pub batch_id: Uuid,
+  pub line: i64,
+  pub custom_id: String,
+  pub body: serde_json::Value,
+  pub status: String,
+  pub response: Option<Json<BatchResponse>>,
+  pub error: Option<Json<BatchRequestError>>,
+}
+
+impl From<BatchRequest> for BatchRequestOutput {
+  fn from(value: BatchRequest) -> Self {
+    Self {
+      id: value.id.to_string(),
+      custom_id: value.custom_id,
+      response: value.response.map(|r| r.0),
+      error: value.error.map(|e| e.0),
+    }
+  }
+}
+
+impl BatchRequest {
+  /// Stores the validated lines of an input file, `line` is 1 based like in error reports.
+  pub async fn insert_all<'e, E: PgExecutor<'e>>(
+    batch_id: Uuid,
+    requests: Vec<(i64, BatchRequestInput)>,
+    executor: E,
+  ) -> Result<()> {
+    let (lines, inputs): (Vec<i64>, Vec<BatchRequestInput>) = requests.into_iter().unzip();
+    let custom_ids: Vec<String> = inputs.iter().map(|r| r.custom_id.clone()).collect();
+    let bodies: Vec<serde_json::Value> = inputs.into_iter().map(|r| r.body).collect();
+    sqlx::query!(
+      r#"
+        INSERT INTO batch_requests(batch_id, line, custom_id, body)
+        SELECT $1, * FROM UNNEST($2::bigint[], $3::text[], $4::jsonb[])
+      "#,
+      batch_id,
+      &lines,
+      &custom_ids,
+      &bodies,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+  }
+
+  pub async fn pending(batch_id: Uuid, limit: i64, pool: &PgPool) -> Result<Vec<BatchRequest>> {
+    let rows = sqlx::query_as!(
+      BatchRequest,
+      r#"
+        SELECT id, batch_id, line, custom_id, body, status,
+          response AS "response: Json<BatchResponse>", error AS "error: Json<BatchRequestError>"
+        FROM batch_requests
+        WHERE batch_id = $1 AND status = 'pending'
+        ORDER BY line
+        LIMIT $2
+      "#,
+      batch_id,
+      limit,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+  }
+
+  /// Records the outcome of a request, exactly one of `response` and `error` is set.
+  pub async fn finish(
+    id: Uuid,
+    response: Option<BatchResponse>,
+    error: Option<BatchRequestError>,
+    pool: &PgPool,
+  ) -> Result<()> {
+    let status = if error.is_some() {
+      "failed"
+    } else {
+      "completed"
+    };
+    sqlx::query!(
+      r#"
+        UPDATE batch_requests
+        SET status = $2, response = $3, error = $4, updated_at = now()
+        WHERE id = $1
+      "#,
+      id,
+      status,
+      response.map(Json) as _,
+      error.map(Json) as _,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+
+  /// All finished requests with the given status, in input file order.
+  pub async fn finished(batch_id: Uuid, status: &str, pool: &PgPool) -> Result<Vec<BatchRequest>> {
+    let rows = sqlx::query_as!(
+      BatchRequest,
+      r#"
+        SELECT id, batch_id, line, custom_id, body, status,
+          response AS "response: Json<BatchResponse>", error AS "error: Json<BatchRequestError>"
+        FROM batch_requests
+        WHERE batch_id = $1 AND status = $2
+        ORDER BY line
+      "#,
+      batch_id,
+      status,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+  }
+}
@@ -0,0 +1,67 @@
+use chrono::prelude::*;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{models::pricing::GoalBudget, Result};
+
+impl GoalBudget {
+  /// The budget of the latest goal of a chat and the cost of the calls made since it was
+  /// submitted, `None` when the chat has no goal.
+  pub async fn for_chat(chat_id: Uuid, pool: &PgPool) -> Result<Option<GoalBudget>> {
+    let budget = sqlx::query_as!(
+      GoalBudget,
+      r#"
+        SELECT g.budget_usd,
+          (SELECT COALESCE(sum(u.cost), 0) FROM usage_events u
+            WHERE u.chat_id = g.chat_id AND u.time >= g.submission_date)::float8 AS "spent_usd!"
+        FROM goals g
+        WHERE g.chat_id = $1
+        ORDER BY g.created_at DESC
+        LIMIT 1
+      "#,
+      chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(budget)
+  }
+
+  /// Sets the budget of the latest goal of a chat, creating the goal if needed.
+  pub async fn set(
+    user_id: Uuid,
+    chat_id: Uuid,
+    budget_usd: Option<f64>,
+    pool: &PgPool,
+  ) -> Result<()> {
+    let updated = sqlx::query!(
+      r#"
+        UPDATE goals
+        SET budget_usd = $3, updated_at = now()
+        WHERE id = (
+          SELECT id FROM goals WHERE user_id = $1 AND chat_id = $2
+          ORDER BY created_at DESC LIMIT 1
+        )
+      "#,
+      user_id,
+      chat_id,
+      budget_usd,
+    )
+    .execute(pool)
+    .await?;
+    if updated.rows_affected() == 0 {
+      sqlx::query!(
+        r#"
+          INSERT INTO goals(chat_id, user_id, prompt, submission_date, budget_usd)
+          VALUES ($1, $2, '', $3, $4)
+        "#,
+        chat_id,
+        user_id,
+        Utc::now(),
+        budget_usd,
+      )
+      .execute(pool)
+      .await?;
+    }
+    Ok(())
+  }
+}
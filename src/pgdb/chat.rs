@@ -13,7 +13,7 @@ use crate::{
     ChatCompletionRequestUserMessage, ChatCompletionRequestUserMessageContent,
     ChatLog as AppChatLog, ChatMessage, Role, SavedMessage,
   },
-  Result,
+  Error, Result,
 };
 
 #[derive(Debug, Deserialize, Serialize, Clone, sqlx::FromRow, Convert)]
@@ -21,6 +21,8 @@ use crate::{
 pub struct Chat {
   pub id: Uuid,
   pub title: Option<String>,
+  pub redact_pii: bool,
+  pub cost: f64,
   pub user_id: Uuid,
   #[convert_field(rename = "email")]
   pub user_email: String,
@@ -33,6 +35,8 @@ impl From<Chat> for AppChat {
     AppChat {
       id: value.id,
       title: value.title,
+      redact_pii: value.redact_pii,
+      cost: value.cost,
       user_id: value.user_id,
       email: value.user_email,
       created_at: value.created_at,
@@ -49,6 +53,8 @@ impl From<(Chat, Vec<Message>, Vec<Variable>, Vec<Log>)> for AppChat {
     AppChat {
       id: chat.id,
       title: chat.title,
+      redact_pii: chat.redact_pii,
+      cost: chat.cost,
       user_id: chat.user_id,
       email: chat.user_email,
       created_at: chat.created_at,
@@ -101,6 +107,8 @@ pub struct Message {
   pub chat_id: Uuid,
   pub user_id: Uuid,
   pub content: Option<String>,
+  /// The parts of user messages with images.
+  pub content_parts: Json<Option<serde_json::Value>>,
   pub name: Option<String>,
   pub tool_calls: Json<Option<serde_json::Value>>,
   pub temporary: bool,
@@ -122,7 +130,16 @@ impl From<Message> for SavedMessage {
       },
       "user" => SavedMessage {
         msg: ChatMessage::User(ChatCompletionRequestUserMessage {
-          content: ChatCompletionRequestUserMessageContent::Text(value.content.unwrap_or_default()),
+          content: match value
+            .content_parts
+            .0
+            .and_then(|v| serde_json::from_value(v).ok())
+          {
+            Some(parts) => ChatCompletionRequestUserMessageContent::Array(parts),
+            None => {
+              ChatCompletionRequestUserMessageContent::Text(value.content.unwrap_or_default())
+            }
+          },
           role: Role::User,
           name: value.name,
         }),
@@ -170,14 +187,16 @@ impl From<Message> for SavedMessage {
 
 impl From<SavedMessage> for Message {
   fn from(value: SavedMessage) -> Self {
+    let mut content_parts = None;
     let (content, name, tool_calls, role, tool_call_id) = match value.msg {
       ChatMessage::System(msg) => (Some(msg.content), msg.name, None, "system", None),
-      ChatMessage::User(msg) => match msg.content {
-        ChatCompletionRequestUserMessageContent::Text(content) => {
-          (Some(content), msg.name, None, "user", None)
+      ChatMessage::User(msg) => {
+        let text = msg.content.text();
+        if let ChatCompletionRequestUserMessageContent::Array(parts) = msg.content {
+          content_parts = serde_json::to_value(parts).ok();
         }
-        _ => (None, msg.name, None, "user", None),
-      },
+        (Some(text), msg.name, None, "user", None)
+      }
       ChatMessage::Assistant(msg) => {
         let tool_calls = msg.tool_calls.map(|v| serde_json::to_value(v).unwrap());
         (msg.content, msg.name, tool_calls, "assistant", None)
@@ -196,6 +215,7 @@ impl From<SavedMessage> for Message {
       chat_id: Uuid::new_v4(),
       user_id: Uuid::new_v4(),
       content,
+      content_parts: Json(content_parts),
       name,
       tool_calls: Json(tool_calls),
       temporary: value.temporary,
@@ -265,6 +285,68 @@ impl Chat {
       .await?;
     Ok(chat.into())
   }
+
+  /// Whether the chat belongs to `user_id`.
+  pub async fn is_owned_by(id: Uuid, user_id: Uuid, pool: &PgPool) -> Result<bool> {
+    let owned = sqlx::query_scalar!(
+      r#"SELECT EXISTS(SELECT 1 FROM chats WHERE id = $1 AND user_id = $2) AS "owned!""#,
+      id,
+      user_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(owned)
+  }
+
+  /// Whether the chat of `user_id` opted in to PII redaction, `None` when it is not theirs.
+  pub async fn redacts_pii(id: Uuid, user_id: Uuid, pool: &PgPool) -> Result<Option<bool>> {
+    let redact_pii = sqlx::query_scalar!(
+      "SELECT redact_pii FROM chats WHERE id = $1 AND user_id = $2",
+      id,
+      user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(redact_pii)
+  }
+
+  pub async fn set_redact_pii(id: Uuid, user_id: Uuid, enabled: bool, pool: &PgPool) -> Result<()> {
+    let result = sqlx::query!(
+      "UPDATE chats SET redact_pii = $3, updated_at = now() WHERE id = $1 AND user_id = $2",
+      id,
+      user_id,
+      enabled
+    )
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+      return Err(Error::NotFound(format!("chat {id}")));
+    }
+    Ok(())
+  }
+
+  /// Appends a message to the history of a chat.
+  pub async fn add_message(chat_id: Uuid, message: SavedMessage, pool: &PgPool) -> Result<()> {
+    let message = Message::from(message);
+    sqlx::query!(
+      r#"
+        INSERT INTO messages(chat_id, role, content, content_parts, name, tool_calls, temporary,
+          tool_call_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+      "#,
+      chat_id,
+      message.role,
+      message.content,
+      message.content_parts.0,
+      message.name,
+      message.tool_calls.0,
+      message.temporary,
+      message.tool_call_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
 }
 
 impl Log {
@@ -0,0 +1,105 @@
+use chrono::prelude::*;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{models::FileObject, Result};
+
+/// The metadata of a file of the local Files API. `owner` arguments restrict the lookups to the
+/// files of a user, `None` finds every file.
+#[derive(Debug, Clone)]
+pub struct StoredFile {
+  pub id: String,
+  pub user_id: Option<Uuid>,
+  pub filename: String,
+  pub purpose: String,
+  pub bytes: i64,
+  pub created_at: DateTime<Utc>,
+}
+
+impl From<StoredFile> for FileObject {
+  fn from(value: StoredFile) -> Self {
+    Self {
+      id: value.id,
+      object: "file".to_string(),
+      bytes: value.bytes as u64,
+      created_at: value.created_at.timestamp(),
+      filename: value.filename,
+      purpose: value.purpose,
+      status: Some("processed".to_string()),
+      status_details: None,
+    }
+  }
+}
+
+impl StoredFile {
+  pub async fn create(&self, pool: &PgPool) -> Result<()> {
+    sqlx::query!(
+      r#"
+        INSERT INTO files(id, user_id, filename, purpose, bytes, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+      "#,
+      self.id,
+      self.user_id,
+      self.filename,
+      self.purpose,
+      self.bytes,
+      self.created_at,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+
+  pub async fn get(id: &str, owner: Option<Uuid>, pool: &PgPool) -> Result<Option<StoredFile>> {
+    let file = sqlx::query_as!(
+      StoredFile,
+      r#"
+        SELECT id, user_id, filename, purpose, bytes, created_at
+        FROM files
+        WHERE id = $1 AND ($2::uuid IS NULL OR user_id = $2)
+      "#,
+      id,
+      owner
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(file)
+  }
+
+  /// The files with the `purpose`, if given, newest first.
+  pub async fn list(
+    owner: Option<Uuid>,
+    purpose: Option<&str>,
+    pool: &PgPool,
+  ) -> Result<Vec<StoredFile>> {
+    let files = sqlx::query_as!(
+      StoredFile,
+      r#"
+        SELECT id, user_id, filename, purpose, bytes, created_at
+        FROM files
+        WHERE ($1::uuid IS NULL OR user_id = $1) AND ($2::text IS NULL OR purpose = $2)
+        ORDER BY created_at DESC
+      "#,
+      owner,
+      purpose
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(files)
+  }
+
+  /// Returns `false` when there was no such file.
+  pub async fn delete(id: &str, owner: Option<Uuid>, pool: &PgPool) -> Result<bool> {
+    let result = sqlx::query!(
+      r#"
+        DELETE FROM files
+        WHERE id = $1 AND ($2::uuid IS NULL OR user_id = $2)
+      "#,
+      id,
+      owner
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+  }
+}
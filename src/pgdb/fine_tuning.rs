@@ -0,0 +1,220 @@
+use async_openai::types::{FineTuningJob, FineTuningJobEvent, FineTuningJobStatus, Level};
+use chrono::prelude::*;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+  models::fine_tuning::{JobEvent, TrackedJob},
+  Result,
+};
+
+fn timestamp(seconds: u32) -> DateTime<Utc> {
+  DateTime::from_timestamp(seconds as i64, 0).unwrap_or_default()
+}
+
+fn status(status: &FineTuningJobStatus) -> &'static str {
+  match status {
+    FineTuningJobStatus::ValidatingFiles => "validating_files",
+    FineTuningJobStatus::Queued => "queued",
+    FineTuningJobStatus::Running => "running",
+    FineTuningJobStatus::Succeeded => "succeeded",
+    FineTuningJobStatus::Failed => "failed",
+    FineTuningJobStatus::Cancelled => "cancelled",
+  }
+}
+
+fn level(level: &Level) -> &'static str {
+  match level {
+    Level::Info => "info",
+    Level::Warn => "warn",
+    Level::Error => "error",
+  }
+}
+
+/// A fine-tuned model with the model it was trained from.
+#[derive(Debug, Clone)]
+pub struct FineTunedModel {
+  pub model: String,
+  pub base_model: String,
+  pub created_at: DateTime<Utc>,
+}
+
+impl TrackedJob {
+  /// Starts tracking a job created for a user.
+  pub async fn create(job: &FineTuningJob, user_id: Option<Uuid>, pool: &PgPool) -> Result<()> {
+    sqlx::query!(
+      r#"
+        INSERT INTO fine_tuning_jobs(id, user_id, model, training_file, validation_file, status,
+          fine_tuned_model, error, trained_tokens, created_at, finished_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        ON CONFLICT (id) DO NOTHING
+      "#,
+      job.id,
+      user_id,
+      job.model,
+      job.training_file,
+      job.validation_file,
+      status(&job.status),
+      job.fine_tuned_model,
+      job.error.as_ref().map(|e| e.message.clone()),
+      job.trained_tokens.map(i64::from),
+      timestamp(job.created_at),
+      job.finished_at.map(timestamp),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+
+  /// Records the state of a job at the provider, `false` when the job is not tracked.
+  pub async fn update(job: &FineTuningJob, pool: &PgPool) -> Result<bool> {
+    let result = sqlx::query!(
+      r#"
+        UPDATE fine_tuning_jobs
+        SET status = $2, fine_tuned_model = $3, error = $4, trained_tokens = $5,
+            finished_at = $6, updated_at = now()
+        WHERE id = $1
+      "#,
+      job.id,
+      status(&job.status),
+      job.fine_tuned_model,
+      job.error.as_ref().map(|e| e.message.clone()),
+      job.trained_tokens.map(i64::from),
+      job.finished_at.map(timestamp),
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+  }
+
+  /// The jobs a user created, newest first.
+  pub async fn list_for_user(user_id: Uuid, pool: &PgPool) -> Result<Vec<TrackedJob>> {
+    let jobs = sqlx::query_as!(
+      TrackedJob,
+      r#"
+        SELECT id, model, training_file, validation_file, status, fine_tuned_model, error,
+          trained_tokens, created_at, finished_at, updated_at
+        FROM fine_tuning_jobs
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+      "#,
+      user_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(jobs)
+  }
+
+  /// Whether the job belongs to the user.
+  pub async fn is_owned_by(id: &str, user_id: Uuid, pool: &PgPool) -> Result<bool> {
+    let owned = sqlx::query_scalar!(
+      r#"SELECT EXISTS(SELECT 1 FROM fine_tuning_jobs WHERE id = $1 AND user_id = $2) AS "owned!""#,
+      id,
+      user_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(owned)
+  }
+
+  /// The jobs the provider is still working on.
+  pub async fn unfinished(pool: &PgPool) -> Result<Vec<String>> {
+    let ids = sqlx::query_scalar!(
+      r#"
+        SELECT id FROM fine_tuning_jobs
+        WHERE status IN ('validating_files', 'queued', 'running')
+        ORDER BY created_at
+      "#
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(ids)
+  }
+
+  /// Whether the user created the job that made the fine-tuned model, which is not deleted yet.
+  pub async fn owns_model(model: &str, user_id: Uuid, pool: &PgPool) -> Result<bool> {
+    let owned = sqlx::query_scalar!(
+      r#"
+        SELECT EXISTS(
+          SELECT 1 FROM fine_tuning_jobs
+          WHERE fine_tuned_model = $1 AND user_id = $2 AND model_deleted_at IS NULL
+        ) AS "owned!"
+      "#,
+      model,
+      user_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(owned)
+  }
+
+  /// Marks the fine-tuned model as deleted, so it is not registered again after a restart.
+  pub async fn delete_model(model: &str, pool: &PgPool) -> Result<()> {
+    sqlx::query!(
+      r#"
+        UPDATE fine_tuning_jobs SET model_deleted_at = now(), updated_at = now()
+        WHERE fine_tuned_model = $1 AND model_deleted_at IS NULL
+      "#,
+      model
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+
+  /// The models of the jobs that succeeded and were not deleted.
+  pub async fn fine_tuned_models(pool: &PgPool) -> Result<Vec<FineTunedModel>> {
+    let models = sqlx::query_as!(
+      FineTunedModel,
+      r#"
+        SELECT fine_tuned_model AS "model!", model AS base_model, created_at
+        FROM fine_tuning_jobs
+        WHERE status = 'succeeded' AND fine_tuned_model IS NOT NULL AND model_deleted_at IS NULL
+        ORDER BY created_at
+      "#
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(models)
+  }
+}
+
+impl JobEvent {
+  /// Records the events of a job, the ones already known are kept.
+  pub async fn save_all(job_id: &str, events: &[FineTuningJobEvent], pool: &PgPool) -> Result<()> {
+    for event in events {
+      sqlx::query!(
+        r#"
+          INSERT INTO fine_tuning_events(id, job_id, level, message, created_at)
+          VALUES ($1, $2, $3, $4, $5)
+          ON CONFLICT (id) DO NOTHING
+        "#,
+        event.id,
+        job_id,
+        level(&event.level),
+        event.message,
+        timestamp(event.created_at),
+      )
+      .execute(pool)
+      .await?;
+    }
+    Ok(())
+  }
+
+  /// The events of a job, oldest first.
+  pub async fn list_for_job(job_id: &str, pool: &PgPool) -> Result<Vec<JobEvent>> {
+    let events = sqlx::query_as!(
+      JobEvent,
+      r#"
+        SELECT id, level, message, created_at
+        FROM fine_tuning_events
+        WHERE job_id = $1
+        ORDER BY created_at, id
+      "#,
+      job_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(events)
+  }
+}
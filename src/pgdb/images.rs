@@ -0,0 +1,50 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{models::images::WorkspaceImage, Result};
+
+impl WorkspaceImage {
+  /// Records an image saved into a workspace, replacing what was known about an earlier file
+  /// with the same name.
+  pub async fn save(&self, user_id: Uuid, pool: &PgPool) -> Result<()> {
+    sqlx::query!(
+      r#"
+        INSERT INTO workspace_images(chat_id, file_name, user_id, prompt, revised_prompt, model, size, source, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (chat_id, file_name) DO UPDATE
+        SET user_id = EXCLUDED.user_id, prompt = EXCLUDED.prompt,
+            revised_prompt = EXCLUDED.revised_prompt, model = EXCLUDED.model,
+            size = EXCLUDED.size, source = EXCLUDED.source, created_at = EXCLUDED.created_at
+      "#,
+      self.chat_id,
+      self.file_name,
+      user_id,
+      self.prompt,
+      self.revised_prompt,
+      self.model,
+      self.size,
+      self.source,
+      self.created_at,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+
+  /// The generated images of a chat, newest first.
+  pub async fn list_for_chat(chat_id: Uuid, pool: &PgPool) -> Result<Vec<WorkspaceImage>> {
+    let images = sqlx::query_as!(
+      WorkspaceImage,
+      r#"
+        SELECT chat_id, file_name, prompt, revised_prompt, model, size, source, created_at
+        FROM workspace_images
+        WHERE chat_id = $1
+        ORDER BY created_at DESC
+      "#,
+      chat_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(images)
+  }
+}
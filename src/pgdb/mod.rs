@@ -1,5 +1,32 @@
+mod api_key;
+mod assistant;
+mod audit;
+mod batch;
+mod budget;
 mod chat;
+mod files;
+mod fine_tuning;
+mod images;
+mod moderation;
+mod pii;
+mod quota;
+mod retrieval;
+mod search;
+mod settings;
+mod usage;
 mod user;
 
+pub use assistant::{
+  parse_object_id, Assistant, NewRun, NewThreadMessage, Run, Thread, ThreadMessage,
+};
+pub use audit::{NewAuditEvent, MAX_AUDIT_PAGE};
+pub use batch::{Batch, BatchRequest};
 pub use chat::{Chat, Log};
+pub use files::StoredFile;
+pub use fine_tuning::FineTunedModel;
+pub use moderation::NewModeration;
+pub use quota::RequestKind;
+pub use retrieval::{ChunkOwner, NewChunk};
+pub use search::{PendingEntry, SearchEntry};
+pub use usage::UsageEvent;
 pub use user::{User, UserInfo};
@@ -0,0 +1,37 @@
+use sqlx::{types::Json, PgPool};
+use uuid::Uuid;
+
+use crate::{models::moderation::ModerationAction, Result};
+
+/// A moderation result that is about to be stored in `moderations`.
+#[derive(Debug, Clone, Default)]
+pub struct NewModeration {
+  pub chat_id: Option<Uuid>,
+  pub user_id: Option<Uuid>,
+  pub source: &'static str,
+  pub action: ModerationAction,
+  pub categories: Vec<String>,
+  /// The `CreateModerationResponse` of the provider, or one in the same shape from the local
+  /// classifier.
+  pub response: serde_json::Value,
+}
+
+impl NewModeration {
+  pub async fn insert(&self, pool: &PgPool) -> Result<()> {
+    sqlx::query!(
+      r#"
+        INSERT INTO moderations(chat_id, user_id, source, action, categories, response)
+        VALUES ($1, $2, $3, $4, $5, $6)
+      "#,
+      self.chat_id,
+      self.user_id,
+      self.source,
+      self.action.as_str(),
+      &self.categories,
+      Json(&self.response) as _,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+}
@@ -0,0 +1,48 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{models::pii::PiiRedaction, Result};
+
+impl PiiRedaction {
+  /// Logs the `(kind, placeholder)` pairs replaced in a single request.
+  pub async fn insert_all(
+    chat_id: Option<Uuid>,
+    user_id: Option<Uuid>,
+    endpoint: &str,
+    redactions: &[(String, String)],
+    pool: &PgPool,
+  ) -> Result<()> {
+    let (kinds, placeholders): (Vec<String>, Vec<String>) = redactions.iter().cloned().unzip();
+    sqlx::query!(
+      r#"
+        INSERT INTO pii_redactions(chat_id, user_id, endpoint, kind, placeholder)
+        SELECT $1, $2, $3, kind, placeholder
+        FROM UNNEST($4::text[], $5::text[]) AS r(kind, placeholder)
+      "#,
+      chat_id,
+      user_id,
+      endpoint,
+      &kinds,
+      &placeholders,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+
+  pub async fn list_for_chat(chat_id: Uuid, pool: &PgPool) -> Result<Vec<PiiRedaction>> {
+    let redactions = sqlx::query_as!(
+      PiiRedaction,
+      r#"
+        SELECT id, chat_id, endpoint, kind, placeholder, created_at
+        FROM pii_redactions
+        WHERE chat_id = $1
+        ORDER BY created_at DESC
+      "#,
+      chat_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(redactions)
+  }
+}
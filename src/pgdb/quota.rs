@@ -0,0 +1,217 @@
+use chrono::prelude::*;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+  models::quota::{QuotaLimits, QuotaUsage},
+  Result,
+};
+
+/// The kind of capacity a request uses, subsidies are granted separately for each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+  Completion,
+  Embedding,
+}
+
+impl QuotaLimits {
+  /// The limits configured for a user, without the defaults applied.
+  pub async fn for_user(user_id: Uuid, pool: &PgPool) -> Result<QuotaLimits> {
+    let limits = sqlx::query_as!(
+      QuotaLimits,
+      r#"
+        SELECT daily_requests, monthly_requests, daily_tokens, monthly_tokens,
+          requests_per_minute
+        FROM user_quotas
+        WHERE user_id = $1
+      "#,
+      user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(limits.unwrap_or_default())
+  }
+
+  pub async fn set_for_user(user_id: Uuid, limits: &QuotaLimits, pool: &PgPool) -> Result<()> {
+    sqlx::query!(
+      r#"
+        INSERT INTO user_quotas(user_id, daily_requests, monthly_requests, daily_tokens,
+          monthly_tokens, requests_per_minute)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (user_id) DO UPDATE
+        SET daily_requests = EXCLUDED.daily_requests,
+          monthly_requests = EXCLUDED.monthly_requests,
+          daily_tokens = EXCLUDED.daily_tokens,
+          monthly_tokens = EXCLUDED.monthly_tokens,
+          requests_per_minute = EXCLUDED.requests_per_minute,
+          updated_at = now()
+      "#,
+      user_id,
+      limits.daily_requests,
+      limits.monthly_requests,
+      limits.daily_tokens,
+      limits.monthly_tokens,
+      limits.requests_per_minute,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+}
+
+impl QuotaUsage {
+  /// Requests are counted in `prompts.llm_requests` per day, tokens come from `usage_events`.
+  pub async fn for_user(user_id: Uuid, pool: &PgPool) -> Result<QuotaUsage> {
+    let usage = sqlx::query_as!(
+      QuotaUsage,
+      r#"
+        SELECT
+          (SELECT COALESCE(sum(llm_requests), 0) FROM prompts
+            WHERE user_id = $1 AND submission_date = current_date)::bigint AS "daily_requests!",
+          (SELECT COALESCE(sum(llm_requests), 0) FROM prompts
+            WHERE user_id = $1 AND submission_date >= date_trunc('month', current_date))::bigint
+            AS "monthly_requests!",
+          (SELECT COALESCE(sum(total_tokens), 0) FROM usage_events
+            WHERE user_id = $1 AND time >= date_trunc('day', now()))::bigint AS "daily_tokens!",
+          (SELECT COALESCE(sum(total_tokens), 0) FROM usage_events
+            WHERE user_id = $1 AND time >= date_trunc('month', now()))::bigint
+            AS "monthly_tokens!",
+          (SELECT COALESCE(sum(subsidized_completion_req), 0) FROM goals
+            WHERE user_id = $1 AND subsidized)::bigint AS "subsidized_completion_requests!",
+          (SELECT COALESCE(sum(subsidized_embedding_req), 0) FROM goals
+            WHERE user_id = $1 AND subsidized)::bigint AS "subsidized_embedding_requests!"
+      "#,
+      user_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(usage)
+  }
+
+  /// Counts a request against today's requests of the user while it stays under the daily and
+  /// monthly request limits, `None` counts it regardless. Returns `false` when a limit is used up.
+  /// The limits are checked on the locked row, so concurrent requests can't all take the last one.
+  pub async fn count_request(
+    user_id: Uuid,
+    limits: Option<&QuotaLimits>,
+    pool: &PgPool,
+  ) -> Result<bool> {
+    let daily = limits.and_then(|limits| limits.daily_requests);
+    let monthly = limits.and_then(|limits| limits.monthly_requests);
+    let result = sqlx::query!(
+      r#"
+        INSERT INTO prompts(user_id, submission_date, prompt, llm_requests)
+        SELECT $1, current_date, '', 1
+        WHERE ($2::bigint IS NULL OR (SELECT COALESCE(sum(llm_requests), 0) FROM prompts
+            WHERE user_id = $1 AND submission_date = current_date) < $2)
+          AND ($3::bigint IS NULL OR (SELECT COALESCE(sum(llm_requests), 0) FROM prompts
+            WHERE user_id = $1 AND submission_date >= date_trunc('month', current_date)) < $3)
+        ON CONFLICT (user_id, submission_date) WHERE prompt = '' DO UPDATE
+        SET llm_requests = prompts.llm_requests + 1, updated_at = now()
+        WHERE ($2::bigint IS NULL OR prompts.llm_requests + (
+            SELECT COALESCE(sum(other.llm_requests), 0) FROM prompts other
+            WHERE other.user_id = $1 AND other.submission_date = current_date
+              AND other.prompt <> '') < $2)
+          AND ($3::bigint IS NULL OR prompts.llm_requests + (
+            SELECT COALESCE(sum(other.llm_requests), 0) FROM prompts other
+            WHERE other.user_id = $1
+              AND other.submission_date >= date_trunc('month', current_date)
+              AND NOT (other.submission_date = current_date AND other.prompt = '')) < $3)
+      "#,
+      user_id,
+      daily,
+      monthly,
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+  }
+
+  /// Uses one subsidized request of the given kind, returns `false` when none is left.
+  pub async fn consume_subsidy(user_id: Uuid, kind: RequestKind, pool: &PgPool) -> Result<bool> {
+    let result = match kind {
+      RequestKind::Completion => {
+        sqlx::query!(
+          r#"
+            UPDATE goals
+            SET subsidized_completion_req = subsidized_completion_req - 1, updated_at = now()
+            WHERE id = (
+              SELECT id FROM goals
+              WHERE user_id = $1 AND subsidized AND subsidized_completion_req > 0
+              ORDER BY created_at
+              LIMIT 1
+              FOR UPDATE SKIP LOCKED
+            )
+          "#,
+          user_id
+        )
+        .execute(pool)
+        .await?
+      }
+      RequestKind::Embedding => {
+        sqlx::query!(
+          r#"
+            UPDATE goals
+            SET subsidized_embedding_req = subsidized_embedding_req - 1, updated_at = now()
+            WHERE id = (
+              SELECT id FROM goals
+              WHERE user_id = $1 AND subsidized AND subsidized_embedding_req > 0
+              ORDER BY created_at
+              LIMIT 1
+              FOR UPDATE SKIP LOCKED
+            )
+          "#,
+          user_id
+        )
+        .execute(pool)
+        .await?
+      }
+    };
+    Ok(result.rows_affected() > 0)
+  }
+
+  /// Grants extra requests to a user on the goal of a chat, creating the goal if needed.
+  pub async fn grant_subsidy(
+    user_id: Uuid,
+    chat_id: Uuid,
+    completion_requests: i64,
+    embedding_requests: i64,
+    pool: &PgPool,
+  ) -> Result<()> {
+    let updated = sqlx::query!(
+      r#"
+        UPDATE goals
+        SET subsidized = true,
+          subsidized_completion_req = subsidized_completion_req + $3::bigint,
+          subsidized_embedding_req = subsidized_embedding_req + $4::bigint,
+          updated_at = now()
+        WHERE id = (
+          SELECT id FROM goals WHERE user_id = $1 AND chat_id = $2 ORDER BY created_at LIMIT 1
+        )
+      "#,
+      user_id,
+      chat_id,
+      completion_requests,
+      embedding_requests,
+    )
+    .execute(pool)
+    .await?;
+    if updated.rows_affected() == 0 {
+      sqlx::query!(
+        r#"
+          INSERT INTO goals(chat_id, user_id, prompt, submission_date, subsidized,
+            subsidized_completion_req, subsidized_embedding_req)
+          VALUES ($1, $2, '', $3, true, $4::bigint, $5::bigint)
+        "#,
+        chat_id,
+        user_id,
+        Utc::now(),
+        completion_requests,
+        embedding_requests,
+      )
+      .execute(pool)
+      .await?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use chrono::prelude::*;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{models::retrieval::WorkspaceChunk, Result};
+
+/// A chunk of a workspace file as it is stored, before it is embedded.
+#[derive(Debug, Clone)]
+pub struct NewChunk {
+  pub start_line: i32,
+  pub end_line: i32,
+  pub content: String,
+}
+
+/// The owner of a chat whose files are indexed.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkOwner {
+  pub user_id: Uuid,
+  pub redact_pii: bool,
+}
+
+impl WorkspaceChunk {
+  /// Who owns the chat of a workspace, `None` when the chat is gone.
+  pub async fn owner(chat_id: Uuid, pool: &PgPool) -> Result<Option<ChunkOwner>> {
+    let owner = sqlx::query_as!(
+      ChunkOwner,
+      "SELECT user_id, redact_pii FROM chats WHERE id = $1",
+      chat_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(owner)
+  }
+
+  /// When each chunked file of a workspace was modified as it was chunked.
+  pub async fn indexed_files(
+    chat_id: Uuid,
+    pool: &PgPool,
+  ) -> Result<HashMap<String, DateTime<Utc>>> {
+    let rows = sqlx::query!(
+      r#"
+        SELECT file_name, max(file_modified_at) AS "file_modified_at!"
+        FROM workspace_chunks
+        WHERE chat_id = $1
+        GROUP BY file_name
+      "#,
+      chat_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(
+      rows
+        .into_iter()
+        .map(|row| (row.file_name, row.file_modified_at))
+        .collect(),
+    )
+  }
+
+  /// Replaces the chunks of a file. `embeddings` are in the order of `chunks`, empty when the
+  /// chunks are not embedded.
+  pub async fn replace(
+    chat_id: Uuid,
+    file_name: &str,
+    file_modified_at: DateTime<Utc>,
+    chunks: &[NewChunk],
+    embeddings: &[Vec<f32>],
+    model: &str,
+    pool: &PgPool,
+  ) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+      "DELETE FROM workspace_chunks WHERE chat_id = $1 AND file_name = $2",
+      chat_id,
+      file_name
+    )
+    .execute(&mut *tx)
+    .await?;
+    for (index, chunk) in chunks.iter().enumerate() {
+      let embedding = embeddings.get(index);
+      sqlx::query!(
+        r#"
+          INSERT INTO workspace_chunks(chat_id, file_name, file_modified_at, chunk_index,
+            start_line, end_line, content, embedding, embedding_model)
+          VALUES ($1, $2, $3, $4, $5, $6, $7, $8::real[]::vector, $9)
+        "#,
+        chat_id,
+        file_name,
+        file_modified_at,
+        index as i32,
+        chunk.start_line,
+        chunk.end_line,
+        chunk.content,
+        embedding.map(|embedding| embedding.as_slice()),
+        embedding.map(|_| model)
+      )
+      .execute(&mut *tx)
+      .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+  }
+
+  /// Forgets the chunks of a file that was removed.
+  pub async fn remove(chat_id: Uuid, file_name: &str, pool: &PgPool) -> Result<()> {
+    sqlx::query!(
+      "DELETE FROM workspace_chunks WHERE chat_id = $1 AND file_name = $2",
+      chat_id,
+      file_name
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+
+  /// The `limit` chunks of the workspace of a chat closest in meaning to the embedded query,
+  /// leaving out those further than `max_distance`.
+  pub async fn nearest(
+    chat_id: Uuid,
+    embedding: &[f32],
+    model: &str,
+    max_distance: f64,
+    limit: i64,
+    pool: &PgPool,
+  ) -> Result<Vec<WorkspaceChunk>> {
+    let chunks = sqlx::query_as!(
+      WorkspaceChunk,
+      r#"
+        SELECT chat_id, file_name, start_line, end_line, content,
+          (1 - (embedding <=> $2::real[]::vector))::float8 AS "score!"
+        FROM workspace_chunks
+        WHERE chat_id = $1 AND embedding_model = $3
+          AND embedding <=> $2::real[]::vector <= $4
+        ORDER BY embedding <=> $2::real[]::vector
+        LIMIT $5
+      "#,
+      chat_id,
+      embedding,
+      model,
+      max_distance,
+      limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(chunks)
+  }
+
+  /// The `limit` chunks of the workspace of a chat that share the most words with the query.
+  /// Any of the words matches, a prompt rarely contains all the words of a passage.
+  pub async fn matching(
+    chat_id: Uuid,
+    query: &str,
+    limit: i64,
+    pool: &PgPool,
+  ) -> Result<Vec<WorkspaceChunk>> {
+    let chunks = sqlx::query_as!(
+      WorkspaceChunk,
+      r#"
+        SELECT chat_id, file_name, start_line, end_line, content,
+          ts_rank_cd(content_tsv, q)::float8 AS "score!"
+        FROM workspace_chunks,
+          NULLIF(replace(plainto_tsquery('english', $2)::text, '&', '|'), '')::tsquery q
+        WHERE chat_id = $1 AND content_tsv @@ q
+        ORDER BY ts_rank_cd(content_tsv, q) DESC
+        LIMIT $3
+      "#,
+      chat_id,
+      query,
+      limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(chunks)
+  }
+}
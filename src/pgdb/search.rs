@@ -0,0 +1,251 @@
+use chrono::prelude::*;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+  models::search::{SearchHit, SearchSource},
+  Result,
+};
+
+/// The candidates each of the full-text and the semantic search contribute to the fused ranking.
+const CANDIDATES: i64 = 50;
+
+/// Damps the reciprocal rank fusion, so neither ranking dominates through its top results.
+const RRF_K: f64 = 60.0;
+
+/// The searchable text of chats, synced from their titles, goals, logs and messages.
+#[derive(Debug)]
+pub struct SearchEntry;
+
+/// An entry whose text still has to be embedded.
+#[derive(Debug, Clone)]
+pub struct PendingEntry {
+  pub id: Uuid,
+  pub user_id: Uuid,
+  pub content: String,
+}
+
+impl SearchEntry {
+  /// Adds the text changed since `since` and replaces entries whose text changed, which are
+  /// embedded again. Titles do not record when they change so all of them are compared, entries
+  /// whose source was deleted or emptied are removed.
+  pub async fn sync(since: DateTime<Utc>, pool: &PgPool) -> Result<u64> {
+    let mut synced = 0;
+    synced += sqlx::query!(
+      r#"
+        INSERT INTO search_entries(user_id, chat_id, kind, source_id, content, created_at)
+        SELECT user_id, id, 'title', id, title, created_at
+        FROM chats
+        WHERE title IS NOT NULL AND title <> ''
+        ON CONFLICT (kind, source_id) DO UPDATE
+        SET content = excluded.content, embedding = NULL, embedding_model = NULL, indexed_at = now()
+        WHERE search_entries.content IS DISTINCT FROM excluded.content
+      "#
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    synced += sqlx::query!(
+      r#"
+        INSERT INTO search_entries(user_id, chat_id, kind, source_id, content, created_at)
+        SELECT user_id, chat_id, 'goal', id, prompt, created_at
+        FROM goals
+        WHERE updated_at >= $1 AND prompt <> ''
+        ON CONFLICT (kind, source_id) DO UPDATE
+        SET content = excluded.content, embedding = NULL, embedding_model = NULL, indexed_at = now()
+        WHERE search_entries.content IS DISTINCT FROM excluded.content
+      "#,
+      since
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    synced += sqlx::query!(
+      r#"
+        INSERT INTO search_entries(user_id, chat_id, kind, source_id, content, created_at)
+        SELECT c.user_id, l.chat_id, 'log', l.id, concat_ws(E'\n', l.title, l.content), l.created_at
+        FROM logs l
+          JOIN chats c ON c.id = l.chat_id
+        WHERE l.updated_at >= $1 AND l.title NOT IN ('', '{}')
+        ON CONFLICT (kind, source_id) DO UPDATE
+        SET content = excluded.content, embedding = NULL, embedding_model = NULL, indexed_at = now()
+        WHERE search_entries.content IS DISTINCT FROM excluded.content
+      "#,
+      since
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    // the user and assistant messages, tool results and system prompts are not what users recall
+    synced += sqlx::query!(
+      r#"
+        INSERT INTO search_entries(user_id, chat_id, kind, source_id, content, created_at)
+        SELECT c.user_id, m.chat_id, 'message', m.id, m.content, m.created_at
+        FROM messages m
+          JOIN chats c ON c.id = m.chat_id
+        WHERE m.updated_at >= $1 AND NOT m.temporary AND m.role IN ('user', 'assistant')
+          AND m.content IS NOT NULL AND m.content <> ''
+        ON CONFLICT (kind, source_id) DO UPDATE
+        SET content = excluded.content, embedding = NULL, embedding_model = NULL, indexed_at = now()
+        WHERE search_entries.content IS DISTINCT FROM excluded.content
+      "#,
+      since
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    synced += sqlx::query!(
+      r#"
+        DELETE FROM search_entries e
+        WHERE (e.kind = 'title' AND NOT EXISTS (
+            SELECT 1 FROM chats c WHERE c.id = e.source_id AND c.title <> ''))
+          OR (e.kind = 'goal' AND NOT EXISTS (
+            SELECT 1 FROM goals g WHERE g.id = e.source_id AND g.prompt <> ''))
+          OR (e.kind = 'log' AND NOT EXISTS (
+            SELECT 1 FROM logs l WHERE l.id = e.source_id AND l.title NOT IN ('', '{}')))
+          OR (e.kind = 'message' AND NOT EXISTS (
+            SELECT 1 FROM messages m WHERE m.id = e.source_id AND NOT m.temporary
+              AND m.content <> ''))
+      "#
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(synced)
+  }
+
+  /// The entries that are not embedded with `model` yet, oldest first. Chats that redact
+  /// personal data are left out, their text never leaves the server.
+  pub async fn pending(model: &str, limit: i64, pool: &PgPool) -> Result<Vec<PendingEntry>> {
+    let entries = sqlx::query_as!(
+      PendingEntry,
+      r#"
+        SELECT e.id, e.user_id, e.content
+        FROM search_entries e
+          JOIN chats c ON c.id = e.chat_id
+        WHERE (e.embedding IS NULL OR e.embedding_model IS DISTINCT FROM $1) AND NOT c.redact_pii
+        ORDER BY e.indexed_at
+        LIMIT $2
+      "#,
+      model,
+      limit
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(entries)
+  }
+
+  /// Moves entries that failed to embed behind the others that wait.
+  pub async fn defer(ids: &[Uuid], pool: &PgPool) -> Result<()> {
+    sqlx::query!(
+      "UPDATE search_entries SET indexed_at = now() WHERE id = ANY($1)",
+      ids
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+
+  pub async fn set_embedding(
+    id: Uuid,
+    model: &str,
+    embedding: &[f32],
+    pool: &PgPool,
+  ) -> Result<()> {
+    sqlx::query!(
+      r#"
+        UPDATE search_entries
+        SET embedding = $3::real[]::vector, embedding_model = $2, indexed_at = now()
+        WHERE id = $1
+      "#,
+      id,
+      model,
+      embedding
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+}
+
+impl SearchHit {
+  /// The chats of a user that best match `query` by its words and, when the query is embedded,
+  /// by meaning. The two rankings are fused by their reciprocal ranks and each chat is returned
+  /// once, with its best matching entry.
+  pub async fn search(
+    user_id: Uuid,
+    query: &str,
+    embedding: Option<&[f32]>,
+    model: &str,
+    limit: i64,
+    pool: &PgPool,
+  ) -> Result<Vec<SearchHit>> {
+    let rows = sqlx::query!(
+      r#"
+        WITH lexical AS (
+          SELECT e.id, row_number() OVER (ORDER BY ts_rank_cd(e.content_tsv, q) DESC) AS rank
+          FROM search_entries e, websearch_to_tsquery('english', $2) q
+          WHERE e.user_id = $1 AND e.content_tsv @@ q
+          ORDER BY rank
+          LIMIT $5
+        ),
+        semantic AS (
+          SELECT e.id, row_number() OVER (ORDER BY e.embedding <=> $3::real[]::vector) AS rank
+          FROM search_entries e
+          WHERE $3::real[] IS NOT NULL AND e.user_id = $1 AND e.embedding_model = $4
+            AND e.embedding IS NOT NULL
+          ORDER BY rank
+          LIMIT $5
+        ),
+        fused AS (
+          SELECT id, COALESCE(1.0 / ($6::float8 + l.rank), 0) + COALESCE(1.0 / ($6::float8 + s.rank), 0) AS score
+          FROM lexical l
+            FULL JOIN semantic s USING (id)
+        ),
+        best AS (
+          SELECT DISTINCT ON (e.chat_id) e.chat_id, e.kind, e.content, e.created_at, f.score
+          FROM fused f
+            JOIN search_entries e ON e.id = f.id
+          ORDER BY e.chat_id, f.score DESC
+        )
+        SELECT b.chat_id AS "chat_id!", c.title AS chat_title, b.kind AS "kind!",
+          b.created_at AS "created_at!", b.score::float8 AS "score!",
+          ts_headline('english', b.content, websearch_to_tsquery('english', $2),
+            'StartSel="' || chr(2) || '", StopSel="' || chr(3) || '", MaxWords=24, MinWords=8, MaxFragments=2, FragmentDelimiter=" … "'
+          ) AS "snippet!"
+        FROM best b
+          JOIN chats c ON c.id = b.chat_id
+        ORDER BY b.score DESC
+        LIMIT $7
+      "#,
+      user_id,
+      query,
+      embedding,
+      model,
+      CANDIDATES,
+      RRF_K,
+      limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(
+      rows
+        .into_iter()
+        .map(|row| SearchHit {
+          chat_id: row.chat_id,
+          chat_title: row.chat_title,
+          source: SearchSource::from(row.kind.as_str()),
+          snippet: row.snippet,
+          created_at: row.created_at,
+          score: row.score,
+        })
+        .collect(),
+    )
+  }
+}
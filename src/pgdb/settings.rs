@@ -0,0 +1,39 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{models::settings::UserSettings, Result};
+
+impl UserSettings {
+  /// The settings of a user, the defaults until they saved any.
+  pub async fn for_user(user_id: Uuid, pool: &PgPool) -> Result<UserSettings> {
+    let settings = sqlx::query!(
+      "SELECT voice, speech_speed FROM user_settings WHERE user_id = $1",
+      user_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .map(|row| UserSettings {
+      voice: row.voice.as_str().into(),
+      speech_speed: row.speech_speed,
+    })
+    .unwrap_or_default();
+    Ok(settings)
+  }
+
+  pub async fn save(&self, user_id: Uuid, pool: &PgPool) -> Result<()> {
+    sqlx::query!(
+      r#"
+        INSERT INTO user_settings(user_id, voice, speech_speed)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (user_id) DO UPDATE
+        SET voice = EXCLUDED.voice, speech_speed = EXCLUDED.speech_speed, updated_at = now()
+      "#,
+      user_id,
+      self.voice.as_str(),
+      self.speech_speed,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+}
@@ -0,0 +1,157 @@
+use chrono::prelude::*;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+  models::usage::{UsageBucket, UsageRollup},
+  Result,
+};
+
+/// A single call to the provider, stored in the `usage_events` hypertable.
+#[derive(Debug, Clone, Default)]
+pub struct UsageEvent {
+  pub user_id: Option<Uuid>,
+  pub chat_id: Option<Uuid>,
+  pub endpoint: String,
+  pub model: String,
+  pub prompt_tokens: i32,
+  pub completion_tokens: i32,
+  pub latency_ms: i32,
+  /// Estimated from the pricing table, in USD.
+  pub cost: f64,
+  pub error: Option<String>,
+}
+
+impl UsageEvent {
+  pub async fn insert(&self, pool: &PgPool) -> Result<()> {
+    let status = if self.error.is_some() { "error" } else { "ok" };
+    sqlx::query!(
+      r#"
+        INSERT INTO usage_events(user_id, chat_id, endpoint, model, prompt_tokens,
+          completion_tokens, total_tokens, latency_ms, status, error, cost)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+      "#,
+      self.user_id,
+      self.chat_id,
+      self.endpoint,
+      self.model,
+      self.prompt_tokens,
+      self.completion_tokens,
+      self.prompt_tokens + self.completion_tokens,
+      self.latency_ms,
+      status,
+      self.error,
+      self.cost,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+  }
+}
+
+impl UsageRollup {
+  /// Usage of a single user per bucket and model since `since`.
+  pub async fn for_user(
+    user_id: Uuid,
+    bucket: UsageBucket,
+    since: DateTime<Utc>,
+    pool: &PgPool,
+  ) -> Result<Vec<UsageRollup>> {
+    let rows = match bucket {
+      UsageBucket::Hour => {
+        sqlx::query_as!(
+          UsageRollup,
+          r#"
+            SELECT bucket AS "bucket!", user_id, model,
+              sum(requests)::bigint AS "requests!", sum(errors)::bigint AS "errors!",
+              sum(prompt_tokens)::bigint AS "prompt_tokens!",
+              sum(completion_tokens)::bigint AS "completion_tokens!",
+              sum(total_tokens)::bigint AS "total_tokens!",
+              (sum(avg_latency_ms * requests) / sum(requests))::float8 AS "avg_latency_ms!"
+            FROM usage_hourly
+            WHERE user_id = $1 AND bucket >= $2
+            GROUP BY bucket, user_id, model
+            ORDER BY bucket, model
+          "#,
+          user_id,
+          since,
+        )
+        .fetch_all(pool)
+        .await?
+      }
+      UsageBucket::Day => {
+        sqlx::query_as!(
+          UsageRollup,
+          r#"
+            SELECT bucket AS "bucket!", user_id, model,
+              sum(requests)::bigint AS "requests!", sum(errors)::bigint AS "errors!",
+              sum(prompt_tokens)::bigint AS "prompt_tokens!",
+              sum(completion_tokens)::bigint AS "completion_tokens!",
+              sum(total_tokens)::bigint AS "total_tokens!",
+              (sum(avg_latency_ms * requests) / sum(requests))::float8 AS "avg_latency_ms!"
+            FROM usage_daily
+            WHERE user_id = $1 AND bucket >= $2
+            GROUP BY bucket, user_id, model
+            ORDER BY bucket, model
+          "#,
+          user_id,
+          since,
+        )
+        .fetch_all(pool)
+        .await?
+      }
+    };
+    Ok(rows)
+  }
+
+  /// Usage of all users per bucket and model since `since`.
+  pub async fn for_models(
+    bucket: UsageBucket,
+    since: DateTime<Utc>,
+    pool: &PgPool,
+  ) -> Result<Vec<UsageRollup>> {
+    let rows = match bucket {
+      UsageBucket::Hour => {
+        sqlx::query_as!(
+          UsageRollup,
+          r#"
+            SELECT bucket AS "bucket!", NULL::uuid AS user_id, model,
+              sum(requests)::bigint AS "requests!", sum(errors)::bigint AS "errors!",
+              sum(prompt_tokens)::bigint AS "prompt_tokens!",
+              sum(completion_tokens)::bigint AS "completion_tokens!",
+              sum(total_tokens)::bigint AS "total_tokens!",
+              (sum(avg_latency_ms * requests) / sum(requests))::float8 AS "avg_latency_ms!"
+            FROM usage_hourly
+            WHERE bucket >= $1
+            GROUP BY bucket, model
+            ORDER BY bucket, model
+          "#,
+          since,
+        )
+        .fetch_all(pool)
+        .await?
+      }
+      UsageBucket::Day => {
+        sqlx::query_as!(
+          UsageRollup,
+          r#"
+            SELECT bucket AS "bucket!", NULL::uuid AS user_id, model,
+              sum(requests)::bigint AS "requests!", sum(errors)::bigint AS "errors!",
+              sum(prompt_tokens)::bigint AS "prompt_tokens!",
+              sum(completion_tokens)::bigint AS "completion_tokens!",
+              sum(total_tokens)::bigint AS "total_tokens!",
+              (sum(avg_latency_ms * requests) / sum(requests))::float8 AS "avg_latency_ms!"
+            FROM usage_daily
+            WHERE bucket >= $1
+            GROUP BY bucket, model
+            ORDER BY bucket, model
+          "#,
+          since,
+        )
+        .fetch_all(pool)
+        .await?
+      }
+    };
+    Ok(rows)
+  }
+}
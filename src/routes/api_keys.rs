@@ -0,0 +1,74 @@
+use cfg_if::cfg_if;
+use leptos::*;
+use uuid::Uuid;
+
+use crate::models::api_keys::{ApiKey, CreateApiKeyRequest, CreatedApiKey};
+
+cfg_if! {
+  if #[cfg(feature = "ssr")] {
+    use crate::app::{auth,pool};
+  }
+}
+
+#[server(GetApiKeys, "/api")]
+pub async fn get_api_keys() -> Result<Vec<ApiKey>, ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      let db = pool()?;
+      let keys = ApiKey::list_for_user(user.id, &db).await?;
+      Ok(keys)
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
+
+/// Creates a key for the current user, the secret is only returned here.
+#[server(CreateApiKey, "/api")]
+pub async fn create_api_key(request: CreateApiKeyRequest) -> Result<CreatedApiKey, ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      if request.name.trim().is_empty() {
+        return Err(ServerFnError::ServerError("A name is required.".into()));
+      }
+      // scopes are matched against paths below /openai/v1, accept them with or without /v1
+      let endpoints = request
+        .endpoints
+        .iter()
+        .map(|e| e.trim().trim_start_matches("/v1").trim_end_matches('/'))
+        .filter(|e| !e.is_empty())
+        .map(|e| format!("/{}", e.trim_start_matches('/')))
+        .collect();
+      let models = request
+        .models
+        .iter()
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty())
+        .collect();
+      let request = CreateApiKeyRequest {
+        endpoints,
+        models,
+        ..request
+      };
+
+      let db = pool()?;
+      let key = ApiKey::create(user.id, request, &db).await?;
+      Ok(key)
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
+
+#[server(RevokeApiKey, "/api")]
+pub async fn revoke_api_key(id: Uuid) -> Result<(), ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      let db = pool()?;
+      ApiKey::revoke(user.id, id, &db).await?;
+      Ok(())
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
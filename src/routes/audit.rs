@@ -0,0 +1,26 @@
+use cfg_if::cfg_if;
+use leptos::*;
+
+use crate::models::audit::{AuditEvent, AuditQuery};
+
+cfg_if! {
+  if #[cfg(feature = "ssr")] {
+    use crate::app::{auth,pool};
+  }
+}
+
+/// Audited model calls matching `query`, newest first. The full log can be exported as JSON
+/// lines from `/api/v1/audit/export`.
+#[server(GetAuditEvents, "/api")]
+pub async fn get_audit_events(query: AuditQuery) -> Result<Vec<AuditEvent>, ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) if user.permissions.contains("admin") => {
+      let db = pool()?;
+      let events = AuditEvent::query(&query, &db).await?;
+      Ok(events)
+    }
+    Some(_) => Err(ServerFnError::ServerError("Not authorized.".into())),
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
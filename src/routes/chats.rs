@@ -2,7 +2,7 @@ use cfg_if::cfg_if;
 use leptos::*;
 use uuid::Uuid;
 
-use crate::models::{Chat, ChatLog};
+use crate::models::{pii::PiiRedaction, pricing::GoalBudget, Chat, ChatLog};
 
 cfg_if! {
   if #[cfg(feature = "ssr")] {
@@ -17,6 +17,10 @@ cfg_if! {
 
     use crate::Result;
     use crate::app::{auth,app_state,pool};
+    use crate::server::{audit::AgentCall, catalog, moderation::{self, Subject}, pii::Redactor, pricing, quota, usage::Tracker};
+    use crate::pgdb::RequestKind;
+    use crate::models::{workspace_images, ChatMessage, Role, SavedMessage};
+    use crate::server::workspace;
     use tracing::info;
   }
 }
@@ -95,31 +99,70 @@ pub async fn update_chat_title(id: Uuid, title: String) -> Result<(), ServerFnEr
   Ok(())
 }
 
+/// Summarizes the prompt of a chat into its title with `model`, the chat model picked in the UI.
 #[server(GenerateTitle, "/api")]
-pub async fn generate_title(_id: Uuid, prompt: String) -> Result<String, ServerFnError> {
+pub async fn generate_title(
+  id: Uuid,
+  prompt: String,
+  model: String,
+) -> Result<String, ServerFnError> {
   let auth = auth()?;
-  if auth.current_user.is_none() {
+  let Some(user) = auth.current_user else {
     return Err(ServerFnError::ServerError("Not authenticated.".into()));
-  }
+  };
 
   let app_state = app_state()?;
+  if !Chat::is_owned_by(id, user.id, &app_state.pool).await? {
+    return Err(ServerFnError::ServerError("Chat not found.".into()));
+  }
+  let model = match catalog::get(&app_state, &model).await {
+    Some(entry) if entry.available && entry.capabilities.chat => entry.id,
+    _ => {
+      return Err(ServerFnError::ServerError(format!(
+        "Model {model} is not available for chats."
+      )))
+    }
+  };
+  // a request refused by the budget does not use up quota
+  let budget = pricing::check_budget(&app_state, id).await?;
+  quota::check(&app_state, Some(user.id), RequestKind::Completion).await?;
+  let mut redactor = Redactor::for_chat(&app_state, Some(user.id), Some(id)).await?;
+  let prompt = match redactor.as_mut() {
+    Some(redactor) => {
+      let prompt = redactor.redact(&prompt);
+      redactor.record(&app_state, "/v1/chat/completions");
+      prompt
+    }
+    None => prompt,
+  };
+  let subject = Subject {
+    source: moderation::SOURCE_GOAL,
+    user_id: Some(user.id),
+    chat_id: Some(id),
+  };
+  moderation::check(&app_state, subject, vec![prompt.clone()]).await?;
   let oai = app_state.openai_client();
 
   let sysprompt = make_sysprompt("Summarize the given prompt using max 4 words")?;
   let userprompt = make_userprompt(prompt)?;
 
-  let response = oai
-    .chat()
-    .create(CreateChatCompletionRequest {
-      messages: vec![
-        ChatCompletionRequestMessage::System(sysprompt),
-        ChatCompletionRequestMessage::User(userprompt),
-      ],
-      model: "gpt-3.5-turbo".into(),
-      ..Default::default()
-    })
-    .await
-    .map_err(ServerFnError::WrappedServerError)?;
+  let request = CreateChatCompletionRequest {
+    messages: vec![
+      ChatCompletionRequestMessage::System(sysprompt),
+      ChatCompletionRequestMessage::User(userprompt),
+    ],
+    model: model.clone(),
+    ..Default::default()
+  };
+  let usage = Tracker::start("/v1/chat/completions", &model)
+    .user(Some(user.id))
+    .chat(Some(id))
+    .budget(budget);
+  let audit = AgentCall::start("/v1/chat/completions", &request).user(Some(user.id));
+  let result = oai.chat().create(request).await;
+  usage.finish(&app_state, &result);
+  audit.finish(&app_state, &result);
+  let response = result.map_err(ServerFnError::WrappedServerError)?;
 
   if response.choices.is_empty() {
     return Err(ServerFnError::ServerError(
@@ -127,11 +170,131 @@ pub async fn generate_title(_id: Uuid, prompt: String) -> Result<String, ServerF
     ));
   }
 
-  response.choices[0]
+  let title = response.choices[0]
     .message
     .content
     .clone()
-    .ok_or_else(|| ServerFnError::ServerError("No content in OpenAI response.".into()))
+    .ok_or_else(|| ServerFnError::ServerError("No content in OpenAI response.".into()))?;
+  Ok(match redactor {
+    Some(redactor) => redactor.restore(&title),
+    None => title,
+  })
+}
+
+/// Adds a user message to a chat. Workspace images embedded as `![alt](workspace://file)` are
+/// sent to vision models along with the text.
+#[server(AddChatMessage, "/api")]
+pub async fn add_chat_message(chat_id: Uuid, content: String) -> Result<(), ServerFnError> {
+  let auth = auth()?;
+  let Some(user) = auth.current_user else {
+    return Err(ServerFnError::ServerError("Not authenticated.".into()));
+  };
+
+  let db = pool()?;
+  if !Chat::is_owned_by(chat_id, user.id, &db).await? {
+    return Err(ServerFnError::ServerError("Chat not found.".into()));
+  }
+  let app_state = app_state()?;
+  let workspace_dir = app_state.upload_store.join(chat_id.to_string());
+  for file_name in workspace_images(&content) {
+    if !workspace::path_is_valid(&file_name)
+      || !tokio::fs::try_exists(workspace_dir.join(&file_name)).await?
+    {
+      return Err(ServerFnError::ServerError(format!(
+        "{file_name} is not in the workspace."
+      )));
+    }
+  }
+
+  let message = SavedMessage {
+    msg: ChatMessage::User(crate::models::ChatCompletionRequestUserMessage {
+      content: crate::models::ChatCompletionRequestUserMessageContent::with_workspace_images(
+        content.clone(),
+      ),
+      role: Role::User,
+      name: None,
+    }),
+    temporary: false,
+  };
+  Chat::add_message(chat_id, message, &db).await?;
+  ChatLog::create(chat_id, "user".into(), content, None, &db).await?;
+  Ok(())
+}
+
+/// Turns replacing personal data in the prompts of a chat on or off.
+#[server(SetChatRedaction, "/api")]
+pub async fn set_chat_redaction(id: Uuid, enabled: bool) -> Result<(), ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      let db = pool()?;
+      Chat::set_redact_pii(id, user.id, enabled, &db).await?;
+      Ok(())
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
+
+/// What was redacted from the prompts of a chat, without the original values.
+#[server(GetChatRedactions, "/api")]
+pub async fn get_chat_redactions(id: Uuid) -> Result<Vec<PiiRedaction>, ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      let db = pool()?;
+      if !Chat::is_owned_by(id, user.id, &db).await? {
+        return Err(ServerFnError::ServerError("Chat not found.".into()));
+      }
+      let redactions = PiiRedaction::list_for_chat(id, &db).await?;
+      Ok(redactions)
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
+
+/// The budget of the goal of a chat and what it spent, `None` when the chat has no goal yet.
+#[server(GetGoalBudget, "/api")]
+pub async fn get_goal_budget(id: Uuid) -> Result<Option<GoalBudget>, ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      let db = pool()?;
+      if !Chat::is_owned_by(id, user.id, &db).await? {
+        return Err(ServerFnError::ServerError("Chat not found.".into()));
+      }
+      let budget = GoalBudget::for_chat(id, &db).await?;
+      Ok(budget)
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
+
+/// Limits what the goal of a chat may spend in USD, an empty budget removes the limit.
+#[server(SetGoalBudget, "/api")]
+pub async fn set_goal_budget(id: Uuid, budget: String) -> Result<(), ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      let budget = match budget.trim().trim_start_matches('$') {
+        "" => None,
+        budget => match budget.parse::<f64>() {
+          Ok(budget) if budget >= 0.0 => Some(budget),
+          _ => {
+            return Err(ServerFnError::ServerError(
+              "The budget must be a positive amount in USD.".into(),
+            ))
+          }
+        },
+      };
+      let db = pool()?;
+      if !Chat::is_owned_by(id, user.id, &db).await? {
+        return Err(ServerFnError::ServerError("Chat not found.".into()));
+      }
+      GoalBudget::set(user.id, id, budget, &db).await?;
+      Ok(())
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
 }
 
 cfg_if! {
@@ -3,12 +3,13 @@ use leptos::*;
 use server_fn::codec::{MultipartData, MultipartFormData};
 use uuid::Uuid;
 
-use crate::models::UploadedFile;
+use crate::models::{images::WorkspaceImage, UploadedFile};
 
 cfg_if! {
   if #[cfg(feature = "ssr")] {
     use crate::Result;
-    use crate::app::{auth,app_state};
+    use crate::app::{auth,app_state,pool};
+    use crate::models::Chat;
     use crate::server::workspace;
     use std::path::PathBuf;
     use std::str::FromStr;
@@ -32,11 +33,12 @@ pub async fn get_files(chat_id: Uuid) -> Result<Vec<UploadedFile>, ServerFnError
         .map_err(ServerFnError::WrappedServerError)?
       {
         let path = entry.path();
+        if workspace::is_hidden(&entry.file_name().to_string_lossy()) {
+          continue;
+        }
         files.push(UploadedFile {
           file_name: path.file_name().unwrap().to_str().unwrap().to_string(),
-          mime_type: mime_guess::from_path(&path)
-            .first_or_octet_stream()
-            .to_string(),
+          mime_type: workspace::mime_type(&path).to_string(),
           workspace: chat_id.to_string(),
         })
       }
@@ -46,15 +48,33 @@ pub async fn get_files(chat_id: Uuid) -> Result<Vec<UploadedFile>, ServerFnError
   }
 }
 
+/// The images generated into the workspace of a chat, newest first.
+#[server(GetWorkspaceImages, "/api")]
+pub async fn get_workspace_images(chat_id: Uuid) -> Result<Vec<WorkspaceImage>, ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      let db = pool()?;
+      if !Chat::is_owned_by(chat_id, user.id, &db).await? {
+        return Err(ServerFnError::ServerError("Chat not found.".into()));
+      }
+      let images = WorkspaceImage::list_for_chat(chat_id, &db).await?;
+      Ok(images)
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
+
 #[server(input = MultipartFormData)]
 #[tracing::instrument(skip(data))]
 pub async fn upload_files(data: MultipartData) -> Result<usize, ServerFnError> {
   let auth = auth()?;
   let app_state = app_state()?;
   match auth.current_user {
-    Some(_user) => {
+    Some(user) => {
       let mut data = data.into_inner().unwrap();
       let mut workspace_dir = PathBuf::new();
+      let mut workspace_id = None;
       let mut count = 0;
       let mut collected_fields = vec![];
       let mut found_chat_id = false;
@@ -62,6 +82,7 @@ pub async fn upload_files(data: MultipartData) -> Result<usize, ServerFnError> {
         if field.name() == Some("chat_id") {
           found_chat_id = true;
           let chat_id = field.text().await?;
+          workspace_id = Uuid::parse_str(&chat_id).ok();
           workspace_dir = app_state.upload_store.join(chat_id);
           if !tokio::fs::try_exists(&workspace_dir).await? {
             tokio::fs::create_dir_all(&workspace_dir).await?;
@@ -85,6 +106,9 @@ pub async fn upload_files(data: MultipartData) -> Result<usize, ServerFnError> {
 
         workspace::stream_to_file(&workspace_dir, &file_name, field).await?;
         count += 1;
+        if let Some(chat_id) = workspace_id {
+          workspace::uploaded(&app_state, user.id, chat_id, &file_name);
+        }
       }
       while let Ok(Some(field)) = data.next_field().await {
         let file_name = if let Some(file_name) = field.file_name() {
@@ -95,6 +119,9 @@ pub async fn upload_files(data: MultipartData) -> Result<usize, ServerFnError> {
 
         workspace::stream_to_file(&workspace_dir, &file_name, field).await?;
         count += 1;
+        if let Some(chat_id) = workspace_id {
+          workspace::uploaded(&app_state, user.id, chat_id, &file_name);
+        }
       }
       Ok(count)
     }
@@ -0,0 +1,88 @@
+use cfg_if::cfg_if;
+use leptos::*;
+use uuid::Uuid;
+
+use crate::models::fine_tuning::{DatasetReport, JobEvent, TrackedJob};
+
+cfg_if! {
+  if #[cfg(feature = "ssr")] {
+    use sqlx::PgPool;
+    use crate::app::{auth,app_state,pool};
+    use crate::models::Chat;
+    use crate::server::datasets::{self, Dataset};
+
+    /// The chats of a user with their history, chats of others are not found.
+    async fn load_dataset(user_id: Uuid, chat_ids: Vec<Uuid>, db: &PgPool) -> Result<Dataset, ServerFnError> {
+      if chat_ids.is_empty() {
+        return Err(ServerFnError::ServerError("No chats selected.".into()));
+      }
+      let mut chats = Vec::with_capacity(chat_ids.len());
+      for chat_id in chat_ids {
+        if !Chat::is_owned_by(chat_id, user_id, db).await? {
+          return Err(ServerFnError::ServerError("Chat not found.".into()));
+        }
+        chats.push(Chat::get(chat_id, db).await?);
+      }
+      Ok(datasets::build(&chats))
+    }
+  }
+}
+
+/// Builds a fine-tuning dataset from chats without uploading it, to review its stats and warnings.
+#[server(PreviewDataset, "/api")]
+pub async fn preview_dataset(chat_ids: Vec<Uuid>) -> Result<DatasetReport, ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      let db = pool()?;
+      let dataset = load_dataset(user.id, chat_ids, &db).await?;
+      Ok(dataset.report)
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
+
+/// Builds a fine-tuning dataset from chats and uploads it as a `fine-tune` file.
+#[server(UploadDataset, "/api")]
+pub async fn upload_dataset(chat_ids: Vec<Uuid>) -> Result<DatasetReport, ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      let db = pool()?;
+      let app_state = app_state()?;
+      let mut dataset = load_dataset(user.id, chat_ids, &db).await?;
+      datasets::upload(&app_state, user.id, &mut dataset).await?;
+      Ok(dataset.report)
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
+
+/// The fine-tuning jobs the user created through the API, newest first.
+#[server(GetFineTuningJobs, "/api")]
+pub async fn get_fine_tuning_jobs() -> Result<Vec<TrackedJob>, ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      let db = pool()?;
+      Ok(TrackedJob::list_for_user(user.id, &db).await?)
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
+
+/// The event timeline of a fine-tuning job of the user.
+#[server(GetFineTuningEvents, "/api")]
+pub async fn get_fine_tuning_events(job_id: String) -> Result<Vec<JobEvent>, ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      let db = pool()?;
+      if !TrackedJob::is_owned_by(&job_id, user.id, &db).await? {
+        return Err(ServerFnError::ServerError("Job not found.".into()));
+      }
+      Ok(JobEvent::list_for_job(&job_id, &db).await?)
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
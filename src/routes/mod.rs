@@ -1,3 +1,11 @@
+pub mod api_keys;
+pub mod audit;
 pub mod authn;
 pub mod chats;
 pub mod files;
+pub mod fine_tuning;
+pub mod models;
+pub mod quota;
+pub mod search;
+pub mod settings;
+pub mod usage;
@@ -0,0 +1,22 @@
+use cfg_if::cfg_if;
+use leptos::*;
+
+use crate::models::catalog::CatalogModel;
+
+cfg_if! {
+  if #[cfg(feature = "ssr")] {
+    use crate::app::{auth,app_state};
+    use crate::server::catalog;
+  }
+}
+
+/// The model catalog, for pickers in the UI.
+#[server(GetModels, "/api")]
+pub async fn get_models() -> Result<Vec<CatalogModel>, ServerFnError> {
+  let auth = auth()?;
+  if !auth.is_authenticated() {
+    return Err(ServerFnError::ServerError("Not authenticated.".into()));
+  }
+  let app_state = app_state()?;
+  Ok(catalog::list(&app_state).await)
+}
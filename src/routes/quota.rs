@@ -0,0 +1,74 @@
+use cfg_if::cfg_if;
+use leptos::*;
+use uuid::Uuid;
+
+use crate::models::quota::{QuotaLimits, QuotaStatus};
+
+cfg_if! {
+  if #[cfg(feature = "ssr")] {
+    use crate::app::{auth,app_state,pool};
+    use crate::models::quota::QuotaUsage;
+    use crate::models::User;
+
+    fn require_admin() -> Result<User, ServerFnError> {
+      match auth()?.current_user {
+        Some(user) if user.permissions.contains("admin") => Ok(user),
+        Some(_) => Err(ServerFnError::ServerError("Not authorized.".into())),
+        None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+      }
+    }
+  }
+}
+
+/// The limits of the current user and how much of them is used.
+#[server(GetQuotaStatus, "/api")]
+pub async fn get_quota_status() -> Result<QuotaStatus, ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      let db = pool()?;
+      let app_state = app_state()?;
+      let limits = QuotaLimits::for_user(user.id, &db)
+        .await?
+        .or(app_state.quotas().defaults());
+      let usage = QuotaUsage::for_user(user.id, &db).await?;
+      Ok(QuotaStatus { limits, usage })
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
+
+/// Overrides the default limits of a user, limits that are not set fall back to the defaults.
+#[server(SetUserQuota, "/api")]
+pub async fn set_user_quota(user_id: Uuid, limits: QuotaLimits) -> Result<(), ServerFnError> {
+  require_admin()?;
+  let db = pool()?;
+  QuotaLimits::set_for_user(user_id, &limits, &db).await?;
+  Ok(())
+}
+
+/// Grants a user subsidized requests on top of their limits, booked on the goal of a chat.
+#[server(GrantSubsidy, "/api")]
+pub async fn grant_subsidy(
+  user_id: Uuid,
+  chat_id: Uuid,
+  completion_requests: i64,
+  embedding_requests: i64,
+) -> Result<(), ServerFnError> {
+  require_admin()?;
+  if completion_requests < 0 || embedding_requests < 0 {
+    return Err(ServerFnError::ServerError(
+      "Subsidies can not be negative.".into(),
+    ));
+  }
+  let db = pool()?;
+  QuotaUsage::grant_subsidy(
+    user_id,
+    chat_id,
+    completion_requests,
+    embedding_requests,
+    &db,
+  )
+  .await?;
+  Ok(())
+}
@@ -0,0 +1,28 @@
+use cfg_if::cfg_if;
+use leptos::*;
+
+use crate::models::search::SearchHit;
+
+cfg_if! {
+  if #[cfg(feature = "ssr")] {
+    use crate::app::{auth, app_state};
+    use crate::server::search;
+  }
+}
+
+/// The most chats a search returns.
+const SEARCH_LIMIT: i64 = 20;
+
+/// The chats of the current user that match `query` by its words or its meaning, best first.
+#[server(SearchChats, "/api")]
+pub async fn search_chats(query: String) -> Result<Vec<SearchHit>, ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      let app_state = app_state()?;
+      let hits = search::search(&app_state, user.id, &query, SEARCH_LIMIT).await?;
+      Ok(hits)
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
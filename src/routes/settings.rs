@@ -0,0 +1,41 @@
+use cfg_if::cfg_if;
+use leptos::*;
+
+use crate::models::settings::UserSettings;
+
+cfg_if! {
+  if #[cfg(feature = "ssr")] {
+    use crate::app::{auth,pool};
+  }
+}
+
+#[server(GetUserSettings, "/api")]
+pub async fn get_user_settings() -> Result<UserSettings, ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      let db = pool()?;
+      let settings = UserSettings::for_user(user.id, &db).await?;
+      Ok(settings)
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
+
+#[server(SaveUserSettings, "/api")]
+pub async fn save_user_settings(settings: UserSettings) -> Result<(), ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      if !(0.25..=4.0).contains(&settings.speech_speed) {
+        return Err(ServerFnError::ServerError(
+          "The speech speed must be between 0.25 and 4.0.".into(),
+        ));
+      }
+      let db = pool()?;
+      settings.save(user.id, &db).await?;
+      Ok(())
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
@@ -0,0 +1,48 @@
+use cfg_if::cfg_if;
+use leptos::*;
+
+use crate::models::usage::{UsageBucket, UsageRollup};
+
+cfg_if! {
+  if #[cfg(feature = "ssr")] {
+    use chrono::{Duration, Utc};
+    use crate::app::{auth,pool};
+  }
+}
+
+/// Token usage of the current user over the last `days` days, per bucket and model.
+#[server(GetUserUsage, "/api")]
+pub async fn get_user_usage(
+  bucket: UsageBucket,
+  days: i64,
+) -> Result<Vec<UsageRollup>, ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) => {
+      let db = pool()?;
+      let since = Utc::now() - Duration::days(days.clamp(1, 366));
+      let usage = UsageRollup::for_user(user.id, bucket, since, &db).await?;
+      Ok(usage)
+    }
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
+
+/// Token usage of all users over the last `days` days, per bucket and model.
+#[server(GetModelUsage, "/api")]
+pub async fn get_model_usage(
+  bucket: UsageBucket,
+  days: i64,
+) -> Result<Vec<UsageRollup>, ServerFnError> {
+  let auth = auth()?;
+  match auth.current_user {
+    Some(user) if user.permissions.contains("admin") => {
+      let db = pool()?;
+      let since = Utc::now() - Duration::days(days.clamp(1, 366));
+      let usage = UsageRollup::for_models(bucket, since, &db).await?;
+      Ok(usage)
+    }
+    Some(_) => Err(ServerFnError::ServerError("Not authorized.".into())),
+    None => Err(ServerFnError::ServerError("Not authenticated.".into())),
+  }
+}
@@ -0,0 +1,130 @@
+use axum::{
+  body::Body,
+  extract::{Request, State},
+  http::{header, HeaderMap, Method},
+  middleware::Next,
+  response::Response,
+};
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
+use uuid::Uuid;
+
+use crate::{app::state::AppState, models::api_keys::ApiKey, Error, Result};
+
+/// The user behind a request to the OpenAI compatible API, resolved from its API key.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Caller {
+  pub user_id: Uuid,
+  pub key_id: Uuid,
+}
+
+/// The most bytes of a JSON body that is buffered before its handler runs, the limit axum puts
+/// on the `Json` extractor.
+pub(crate) const MAX_JSON_BODY: usize = 2 * 1024 * 1024;
+
+/// Buffers a JSON body, failing with [Error::PayloadTooLarge] past [MAX_JSON_BODY].
+pub(crate) async fn json_bytes(body: Body) -> Result<Bytes> {
+  let mut stream = body.into_data_stream();
+  let mut bytes = BytesMut::new();
+  while let Some(chunk) = stream.next().await {
+    let chunk = chunk.map_err(|e| Error::InvalidArgument(e.to_string()))?;
+    if bytes.len() + chunk.len() > MAX_JSON_BODY {
+      return Err(Error::PayloadTooLarge {
+        param: "body".to_string(),
+        limit: MAX_JSON_BODY as u64,
+      });
+    }
+    bytes.extend_from_slice(&chunk);
+  }
+  Ok(bytes.freeze())
+}
+
+/// The models the key of a request is limited to. JSON requests naming a model are checked
+/// before they reach their handler, multipart forms once their `model` field is read and runs
+/// against the model they take from their assistant. Requests without a model are let through.
+#[derive(Debug, Clone)]
+pub(crate) struct ModelScope(ApiKey);
+
+impl ModelScope {
+  pub fn models(&self) -> &[String] {
+    &self.0.models
+  }
+
+  pub fn check(&self, model: &str) -> Result<()> {
+    if self.0.allows_model(model) {
+      return Ok(());
+    }
+    Err(Error::Forbidden(format!(
+      "api key {} can not use model {model}",
+      self.0.prefix
+    )))
+  }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+  headers
+    .get(header::AUTHORIZATION)?
+    .to_str()
+    .ok()?
+    .strip_prefix("Bearer ")
+    .map(str::trim)
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+  headers
+    .get(header::CONTENT_TYPE)
+    .and_then(|v| v.to_str().ok())
+    .is_some_and(|v| v.starts_with("application/json"))
+}
+
+/// Middleware requiring a valid API key on the OpenAI compatible API and enforcing its scopes.
+pub(crate) async fn authenticate(
+  State(app_state): State<AppState>,
+  request: Request,
+  next: Next,
+) -> Result<Response> {
+  let Some(secret) = bearer_token(request.headers()) else {
+    return Err(Error::UserNotAuthenticated);
+  };
+  let Some(key) = ApiKey::authenticate(secret, &app_state.pool).await? else {
+    return Err(Error::UserNotAuthenticated);
+  };
+
+  let path = request.uri().path();
+  if !key.allows_endpoint(path) {
+    return Err(Error::Forbidden(format!(
+      "api key {} can not call {path}",
+      key.prefix
+    )));
+  }
+
+  let mut request = if key.models.is_empty() || request.method() != Method::POST {
+    request
+  } else {
+    let scope = ModelScope(key.clone());
+    let mut request = match is_json(request.headers()) {
+      true => check_model(&scope, request).await?,
+      false => request,
+    };
+    request.extensions_mut().insert(scope);
+    request
+  };
+
+  request.extensions_mut().insert(Caller {
+    user_id: key.user_id,
+    key_id: key.id,
+  });
+  Ok(next.run(request).await)
+}
+
+/// Checks the `model` of a JSON request for a key that is limited to some models, the body is
+/// buffered and handed on unchanged.
+async fn check_model(scope: &ModelScope, request: Request) -> Result<Request> {
+  let (parts, body) = request.into_parts();
+  let bytes = json_bytes(body).await?;
+  let value = serde_json::from_slice::<serde_json::Value>(&bytes)?;
+  if let Some(model) = value.get("model").and_then(|m| m.as_str()) {
+    scope.check(model)?;
+  }
+  Ok(Request::from_parts(parts, Body::from(bytes)))
+}
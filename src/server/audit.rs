@@ -0,0 +1,510 @@
+use std::{collections::HashSet, fmt::Display, sync::Arc, time::Instant};
+
+use axum::{
+  body::Body,
+  extract::{Extension, Query, Request, State},
+  http::{header, HeaderMap, StatusCode},
+  middleware::Next,
+  response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use chrono::Utc;
+use futures::{stream, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+  app::{handlers::AuthSession, state::AppState},
+  models::audit::{AuditEvent, AuditQuery, AUDIT_SOURCE_AGENT, AUDIT_SOURCE_PROXY},
+  pgdb::{NewAuditEvent, MAX_AUDIT_PAGE},
+  server::{
+    api_key::{self, Caller},
+    openapi::{self, ApiRouter, Operation, Schema},
+    pii::{Redactor, CHAT_ID_HEADER},
+  },
+  Error, Result,
+};
+
+const REDACTED: &str = "[REDACTED]";
+
+/// What the audit log records and for how long.
+#[derive(Debug)]
+pub struct AuditPolicy {
+  enabled: bool,
+  capture_bodies: bool,
+  max_body_bytes: usize,
+  /// JSON keys whose values are replaced before a body is stored, compared case insensitively.
+  redact_fields: HashSet<String>,
+  retention_days: Option<i64>,
+}
+
+impl Default for AuditPolicy {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      capture_bodies: true,
+      max_body_bytes: 16 * 1024,
+      redact_fields: ["api_key", "authorization", "password", "secret", "token"]
+        .into_iter()
+        .map(ToString::to_string)
+        .collect(),
+      retention_days: Some(90),
+    }
+  }
+}
+
+fn env_flag(name: &str) -> Option<bool> {
+  dotenvy::var(name)
+    .ok()
+    .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+}
+
+impl AuditPolicy {
+  /// Reads `MIKO_AUDIT_ENABLED`, `MIKO_AUDIT_BODIES`, `MIKO_AUDIT_MAX_BODY_BYTES`,
+  /// `MIKO_AUDIT_REDACT_FIELDS` (comma separated) and `MIKO_AUDIT_RETENTION_DAYS` (0 keeps
+  /// events forever), falling back to the defaults.
+  pub fn from_env() -> Self {
+    let defaults = Self::default();
+    Self {
+      enabled: env_flag("MIKO_AUDIT_ENABLED").unwrap_or(defaults.enabled),
+      capture_bodies: env_flag("MIKO_AUDIT_BODIES").unwrap_or(defaults.capture_bodies),
+      max_body_bytes: dotenvy::var("MIKO_AUDIT_MAX_BODY_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(defaults.max_body_bytes),
+      redact_fields: dotenvy::var("MIKO_AUDIT_REDACT_FIELDS")
+        .map(|v| {
+          v.split(',')
+            .map(|f| f.trim().to_lowercase())
+            .filter(|f| !f.is_empty())
+            .collect()
+        })
+        .unwrap_or(defaults.redact_fields),
+      retention_days: match dotenvy::var("MIKO_AUDIT_RETENTION_DAYS") {
+        Ok(v) => v.parse().ok().filter(|days| *days > 0),
+        Err(_) => defaults.retention_days,
+      },
+    }
+  }
+
+  fn redact(&self, value: &mut Value) {
+    match value {
+      Value::Object(map) => {
+        for (key, value) in map.iter_mut() {
+          if self.redact_fields.contains(&key.to_lowercase()) {
+            *value = Value::String(REDACTED.to_string());
+          } else {
+            self.redact(value);
+          }
+        }
+      }
+      Value::Array(values) => values.iter_mut().for_each(|v| self.redact(v)),
+      _ => {}
+    }
+  }
+
+  /// Cuts `text` down to the body limit on a char boundary, returns whether anything was cut.
+  fn truncate(&self, mut text: String) -> (String, bool) {
+    if text.len() <= self.max_body_bytes {
+      return (text, false);
+    }
+    let mut end = self.max_body_bytes;
+    while !text.is_char_boundary(end) {
+      end -= 1;
+    }
+    text.truncate(end);
+    (text, true)
+  }
+
+  fn json_body(&self, mut value: Value) -> (Option<String>, bool) {
+    if !self.capture_bodies {
+      return (None, false);
+    }
+    self.redact(&mut value);
+    let (text, truncated) = self.truncate(value.to_string());
+    (Some(text), truncated)
+  }
+
+  /// Stores JSON bodies redacted, other text as is and binary bodies only by their size. A JSON
+  /// body that was already cut off can not be parsed and is kept as truncated text.
+  fn raw_body(
+    &self,
+    content_type: Option<&str>,
+    bytes: &[u8],
+    cut: bool,
+  ) -> (Option<String>, bool) {
+    if !self.capture_bodies {
+      return (None, false);
+    }
+    let content_type = content_type.unwrap_or("application/octet-stream");
+    if content_type.starts_with("application/json") {
+      if let Ok(value) = serde_json::from_slice::<Value>(bytes) {
+        return self.json_body(value);
+      }
+    }
+    if content_type.starts_with("application/json") || content_type.starts_with("text/") {
+      let (text, truncated) = self.truncate(String::from_utf8_lossy(bytes).into_owned());
+      return (Some(text), truncated || cut);
+    }
+    (
+      Some(format!("<{content_type}, {} bytes>", bytes.len())),
+      false,
+    )
+  }
+}
+
+fn content_type(headers: &HeaderMap) -> Option<String> {
+  headers
+    .get(header::CONTENT_TYPE)
+    .and_then(|v| v.to_str().ok())
+    .map(ToString::to_string)
+}
+
+fn elapsed_ms(started: Instant) -> i32 {
+  started.elapsed().as_millis().min(i32::MAX as u128) as i32
+}
+
+/// Whether the request is a chat completion whose prompts are redacted before they reach the
+/// provider, the personal data in its bodies is kept out of the log as well.
+async fn redacts_pii(
+  app_state: &AppState,
+  user_id: Option<Uuid>,
+  path: &str,
+  headers: &HeaderMap,
+) -> Result<bool> {
+  if !path.starts_with("/chat/") {
+    return Ok(false);
+  }
+  let chat_id = headers
+    .get(CHAT_ID_HEADER)
+    .and_then(|v| v.to_str().ok())
+    .and_then(|v| v.parse().ok());
+  Ok(
+    Redactor::for_chat(app_state, user_id, chat_id)
+      .await?
+      .is_some(),
+  )
+}
+
+fn insert(app_state: &AppState, event: NewAuditEvent) {
+  let pool = app_state.pool.clone();
+  tokio::spawn(async move {
+    if let Err(e) = event.insert(&pool).await {
+      tracing::warn!("failed to record audit event for {}: {e}", event.endpoint);
+    }
+  });
+}
+
+/// Middleware recording every call to the OpenAI compatible API, it runs after the caller was
+/// resolved from the API key.
+pub(crate) async fn record(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  request: Request,
+  next: Next,
+) -> Result<Response> {
+  let policy = app_state.audit();
+  if !policy.enabled {
+    return Ok(next.run(request).await);
+  }
+
+  let started = Instant::now();
+  let mut event = NewAuditEvent {
+    source: AUDIT_SOURCE_PROXY,
+    user_id: caller.as_ref().map(|Extension(caller)| caller.user_id),
+    api_key_id: caller.as_ref().map(|Extension(caller)| caller.key_id),
+    method: request.method().to_string(),
+    endpoint: format!("/v1{}", request.uri().path()),
+    ..Default::default()
+  };
+  let capture_bodies = policy.capture_bodies
+    && !redacts_pii(
+      &app_state,
+      event.user_id,
+      request.uri().path(),
+      request.headers(),
+    )
+    .await?;
+
+  // only JSON bodies are buffered, uploads are passed through and logged by their size
+  let request_type = content_type(request.headers());
+  let request = if request_type
+    .as_deref()
+    .is_some_and(|t| t.starts_with("application/json"))
+  {
+    let (parts, body) = request.into_parts();
+    let bytes = api_key::json_bytes(body).await?;
+    if let Ok(value) = serde_json::from_slice::<Value>(&bytes) {
+      event.model = value
+        .get("model")
+        .and_then(|m| m.as_str())
+        .map(ToString::to_string);
+      if capture_bodies {
+        (event.request_body, event.truncated) = policy.json_body(value);
+      }
+    }
+    Request::from_parts(parts, Body::from(bytes))
+  } else {
+    let length = request
+      .headers()
+      .get(header::CONTENT_LENGTH)
+      .and_then(|v| v.to_str().ok())
+      .unwrap_or("0");
+    if capture_bodies && request_type.is_some() {
+      event.request_body = Some(format!(
+        "<{}, {length} bytes>",
+        request_type.as_deref().unwrap_or_default()
+      ));
+    }
+    request
+  };
+
+  let response = next.run(request).await;
+  event.status = response.status().as_u16() as i32;
+  if !capture_bodies {
+    event.latency_ms = elapsed_ms(started);
+    insert(&app_state, event);
+    return Ok(response);
+  }
+
+  let (parts, body) = response.into_parts();
+  let mut capture = ResponseCapture {
+    content_type: content_type(&parts.headers),
+    app_state,
+    policy,
+    event: Some(event),
+    started,
+    buffer: Vec::new(),
+    cut: false,
+  };
+  let body = body.into_data_stream().map(move |chunk| {
+    if let Ok(bytes) = &chunk {
+      capture.push(bytes);
+    }
+    chunk
+  });
+  Ok(Response::from_parts(parts, Body::from_stream(body)))
+}
+
+/// Keeps the start of a response body while it is sent and records the event once the body is
+/// dropped, so streamed responses are logged when they end or the client goes away.
+struct ResponseCapture {
+  app_state: AppState,
+  policy: Arc<AuditPolicy>,
+  event: Option<NewAuditEvent>,
+  content_type: Option<String>,
+  started: Instant,
+  buffer: Vec<u8>,
+  cut: bool,
+}
+
+impl ResponseCapture {
+  fn push(&mut self, bytes: &Bytes) {
+    let room = self.policy.max_body_bytes.saturating_sub(self.buffer.len());
+    if bytes.len() > room {
+      self.cut = true;
+    }
+    self
+      .buffer
+      .extend_from_slice(&bytes[..bytes.len().min(room)]);
+  }
+}
+
+impl Drop for ResponseCapture {
+  fn drop(&mut self) {
+    let Some(mut event) = self.event.take() else {
+      return;
+    };
+    event.latency_ms = elapsed_ms(self.started);
+    let (body, truncated) =
+      self
+        .policy
+        .raw_body(self.content_type.as_deref(), &self.buffer, self.cut);
+    event.response_body = body;
+    event.truncated |= truncated;
+    insert(&self.app_state, event);
+  }
+}
+
+/// Records a call the app makes to a model on its own behalf, like chat titles, assistant runs
+/// and batch requests.
+#[derive(Debug)]
+pub(crate) struct AgentCall {
+  event: NewAuditEvent,
+  request: Value,
+  started: Instant,
+}
+
+impl AgentCall {
+  pub fn start<R: Serialize>(endpoint: &str, request: &R) -> Self {
+    let request = serde_json::to_value(request).unwrap_or_default();
+    Self {
+      event: NewAuditEvent {
+        source: AUDIT_SOURCE_AGENT,
+        method: "POST".to_string(),
+        endpoint: endpoint.to_string(),
+        model: request
+          .get("model")
+          .and_then(|m| m.as_str())
+          .map(ToString::to_string),
+        ..Default::default()
+      },
+      request,
+      started: Instant::now(),
+    }
+  }
+
+  pub fn user(mut self, user_id: Option<Uuid>) -> Self {
+    self.event.user_id = user_id;
+    self
+  }
+
+  pub fn finish<T: Serialize, E: Display>(self, app_state: &AppState, result: &Result<T, E>) {
+    let policy = app_state.audit();
+    if !policy.enabled {
+      return;
+    }
+    let mut event = self.event;
+    event.latency_ms = elapsed_ms(self.started);
+    let request_truncated;
+    (event.request_body, request_truncated) = policy.json_body(self.request);
+    let (status, response) = match result {
+      Ok(response) => (
+        StatusCode::OK,
+        serde_json::to_value(response).unwrap_or_default(),
+      ),
+      Err(e) => (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        serde_json::json!({ "error": e.to_string() }),
+      ),
+    };
+    event.status = status.as_u16() as i32;
+    let response_truncated;
+    (event.response_body, response_truncated) = policy.json_body(response);
+    event.truncated = request_truncated || response_truncated;
+    insert(app_state, event);
+  }
+}
+
+/// Deletes the events that are older than the retention period once an hour.
+pub fn spawn_retention(app_state: AppState) {
+  let Some(days) = app_state.audit().retention_days else {
+    return;
+  };
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+    loop {
+      interval.tick().await;
+      let before = Utc::now() - chrono::Duration::days(days);
+      match AuditEvent::prune(before, &app_state.pool).await {
+        Ok(0) => {}
+        Ok(count) => tracing::info!("pruned {count} audit events older than {days} days"),
+        Err(e) => tracing::warn!("failed to prune audit events: {e}"),
+      }
+    }
+  });
+}
+
+/// The filters of [AuditQuery].
+const QUERY: &[&str] = &[
+  "user_id",
+  "api_key_id",
+  "source",
+  "endpoint",
+  "model",
+  "since",
+  "until",
+  "before",
+  "limit",
+];
+
+pub(crate) fn api() -> ApiRouter {
+  ApiRouter::new()
+    .route(
+      Operation::get("/", "List audit events, newest first, admins only")
+        .query(QUERY)
+        .response(openapi::Body::Json(Schema::Model(
+          openapi::model::<Vec<AuditEvent>>,
+        ))),
+      list_events,
+    )
+    .route(
+      Operation::get("/export", "Export audit events as JSON lines, admins only")
+        .query(QUERY)
+        .response(openapi::Body::Text("application/x-ndjson")),
+      export_events,
+    )
+}
+
+fn require_admin(auth: &AuthSession) -> Result<()> {
+  match &auth.current_user {
+    Some(user) if user.permissions.contains("admin") => Ok(()),
+    Some(_) => Err(Error::Forbidden(
+      "the audit log is only available to admins".into(),
+    )),
+    None => Err(Error::UserNotAuthenticated),
+  }
+}
+
+#[tracing::instrument(skip(app_state, auth))]
+async fn list_events(
+  State(app_state): State<AppState>,
+  auth: AuthSession,
+  Query(query): Query<AuditQuery>,
+) -> Result<impl IntoResponse> {
+  require_admin(&auth)?;
+  let events = AuditEvent::query(&query, &app_state.pool).await?;
+  Ok(axum::Json(events))
+}
+
+/// Streams every event matching the query as JSON lines, paging through the table so the
+/// export never holds more than a page in memory. `limit` sets the page size.
+#[tracing::instrument(skip(app_state, auth))]
+async fn export_events(
+  State(app_state): State<AppState>,
+  auth: AuthSession,
+  Query(query): Query<AuditQuery>,
+) -> Result<impl IntoResponse> {
+  require_admin(&auth)?;
+  let query = AuditQuery {
+    limit: Some(
+      query
+        .limit
+        .unwrap_or(MAX_AUDIT_PAGE)
+        .clamp(1, MAX_AUDIT_PAGE),
+    ),
+    ..query
+  };
+  let pages = stream::try_unfold(Some(query), move |query| {
+    let pool = app_state.pool.clone();
+    async move {
+      let Some(query) = query else {
+        return Ok(None);
+      };
+      let events = AuditEvent::query(&query, &pool).await?;
+      let next = (events.len() as i64 == query.limit.unwrap_or_default()).then(|| AuditQuery {
+        before: events.last().map(|e| e.id),
+        ..query
+      });
+      let mut lines = Vec::new();
+      for event in &events {
+        serde_json::to_writer(&mut lines, event)?;
+        lines.push(b'\n');
+      }
+      Ok::<_, Error>(Some((Bytes::from(lines), next)))
+    }
+  });
+
+  let file_name = format!("audit-{}.jsonl", Utc::now().format("%Y%m%d%H%M%S"));
+  Ok((
+    [
+      (header::CONTENT_TYPE, "application/x-ndjson".to_string()),
+      (
+        header::CONTENT_DISPOSITION,
+        format!("attachment; filename=\"{file_name}\""),
+      ),
+    ],
+    Body::from_stream(pages),
+  ))
+}
@@ -0,0 +1,300 @@
+use std::{
+  collections::HashMap,
+  sync::RwLock as SyncRwLock,
+  time::{Duration, Instant},
+};
+
+use async_openai::types::Model;
+use serde::Deserialize;
+use tokio::sync::RwLock;
+
+use crate::{
+  app::state::AppState,
+  models::catalog::{CatalogModel, ModelCapabilities},
+  pgdb::FineTunedModel,
+  Result,
+};
+
+/// The provider behind `OPENAI_API_BASE`.
+const UPSTREAM_PROVIDER: &str = "openai";
+
+/// How long the model list of the upstream provider is reused.
+const UPSTREAM_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// A model declared in the catalog file, for providers that do not list their models or to
+/// correct the builtin metadata.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct ConfiguredModel {
+  provider: Option<String>,
+  owned_by: Option<String>,
+  /// Forces the availability, otherwise a model is available when the upstream lists it.
+  available: Option<bool>,
+  capabilities: ModelCapabilities,
+}
+
+fn chat(context_window: u32, max_output_tokens: u32) -> ModelCapabilities {
+  ModelCapabilities {
+    chat: true,
+    tools: true,
+    context_window: Some(context_window),
+    max_output_tokens: Some(max_output_tokens),
+    ..Default::default()
+  }
+}
+
+fn json_mode(capabilities: ModelCapabilities) -> ModelCapabilities {
+  ModelCapabilities {
+    json_mode: true,
+    ..capabilities
+  }
+}
+
+fn vision(capabilities: ModelCapabilities) -> ModelCapabilities {
+  ModelCapabilities {
+    vision: true,
+    ..capabilities
+  }
+}
+
+fn embedding(dimensions: u32) -> ModelCapabilities {
+  ModelCapabilities {
+    embedding_dimensions: Some(dimensions),
+    context_window: Some(8191),
+    ..Default::default()
+  }
+}
+
+/// Every model the configured providers serve, with what they can do and cost.
+#[derive(Debug)]
+pub struct ModelCatalog {
+  /// Looked up by the longest name that prefixes the model, like the pricing table.
+  capabilities: HashMap<String, ModelCapabilities>,
+  configured: HashMap<String, ConfiguredModel>,
+  /// The models of succeeded fine-tuning jobs, which can do what their base model can.
+  fine_tuned: SyncRwLock<HashMap<String, FineTunedModel>>,
+  upstream: RwLock<Option<(Instant, Vec<Model>)>>,
+}
+
+impl Default for ModelCatalog {
+  /// The capabilities of the OpenAI models.
+  fn default() -> Self {
+    let capabilities = [
+      ("gpt-3.5-turbo", json_mode(chat(16385, 4096))),
+      ("gpt-3.5-turbo-0613", chat(4096, 4096)),
+      ("gpt-3.5-turbo-16k", chat(16385, 4096)),
+      (
+        "gpt-3.5-turbo-instruct",
+        ModelCapabilities {
+          context_window: Some(4096),
+          max_output_tokens: Some(4096),
+          ..Default::default()
+        },
+      ),
+      ("gpt-4", chat(8192, 8192)),
+      ("gpt-4-32k", chat(32768, 32768)),
+      ("gpt-4-turbo", vision(json_mode(chat(128000, 4096)))),
+      ("gpt-4-turbo-preview", json_mode(chat(128000, 4096))),
+      ("gpt-4-1106", json_mode(chat(128000, 4096))),
+      ("gpt-4-0125", json_mode(chat(128000, 4096))),
+      (
+        "gpt-4-vision",
+        ModelCapabilities {
+          tools: false,
+          ..vision(chat(128000, 4096))
+        },
+      ),
+      (
+        "gpt-4-1106-vision",
+        ModelCapabilities {
+          tools: false,
+          ..vision(chat(128000, 4096))
+        },
+      ),
+      ("text-embedding-ada-002", embedding(1536)),
+      ("text-embedding-3-small", embedding(1536)),
+      ("text-embedding-3-large", embedding(3072)),
+    ];
+    Self {
+      capabilities: capabilities
+        .into_iter()
+        .map(|(model, capabilities)| (model.to_string(), capabilities))
+        .collect(),
+      configured: HashMap::new(),
+      fine_tuned: SyncRwLock::new(HashMap::new()),
+      upstream: RwLock::new(None),
+    }
+  }
+}
+
+impl ModelCatalog {
+  /// The builtin capabilities and the models declared in the JSON object of model to
+  /// `{provider, owned_by, available, capabilities}` in the file at `MIKO_MODEL_CATALOG`.
+  pub fn from_env() -> Result<Self> {
+    let mut catalog = Self::default();
+    if let Ok(path) = dotenvy::var("MIKO_MODEL_CATALOG") {
+      catalog.configured = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    }
+    Ok(catalog)
+  }
+
+  /// The capabilities of a model, `None` when neither the catalog file nor the builtin table
+  /// knows it.
+  fn known_capabilities(&self, model: &str) -> Option<&ModelCapabilities> {
+    if let Some(configured) = self.configured.get(model) {
+      return Some(&configured.capabilities);
+    }
+    let base_model = self
+      .fine_tuned
+      .read()
+      .unwrap()
+      .get(model)
+      .map(|fine_tuned| fine_tuned.base_model.clone());
+    let model = base_model.as_deref().unwrap_or(model);
+    self
+      .capabilities
+      .iter()
+      .filter(|(name, _)| model.starts_with(name.as_str()))
+      .max_by_key(|(name, _)| name.len())
+      .map(|(_, capabilities)| capabilities)
+  }
+
+  fn capabilities(&self, model: &str) -> ModelCapabilities {
+    self.known_capabilities(model).cloned().unwrap_or_default()
+  }
+
+  /// The length of the vectors an embedding model makes, `None` for unknown models.
+  pub fn embedding_dimensions(&self, model: &str) -> Option<u32> {
+    self
+      .known_capabilities(model)
+      .and_then(|capabilities| capabilities.embedding_dimensions)
+  }
+
+  /// Adds the model of a succeeded fine-tuning job, it is listed even before the provider does.
+  pub fn register(&self, model: FineTunedModel) {
+    tracing::info!("registering fine-tuned model {}", model.model);
+    self
+      .fine_tuned
+      .write()
+      .unwrap()
+      .insert(model.model.clone(), model);
+  }
+
+  /// Removes a deleted fine-tuned model.
+  pub fn unregister(&self, model: &str) {
+    tracing::info!("unregistering fine-tuned model {model}");
+    self.fine_tuned.write().unwrap().remove(model);
+  }
+
+  /// Whether a model takes `tools` itself. Of the models the catalog does not know only new
+  /// OpenAI chat models are assumed to, others get their tools emulated.
+  pub fn native_tools(&self, model: &str) -> bool {
+    match self.known_capabilities(model) {
+      Some(capabilities) => capabilities.tools,
+      None => model.starts_with("gpt-"),
+    }
+  }
+
+  /// The models of the upstream provider, cached for [UPSTREAM_TTL]. When the provider can not
+  /// be reached the last list is used, and none at all before the first success.
+  async fn upstream(&self, app_state: &AppState) -> Vec<Model> {
+    if let Some((fetched_at, models)) = self.upstream.read().await.as_ref() {
+      if fetched_at.elapsed() < UPSTREAM_TTL {
+        return models.clone();
+      }
+    }
+    // the lock is only taken to swap the list, readers keep the old one while it is fetched
+    match app_state.openai_client().models().list().await {
+      Ok(response) => {
+        *self.upstream.write().await = Some((Instant::now(), response.data.clone()));
+        response.data
+      }
+      Err(e) => {
+        tracing::warn!("failed to list the models of the provider: {e}");
+        self
+          .upstream
+          .read()
+          .await
+          .as_ref()
+          .map(|(_, models)| models.clone())
+          .unwrap_or_default()
+      }
+    }
+  }
+
+  fn entry(&self, app_state: &AppState, model: Model, listed: bool) -> CatalogModel {
+    let configured = self.configured.get(&model.id);
+    CatalogModel {
+      capabilities: self.capabilities(&model.id),
+      pricing: app_state.pricing().price(&model.id).cloned(),
+      provider: configured
+        .and_then(|c| c.provider.clone())
+        .unwrap_or_else(|| UPSTREAM_PROVIDER.to_string()),
+      available: configured.and_then(|c| c.available).unwrap_or(listed),
+      id: model.id,
+      object: "model".into(),
+      created: model.created,
+      owned_by: model.owned_by,
+    }
+  }
+}
+
+/// The models of the upstream provider merged with the configured ones, sorted by id.
+pub(crate) async fn list(app_state: &AppState) -> Vec<CatalogModel> {
+  let catalog = app_state.catalog();
+  let upstream = catalog.upstream(app_state).await;
+  let mut models: Vec<CatalogModel> = catalog
+    .configured
+    .iter()
+    .filter(|(id, _)| !upstream.iter().any(|model| &model.id == *id))
+    .map(|(id, configured)| {
+      let model = Model {
+        id: id.clone(),
+        object: "model".into(),
+        created: 0,
+        owned_by: configured
+          .owned_by
+          .clone()
+          .or_else(|| configured.provider.clone())
+          .unwrap_or_else(|| UPSTREAM_PROVIDER.to_string()),
+      };
+      catalog.entry(app_state, model, false)
+    })
+    .collect();
+  let fine_tuned: Vec<Model> = catalog
+    .fine_tuned
+    .read()
+    .unwrap()
+    .values()
+    .filter(|fine_tuned| {
+      !catalog.configured.contains_key(&fine_tuned.model)
+        && !upstream.iter().any(|model| model.id == fine_tuned.model)
+    })
+    .map(|fine_tuned| Model {
+      id: fine_tuned.model.clone(),
+      object: "model".into(),
+      created: fine_tuned.created_at.timestamp() as u32,
+      owned_by: UPSTREAM_PROVIDER.to_string(),
+    })
+    .collect();
+  models.extend(
+    fine_tuned
+      .into_iter()
+      .map(|model| catalog.entry(app_state, model, true)),
+  );
+  models.extend(
+    upstream
+      .into_iter()
+      .map(|model| catalog.entry(app_state, model, true)),
+  );
+  models.sort_by(|a, b| a.id.cmp(&b.id));
+  models
+}
+
+/// A single model of the catalog.
+pub(crate) async fn get(app_state: &AppState, id: &str) -> Option<CatalogModel> {
+  list(app_state)
+    .await
+    .into_iter()
+    .find(|model| model.id == id)
+}
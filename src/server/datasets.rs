@@ -0,0 +1,228 @@
+use std::collections::HashSet;
+
+use async_openai::types::InputSource;
+use chrono::Utc;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+  app::state::AppState,
+  models::{
+    fine_tuning::{DatasetReport, DatasetWarning},
+    Chat, ChatCompletionRequestMessageContentPart, ChatCompletionRequestUserMessageContent,
+    ChatMessage, FileObject,
+  },
+  server::{file_store, usage::estimate_tokens},
+  Error, Result,
+};
+
+/// The most tokens a training example may have, the context of `gpt-3.5-turbo-1106`.
+const MAX_EXAMPLE_TOKENS: usize = 16_385;
+
+/// Fine-tuning jobs are rejected with fewer examples.
+const MIN_EXAMPLES: usize = 10;
+
+/// The tokens every message costs on top of its content.
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+/// A training file in the chat fine-tuning JSONL format with what was found building it.
+#[derive(Debug, Clone)]
+pub(crate) struct Dataset {
+  pub jsonl: String,
+  pub report: DatasetReport,
+}
+
+/// A chat converted to a training example.
+struct Example {
+  messages: Vec<Value>,
+  tokens: usize,
+  assistant_messages: usize,
+  tool_calls: usize,
+}
+
+/// Converts the persisted history of a chat, `Err` with the reason when it can not be used.
+fn example(chat: &Chat, warnings: &mut Vec<DatasetWarning>) -> Result<Example, String> {
+  let mut warn = |message: String| {
+    warnings.push(DatasetWarning {
+      chat_id: Some(chat.id),
+      message,
+      skipped: false,
+    })
+  };
+  let mut example = Example {
+    messages: vec![],
+    tokens: 0,
+    assistant_messages: 0,
+    tool_calls: 0,
+  };
+  // the calls of the last assistant message that still wait for their results
+  let mut pending: HashSet<String> = HashSet::new();
+
+  for message in chat.messages.iter().filter(|m| !m.temporary) {
+    if !pending.is_empty() && !matches!(message.msg, ChatMessage::Tool(_)) {
+      return Err("a tool call has no result".into());
+    }
+    let (value, text) = match &message.msg {
+      ChatMessage::System(message) => (
+        json!({"role": "system", "content": message.content}),
+        message.content.clone(),
+      ),
+      ChatMessage::User(message) => {
+        if let ChatCompletionRequestUserMessageContent::Array(parts) = &message.content {
+          if parts
+            .iter()
+            .any(|part| matches!(part, ChatCompletionRequestMessageContentPart::Image(_)))
+          {
+            warn("images in user messages are left out, only their text is trained".into());
+          }
+        }
+        let content = message.content.text();
+        (json!({"role": "user", "content": content}), content)
+      }
+      ChatMessage::Assistant(message) => {
+        let mut value = json!({"role": "assistant", "content": message.content});
+        let mut text = message.content.clone().unwrap_or_default();
+        if let Some(tool_calls) = message.tool_calls.as_ref().filter(|c| !c.is_empty()) {
+          for call in tool_calls {
+            if serde_json::from_str::<Value>(&call.function.arguments).is_err() {
+              return Err(format!(
+                "the arguments of the call to {} are not JSON",
+                call.function.name
+              ));
+            }
+            if !pending.insert(call.id.clone()) {
+              return Err(format!("the tool call id {} is used twice", call.id));
+            }
+            text.push_str(&call.function.name);
+            text.push_str(&call.function.arguments);
+          }
+          example.tool_calls += tool_calls.len();
+          value["tool_calls"] = serde_json::to_value(tool_calls).map_err(|e| e.to_string())?;
+        }
+        example.assistant_messages += 1;
+        (value, text)
+      }
+      ChatMessage::Tool(message) => {
+        if !pending.remove(&message.tool_call_id) {
+          return Err(format!(
+            "the tool result {} answers no call",
+            message.tool_call_id
+          ));
+        }
+        (
+          json!({"role": "tool", "tool_call_id": message.tool_call_id, "content": message.content}),
+          message.content.clone(),
+        )
+      }
+      ChatMessage::Function(_) => {
+        return Err("legacy function messages can not be trained, only tool calls".into());
+      }
+    };
+    if text.trim().is_empty() {
+      let role = value["role"].as_str().unwrap_or_default();
+      warn(format!("an empty {role} message is left out"));
+      continue;
+    }
+    example.tokens += estimate_tokens(&text) + MESSAGE_OVERHEAD_TOKENS;
+    example.messages.push(value);
+  }
+  if !pending.is_empty() {
+    return Err("a tool call has no result".into());
+  }
+
+  // messages after the last answer teach the model nothing
+  let last_answer = example
+    .messages
+    .iter()
+    .rposition(|m| m["role"] == "assistant")
+    .ok_or_else(|| "it has no assistant messages".to_string())?;
+  if last_answer + 1 < example.messages.len() {
+    warn(format!(
+      "{} messages after the last assistant message are left out",
+      example.messages.len() - last_answer - 1
+    ));
+    example.messages.truncate(last_answer + 1);
+  }
+  if !example.messages.iter().any(|m| m["role"] == "user") {
+    return Err("it has no user messages".into());
+  }
+  if example.tokens > MAX_EXAMPLE_TOKENS {
+    return Err(format!(
+      "it has about {} tokens, more than the {MAX_EXAMPLE_TOKENS} of an example",
+      example.tokens
+    ));
+  }
+  Ok(example)
+}
+
+/// Builds a training file from the persisted history of chats, leaving out the chats that do
+/// not make valid examples.
+pub(crate) fn build(chats: &[Chat]) -> Dataset {
+  let mut report = DatasetReport::default();
+  let mut jsonl = String::new();
+  let mut min_tokens = usize::MAX;
+  for chat in chats {
+    match example(chat, &mut report.warnings) {
+      Ok(example) => {
+        jsonl.push_str(&json!({ "messages": example.messages }).to_string());
+        jsonl.push('\n');
+        report.examples += 1;
+        report.messages += example.messages.len();
+        report.assistant_messages += example.assistant_messages;
+        report.tool_calls += example.tool_calls;
+        report.total_tokens += example.tokens;
+        report.max_tokens = report.max_tokens.max(example.tokens);
+        min_tokens = min_tokens.min(example.tokens);
+      }
+      Err(reason) => {
+        report.skipped_chats += 1;
+        report.warnings.push(DatasetWarning {
+          chat_id: Some(chat.id),
+          message: format!("left out because {reason}"),
+          skipped: true,
+        });
+      }
+    }
+  }
+  if report.examples > 0 {
+    report.min_tokens = min_tokens;
+    report.mean_tokens = report.total_tokens / report.examples;
+  }
+  if report.examples < MIN_EXAMPLES {
+    report.warnings.push(DatasetWarning {
+      chat_id: None,
+      message: format!(
+        "fine-tuning needs at least {MIN_EXAMPLES} examples, the dataset has {}",
+        report.examples
+      ),
+      skipped: false,
+    });
+  }
+  Dataset { jsonl, report }
+}
+
+/// Uploads a dataset as a `fine-tune` file of the user, ready to be passed to `create_job`.
+pub(crate) async fn upload(
+  app_state: &AppState,
+  user_id: Uuid,
+  dataset: &mut Dataset,
+) -> Result<FileObject> {
+  if dataset.report.examples == 0 {
+    return Err(Error::InvalidArgument(
+      "none of the chats make a training example".into(),
+    ));
+  }
+  let file_name = format!(
+    "miko-{}-{}.jsonl",
+    Utc::now().format("%Y%m%d%H%M%S"),
+    &Uuid::new_v4().simple().to_string()[..8]
+  );
+  let source = InputSource::Bytes {
+    filename: file_name.clone(),
+    bytes: dataset.jsonl.clone().into_bytes().into(),
+  };
+  let file = file_store::create(app_state, Some(user_id), "fine-tune".into(), source).await?;
+  dataset.report.file_id = Some(file.id.clone());
+  dataset.report.file_name = Some(file_name);
+  Ok(file)
+}
@@ -0,0 +1,77 @@
+use std::{collections::HashMap, io::Cursor};
+
+use quick_xml::{events::Event, Reader};
+
+use super::{attribute, html, zipped_text, Extracted, Extractor};
+
+/// E-books, their chapters in reading order.
+pub(super) struct Epub;
+
+impl Extractor for Epub {
+  fn extensions(&self) -> &'static [&'static str] {
+    &["epub"]
+  }
+
+  fn extract(&self, bytes: &[u8]) -> Extracted {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+
+    // the container names the package, which lists the chapters
+    let container = zipped_text(&mut archive, "META-INF/container.xml")?;
+    let mut reader = Reader::from_str(&container);
+    let package = loop {
+      match reader.read_event()? {
+        Event::Start(element) | Event::Empty(element)
+          if element.local_name().as_ref() == b"rootfile" =>
+        {
+          if let Some(path) = attribute(&element, b"full-path") {
+            break path;
+          }
+        }
+        Event::Eof => return Err("the container names no package".into()),
+        _ => {}
+      }
+    };
+
+    let opf = zipped_text(&mut archive, &package)?;
+    let mut reader = Reader::from_str(&opf);
+    let mut manifest = HashMap::new();
+    let mut spine = vec![];
+    loop {
+      match reader.read_event()? {
+        Event::Start(element) | Event::Empty(element) => match element.local_name().as_ref() {
+          b"item" => {
+            if let (Some(id), Some(href)) =
+              (attribute(&element, b"id"), attribute(&element, b"href"))
+            {
+              manifest.insert(id, href);
+            }
+          }
+          b"itemref" => spine.extend(attribute(&element, b"idref")),
+          _ => {}
+        },
+        Event::Eof => break,
+        _ => {}
+      }
+    }
+
+    // chapters are named relative to the package
+    let base = package.rsplit_once('/').map_or("", |(base, _)| base);
+    let mut chapters = vec![];
+    for id in spine {
+      let Some(href) = manifest.get(&id) else {
+        continue;
+      };
+      let href = href.split('#').next().unwrap_or_default();
+      let path = if base.is_empty() {
+        href.to_string()
+      } else {
+        format!("{base}/{href}")
+      };
+      let chapter = html::markdown(&zipped_text(&mut archive, &path)?);
+      if !chapter.is_empty() {
+        chapters.push(chapter);
+      }
+    }
+    Ok(chapters.join("\n\n---\n\n"))
+  }
+}
@@ -0,0 +1,19 @@
+use super::{Extracted, Extractor};
+
+/// HTML pages as markdown.
+pub(super) struct Html;
+
+impl Extractor for Html {
+  fn extensions(&self) -> &'static [&'static str] {
+    &["html", "htm", "xhtml"]
+  }
+
+  fn extract(&self, bytes: &[u8]) -> Extracted {
+    Ok(markdown(&String::from_utf8_lossy(bytes)))
+  }
+}
+
+/// The markdown of an HTML document, shared with the chapters of EPUBs.
+pub(super) fn markdown(html: &str) -> String {
+  html2md::parse_html(html).trim().to_string()
+}
@@ -0,0 +1,165 @@
+use std::{
+  io::{Cursor, Read},
+  path::{Path, PathBuf},
+};
+
+use quick_xml::events::BytesStart;
+use tokio::io::AsyncReadExt;
+use uuid::Uuid;
+
+use crate::{server::workspace, Error, Result};
+
+mod epub;
+mod html;
+mod office;
+mod pdf;
+mod spreadsheet;
+
+type Extracted = std::result::Result<String, Box<dyn std::error::Error + Send + Sync>>;
+
+/// The most text read from a plain text file or a single file of a zip archive, so a small
+/// upload can not unpack into gigabytes.
+const MAX_EXTRACTED_BYTES: u64 = 32 * 1024 * 1024;
+
+/// Turns the bytes of a kind of document into markdown, with headings at the boundaries of
+/// pages, slides, sheets and chapters.
+trait Extractor: Sync {
+  /// The lowercase extensions of the files it reads.
+  fn extensions(&self) -> &'static [&'static str];
+
+  fn extract(&self, bytes: &[u8]) -> Extracted;
+}
+
+const EXTRACTORS: &[&dyn Extractor] = &[
+  &pdf::Pdf,
+  &office::Docx,
+  &office::Pptx,
+  &spreadsheet::Spreadsheet,
+  &html::Html,
+  &epub::Epub,
+];
+
+/// The extractor for the extension of a file.
+fn extractor(path: &Path) -> Option<&'static dyn Extractor> {
+  let extension = path.extension()?.to_str()?.to_lowercase();
+  EXTRACTORS
+    .iter()
+    .find(|extractor| extractor.extensions().contains(&extension.as_str()))
+    .copied()
+}
+
+/// Whether the file is read as it is, without an extractor.
+fn is_plain_text(path: &Path) -> bool {
+  let mime = workspace::mime_type(path);
+  (mime.type_() == mime_guess::mime::TEXT && mime.subtype() != mime_guess::mime::HTML)
+    || matches!(
+      mime.subtype().as_str(),
+      "json" | "xml" | "yaml" | "toml" | "x-sh"
+    )
+}
+
+/// Where the text extracted from a document is cached.
+fn cache_path(path: &Path) -> Option<PathBuf> {
+  let file_name = path.file_name()?.to_str()?;
+  Some(path.with_file_name(format!(".{file_name}.md")))
+}
+
+fn too_large(name: &str) -> String {
+  format!(
+    "{name} holds more than {} MiB of text",
+    MAX_EXTRACTED_BYTES / 1024 / 1024
+  )
+}
+
+/// The text of a file, as markdown for documents. The text of documents is extracted once and
+/// cached until the document changes. `None` for files that are neither text nor a document.
+pub(crate) async fn text(path: &Path) -> Result<Option<String>> {
+  let file = path
+    .file_name()
+    .unwrap_or_default()
+    .to_string_lossy()
+    .to_string();
+  if is_plain_text(path) {
+    let mut bytes = Vec::new();
+    tokio::fs::File::open(path)
+      .await?
+      .take(MAX_EXTRACTED_BYTES + 1)
+      .read_to_end(&mut bytes)
+      .await?;
+    if bytes.len() as u64 > MAX_EXTRACTED_BYTES {
+      return Err(Error::Extraction {
+        message: too_large(&file),
+        file,
+      });
+    }
+    return Ok(Some(String::from_utf8_lossy(&bytes).into_owned()));
+  }
+  let (Some(extractor), Some(cache)) = (extractor(path), cache_path(path)) else {
+    return Ok(None);
+  };
+
+  let modified = tokio::fs::metadata(path).await?.modified()?;
+  if let Ok(cached) = tokio::fs::metadata(&cache).await {
+    if cached.modified()? >= modified {
+      return Ok(Some(tokio::fs::read_to_string(&cache).await?));
+    }
+  }
+
+  let bytes = tokio::fs::read(path).await?;
+  let text = tokio::task::spawn_blocking(move || extractor.extract(&bytes))
+    .await
+    .map_err(|e| Error::Extraction {
+      file: file.clone(),
+      message: e.to_string(),
+    })?
+    .map_err(|e| Error::Extraction {
+      file,
+      message: e.to_string(),
+    })?;
+  // written aside and moved into place, so a concurrent read never sees half a cache
+  let partial = cache.with_file_name(format!(
+    "{}.{}.tmp",
+    cache.file_name().unwrap_or_default().to_string_lossy(),
+    Uuid::new_v4()
+  ));
+  tokio::fs::write(&partial, &text).await?;
+  if let Err(e) = tokio::fs::rename(&partial, &cache).await {
+    _ = tokio::fs::remove_file(&partial).await;
+    return Err(e.into());
+  }
+  Ok(Some(text))
+}
+
+/// Removes the cached text of a document that is gone.
+pub(crate) async fn forget(path: &Path) -> Result<()> {
+  let Some(cache) = cache_path(path) else {
+    return Ok(());
+  };
+  match tokio::fs::remove_file(cache).await {
+    Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+    _ => Ok(()),
+  }
+}
+
+/// The value of the attribute with the local name `name`, ignoring its namespace.
+fn attribute(element: &BytesStart, name: &[u8]) -> Option<String> {
+  element
+    .attributes()
+    .flatten()
+    .find(|attribute| attribute.key.local_name().as_ref() == name)
+    .and_then(|attribute| attribute.unescape_value().ok())
+    .map(|value| value.into_owned())
+}
+
+/// A file of a zip archive as text, Office documents and EPUBs are zipped XML.
+fn zipped_text(archive: &mut zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Extracted {
+  let mut bytes = Vec::new();
+  archive
+    .by_name(name)?
+    .take(MAX_EXTRACTED_BYTES + 1)
+    .read_to_end(&mut bytes)?;
+  if bytes.len() as u64 > MAX_EXTRACTED_BYTES {
+    return Err(too_large(name).into());
+  }
+  Ok(String::from_utf8(bytes)?)
+}
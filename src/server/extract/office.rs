@@ -0,0 +1,147 @@
+use std::io::Cursor;
+
+use quick_xml::{events::Event, Reader};
+
+use super::{attribute, zipped_text, Extracted, Extractor};
+
+/// Word documents, with their headings and tables in markdown.
+pub(super) struct Docx;
+
+impl Extractor for Docx {
+  fn extensions(&self) -> &'static [&'static str] {
+    &["docx"]
+  }
+
+  fn extract(&self, bytes: &[u8]) -> Extracted {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+    let xml = zipped_text(&mut archive, "word/document.xml")?;
+    let mut reader = Reader::from_str(&xml);
+
+    let mut blocks: Vec<String> = vec![];
+    let mut paragraph = String::new();
+    let mut heading = 0;
+    // field codes and other markup hold text that is not shown
+    let mut in_text = false;
+    // the cells of the row of a table, and the rows written so far
+    let mut row: Option<Vec<String>> = None;
+    let mut rows: Vec<String> = vec![];
+    loop {
+      match reader.read_event()? {
+        Event::Start(element) if element.local_name().as_ref() == b"t" => in_text = true,
+        Event::Start(element) | Event::Empty(element) => match element.local_name().as_ref() {
+          b"pStyle" => {
+            heading = attribute(&element, b"val")
+              .and_then(|style| heading_level(&style))
+              .unwrap_or(0);
+          }
+          b"tab" => paragraph.push('\t'),
+          b"br" | b"cr" => paragraph.push('\n'),
+          b"tr" => row = Some(vec![]),
+          b"tc" => paragraph.clear(),
+          _ => {}
+        },
+        Event::Text(text) if in_text => paragraph.push_str(&text.unescape()?),
+        Event::End(element) => match element.local_name().as_ref() {
+          b"t" => in_text = false,
+          b"p" if row.is_some() => paragraph.push(' '),
+          b"p" => {
+            let text = paragraph.trim();
+            if !text.is_empty() {
+              blocks.push(match heading {
+                0 => text.to_string(),
+                level => format!("{} {text}", "#".repeat(level)),
+              });
+            }
+            paragraph.clear();
+            heading = 0;
+          }
+          b"tc" => {
+            if let Some(row) = row.as_mut() {
+              row.push(paragraph.trim().replace('|', "\\|").replace('\n', " "));
+            }
+            paragraph.clear();
+          }
+          b"tr" => {
+            if let Some(cells) = row.take() {
+              rows.push(format!("| {} |", cells.join(" | ")));
+              // the first row is taken as the header, markdown tables need one
+              if rows.len() == 1 {
+                rows.push(format!("|{}", " --- |".repeat(cells.len())));
+              }
+            }
+          }
+          b"tbl" => blocks.push(std::mem::take(&mut rows).join("\n")),
+          _ => {}
+        },
+        Event::Eof => break,
+        _ => {}
+      }
+    }
+    Ok(blocks.join("\n\n"))
+  }
+}
+
+/// The markdown heading level of a paragraph style, `Title` and `Heading1` to `Heading6`.
+fn heading_level(style: &str) -> Option<usize> {
+  if style == "Title" {
+    return Some(1);
+  }
+  style
+    .strip_prefix("Heading")
+    .and_then(|level| level.parse().ok())
+    .filter(|level| (1..=6).contains(level))
+}
+
+/// PowerPoint presentations, the text of every slide in order.
+pub(super) struct Pptx;
+
+impl Extractor for Pptx {
+  fn extensions(&self) -> &'static [&'static str] {
+    &["pptx"]
+  }
+
+  fn extract(&self, bytes: &[u8]) -> Extracted {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+    // slides are numbered in the names of their files
+    let mut slides = archive
+      .file_names()
+      .filter_map(|name| {
+        let number = name
+          .strip_prefix("ppt/slides/slide")?
+          .strip_suffix(".xml")?
+          .parse::<u32>()
+          .ok()?;
+        Some((number, name.to_string()))
+      })
+      .collect::<Vec<_>>();
+    slides.sort();
+
+    let mut text = vec![];
+    for (number, name) in slides {
+      let xml = zipped_text(&mut archive, &name)?;
+      let mut reader = Reader::from_str(&xml);
+      let mut paragraphs = vec![];
+      let mut paragraph = String::new();
+      let mut in_text = false;
+      loop {
+        match reader.read_event()? {
+          Event::Start(element) if element.local_name().as_ref() == b"t" => in_text = true,
+          Event::End(element) if element.local_name().as_ref() == b"t" => in_text = false,
+          Event::Text(text) if in_text => paragraph.push_str(&text.unescape()?),
+          Event::Empty(element) if element.local_name().as_ref() == b"br" => paragraph.push('\n'),
+          Event::End(element) if element.local_name().as_ref() == b"p" => {
+            let text = paragraph.trim();
+            if !text.is_empty() {
+              paragraphs.push(text.to_string());
+            }
+            paragraph.clear();
+          }
+          Event::Eof => break,
+          _ => {}
+        }
+      }
+      text.push(format!("## Slide {number}\n\n{}", paragraphs.join("\n\n")));
+    }
+    Ok(text.join("\n\n"))
+  }
+}
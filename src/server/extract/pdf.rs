@@ -0,0 +1,20 @@
+use super::{Extracted, Extractor};
+
+/// The text of every page of a PDF, pages that are scans have none.
+pub(super) struct Pdf;
+
+impl Extractor for Pdf {
+  fn extensions(&self) -> &'static [&'static str] {
+    &["pdf"]
+  }
+
+  fn extract(&self, bytes: &[u8]) -> Extracted {
+    let document = lopdf::Document::load_mem(bytes)?;
+    let mut pages = vec![];
+    for number in document.get_pages().into_keys() {
+      let text = document.extract_text(&[number])?;
+      pages.push(format!("## Page {number}\n\n{}", text.trim()));
+    }
+    Ok(pages.join("\n\n"))
+  }
+}
@@ -0,0 +1,41 @@
+use std::io::Cursor;
+
+use calamine::Reader;
+
+use super::{Extracted, Extractor};
+
+/// Every sheet of a workbook as a markdown table.
+pub(super) struct Spreadsheet;
+
+impl Extractor for Spreadsheet {
+  fn extensions(&self) -> &'static [&'static str] {
+    &["xlsx", "xlsm", "xls", "ods"]
+  }
+
+  fn extract(&self, bytes: &[u8]) -> Extracted {
+    let mut workbook = calamine::open_workbook_auto_from_rs(Cursor::new(bytes.to_vec()))?;
+    let mut sheets = vec![];
+    for name in workbook.sheet_names() {
+      let range = workbook.worksheet_range(&name)?;
+      let mut rows = range.rows().map(|row| {
+        let cells = row
+          .iter()
+          .map(|cell| cell.to_string().replace('|', "\\|").replace('\n', " "))
+          .collect::<Vec<_>>();
+        format!("| {} |", cells.join(" | "))
+      });
+      let Some(header) = rows.next() else {
+        continue;
+      };
+      // the first row is taken as the header, markdown tables need one
+      let separator = format!("|{}", " --- |".repeat(range.width()));
+      let table = [header, separator]
+        .into_iter()
+        .chain(rows)
+        .collect::<Vec<_>>()
+        .join("\n");
+      sheets.push(format!("## Sheet {name}\n\n{table}"));
+    }
+    Ok(sheets.join("\n\n"))
+  }
+}
@@ -0,0 +1,254 @@
+use std::path::PathBuf;
+
+use async_openai::types::{CreateFileRequest, DeleteFileResponse, FileInput, InputSource};
+use chrono::Utc;
+use mime_guess::{mime, Mime};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+  app::state::AppState,
+  models::{FileList, FileObject},
+  pgdb::StoredFile,
+  server::workspace,
+  Error, Result,
+};
+
+/// Where the Files API keeps files, the upstream provider unless `MIKO_FILES_BACKEND=local`.
+#[derive(Debug, Default)]
+pub struct FileStore {
+  /// The directory of the local files, `None` forwards to the provider.
+  local: Option<PathBuf>,
+}
+
+impl FileStore {
+  /// Reads `MIKO_FILES_BACKEND` and `MIKO_FILES_DIR`, local files are kept in `files` of the
+  /// upload store by default.
+  pub fn from_env(upload_store: &std::path::Path) -> Result<Self> {
+    let local = match dotenvy::var("MIKO_FILES_BACKEND").as_deref() {
+      Err(_) | Ok("openai") => None,
+      Ok("local") => Some(
+        dotenvy::var("MIKO_FILES_DIR")
+          .map(PathBuf::from)
+          .unwrap_or_else(|_| upload_store.join("files")),
+      ),
+      Ok(backend) => {
+        return Err(Error::InvalidArgument(format!(
+          "MIKO_FILES_BACKEND must be openai or local, not {backend}"
+        )))
+      }
+    };
+    Ok(Self { local })
+  }
+
+  pub fn is_local(&self) -> bool {
+    self.local.is_some()
+  }
+
+  fn path(&self, id: &str) -> Option<PathBuf> {
+    self.local.as_ref().map(|dir| dir.join(id))
+  }
+}
+
+#[derive(Debug, Serialize)]
+struct ListQuery<'a> {
+  purpose: Option<&'a str>,
+}
+
+/// The name of the file a source uploads.
+fn source_name(source: &InputSource) -> String {
+  match source {
+    InputSource::Path { path } => path
+      .file_name()
+      .map(|name| name.to_string_lossy().to_string())
+      .unwrap_or_default(),
+    InputSource::Bytes { filename, .. } | InputSource::VecU8 { filename, .. } => filename.clone(),
+  }
+}
+
+/// Stores a file. Local files belong to `owner`, the provider has no owners.
+pub(crate) async fn create(
+  app_state: &AppState,
+  owner: Option<Uuid>,
+  purpose: String,
+  source: InputSource,
+) -> Result<FileObject> {
+  if purpose.is_empty() {
+    return Err(Error::InvalidArgument("purpose is required".into()));
+  }
+  let store = app_state.file_store();
+  let Some(dir) = &store.local else {
+    let request = CreateFileRequest {
+      file: FileInput { source },
+      purpose,
+    };
+    let file = app_state.openai_client().files().create(request).await?;
+    return Ok(file.into());
+  };
+
+  let id = format!("file-{}", Uuid::new_v4().simple());
+  let path = dir.join(&id);
+  tokio::fs::create_dir_all(dir).await?;
+  let filename = source_name(&source);
+  let bytes = match source {
+    // large uploads are copied from their temporary file rather than read into memory
+    InputSource::Path { path: from } => tokio::fs::copy(from, &path).await?,
+    InputSource::Bytes { bytes, .. } => {
+      tokio::fs::write(&path, &bytes).await?;
+      bytes.len() as u64
+    }
+    InputSource::VecU8 { vec, .. } => {
+      tokio::fs::write(&path, &vec).await?;
+      vec.len() as u64
+    }
+  };
+  let file = StoredFile {
+    id,
+    user_id: owner,
+    filename,
+    purpose,
+    bytes: bytes as i64,
+    created_at: Utc::now(),
+  };
+  if let Err(e) = file.create(&app_state.pool).await {
+    _ = tokio::fs::remove_file(&path).await;
+    return Err(e);
+  }
+  Ok(file.into())
+}
+
+/// The files with the `purpose`, if given, of `owner` or all files for `None`.
+pub(crate) async fn list(
+  app_state: &AppState,
+  owner: Option<Uuid>,
+  purpose: Option<&str>,
+) -> Result<FileList> {
+  let data = if app_state.file_store().is_local() {
+    StoredFile::list(owner, purpose, &app_state.pool)
+      .await?
+      .into_iter()
+      .map(Into::into)
+      .collect()
+  } else {
+    app_state
+      .openai_client()
+      .files()
+      .list(&ListQuery { purpose })
+      .await?
+      .data
+      .into_iter()
+      .map(Into::into)
+      .collect()
+  };
+  Ok(FileList {
+    object: "list".to_string(),
+    data,
+  })
+}
+
+async fn stored(app_state: &AppState, owner: Option<Uuid>, id: &str) -> Result<StoredFile> {
+  StoredFile::get(id, owner, &app_state.pool)
+    .await?
+    .ok_or_else(|| Error::NotFound(format!("file {id}")))
+}
+
+pub(crate) async fn retrieve(
+  app_state: &AppState,
+  owner: Option<Uuid>,
+  id: &str,
+) -> Result<FileObject> {
+  if app_state.file_store().is_local() {
+    Ok(stored(app_state, owner, id).await?.into())
+  } else {
+    Ok(app_state.openai_client().files().retrieve(id).await?.into())
+  }
+}
+
+pub(crate) async fn delete(
+  app_state: &AppState,
+  owner: Option<Uuid>,
+  id: &str,
+) -> Result<DeleteFileResponse> {
+  let store = app_state.file_store();
+  let Some(path) = store.path(id) else {
+    return Ok(app_state.openai_client().files().delete(id).await?);
+  };
+  if !StoredFile::delete(id, owner, &app_state.pool).await? {
+    return Err(Error::NotFound(format!("file {id}")));
+  }
+  if let Err(e) = tokio::fs::remove_file(path).await {
+    tracing::warn!("failed to remove the contents of file {id}: {e}");
+  }
+  Ok(DeleteFileResponse {
+    id: id.to_string(),
+    object: "file".to_string(),
+    deleted: true,
+  })
+}
+
+/// The contents of a file with their media type, `async-openai` reads the contents of files of
+/// the provider as text.
+pub(crate) async fn content(
+  app_state: &AppState,
+  owner: Option<Uuid>,
+  id: &str,
+) -> Result<(Mime, Vec<u8>)> {
+  let store = app_state.file_store();
+  let Some(path) = store.path(id) else {
+    let content = app_state
+      .openai_client()
+      .files()
+      .retrieve_content(id)
+      .await?;
+    return Ok((mime::TEXT_PLAIN_UTF_8, content.into_bytes()));
+  };
+  let file = stored(app_state, owner, id).await?;
+  let content = tokio::fs::read(path)
+    .await
+    .map_err(|_| Error::NotFound(format!("contents of file {id}")))?;
+  Ok((workspace::mime_type(&file.filename), content))
+}
+
+/// The id of a file at the provider, local files of `owner` are uploaded there first. For the
+/// APIs that are only forwarded, like fine-tuning.
+pub(crate) async fn provider_file_id(
+  app_state: &AppState,
+  owner: Option<Uuid>,
+  id: &str,
+) -> Result<String> {
+  if !app_state.file_store().is_local() {
+    return Ok(id.to_string());
+  }
+  // ids the local store does not know may already be files of the provider
+  let Some(file) = StoredFile::get(id, owner, &app_state.pool).await? else {
+    return Ok(id.to_string());
+  };
+  let (_, content) = content(app_state, owner, id).await?;
+  let request = CreateFileRequest {
+    file: FileInput {
+      source: InputSource::Bytes {
+        filename: file.filename,
+        bytes: content.into(),
+      },
+    },
+    purpose: file.purpose,
+  };
+  let uploaded = app_state.openai_client().files().create(request).await?;
+  Ok(uploaded.id)
+}
+
+/// Fails with `NotFound` for the first of `ids` the local store does not have for `owner`,
+/// files of the provider are not checked.
+pub(crate) async fn ensure_exist(
+  app_state: &AppState,
+  owner: Option<Uuid>,
+  ids: &[String],
+) -> Result<()> {
+  if !app_state.file_store().is_local() {
+    return Ok(());
+  }
+  for id in ids {
+    stored(app_state, owner, id).await?;
+  }
+  Ok(())
+}
@@ -0,0 +1,124 @@
+use axum::{
+  extract::{Extension, Path, Query, State},
+  Json,
+};
+use uuid::Uuid;
+
+use crate::{
+  app::state::AppState,
+  models::assistants::{
+    AssistantObject, CreateAssistantRequest, DeletedObject, ListQuery, ListResponse,
+    ModifyAssistantRequest,
+  },
+  pgdb::Assistant,
+  server::{
+    api_key::Caller,
+    file_store,
+    openapi::{ApiRouter, Body, Operation, Schema},
+  },
+  Error, Result,
+};
+
+pub(super) fn api() -> ApiRouter {
+  ApiRouter::new()
+    .route(
+      Operation::get("/", "List assistants")
+        .query(&["limit", "order", "after"])
+        .response(Body::Json(Schema::OpenAI("ListAssistantsResponse"))),
+      list_assistants,
+    )
+    .route(
+      Operation::post("/", "Create an assistant")
+        .request(Body::Json(Schema::OpenAI("CreateAssistantRequest")))
+        .response(Body::Json(Schema::OpenAI("AssistantObject"))),
+      create,
+    )
+    .route(
+      Operation::get("/:assistant_id", "Retrieve an assistant")
+        .response(Body::Json(Schema::OpenAI("AssistantObject"))),
+      get_assistant,
+    )
+    .route(
+      Operation::post("/:assistant_id", "Modify an assistant")
+        .request(Body::Json(Schema::OpenAI("ModifyAssistantRequest")))
+        .response(Body::Json(Schema::OpenAI("AssistantObject"))),
+      modify,
+    )
+    .route(
+      Operation::delete("/:assistant_id", "Delete an assistant")
+        .response(Body::Json(Schema::OpenAI("DeleteAssistantResponse"))),
+      delete_assistant,
+    )
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn create(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Json(request): Json<CreateAssistantRequest>,
+) -> Result<Json<AssistantObject>> {
+  let user_id = super::user_id(caller)?;
+  if request.model.is_empty() {
+    return Err(Error::InvalidArgument("model is required".to_string()));
+  }
+  if let Some(file_ids) = &request.file_ids {
+    file_store::ensure_exist(&app_state, Some(user_id), file_ids).await?;
+  }
+  let assistant = Assistant::create(user_id, request, &app_state.pool).await?;
+  Ok(Json(assistant.into()))
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn list_assistants(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Query(query): Query<ListQuery>,
+) -> Result<Json<ListResponse<AssistantObject>>> {
+  let user_id = super::user_id(caller)?;
+  Assistant::list(user_id, &query, &app_state.pool)
+    .await
+    .map(Json)
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn get_assistant(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path(assistant_id): Path<Uuid>,
+) -> Result<Json<AssistantObject>> {
+  let user_id = super::user_id(caller)?;
+  let assistant = Assistant::get(user_id, assistant_id, &app_state.pool).await?;
+  Ok(Json(assistant.into()))
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn modify(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path(assistant_id): Path<Uuid>,
+  Json(request): Json<ModifyAssistantRequest>,
+) -> Result<Json<AssistantObject>> {
+  let user_id = super::user_id(caller)?;
+  if let Some(file_ids) = &request.file_ids {
+    file_store::ensure_exist(&app_state, Some(user_id), file_ids).await?;
+  }
+  let assistant = Assistant::update(user_id, assistant_id, request, &app_state.pool).await?;
+  Ok(Json(assistant.into()))
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn delete_assistant(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path(assistant_id): Path<Uuid>,
+) -> Result<Json<DeletedObject>> {
+  let user_id = super::user_id(caller)?;
+  if !Assistant::delete(user_id, assistant_id, &app_state.pool).await? {
+    return Err(Error::NotFound(format!("assistant {assistant_id}")));
+  }
+  Ok(Json(DeletedObject {
+    id: assistant_id.to_string(),
+    object: "assistant.deleted".to_string(),
+    deleted: true,
+  }))
+}
@@ -2,145 +2,160 @@ use async_openai::types::{
   AudioInput, CreateTranscriptionRequest, CreateTranscriptionResponse, CreateTranslationRequest,
   CreateTranslationResponse,
 };
-use axum::{
-  extract::{Json, Multipart, State},
-  routing::post,
-};
+use axum::extract::{Extension, Json, State};
 use bytes::Bytes;
 
-use crate::{app::state::AppState, models::audio::CreateSpeechRequest, Result};
+use super::multipart::{check_range, Form, FromForm, TypedMultipart, Upload};
+use crate::{
+  app::state::AppState,
+  models::audio::{AudioResponseFormat, CreateSpeechRequest},
+  server::{
+    api_key::Caller,
+    openapi::{ApiRouter, Body, Operation, Schema},
+    usage::{audio_seconds, model_name, Tracker},
+  },
+  Result,
+};
 
-pub fn routes(app_state: AppState) -> axum::Router<AppState> {
-  axum::Router::new()
-    .route("/speech", post(speech))
-    .route("/transcriptions", post(transcriptions))
-    .route("/translations", post(translations))
-    .with_state(app_state)
+pub(super) fn api() -> ApiRouter {
+  ApiRouter::new()
+    .route(
+      Operation::post("/speech", "Generate audio from text")
+        .request(Body::Json(Schema::OpenAI("CreateSpeechRequest")))
+        .response(Body::Binary),
+      speech,
+    )
+    .upload(
+      Operation::post("/transcriptions", "Transcribe audio, files up to 25 MB")
+        .request(Body::Multipart(Schema::OpenAI(
+          "CreateTranscriptionRequest",
+        )))
+        .response(Body::Json(Schema::OpenAI(
+          "CreateTranscriptionResponseJson",
+        ))),
+      transcriptions,
+    )
+    .upload(
+      Operation::post(
+        "/translations",
+        "Translate audio into English, files up to 25 MB",
+      )
+      .request(Body::Multipart(Schema::OpenAI("CreateTranslationRequest")))
+      .response(Body::Json(Schema::OpenAI("CreateTranslationResponseJson"))),
+      translations,
+    )
 }
 
 #[tracing::instrument(skip(app_state))]
 async fn speech(
   State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
   Json(request): Json<CreateSpeechRequest>,
 ) -> Result<Bytes> {
-  app_state
+  let usage = Tracker::start("/v1/audio/speech", model_name(&request.model))
+    .caller(caller)
+    .input_chars(request.input.chars().count());
+  let result = app_state
     .openai_client()
     .audio()
     .speech(request.into())
-    .await
-    .map_err(Into::into)
-    .map(|v| v.bytes)
+    .await;
+  usage.finish(&app_state, &result);
+  Ok(result?.bytes)
 }
 
-async fn create_transcription_request(
-  mut request: Multipart,
-) -> Result<CreateTranscriptionRequest> {
-  let mut req = CreateTranscriptionRequest::default();
-  while let Ok(Some(field)) = request.next_field().await {
-    if let Some(field_name) = field.name() {
-      match field_name {
-        "file" => {
-          req.file = AudioInput {
-            source: async_openai::types::InputSource::Bytes {
-              filename: field.file_name().unwrap_or_default().to_string(),
-              bytes: field
-                .bytes()
-                .await
-                .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?,
-            },
-          }
-        }
-        "model" => {
-          req.model = field
-            .text()
-            .await
-            .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?
-        }
-        "prompt" => req.prompt = field.text().await.ok(),
-        "response_format" => {
-          req.response_format = field.text().await.ok().map(|v| match v.as_str() {
-            "json" => async_openai::types::AudioResponseFormat::Json,
-            "text" => async_openai::types::AudioResponseFormat::Text,
-            "srt" => async_openai::types::AudioResponseFormat::Srt,
-            "verbose_json" => async_openai::types::AudioResponseFormat::VerboseJson,
-            "vtt" => async_openai::types::AudioResponseFormat::Vtt,
-            _ => async_openai::types::AudioResponseFormat::Json,
-          })
-        }
-        "temperature" => req.temperature = field.text().await.ok().and_then(|v| v.parse().ok()),
-        "language" => req.language = field.text().await.ok(),
-        _ => {}
-      }
-    }
+/// The most bytes of a recording the provider accepts.
+const AUDIO_LIMIT: u64 = 25 * 1024 * 1024;
+
+/// A transcription request with the uploaded recording, which lives as long as the request.
+#[derive(Debug)]
+struct TranscriptionForm {
+  file: Upload,
+  request: CreateTranscriptionRequest,
+}
+
+impl FromForm for TranscriptionForm {
+  const FIELDS: &'static [&'static str] = &[
+    "model",
+    "prompt",
+    "response_format",
+    "temperature",
+    "language",
+  ];
+  const FILES: &'static [&'static str] = &["file"];
+  const FILE_LIMIT: u64 = AUDIO_LIMIT;
+
+  fn from_form(mut form: Form) -> Result<Self> {
+    let file = form.required_file("file")?;
+    let request = CreateTranscriptionRequest {
+      file: AudioInput {
+        source: file.input_source(),
+      },
+      model: form.required("model")?,
+      prompt: form.text("prompt"),
+      response_format: form
+        .parse::<AudioResponseFormat>("response_format")?
+        .map(Into::into),
+      temperature: check_range("temperature", form.parse("temperature")?, 0.0, 1.0)?,
+      language: form.text("language"),
+    };
+    Ok(Self { file, request })
   }
-  Ok(req)
 }
 
 #[tracing::instrument(skip(app_state))]
 async fn transcriptions(
   State(app_state): State<AppState>,
-  request: Multipart,
+  caller: Option<Extension<Caller>>,
+  TypedMultipart(TranscriptionForm { file, request }): TypedMultipart<TranscriptionForm>,
 ) -> Result<Json<CreateTranscriptionResponse>> {
-  app_state
-    .openai_client()
-    .audio()
-    .transcribe(create_transcription_request(request).await?)
-    .await
-    .map_err(Into::into)
-    .map(Into::into)
+  let usage = Tracker::start("/v1/audio/transcriptions", &request.model)
+    .caller(caller)
+    .audio_seconds(audio_seconds(&file.head, file.len));
+  let result = app_state.openai_client().audio().transcribe(request).await;
+  usage.finish(&app_state, &result);
+  Ok(Json(result?))
+}
+
+/// A translation request with the uploaded recording, which lives as long as the request.
+#[derive(Debug)]
+struct TranslationForm {
+  file: Upload,
+  request: CreateTranslationRequest,
 }
 
-async fn create_translation_request(mut request: Multipart) -> Result<CreateTranslationRequest> {
-  let mut req = CreateTranslationRequest::default();
-  while let Ok(Some(field)) = request.next_field().await {
-    if let Some(field_name) = field.name() {
-      match field_name {
-        "file" => {
-          req.file = AudioInput {
-            source: async_openai::types::InputSource::Bytes {
-              filename: field.file_name().unwrap_or_default().to_string(),
-              bytes: field
-                .bytes()
-                .await
-                .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?,
-            },
-          }
-        }
-        "model" => {
-          req.model = field
-            .text()
-            .await
-            .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?
-        }
-        "prompt" => req.prompt = field.text().await.ok(),
-        "response_format" => {
-          req.response_format = field.text().await.ok().map(|v| match v.as_str() {
-            "json" => async_openai::types::AudioResponseFormat::Json,
-            "text" => async_openai::types::AudioResponseFormat::Text,
-            "srt" => async_openai::types::AudioResponseFormat::Srt,
-            "verbose_json" => async_openai::types::AudioResponseFormat::VerboseJson,
-            "vtt" => async_openai::types::AudioResponseFormat::Vtt,
-            _ => async_openai::types::AudioResponseFormat::Json,
-          })
-        }
-        "temperature" => req.temperature = field.text().await.ok().and_then(|v| v.parse().ok()),
-        _ => {}
-      }
-    }
+impl FromForm for TranslationForm {
+  const FIELDS: &'static [&'static str] = &["model", "prompt", "response_format", "temperature"];
+  const FILES: &'static [&'static str] = &["file"];
+  const FILE_LIMIT: u64 = AUDIO_LIMIT;
+
+  fn from_form(mut form: Form) -> Result<Self> {
+    let file = form.required_file("file")?;
+    let request = CreateTranslationRequest {
+      file: AudioInput {
+        source: file.input_source(),
+      },
+      model: form.required("model")?,
+      prompt: form.text("prompt"),
+      response_format: form
+        .parse::<AudioResponseFormat>("response_format")?
+        .map(Into::into),
+      temperature: check_range("temperature", form.parse("temperature")?, 0.0, 1.0)?,
+    };
+    Ok(Self { file, request })
   }
-  Ok(req)
 }
 
 #[tracing::instrument(skip(app_state))]
 async fn translations(
   State(app_state): State<AppState>,
-  request: Multipart,
+  caller: Option<Extension<Caller>>,
+  TypedMultipart(TranslationForm { file, request }): TypedMultipart<TranslationForm>,
 ) -> Result<Json<CreateTranslationResponse>> {
-  app_state
-    .openai_client()
-    .audio()
-    .translate(create_translation_request(request).await?)
-    .await
-    .map_err(Into::into)
-    .map(Into::into)
+  let usage = Tracker::start("/v1/audio/translations", &request.model)
+    .caller(caller)
+    .audio_seconds(audio_seconds(&file.head, file.len));
+  let result = app_state.openai_client().audio().translate(request).await;
+  usage.finish(&app_state, &result);
+  Ok(Json(result?))
 }
@@ -0,0 +1,609 @@
+use std::collections::HashSet;
+
+use async_openai::{
+  error::OpenAIError,
+  types::{CreateChatCompletionRequest, CreateEmbeddingRequest, InputSource},
+};
+use axum::{
+  extract::{Extension, Path, Query, State},
+  Json,
+};
+use chrono::{Duration, Utc};
+use futures::StreamExt;
+use uuid::Uuid;
+
+use crate::{
+  app::state::AppState,
+  models::{
+    assistants::{ListQuery, ListResponse},
+    batches::{
+      BatchError, BatchObject, BatchRequestError, BatchRequestInput, BatchRequestOutput,
+      BatchResponse, BatchStatus, CreateBatchRequest, BATCH_ENDPOINTS,
+    },
+  },
+  pgdb::{Batch, BatchRequest, RequestKind},
+  server::{
+    api_key::{Caller, ModelScope},
+    audit::AgentCall,
+    file_store,
+    moderation::{self, Subject},
+    openapi::{self, ApiRouter, Body, Operation, Schema},
+    pii::Redactor,
+    quota,
+    usage::Tracker,
+  },
+  Error, Result,
+};
+
+pub(super) fn api() -> ApiRouter {
+  ApiRouter::new()
+    .route(
+      Operation::get("/", "List batches")
+        .query(&["limit", "order", "after"])
+        .response(Body::Json(Schema::OpenAI("ListBatchesResponse"))),
+      list_batches,
+    )
+    .route(
+      Operation::post("/", "Create a batch from a file of requests")
+        .request(Body::Json(Schema::Model(
+          openapi::model::<CreateBatchRequest>,
+        )))
+        .response(Body::Json(Schema::OpenAI("Batch"))),
+      create,
+    )
+    .route(
+      Operation::get("/:batch_id", "Retrieve a batch")
+        .response(Body::Json(Schema::OpenAI("Batch"))),
+      get_batch,
+    )
+    .route(
+      Operation::post("/:batch_id/cancel", "Cancel a batch")
+        .response(Body::Json(Schema::OpenAI("Batch"))),
+      cancel,
+    )
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn create(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  scope: Option<Extension<ModelScope>>,
+  Json(request): Json<CreateBatchRequest>,
+) -> Result<Json<BatchObject>> {
+  let user_id = super::user_id(caller)?;
+  if request.input_file_id.is_empty() {
+    return Err(Error::InvalidArgument(
+      "input_file_id is required".to_string(),
+    ));
+  }
+  if !BATCH_ENDPOINTS.contains(&request.endpoint.as_str()) {
+    return Err(Error::InvalidArgument(format!(
+      "unsupported endpoint {}, expected one of {}",
+      request.endpoint,
+      BATCH_ENDPOINTS.join(", ")
+    )));
+  }
+  if request.completion_window != "24h" {
+    return Err(Error::InvalidArgument(
+      "completion_window must be 24h".to_string(),
+    ));
+  }
+
+  // the lines name their own models, they are checked against the key once the file is read
+  let models = scope.map(|Extension(scope)| scope.models().to_vec());
+  let batch = Batch::create(
+    user_id,
+    request,
+    &models.unwrap_or_default(),
+    Utc::now() + Duration::hours(24),
+    &app_state.pool,
+  )
+  .await?;
+  spawn(app_state, batch.id);
+  Ok(Json(batch.into()))
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn list_batches(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Query(query): Query<ListQuery>,
+) -> Result<Json<ListResponse<BatchObject>>> {
+  let user_id = super::user_id(caller)?;
+  Batch::list(user_id, &query, &app_state.pool)
+    .await
+    .map(Json)
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn get_batch(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path(batch_id): Path<Uuid>,
+) -> Result<Json<BatchObject>> {
+  let user_id = super::user_id(caller)?;
+  let batch = Batch::get(batch_id, Some(user_id), &app_state.pool).await?;
+  Ok(Json(batch.into()))
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn cancel(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path(batch_id): Path<Uuid>,
+) -> Result<Json<BatchObject>> {
+  let user_id = super::user_id(caller)?;
+  let pool = &app_state.pool;
+  let batch = Batch::get(batch_id, Some(user_id), pool).await?;
+  match batch.status() {
+    BatchStatus::Cancelling | BatchStatus::Cancelled => {}
+    _ => {
+      // the worker notices the new status between chunks and finalizes the batch
+      let cancelled = Batch::transition(
+        batch_id,
+        &[BatchStatus::Validating, BatchStatus::InProgress],
+        BatchStatus::Cancelling,
+        None,
+        pool,
+      )
+      .await?;
+      if !cancelled {
+        return Err(Error::InvalidArgument(format!(
+          "cannot cancel batch with status {}",
+          batch.status
+        )));
+      }
+    }
+  }
+  let batch = Batch::get(batch_id, Some(user_id), pool).await?;
+  Ok(Json(batch.into()))
+}
+
+/// Picks up all batches that were still running when the server stopped.
+pub(crate) async fn resume(app_state: &AppState) -> Result<()> {
+  for batch_id in Batch::unfinished(&app_state.pool).await? {
+    tracing::info!("resuming batch {batch_id}");
+    spawn(app_state.clone(), batch_id);
+  }
+  Ok(())
+}
+
+/// Processes a batch in the background.
+pub(crate) fn spawn(app_state: AppState, batch_id: Uuid) {
+  tokio::spawn(async move {
+    if let Err(e) = execute(&app_state, batch_id).await {
+      tracing::error!("batch {batch_id} failed: {e}");
+      let errors = vec![BatchError {
+        code: "server_error".to_string(),
+        message: e.to_string(),
+        param: None,
+        line: None,
+      }];
+      _ = Batch::transition(
+        batch_id,
+        &[
+          BatchStatus::Validating,
+          BatchStatus::InProgress,
+          BatchStatus::Finalizing,
+          BatchStatus::Cancelling,
+        ],
+        BatchStatus::Failed,
+        Some(errors.into()),
+        &app_state.pool,
+      )
+      .await;
+    }
+  });
+}
+
+/// The number of requests of a single batch sent to the provider at the same time.
+fn concurrency() -> usize {
+  dotenvy::var("MIKO_BATCH_CONCURRENCY")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .filter(|v| *v > 0)
+    .unwrap_or(8)
+}
+
+/// Drives a batch from wherever it currently is to a terminal state, every step can be repeated
+/// so this is also how a batch continues after a restart.
+#[tracing::instrument(skip(app_state))]
+async fn execute(app_state: &AppState, batch_id: Uuid) -> Result<()> {
+  let pool = &app_state.pool;
+  let batch = Batch::get(batch_id, None, pool).await?;
+
+  if batch.status() == BatchStatus::Validating && !validate(app_state, &batch).await? {
+    return Ok(());
+  }
+
+  let concurrency = concurrency();
+  let outcome = loop {
+    let batch = Batch::get(batch_id, None, pool).await?;
+    match batch.status() {
+      BatchStatus::InProgress => {}
+      BatchStatus::Cancelling => break BatchStatus::Cancelled,
+      BatchStatus::Finalizing => break BatchStatus::Completed,
+      _ => return Ok(()),
+    }
+    if Utc::now() > batch.expires_at {
+      break BatchStatus::Expired;
+    }
+
+    let requests = BatchRequest::pending(batch_id, concurrency as i64 * 4, pool).await?;
+    if requests.is_empty() {
+      break BatchStatus::Completed;
+    }
+    futures::stream::iter(requests)
+      .map(|request| process(app_state, batch.user_id, &batch.endpoint, request))
+      .buffer_unordered(concurrency)
+      .collect::<Vec<_>>()
+      .await
+      .into_iter()
+      .collect::<Result<Vec<_>>>()?;
+  };
+
+  // completed batches pass through finalizing, cancelled and expired ones keep their status
+  // until the partial results are written
+  let from: &[BatchStatus] = match outcome {
+    BatchStatus::Completed => {
+      let finalizing = Batch::transition(
+        batch_id,
+        &[BatchStatus::InProgress, BatchStatus::Finalizing],
+        BatchStatus::Finalizing,
+        None,
+        pool,
+      )
+      .await?;
+      if !finalizing {
+        return Ok(());
+      }
+      &[BatchStatus::Finalizing]
+    }
+    BatchStatus::Cancelled => &[BatchStatus::Cancelling],
+    _ => &[BatchStatus::InProgress, BatchStatus::Cancelling],
+  };
+
+  let owner = Some(batch.user_id);
+  let output_file_id = upload_results(app_state, owner, batch_id, "completed", "output").await?;
+  let error_file_id = upload_results(app_state, owner, batch_id, "failed", "error").await?;
+  Batch::set_files(batch_id, output_file_id, error_file_id, pool).await?;
+
+  Batch::transition(batch_id, from, outcome, None, pool).await?;
+  Ok(())
+}
+
+/// Checks every line of the input file and stores the requests, returns `false` when the batch
+/// did not make it to `in_progress`.
+async fn validate(app_state: &AppState, batch: &Batch) -> Result<bool> {
+  let pool = &app_state.pool;
+  let content = match file_store::content(app_state, Some(batch.user_id), &batch.input_file_id)
+    .await
+    .and_then(|(_, content)| {
+      String::from_utf8(content)
+        .map_err(|_| Error::InvalidArgument("the input file is not UTF-8".to_string()))
+    }) {
+    Ok(content) => content,
+    Err(e) => {
+      let errors = vec![BatchError {
+        code: "invalid_input_file".to_string(),
+        message: e.to_string(),
+        param: Some("input_file_id".to_string()),
+        line: None,
+      }];
+      Batch::transition(
+        batch.id,
+        &[BatchStatus::Validating],
+        BatchStatus::Failed,
+        Some(errors.into()),
+        pool,
+      )
+      .await?;
+      return Ok(false);
+    }
+  };
+
+  let (requests, errors) = parse_input(&content, &batch.endpoint, &batch.models);
+  if !errors.is_empty() {
+    Batch::transition(
+      batch.id,
+      &[BatchStatus::Validating],
+      BatchStatus::Failed,
+      Some(errors.into()),
+      pool,
+    )
+    .await?;
+    return Ok(false);
+  }
+
+  let mut tx = pool.begin().await?;
+  BatchRequest::insert_all(batch.id, requests, &mut *tx).await?;
+  if Batch::transition(
+    batch.id,
+    &[BatchStatus::Validating],
+    BatchStatus::InProgress,
+    None,
+    &mut *tx,
+  )
+  .await?
+  {
+    tx.commit().await?;
+    return Ok(true);
+  }
+
+  // cancelled while validating, nothing was sent to the provider yet
+  tx.rollback().await?;
+  Batch::transition(
+    batch.id,
+    &[BatchStatus::Cancelling],
+    BatchStatus::Cancelled,
+    None,
+    pool,
+  )
+  .await?;
+  Ok(false)
+}
+
+/// Parses a JSONL input file, every line must be a request to the batch's endpoint with one of
+/// `models`, when the batch is limited to some.
+fn parse_input(
+  content: &str,
+  endpoint: &str,
+  models: &[String],
+) -> (Vec<(i64, BatchRequestInput)>, Vec<BatchError>) {
+  let mut requests = vec![];
+  let mut errors = vec![];
+  let mut custom_ids = HashSet::new();
+
+  let error = |line: i64, code: &str, param: Option<&str>, message: String| BatchError {
+    code: code.to_string(),
+    message,
+    param: param.map(ToString::to_string),
+    line: Some(line),
+  };
+
+  for (index, text) in content.lines().enumerate() {
+    let line = index as i64 + 1;
+    if text.trim().is_empty() {
+      continue;
+    }
+    let request: BatchRequestInput = match serde_json::from_str(text) {
+      Ok(request) => request,
+      Err(e) => {
+        errors.push(error(line, "invalid_json_line", None, e.to_string()));
+        continue;
+      }
+    };
+    if request.method != "POST" {
+      errors.push(error(
+        line,
+        "invalid_request",
+        Some("method"),
+        format!(
+          "unsupported method {}, only POST is supported",
+          request.method
+        ),
+      ));
+    }
+    if request.url != endpoint {
+      errors.push(error(
+        line,
+        "mismatched_endpoint",
+        Some("url"),
+        format!(
+          "url {} does not match the batch endpoint {endpoint}",
+          request.url
+        ),
+      ));
+    }
+    if !custom_ids.insert(request.custom_id.clone()) {
+      errors.push(error(
+        line,
+        "duplicate_custom_id",
+        Some("custom_id"),
+        format!("custom_id {} is used more than once", request.custom_id),
+      ));
+    }
+    if request.body.get("stream").and_then(|v| v.as_bool()) == Some(true) {
+      errors.push(error(
+        line,
+        "invalid_request",
+        Some("body.stream"),
+        "streaming is not supported in batches".to_string(),
+      ));
+    }
+    let model = request.body.get("model").and_then(|m| m.as_str());
+    if let Some(model) = model.filter(|m| !models.is_empty() && !models.iter().any(|a| a == m)) {
+      errors.push(error(
+        line,
+        "model_not_allowed",
+        Some("body.model"),
+        format!("the api key of the batch can not use model {model}"),
+      ));
+    }
+    if let Err(e) = check_body(endpoint, &request.body) {
+      errors.push(error(line, "invalid_request", Some("body"), e.to_string()));
+    }
+    requests.push((line, request));
+  }
+
+  if requests.is_empty() && errors.is_empty() {
+    errors.push(BatchError {
+      code: "empty_file".to_string(),
+      message: "the input file does not contain any requests".to_string(),
+      param: Some("input_file_id".to_string()),
+      line: None,
+    });
+  }
+  (requests, errors)
+}
+
+fn check_body(endpoint: &str, body: &serde_json::Value) -> serde_json::Result<()> {
+  match endpoint {
+    "/v1/chat/completions" => {
+      serde_json::from_value::<CreateChatCompletionRequest>(body.clone()).map(|_| ())
+    }
+    _ => serde_json::from_value::<CreateEmbeddingRequest>(body.clone()).map(|_| ()),
+  }
+}
+
+/// Sends a single request to the provider and records its outcome.
+async fn process(
+  app_state: &AppState,
+  user_id: Uuid,
+  endpoint: &str,
+  request: BatchRequest,
+) -> Result<()> {
+  let (response, error) = match admit(app_state, user_id, endpoint).await {
+    Ok(()) => match send(app_state, user_id, endpoint, request.body).await {
+      Ok(body) => (
+        Some(BatchResponse {
+          status_code: 200,
+          request_id: Uuid::new_v4().to_string(),
+          body,
+        }),
+        None,
+      ),
+      Err(e) => (None, Some(request_error(&e))),
+    },
+    Err(Error::QuotaExceeded(exceeded)) => (
+      None,
+      Some(BatchRequestError {
+        code: "quota_exceeded".to_string(),
+        message: exceeded.to_string(),
+      }),
+    ),
+    Err(e) => return Err(e),
+  };
+  BatchRequest::finish(request.id, response, error, &app_state.pool).await
+}
+
+/// Counts a request of a batch against the quota of its owner like any other request. A batch
+/// has a day to complete, so it waits for the requests per minute limit instead of failing.
+async fn admit(app_state: &AppState, user_id: Uuid, endpoint: &str) -> Result<()> {
+  let kind = match endpoint {
+    "/v1/embeddings" => RequestKind::Embedding,
+    _ => RequestKind::Completion,
+  };
+  loop {
+    match quota::check(app_state, Some(user_id), kind).await {
+      Err(Error::QuotaExceeded(exceeded)) if exceeded.limit == "requests_per_minute" => {
+        let wait = (exceeded.reset_at - Utc::now())
+          .to_std()
+          .unwrap_or_default();
+        tokio::time::sleep(wait.max(std::time::Duration::from_secs(1))).await;
+      }
+      result => return result,
+    }
+  }
+}
+
+async fn send(
+  app_state: &AppState,
+  user_id: Uuid,
+  endpoint: &str,
+  body: serde_json::Value,
+) -> Result<serde_json::Value> {
+  let client = app_state.openai_client();
+  let response = match endpoint {
+    "/v1/chat/completions" => {
+      let mut request: CreateChatCompletionRequest =
+        serde_json::from_value(body).map_err(OpenAIError::JSONDeserialize)?;
+      // the lines are proxied user content, gated like chat completions without a chat
+      let redactor = match Redactor::for_chat(app_state, Some(user_id), None).await? {
+        Some(mut redactor) => {
+          redactor.redact_messages(&mut request.messages);
+          redactor.record(app_state, endpoint);
+          Some(redactor)
+        }
+        None => None,
+      };
+      let subject = Subject {
+        source: moderation::SOURCE_CHAT,
+        user_id: Some(user_id),
+        chat_id: None,
+      };
+      moderation::check(
+        app_state,
+        subject,
+        moderation::user_content(&request.messages),
+      )
+      .await?;
+
+      let usage = Tracker::start(endpoint, &request.model).user(Some(user_id));
+      let audit = AgentCall::start(endpoint, &request).user(Some(user_id));
+      let result = client.chat().create(request).await;
+      usage.finish(app_state, &result);
+      audit.finish(app_state, &result);
+      let mut response = result?;
+      if let Some(redactor) = &redactor {
+        redactor.restore_response(&mut response);
+      }
+      serde_json::to_value(response)
+    }
+    _ => {
+      let request: CreateEmbeddingRequest =
+        serde_json::from_value(body).map_err(OpenAIError::JSONDeserialize)?;
+      let usage = Tracker::start(endpoint, &request.model).user(Some(user_id));
+      let audit = AgentCall::start(endpoint, &request).user(Some(user_id));
+      let result = client.embeddings().create(request).await;
+      usage.finish(app_state, &result);
+      audit.finish(app_state, &result);
+      serde_json::to_value(result?)
+    }
+  };
+  Ok(response?)
+}
+
+fn request_error(err: &Error) -> BatchRequestError {
+  match err {
+    Error::OpenAI(OpenAIError::ApiError(api_error)) => BatchRequestError {
+      code: api_error
+        .code
+        .as_ref()
+        .and_then(|c| c.as_str())
+        .or(api_error.r#type.as_deref())
+        .unwrap_or("server_error")
+        .to_string(),
+      message: api_error.message.clone(),
+    },
+    Error::OpenAI(OpenAIError::JSONDeserialize(e)) => BatchRequestError {
+      code: "invalid_request".to_string(),
+      message: e.to_string(),
+    },
+    Error::ContentBlocked(blocked) => BatchRequestError {
+      code: "content_policy_violation".to_string(),
+      message: blocked.to_string(),
+    },
+    e => BatchRequestError {
+      code: "server_error".to_string(),
+      message: e.to_string(),
+    },
+  }
+}
+
+/// Writes the finished requests with `status` to a JSONL file, returns `None` when there are none.
+async fn upload_results(
+  app_state: &AppState,
+  owner: Option<Uuid>,
+  batch_id: Uuid,
+  status: &str,
+  kind: &str,
+) -> Result<Option<String>> {
+  let requests = BatchRequest::finished(batch_id, status, &app_state.pool).await?;
+  if requests.is_empty() {
+    return Ok(None);
+  }
+
+  let mut content = String::new();
+  for request in requests {
+    content.push_str(&serde_json::to_string(&BatchRequestOutput::from(request))?);
+    content.push('\n');
+  }
+
+  let source = InputSource::Bytes {
+    filename: format!("batch_{batch_id}_{kind}.jsonl"),
+    bytes: content.into(),
+  };
+  let file = file_store::create(app_state, owner, "batch_output".to_string(), source).await?;
+  Ok(Some(file.id))
+}
@@ -1,55 +1,128 @@
-use std::time::Duration;
-
 use async_openai::types::CreateChatCompletionRequest;
 use axum::{
-  extract::State,
-  response::{
-    sse::{Event, KeepAlive},
-    IntoResponse, Sse,
-  },
-  routing::post,
+  extract::{Extension, State},
+  http::HeaderMap,
+  response::{IntoResponse, Response},
   Json,
 };
-use futures::StreamExt;
 
-use crate::{app::state::AppState, Result};
+use super::{provider::tools::ToolEmulation, sse};
+use crate::{
+  app::state::AppState,
+  pgdb::Chat,
+  server::{
+    api_key::Caller,
+    moderation::{self, Subject},
+    openapi::{ApiRouter, Body, Operation, Schema},
+    pii::{Redactor, CHAT_ID_HEADER},
+    pricing, retrieval,
+    usage::{self, Tracker},
+    vision,
+  },
+  Result,
+};
 
-pub fn routes(app_state: AppState) -> axum::Router<AppState> {
-  axum::Router::new()
-    .route("/completions", post(completions))
-    .with_state(app_state)
+pub(super) fn api() -> ApiRouter {
+  ApiRouter::new().route(
+    Operation::post(
+      "/completions",
+      "Create a chat completion, streamed as events when `stream` is set",
+    )
+    .request(Body::Json(Schema::OpenAI("CreateChatCompletionRequest")))
+    .response(Body::Json(Schema::OpenAI("CreateChatCompletionResponse"))),
+    completions,
+  )
 }
 
 #[tracing::instrument(skip(app_state))]
 async fn completions(
   State(app_state): State<AppState>,
-  Json(params): Json<CreateChatCompletionRequest>,
-) -> Result<impl IntoResponse> {
+  caller: Option<Extension<Caller>>,
+  headers: HeaderMap,
+  Json(mut params): Json<CreateChatCompletionRequest>,
+) -> Result<Response> {
+  let user_id = caller.as_ref().map(|Extension(caller)| caller.user_id);
+  let chat_id = headers
+    .get(CHAT_ID_HEADER)
+    .and_then(|v| v.to_str().ok())
+    .and_then(|v| v.parse().ok());
+  // costs are only attributed to chats of the caller
+  let owned_chat = match (user_id, chat_id) {
+    (Some(user_id), Some(chat_id)) => Chat::redacts_pii(chat_id, user_id, &app_state.pool)
+      .await?
+      .map(|redact_pii| (user_id, chat_id, redact_pii)),
+    _ => None,
+  };
+  let owned_chat_id = owned_chat.map(|(_, chat_id, _)| chat_id);
+  let budget = match owned_chat_id {
+    Some(chat_id) => pricing::check_budget(&app_state, chat_id).await?,
+    None => None,
+  };
+  // before the redaction, which covers the excerpts of the files too
+  if let Some((user_id, chat_id, redact_pii)) = owned_chat {
+    retrieval::inject(
+      &app_state,
+      user_id,
+      chat_id,
+      redact_pii,
+      &mut params.messages,
+    )
+    .await?;
+  }
+  let redactor = match Redactor::for_chat(&app_state, user_id, owned_chat_id).await? {
+    Some(mut redactor) => {
+      redactor.redact_messages(&mut params.messages);
+      redactor.record(&app_state, "/v1/chat/completions");
+      Some(redactor)
+    }
+    None => None,
+  };
+
+  let subject = Subject {
+    source: moderation::SOURCE_CHAT,
+    user_id,
+    chat_id: owned_chat_id,
+  };
+  moderation::check(
+    &app_state,
+    subject,
+    moderation::user_content(&params.messages),
+  )
+  .await?;
+
+  vision::inline_images(&app_state, owned_chat_id, &mut params.messages).await?;
+
+  let emulation = match app_state.catalog().native_tools(&params.model) {
+    true => None,
+    false => ToolEmulation::prepare(&mut params)?,
+  };
+
+  let usage = Tracker::start("/v1/chat/completions", &params.model)
+    .caller(caller)
+    .chat(owned_chat_id)
+    .budget(budget);
   if params.stream.unwrap_or_default() {
-    let mut result = app_state
-      .openai_client()
-      .chat()
-      .create_stream(params)
-      .await?;
-    let (tx, rx) = futures::channel::mpsc::unbounded();
-    tokio::spawn(async move {
-      while let Some(event) = result.next().await {
-        if let Ok(event) = event {
-          tx.unbounded_send(Event::default().json_data(event))
-            .unwrap();
-        }
-      }
-    });
-    return Ok(
-      Sse::new(rx)
-        .keep_alive(
-          KeepAlive::new()
-            .interval(Duration::from_secs(15))
-            .text("keep-alive-text"),
-        )
-        .into_response(),
-    );
+    let prompt_tokens = usage::estimate_prompt_tokens(&params);
+    let upstream = app_state.openai_client().chat().create_stream(params).await;
+    let upstream = usage.finish_stream(&app_state, prompt_tokens, upstream);
+    let upstream = match emulation {
+      Some(emulation) => emulation.restore_stream(upstream?),
+      None => upstream?,
+    };
+    let upstream = match redactor {
+      Some(redactor) => redactor.restore_stream(upstream),
+      None => upstream,
+    };
+    return Ok(sse::stream_response(upstream).into_response());
+  }
+  let result = app_state.openai_client().chat().create(params).await;
+  usage.finish(&app_state, &result);
+  let mut response = result?;
+  if let Some(emulation) = &emulation {
+    emulation.restore_response(&mut response);
+  }
+  if let Some(redactor) = &redactor {
+    redactor.restore_response(&mut response);
   }
-  let result = app_state.openai_client().chat().create(params).await?;
-  Ok(Json(result).into_response())
+  Ok(Json(response).into_response())
 }
@@ -1,27 +1,40 @@
-use axum::{extract::State, routing::post, Json};
+use axum::{
+  extract::{Extension, State},
+  Json,
+};
 
 use crate::{
   app::state::AppState,
   models::embeddings::{CreateEmbeddingRequest, CreateEmbeddingResponse},
+  server::{
+    api_key::Caller,
+    openapi::{ApiRouter, Body, Operation, Schema},
+    usage::Tracker,
+  },
   Result,
 };
 
-pub fn routes(app_state: AppState) -> axum::Router<AppState> {
-  axum::Router::new()
-    .route("/", post(create))
-    .with_state(app_state)
+pub(super) fn api() -> ApiRouter {
+  ApiRouter::new().route(
+    Operation::post("/", "Create embeddings")
+      .request(Body::Json(Schema::OpenAI("CreateEmbeddingRequest")))
+      .response(Body::Json(Schema::OpenAI("CreateEmbeddingResponse"))),
+    create,
+  )
 }
 
 #[tracing::instrument(skip(app_state))]
 async fn create(
   State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
   Json(params): Json<CreateEmbeddingRequest>,
 ) -> Result<Json<CreateEmbeddingResponse>> {
-  app_state
+  let usage = Tracker::start("/v1/embeddings", &params.model).caller(caller);
+  let result = app_state
     .openai_client()
     .embeddings()
     .create(params.into())
-    .await
-    .map_err(Into::into)
-    .map(|v| Json(v.into()))
+    .await;
+  usage.finish(&app_state, &result);
+  Ok(Json(result?.into()))
 }
@@ -0,0 +1,63 @@
+use axum::{
+  body::to_bytes,
+  extract::Request,
+  http::header,
+  middleware::Next,
+  response::{IntoResponse, Response},
+  Json,
+};
+use serde_json::json;
+
+use crate::models::ApiError;
+
+/// The most bytes of an error body that is not JSON which are read into the message.
+const MESSAGE_LIMIT: usize = 16 * 1024;
+
+/// Middleware returning errors in the shape of the OpenAI API, `{"error": {message, type, param,
+/// code}}`, which the SDKs parse. Errors of the app carry their [ApiError], the plain text
+/// rejections of axum extractors are wrapped. Status and headers are kept.
+pub(crate) async fn openai_errors(request: Request, next: Next) -> Response {
+  let response = next.run(request).await;
+  let status = response.status();
+  if !status.is_client_error() && !status.is_server_error() {
+    return response;
+  }
+  let (mut parts, body) = response.into_parts();
+  let api_error = match parts.extensions.remove::<ApiError>() {
+    Some(api_error) => api_error,
+    None => {
+      let is_json = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+      if is_json {
+        return Response::from_parts(parts, body);
+      }
+      let message = to_bytes(body, MESSAGE_LIMIT)
+        .await
+        .map(|bytes| String::from_utf8_lossy(&bytes).trim().to_string())
+        .ok()
+        .filter(|message| !message.is_empty())
+        .unwrap_or_else(|| status.canonical_reason().unwrap_or("error").to_string());
+      let r#type = if status.is_server_error() {
+        "server_error"
+      } else {
+        "invalid_request_error"
+      };
+      ApiError {
+        message,
+        r#type: Some(r#type.to_string()),
+        param: None,
+        code: None,
+      }
+    }
+  };
+
+  let (body_parts, body) = Json(json!({ "error": api_error }))
+    .into_response()
+    .into_parts();
+  parts.headers.remove(header::CONTENT_LENGTH);
+  parts.headers.extend(body_parts.headers);
+  Response::from_parts(parts, body)
+}
@@ -1,64 +1,96 @@
-use async_openai::types::{
-  CreateFileRequest, DeleteFileResponse, FileInput, ListFilesResponse, OpenAIFile,
-};
+use async_openai::types::DeleteFileResponse;
 use axum::{
-  extract::{Multipart, Path, Query, State},
-  routing::get,
+  extract::{Extension, Path, Query, State},
+  http::header,
+  response::IntoResponse,
   Json,
 };
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::multipart::{Form, FromForm, TypedMultipart, Upload};
+use crate::{
+  app::state::AppState,
+  models::{FileList, FileObject},
+  server::{
+    api_key::Caller,
+    file_store,
+    openapi::{self, ApiRouter, Body, Operation, Schema},
+  },
+  Error, Result,
+};
+
+pub(super) fn api() -> ApiRouter {
+  ApiRouter::new()
+    .route(
+      Operation::get("/", "List files")
+        .query(&["purpose"])
+        .response(Body::Json(Schema::Model(openapi::model::<FileList>))),
+      list_files,
+    )
+    .upload(
+      Operation::post("/", "Upload a file, up to 512 MB")
+        .request(Body::Multipart(Schema::OpenAI("CreateFileRequest")))
+        .response(Body::Json(Schema::Model(openapi::model::<FileObject>))),
+      create,
+    )
+    .route(
+      Operation::get("/:file_id", "Retrieve a file")
+        .response(Body::Json(Schema::Model(openapi::model::<FileObject>))),
+      get_file,
+    )
+    .route(
+      Operation::delete("/:file_id", "Delete a file")
+        .response(Body::Json(Schema::OpenAI("DeleteFileResponse"))),
+      delete_file,
+    )
+    .route(
+      Operation::get("/:file_id/content", "Retrieve the contents of a file").response(Body::Binary),
+      get_file_content,
+    )
+}
 
-use crate::{app::state::AppState, Result};
+/// The user whose files a request sees, local files are kept apart per user.
+fn owner(caller: Option<Extension<Caller>>) -> Result<Option<Uuid>> {
+  match caller {
+    Some(Extension(caller)) => Ok(Some(caller.user_id)),
+    None => Err(Error::UserNotAuthenticated),
+  }
+}
+
+/// The most bytes of a file the provider accepts.
+const UPLOAD_LIMIT: u64 = 512 * 1024 * 1024;
 
-pub fn routes(app_state: AppState) -> axum::Router<AppState> {
-  axum::Router::new()
-    .route("/", get(list_files).post(create))
-    .route("/:file_id", get(get_file).delete(delete_file))
-    .route("/:file_id/content", get(get_file_content))
-    .with_state(app_state)
+/// An upload to the Files API.
+#[derive(Debug)]
+struct FileForm {
+  file: Upload,
+  purpose: String,
 }
 
-async fn create_file_request(mut request: Multipart) -> Result<CreateFileRequest> {
-  let mut req = CreateFileRequest::default();
-  while let Ok(Some(field)) = request.next_field().await {
-    if let Some(field_name) = field.name() {
-      match field_name {
-        "file" => {
-          req.file = FileInput {
-            source: async_openai::types::InputSource::Bytes {
-              filename: field.file_name().unwrap_or_default().to_string(),
-              bytes: field
-                .bytes()
-                .await
-                .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?,
-            },
-          }
-        }
-        "purpose" => {
-          req.purpose = field
-            .text()
-            .await
-            .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?
-        }
-        _ => {}
-      }
-    }
+impl FromForm for FileForm {
+  const FIELDS: &'static [&'static str] = &["purpose"];
+  const FILES: &'static [&'static str] = &["file"];
+  const FILE_LIMIT: u64 = UPLOAD_LIMIT;
+
+  fn from_form(mut form: Form) -> Result<Self> {
+    Ok(Self {
+      file: form.required_file("file")?,
+      purpose: form.required("purpose")?,
+    })
   }
-  Ok(req)
 }
 
 #[tracing::instrument(skip(app_state))]
 async fn create(
   State(app_state): State<AppState>,
-  multipart: axum::extract::Multipart,
-) -> Result<Json<OpenAIFile>> {
-  app_state
-    .openai_client()
-    .files()
-    .create(create_file_request(multipart).await?)
+  caller: Option<Extension<Caller>>,
+  TypedMultipart(FileForm { file, purpose }): TypedMultipart<FileForm>,
+) -> Result<Json<FileObject>> {
+  let owner = owner(caller)?;
+  file_store::create(&app_state, owner, purpose, file.input_source())
     .await
-    .map_err(Into::into)
-    .map(Into::into)
+    .map(Json)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -69,55 +101,46 @@ struct ListFilesRequest {
 #[tracing::instrument(skip(app_state))]
 async fn list_files(
   State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
   Query(params): Query<ListFilesRequest>,
-) -> Result<Json<ListFilesResponse>> {
-  app_state
-    .openai_client()
-    .files()
-    .list(&params)
+) -> Result<Json<FileList>> {
+  let owner = owner(caller)?;
+  file_store::list(&app_state, owner, params.purpose.as_deref())
     .await
-    .map_err(Into::into)
-    .map(Into::into)
+    .map(Json)
 }
 
 #[tracing::instrument(skip(app_state))]
 async fn get_file(
   State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
   Path(file_id): Path<String>,
-) -> Result<Json<OpenAIFile>> {
-  app_state
-    .openai_client()
-    .files()
-    .retrieve(&file_id)
+) -> Result<Json<FileObject>> {
+  let owner = owner(caller)?;
+  file_store::retrieve(&app_state, owner, &file_id)
     .await
-    .map_err(Into::into)
-    .map(Into::into)
+    .map(Json)
 }
 
 #[tracing::instrument(skip(app_state))]
 async fn delete_file(
   State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
   Path(file_id): Path<String>,
 ) -> Result<Json<DeleteFileResponse>> {
-  app_state
-    .openai_client()
-    .files()
-    .delete(&file_id)
+  let owner = owner(caller)?;
+  file_store::delete(&app_state, owner, &file_id)
     .await
-    .map_err(Into::into)
-    .map(Into::into)
+    .map(Json)
 }
 
 #[tracing::instrument(skip(app_state))]
 async fn get_file_content(
   State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
   Path(file_id): Path<String>,
-) -> Result<String> {
-  app_state
-    .openai_client()
-    .files()
-    .retrieve_content(&file_id)
-    .await
-    .map_err(Into::into)
-    .map(Into::into)
+) -> Result<impl IntoResponse> {
+  let owner = owner(caller)?;
+  let (media_type, content) = file_store::content(&app_state, owner, &file_id).await?;
+  Ok(([(header::CONTENT_TYPE, media_type.to_string())], content))
 }
@@ -1,36 +1,100 @@
+use std::time::Duration;
+
 use async_openai::types::{
-  FineTuningJob, ListFineTuningJobEventsResponse, ListPaginatedFineTuningJobsResponse,
+  FineTuningJob, FineTuningJobStatus, ListFineTuningJobEventsResponse,
+  ListPaginatedFineTuningJobsResponse,
 };
 use axum::{
-  extract::{Path, Query, State},
-  routing::{get, post},
+  extract::{Extension, Path, Query, State},
   Json,
 };
 use serde::{Deserialize, Serialize};
 
-use crate::{app::state::AppState, models::fine_tuning::CreateFineTuningJobRequest, Result};
+use crate::{
+  app::state::AppState,
+  models::fine_tuning::{CreateFineTuningJobRequest, JobEvent, TrackedJob},
+  pgdb::FineTunedModel,
+  server::{
+    api_key::Caller,
+    file_store,
+    openapi::{ApiRouter, Body, Operation, Schema},
+  },
+  Error, Result,
+};
+
+/// How often the provider is asked about a running job.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The most events fetched per poll, the provider returns the newest first.
+const EVENTS_PER_POLL: usize = 100;
+
+/// The jobs listed when the request does not say, like the provider.
+const DEFAULT_PAGE_SIZE: usize = 20;
 
-pub fn routes(app_state: AppState) -> axum::Router<AppState> {
-  axum::Router::new()
-    .route("/jobs", get(list_jobs).post(create_job))
-    .route("/jobs/:fine_tuning_job_id", get(get_job))
-    .route("/jobs/:fine_tuning_job_id/events", get(list_job_events))
-    .route("/jobs/:fine_tuning_job_id/cancel", post(cancel_job))
-    .with_state(app_state)
+pub(super) fn api() -> ApiRouter {
+  ApiRouter::new()
+    .route(
+      Operation::get("/jobs", "List fine-tuning jobs")
+        .query(&["after", "limit"])
+        .response(Body::Json(Schema::OpenAI(
+          "ListPaginatedFineTuningJobsResponse",
+        ))),
+      list_jobs,
+    )
+    .route(
+      Operation::post("/jobs", "Create a fine-tuning job")
+        .request(Body::Json(Schema::OpenAI("CreateFineTuningJobRequest")))
+        .response(Body::Json(Schema::OpenAI("FineTuningJob"))),
+      create_job,
+    )
+    .route(
+      Operation::get("/jobs/:fine_tuning_job_id", "Retrieve a fine-tuning job")
+        .response(Body::Json(Schema::OpenAI("FineTuningJob"))),
+      get_job,
+    )
+    .route(
+      Operation::get(
+        "/jobs/:fine_tuning_job_id/events",
+        "List the events of a fine-tuning job",
+      )
+      .query(&["after", "limit"])
+      .response(Body::Json(Schema::OpenAI(
+        "ListFineTuningJobEventsResponse",
+      ))),
+      list_job_events,
+    )
+    .route(
+      Operation::post(
+        "/jobs/:fine_tuning_job_id/cancel",
+        "Cancel a fine-tuning job",
+      )
+      .response(Body::Json(Schema::OpenAI("FineTuningJob"))),
+      cancel_job,
+    )
 }
 
 #[tracing::instrument(skip(app_state))]
 async fn create_job(
   State(app_state): State<AppState>,
-  Json(request): Json<CreateFineTuningJobRequest>,
+  caller: Option<Extension<Caller>>,
+  Json(mut request): Json<CreateFineTuningJobRequest>,
 ) -> Result<Json<FineTuningJob>> {
-  app_state
+  let user_id = caller.map(|Extension(caller)| caller.user_id);
+  // the provider trains on its own copies of local files
+  request.training_file =
+    file_store::provider_file_id(&app_state, user_id, &request.training_file).await?;
+  if let Some(validation_file) = &request.validation_file {
+    request.validation_file =
+      Some(file_store::provider_file_id(&app_state, user_id, validation_file).await?);
+  }
+  let job = app_state
     .openai_client()
     .fine_tuning()
     .create(request.into())
-    .await
-    .map_err(Into::into)
-    .map(Into::into)
+    .await?;
+  TrackedJob::create(&job, user_id, &app_state.pool).await?;
+  spawn(app_state, job.id.clone());
+  Ok(Json(job))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -39,40 +103,74 @@ struct ListJobsQuery {
   limit: Option<usize>,
 }
 
+/// Fails with `NotFound` unless the caller created the job through the proxy, the provider
+/// knows the jobs of every user.
+async fn ensure_owned(
+  app_state: &AppState,
+  caller: Option<Extension<Caller>>,
+  job_id: &str,
+) -> Result<()> {
+  let user_id = super::user_id(caller)?;
+  if !TrackedJob::is_owned_by(job_id, user_id, &app_state.pool).await? {
+    return Err(Error::NotFound(format!("fine-tuning job {job_id}")));
+  }
+  Ok(())
+}
+
+/// The jobs of the caller as they were last polled, paginated like the provider does.
 #[tracing::instrument(skip(app_state))]
 async fn list_jobs(
   State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
   Query(params): Query<ListJobsQuery>,
 ) -> Result<Json<ListPaginatedFineTuningJobsResponse>> {
-  app_state
-    .openai_client()
-    .fine_tuning()
-    .list_paginated(&params)
-    .await
-    .map_err(Into::into)
-    .map(Into::into)
+  let user_id = super::user_id(caller)?;
+  let jobs = TrackedJob::list_for_user(user_id, &app_state.pool).await?;
+  let start = match &params.after {
+    Some(after) => jobs
+      .iter()
+      .position(|job| &job.id == after)
+      .map_or(jobs.len(), |index| index + 1),
+    None => 0,
+  };
+  let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+  let has_more = jobs.len() > start + limit;
+  Ok(Json(ListPaginatedFineTuningJobsResponse {
+    data: jobs
+      .into_iter()
+      .skip(start)
+      .take(limit)
+      .map(Into::into)
+      .collect(),
+    has_more,
+    object: "list".to_string(),
+  }))
 }
 
 #[tracing::instrument(skip(app_state))]
 async fn get_job(
   State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
   Path(fine_tuning_job_id): Path<String>,
 ) -> Result<Json<FineTuningJob>> {
-  app_state
+  ensure_owned(&app_state, caller, &fine_tuning_job_id).await?;
+  let job = app_state
     .openai_client()
     .fine_tuning()
     .retrieve(&fine_tuning_job_id)
-    .await
-    .map_err(Into::into)
-    .map(Into::into)
+    .await?;
+  refresh(&app_state, &job).await?;
+  Ok(Json(job))
 }
 
 #[tracing::instrument(skip(app_state))]
 async fn list_job_events(
   State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
   Path(fine_tuning_job_id): Path<String>,
   Query(params): Query<ListJobsQuery>,
 ) -> Result<Json<ListFineTuningJobEventsResponse>> {
+  ensure_owned(&app_state, caller, &fine_tuning_job_id).await?;
   app_state
     .openai_client()
     .fine_tuning()
@@ -85,13 +183,90 @@ async fn list_job_events(
 #[tracing::instrument(skip(app_state))]
 async fn cancel_job(
   State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
   Path(fine_tuning_job_id): Path<String>,
 ) -> Result<Json<FineTuningJob>> {
-  app_state
+  ensure_owned(&app_state, caller, &fine_tuning_job_id).await?;
+  let job = app_state
     .openai_client()
     .fine_tuning()
     .cancel(&fine_tuning_job_id)
-    .await
-    .map_err(Into::into)
-    .map(Into::into)
+    .await?;
+  refresh(&app_state, &job).await?;
+  Ok(Json(job))
+}
+
+/// Updates a tracked job with what the provider returned, jobs created elsewhere are ignored.
+async fn refresh(app_state: &AppState, job: &FineTuningJob) -> Result<()> {
+  if TrackedJob::update(job, &app_state.pool).await? && job.status == FineTuningJobStatus::Succeeded
+  {
+    register(app_state, job);
+  }
+  Ok(())
+}
+
+/// Adds the model of a succeeded job to the catalog.
+fn register(app_state: &AppState, job: &FineTuningJob) {
+  if let Some(model) = &job.fine_tuned_model {
+    app_state.catalog().register(FineTunedModel {
+      model: model.clone(),
+      base_model: job.model.clone(),
+      created_at: chrono::DateTime::from_timestamp(job.created_at as i64, 0).unwrap_or_default(),
+    });
+  }
+}
+
+/// Registers the models of succeeded jobs and polls the unfinished ones again after a restart.
+pub(crate) async fn resume(app_state: &AppState) -> Result<()> {
+  let catalog = app_state.catalog();
+  for model in TrackedJob::fine_tuned_models(&app_state.pool).await? {
+    catalog.register(model);
+  }
+  for job_id in TrackedJob::unfinished(&app_state.pool).await? {
+    tracing::info!("resuming fine-tuning job {job_id}");
+    spawn(app_state.clone(), job_id);
+  }
+  Ok(())
+}
+
+/// Polls a job in the background until it finishes, recording its status and events.
+pub(crate) fn spawn(app_state: AppState, job_id: String) {
+  tokio::spawn(async move {
+    loop {
+      match poll(&app_state, &job_id).await {
+        Ok(true) => break,
+        Ok(false) => {}
+        // the provider may be down for a while, the job keeps running there
+        Err(e) => tracing::warn!("failed to poll fine-tuning job {job_id}: {e}"),
+      }
+      tokio::time::sleep(POLL_INTERVAL).await;
+    }
+  });
+}
+
+/// Refreshes a job and its events, `true` once the job finished.
+async fn poll(app_state: &AppState, job_id: &str) -> Result<bool> {
+  let client = app_state.openai_client();
+  let job = client.fine_tuning().retrieve(job_id).await?;
+  let events = client
+    .fine_tuning()
+    .list_events(
+      job_id,
+      &ListJobsQuery {
+        after: None,
+        limit: Some(EVENTS_PER_POLL),
+      },
+    )
+    .await?;
+  JobEvent::save_all(job_id, &events.data, &app_state.pool).await?;
+  TrackedJob::update(&job, &app_state.pool).await?;
+
+  match job.status {
+    FineTuningJobStatus::Succeeded => {
+      register(app_state, &job);
+      Ok(true)
+    }
+    FineTuningJobStatus::Failed | FineTuningJobStatus::Cancelled => Ok(true),
+    _ => Ok(false),
+  }
 }
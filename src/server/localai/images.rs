@@ -2,234 +2,204 @@ use async_openai::types::{
   CreateImageEditRequest, CreateImageVariationRequest, ImageInput, ImagesResponse,
 };
 use axum::{
-  extract::{Multipart, State},
-  routing::post,
+  extract::{Extension, State},
   Json,
 };
 
-use crate::{app::state::AppState, models::images::CreateImageRequest, Result};
+use super::multipart::{check_range, Form, FromForm, TypedMultipart, Upload};
+use crate::{
+  app::state::AppState,
+  models::images::{CreateImageRequest, DallE2ImageSize, ImageModel, ResponseFormat},
+  server::{
+    api_key::Caller,
+    openapi::{ApiRouter, Body, Operation, Schema},
+    usage::{model_name, Tracker},
+  },
+  Result,
+};
+
+/// The wire name of the requested size, images are 1024x1024 unless asked otherwise.
+fn image_size<T: serde::Serialize>(size: &Option<T>) -> String {
+  size
+    .as_ref()
+    .map(model_name)
+    .unwrap_or_else(|| "1024x1024".to_string())
+}
 
-pub fn routes(app_state: AppState) -> axum::Router<AppState> {
-  axum::Router::new()
-    .route("/generations", post(create))
-    .route("/edits", post(edit))
-    .route("/variations", post(variations))
-    .with_state(app_state)
+pub(super) fn api() -> ApiRouter {
+  ApiRouter::new()
+    .route(
+      Operation::post("/generations", "Create images from a prompt")
+        .request(Body::Json(Schema::OpenAI("CreateImageRequest")))
+        .response(Body::Json(Schema::OpenAI("ImagesResponse"))),
+      create,
+    )
+    .upload(
+      Operation::post("/edits", "Edit an image, files up to 4 MB")
+        .request(Body::Multipart(Schema::OpenAI("CreateImageEditRequest")))
+        .response(Body::Json(Schema::OpenAI("ImagesResponse"))),
+      edit,
+    )
+    .upload(
+      Operation::post(
+        "/variations",
+        "Create variations of an image, files up to 4 MB",
+      )
+      .request(Body::Multipart(Schema::OpenAI(
+        "CreateImageVariationRequest",
+      )))
+      .response(Body::Json(Schema::OpenAI("ImagesResponse"))),
+      variations,
+    )
 }
 
 #[tracing::instrument(skip(app_state))]
 async fn create(
   State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
   Json(params): Json<CreateImageRequest>,
 ) -> Result<Json<ImagesResponse>> {
-  app_state
+  let usage = Tracker::start(
+    "/v1/images/generations",
+    model_name(&params.model.clone().unwrap_or_default()),
+  )
+  .caller(caller)
+  .images(image_size(&params.size), params.n.unwrap_or(1) as u32);
+  let result = app_state
     .openai_client()
     .images()
     .create(params.into())
-    .await
-    .map_err(Into::into)
-    .map(Into::into)
+    .await;
+  usage.finish(&app_state, &result);
+  Ok(Json(result?))
 }
 
-async fn create_image_edit_request(mut request: Multipart) -> Result<CreateImageEditRequest> {
-  let mut req = CreateImageEditRequest::default();
-  while let Ok(Some(field)) = request.next_field().await {
-    if let Some(field_name) = field.name() {
-      match field_name {
-        "image" => {
-          req.image = async_openai::types::ImageInput {
-            source: async_openai::types::InputSource::Bytes {
-              filename: field.file_name().unwrap_or_default().to_string(),
-              bytes: field
-                .bytes()
-                .await
-                .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?,
-            },
-          }
-        }
-        "prompt" => {
-          req.prompt = field
-            .text()
-            .await
-            .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?
-        }
-        "mask" => {
-          req.mask = Some(ImageInput {
-            source: async_openai::types::InputSource::Bytes {
-              filename: field.file_name().unwrap_or_default().to_string(),
-              bytes: field
-                .bytes()
-                .await
-                .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?,
-            },
-          })
-        }
-        "model" => {
-          req.model = Some(
-            match field
-              .text()
-              .await
-              .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?
-              .as_str()
-            {
-              "dall-e-3" => async_openai::types::ImageModel::DallE3,
-              "dall-e-2" => async_openai::types::ImageModel::DallE2,
-              s if !s.is_empty() => async_openai::types::ImageModel::Other(s.to_string()),
-              _ => async_openai::types::ImageModel::DallE2,
-            },
-          )
-        }
-        "n" => {
-          req.n = field
-            .text()
-            .await
-            .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?
-            .parse()
-            .ok()
-        }
-        "size" => {
-          req.size = match field
-            .text()
-            .await
-            .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?
-            .as_str()
-          {
-            "256x256" => Some(async_openai::types::DallE2ImageSize::S256x256),
-            "512x512" => Some(async_openai::types::DallE2ImageSize::S512x512),
-            "1024x1024" => Some(async_openai::types::DallE2ImageSize::S1024x1024),
-            _ => None,
-          }
-        }
-        "response_format" => {
-          req.response_format = match field
-            .text()
-            .await
-            .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?
-            .as_str()
-          {
-            "url" => Some(async_openai::types::ResponseFormat::Url),
-            "b64_json" => Some(async_openai::types::ResponseFormat::B64Json),
-            _ => None,
-          }
-        }
-        "user" => {
-          req.user = Some(
-            field
-              .text()
-              .await
-              .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?,
-          )
-        }
-        _ => {}
-      }
-    }
+/// The most bytes of an image or mask the provider accepts.
+const IMAGE_LIMIT: u64 = 4 * 1024 * 1024;
+
+fn image_input(upload: &Upload) -> ImageInput {
+  ImageInput {
+    source: upload.input_source(),
+  }
+}
+
+/// An image edit request with the uploads, which live as long as the request.
+#[derive(Debug)]
+struct ImageEditForm {
+  uploads: Vec<Upload>,
+  request: CreateImageEditRequest,
+}
+
+impl FromForm for ImageEditForm {
+  const FIELDS: &'static [&'static str] =
+    &["prompt", "model", "n", "size", "response_format", "user"];
+  const FILES: &'static [&'static str] = &["image", "mask"];
+  const FILE_LIMIT: u64 = IMAGE_LIMIT;
+
+  fn from_form(mut form: Form) -> Result<Self> {
+    let image = form.required_file("image")?;
+    let mask = form.file("mask");
+    let request = CreateImageEditRequest {
+      image: image_input(&image),
+      prompt: form.required("prompt")?,
+      mask: mask.as_ref().map(image_input),
+      model: form.parse::<ImageModel>("model")?.map(Into::into),
+      n: check_range("n", form.parse("n")?, 1, 10)?,
+      size: form.parse::<DallE2ImageSize>("size")?.map(Into::into),
+      response_format: form
+        .parse::<ResponseFormat>("response_format")?
+        .map(Into::into),
+      user: form.text("user"),
+    };
+    Ok(Self {
+      uploads: [Some(image), mask].into_iter().flatten().collect(),
+      request,
+    })
   }
-  Ok(req)
 }
 
 #[tracing::instrument(skip(app_state))]
 async fn edit(
   State(app_state): State<AppState>,
-  request: Multipart,
+  caller: Option<Extension<Caller>>,
+  TypedMultipart(ImageEditForm {
+    uploads: _uploads,
+    request,
+  }): TypedMultipart<ImageEditForm>,
 ) -> Result<Json<ImagesResponse>> {
-  app_state
+  let usage = Tracker::start(
+    "/v1/images/edits",
+    model_name(
+      &request
+        .model
+        .clone()
+        .unwrap_or(async_openai::types::ImageModel::DallE2),
+    ),
+  )
+  .caller(caller)
+  .images(image_size(&request.size), request.n.unwrap_or(1) as u32);
+  let result = app_state
     .openai_client()
     .images()
-    .create_edit(create_image_edit_request(request).await?)
-    .await
-    .map_err(Into::into)
-    .map(Into::into)
+    .create_edit(request)
+    .await;
+  usage.finish(&app_state, &result);
+  Ok(Json(result?))
 }
 
-async fn create_variation_request(mut request: Multipart) -> Result<CreateImageVariationRequest> {
-  let mut req = CreateImageVariationRequest::default();
-  while let Ok(Some(field)) = request.next_field().await {
-    if let Some(field_name) = field.name() {
-      match field_name {
-        "image" => {
-          req.image = async_openai::types::ImageInput {
-            source: async_openai::types::InputSource::Bytes {
-              filename: field.file_name().unwrap_or_default().to_string(),
-              bytes: field
-                .bytes()
-                .await
-                .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?,
-            },
-          }
-        }
-        "model" => {
-          req.model = Some(
-            match field
-              .text()
-              .await
-              .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?
-              .as_str()
-            {
-              "dall-e-3" => async_openai::types::ImageModel::DallE3,
-              "dall-e-2" => async_openai::types::ImageModel::DallE2,
-              s if !s.is_empty() => async_openai::types::ImageModel::Other(s.to_string()),
-              _ => async_openai::types::ImageModel::DallE2,
-            },
-          )
-        }
-        "n" => {
-          req.n = field
-            .text()
-            .await
-            .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?
-            .parse()
-            .ok()
-        }
-        "size" => {
-          req.size = match field
-            .text()
-            .await
-            .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?
-            .as_str()
-          {
-            "256x256" => Some(async_openai::types::DallE2ImageSize::S256x256),
-            "512x512" => Some(async_openai::types::DallE2ImageSize::S512x512),
-            "1024x1024" => Some(async_openai::types::DallE2ImageSize::S1024x1024),
-            _ => None,
-          }
-        }
-        "response_format" => {
-          req.response_format = match field
-            .text()
-            .await
-            .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?
-            .as_str()
-          {
-            "url" => Some(async_openai::types::ResponseFormat::Url),
-            "b64_json" => Some(async_openai::types::ResponseFormat::B64Json),
-            _ => None,
-          }
-        }
-        "user" => {
-          req.user = Some(
-            field
-              .text()
-              .await
-              .map_err(|e| crate::Error::InvalidArgument(e.to_string()))?,
-          )
-        }
-        _ => {}
-      }
-    }
+/// An image variation request with the upload, which lives as long as the request.
+#[derive(Debug)]
+struct ImageVariationForm {
+  image: Upload,
+  request: CreateImageVariationRequest,
+}
+
+impl FromForm for ImageVariationForm {
+  const FIELDS: &'static [&'static str] = &["model", "n", "size", "response_format", "user"];
+  const FILES: &'static [&'static str] = &["image"];
+  const FILE_LIMIT: u64 = IMAGE_LIMIT;
+
+  fn from_form(mut form: Form) -> Result<Self> {
+    let image = form.required_file("image")?;
+    let request = CreateImageVariationRequest {
+      image: image_input(&image),
+      model: form.parse::<ImageModel>("model")?.map(Into::into),
+      n: check_range("n", form.parse("n")?, 1, 10)?,
+      size: form.parse::<DallE2ImageSize>("size")?.map(Into::into),
+      response_format: form
+        .parse::<ResponseFormat>("response_format")?
+        .map(Into::into),
+      user: form.text("user"),
+    };
+    Ok(Self { image, request })
   }
-  Ok(req)
 }
 
 #[tracing::instrument(skip(app_state))]
 async fn variations(
   State(app_state): State<AppState>,
-  request: Multipart,
+  caller: Option<Extension<Caller>>,
+  TypedMultipart(ImageVariationForm {
+    image: _image,
+    request,
+  }): TypedMultipart<ImageVariationForm>,
 ) -> Result<Json<ImagesResponse>> {
-  app_state
+  let usage = Tracker::start(
+    "/v1/images/variations",
+    model_name(
+      &request
+        .model
+        .clone()
+        .unwrap_or(async_openai::types::ImageModel::DallE2),
+    ),
+  )
+  .caller(caller)
+  .images(image_size(&request.size), request.n.unwrap_or(1) as u32);
+  let result = app_state
     .openai_client()
     .images()
-    .create_variation(create_variation_request(request).await?)
-    .await
-    .map_err(Into::into)
-    .map(Into::into)
+    .create_variation(request)
+    .await;
+  usage.finish(&app_state, &result);
+  Ok(Json(result?))
 }
@@ -1,24 +1,89 @@
+mod assistants;
 mod audio;
+mod batches;
 mod chat;
 mod embeddings;
+mod errors;
 mod files;
 mod fine_tuning;
 mod images;
 mod models;
 mod moderations;
+mod multipart;
 mod provider;
+mod runs;
+mod sse;
+mod threads;
+mod tools;
 
-use crate::app::state::AppState;
+use axum::Extension;
+use uuid::Uuid;
+
+use crate::{
+  app::state::AppState,
+  server::{
+    api_key::{self, Caller},
+    audit,
+    openapi::ApiRouter,
+    quota,
+  },
+  Error, Result,
+};
 
 pub fn routes(app_state: AppState) -> axum::Router<AppState> {
-  axum::Router::new()
-    .nest("/audio", audio::routes(app_state.clone()))
-    .nest("/chat", chat::routes(app_state.clone()))
-    .nest("/embeddings", embeddings::routes(app_state.clone()))
-    .nest("/fine_tuning", fine_tuning::routes(app_state.clone()))
-    .nest("/files", files::routes(app_state.clone()))
-    .nest("/images", images::routes(app_state.clone()))
-    .nest("/models", models::routes(app_state.clone()))
-    .nest("/moderations", moderations::routes(app_state.clone()))
+  apis()
+    .into_iter()
+    .fold(axum::Router::new(), |router, (prefix, api)| {
+      router.nest(prefix, api.into_router())
+    })
+    .layer(axum::middleware::from_fn_with_state(
+      app_state.clone(),
+      quota::enforce,
+    ))
+    // records quota rejections as well, but needs the caller so it runs after authentication
+    .layer(axum::middleware::from_fn_with_state(
+      app_state.clone(),
+      audit::record,
+    ))
+    // runs before the other middlewares, quotas need the caller it resolves
+    .layer(axum::middleware::from_fn_with_state(
+      app_state.clone(),
+      api_key::authenticate,
+    ))
+    // outside of everything so the rejections of the middlewares are reshaped as well
+    .layer(axum::middleware::from_fn(errors::openai_errors))
     .with_state(app_state)
 }
+
+/// The user a request is made for, the objects of other users are not found.
+fn user_id(caller: Option<Extension<Caller>>) -> Result<Uuid> {
+  match caller {
+    Some(Extension(caller)) => Ok(caller.user_id),
+    None => Err(Error::UserNotAuthenticated),
+  }
+}
+
+/// The nested routers by their prefix, served and documented from this one list.
+pub(crate) fn apis() -> Vec<(&'static str, ApiRouter)> {
+  vec![
+    ("/assistants", assistants::api()),
+    ("/audio", audio::api()),
+    ("/batches", batches::api()),
+    ("/chat", chat::api()),
+    ("/embeddings", embeddings::api()),
+    ("/fine_tuning", fine_tuning::api()),
+    ("/files", files::api()),
+    ("/images", images::api()),
+    ("/models", models::api()),
+    ("/moderations", moderations::api()),
+    ("/threads", threads::api()),
+    ("/tools", tools::api()),
+  ]
+}
+
+/// Restarts the background work that was interrupted by a shutdown.
+pub async fn resume(app_state: &AppState) -> Result<()> {
+  batches::resume(app_state).await?;
+  runs::resume(app_state).await?;
+  fine_tuning::resume(app_state).await
+}
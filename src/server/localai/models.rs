@@ -1,49 +1,77 @@
-use async_openai::types::{DeleteModelResponse, ListModelResponse, Model};
+use async_openai::types::DeleteModelResponse;
 use axum::{
-  extract::{Path, State},
-  routing::get,
+  extract::{Extension, Path, State},
   Json,
 };
 
-use crate::{app::state::AppState, Result};
+use crate::{
+  app::state::AppState,
+  models::{
+    catalog::{CatalogModel, ModelCatalogList},
+    fine_tuning::TrackedJob,
+  },
+  server::{
+    api_key::Caller,
+    catalog,
+    openapi::{self, ApiRouter, Body, Operation, Schema},
+  },
+  Error, Result,
+};
 
-pub fn routes(app_state: AppState) -> axum::Router<AppState> {
-  axum::Router::new()
-    .route("/", get(list_models))
-    .route("/:model_id", get(get_model).delete(delete_model))
-    .with_state(app_state)
+pub(super) fn api() -> ApiRouter {
+  ApiRouter::new()
+    .route(
+      Operation::get("/", "List the models of the catalog").response(Body::Json(Schema::Model(
+        openapi::model::<ModelCatalogList>,
+      ))),
+      list_models,
+    )
+    .route(
+      Operation::get("/:model_id", "Retrieve a model of the catalog")
+        .response(Body::Json(Schema::Model(openapi::model::<CatalogModel>))),
+      get_model,
+    )
+    .route(
+      Operation::delete("/:model_id", "Delete a fine-tuned model")
+        .response(Body::Json(Schema::OpenAI("DeleteModelResponse"))),
+      delete_model,
+    )
 }
 
 #[tracing::instrument(skip(app_state))]
-async fn list_models(State(app_state): State<AppState>) -> Result<Json<ListModelResponse>> {
-  let models = app_state.openai_client().models().list().await?;
-  Ok(Json(models))
+async fn list_models(State(app_state): State<AppState>) -> Json<ModelCatalogList> {
+  Json(ModelCatalogList {
+    object: "list".into(),
+    data: catalog::list(&app_state).await,
+  })
 }
 
 #[tracing::instrument(skip(app_state))]
 async fn get_model(
   State(app_state): State<AppState>,
   Path(model_id): Path<String>,
-) -> Result<Json<Model>> {
-  app_state
-    .openai_client()
-    .models()
-    .retrieve(&model_id)
+) -> Result<Json<CatalogModel>> {
+  catalog::get(&app_state, &model_id)
     .await
-    .map_err(Into::into)
-    .map(Into::into)
+    .map(Json)
+    .ok_or_else(|| Error::NotFound(format!("model {model_id}")))
 }
 
+/// Deletes a model fine-tuned by a job of the caller and drops it from the catalog.
 #[tracing::instrument(skip(app_state))]
 async fn delete_model(
   State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
   Path(model_id): Path<String>,
 ) -> Result<Json<DeleteModelResponse>> {
-  app_state
-    .openai_client()
-    .models()
-    .delete(&model_id)
-    .await
-    .map_err(Into::into)
-    .map(Into::into)
+  let user_id = super::user_id(caller)?;
+  if !TrackedJob::owns_model(&model_id, user_id, &app_state.pool).await? {
+    return Err(Error::NotFound(format!("model {model_id}")));
+  }
+  let deleted = app_state.openai_client().models().delete(&model_id).await?;
+  if deleted.deleted {
+    TrackedJob::delete_model(&model_id, &app_state.pool).await?;
+    app_state.catalog().unregister(&model_id);
+  }
+  Ok(Json(deleted))
 }
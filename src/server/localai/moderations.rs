@@ -1,12 +1,20 @@
 use async_openai::types::CreateModerationResponse;
-use axum::{extract::State, routing::post, Json};
+use axum::{extract::State, Json};
 
-use crate::{app::state::AppState, models::moderation::CreateModerationRequest, Result};
+use crate::{
+  app::state::AppState,
+  models::moderation::CreateModerationRequest,
+  server::openapi::{ApiRouter, Body, Operation, Schema},
+  Result,
+};
 
-pub fn routes(app_state: AppState) -> axum::Router<AppState> {
-  axum::Router::new()
-    .route("/", post(create))
-    .with_state(app_state)
+pub(super) fn api() -> ApiRouter {
+  ApiRouter::new().route(
+    Operation::post("/", "Classify text against the content policy")
+      .request(Body::Json(Schema::OpenAI("CreateModerationRequest")))
+      .response(Body::Json(Schema::OpenAI("CreateModerationResponse"))),
+    create,
+  )
 }
 
 #[tracing::instrument(skip(app_state))]
@@ -0,0 +1,268 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use async_openai::types::InputSource;
+use async_trait::async_trait;
+use axum::extract::{multipart::Field, FromRequest, Multipart, Request};
+use bytes::{Bytes, BytesMut};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::{
+  server::{api_key::ModelScope, workspace},
+  Error, Result,
+};
+
+/// Files up to this size stay in memory, larger ones are written to a temporary file.
+const MEMORY_LIMIT: usize = 1024 * 1024;
+
+/// The most bytes of all text fields of a form together.
+const TEXT_LIMIT: usize = 64 * 1024;
+
+/// The start of a file that is kept in memory to tell its format.
+const HEAD_LEN: usize = 64;
+
+fn invalid(param: &str, message: impl ToString) -> Error {
+  Error::InvalidParam {
+    param: param.to_string(),
+    message: message.to_string(),
+  }
+}
+
+/// A temporary directory that is removed with everything in it when dropped.
+#[derive(Debug)]
+struct TempDir(PathBuf);
+
+impl TempDir {
+  async fn create() -> Result<Self> {
+    let path = std::env::temp_dir().join(format!("miko-upload-{}", Uuid::new_v4().simple()));
+    tokio::fs::create_dir_all(&path).await?;
+    Ok(Self(path))
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    if let Err(e) = std::fs::remove_dir_all(&self.0) {
+      tracing::warn!("failed to remove upload {}: {e}", self.0.display());
+    }
+  }
+}
+
+#[derive(Debug)]
+enum Contents {
+  Memory(Bytes),
+  /// The file keeps its name in its own directory, the provider reads the format from it.
+  Disk {
+    path: PathBuf,
+    _dir: TempDir,
+  },
+}
+
+/// A file field of a form. Large files live in a temporary file until the upload is dropped.
+#[derive(Debug)]
+pub(crate) struct Upload {
+  pub filename: String,
+  pub len: u64,
+  /// The first bytes of the file.
+  pub head: Bytes,
+  contents: Contents,
+}
+
+impl Upload {
+  /// The file for `async-openai`, which streams temporary files from disk.
+  pub fn input_source(&self) -> InputSource {
+    match &self.contents {
+      Contents::Memory(bytes) => InputSource::Bytes {
+        filename: self.filename.clone(),
+        bytes: bytes.clone(),
+      },
+      Contents::Disk { path, .. } => InputSource::Path { path: path.clone() },
+    }
+  }
+
+  async fn read(name: &str, mut field: Field<'_>, limit: u64) -> Result<Self> {
+    let Some(filename) = field.file_name().map(str::to_string) else {
+      return Err(invalid(name, "must be a file"));
+    };
+    let mut len = 0;
+    let mut head = BytesMut::new();
+    let mut memory = BytesMut::new();
+    let mut disk = None;
+    while let Some(chunk) = field.chunk().await.map_err(|e| invalid(name, e))? {
+      len += chunk.len() as u64;
+      if len > limit {
+        return Err(Error::PayloadTooLarge {
+          param: name.to_string(),
+          limit,
+        });
+      }
+      let missing = HEAD_LEN.saturating_sub(head.len()).min(chunk.len());
+      head.extend_from_slice(&chunk[..missing]);
+
+      match &mut disk {
+        Some((file, _, _)) => file.write_all(&chunk).await?,
+        None if memory.len() + chunk.len() > MEMORY_LIMIT => {
+          let dir = TempDir::create().await?;
+          let path = dir.0.join(temp_name(&filename));
+          let mut file = tokio::fs::File::create(&path).await?;
+          file.write_all(&memory).await?;
+          file.write_all(&chunk).await?;
+          memory.clear();
+          disk = Some((file, path, dir));
+        }
+        None => memory.extend_from_slice(&chunk),
+      }
+    }
+
+    let contents = match disk {
+      Some((mut file, path, dir)) => {
+        file.flush().await?;
+        Contents::Disk { path, _dir: dir }
+      }
+      None => Contents::Memory(memory.freeze()),
+    };
+    Ok(Self {
+      filename,
+      len,
+      head: head.freeze(),
+      contents,
+    })
+  }
+}
+
+/// The name of a temporary file, the uploaded name when it is a plain file name.
+fn temp_name(filename: &str) -> String {
+  if workspace::path_is_valid(filename) {
+    filename.to_string()
+  } else {
+    "upload".to_string()
+  }
+}
+
+/// The fields of a multipart form. The accessors take the fields out, so each is read once.
+#[derive(Debug, Default)]
+pub(crate) struct Form {
+  texts: HashMap<String, String>,
+  files: HashMap<String, Upload>,
+}
+
+impl Form {
+  /// Reads the fields `T` accepts, any other field is an error.
+  async fn read<T: FromForm>(mut multipart: Multipart) -> Result<Self> {
+    let mut form = Self::default();
+    let mut text_len = 0;
+    while let Some(mut field) = multipart
+      .next_field()
+      .await
+      .map_err(|e| invalid("body", e))?
+    {
+      let name = field.name().unwrap_or_default().to_string();
+      if form.texts.contains_key(&name) || form.files.contains_key(&name) {
+        return Err(invalid(&name, "is given more than once"));
+      }
+      if T::FILES.contains(&name.as_str()) {
+        let upload = Upload::read(&name, field, T::FILE_LIMIT).await?;
+        form.files.insert(name, upload);
+      } else if T::FIELDS.contains(&name.as_str()) {
+        let mut text = Vec::new();
+        while let Some(chunk) = field.chunk().await.map_err(|e| invalid(&name, e))? {
+          text_len += chunk.len();
+          if text_len > TEXT_LIMIT {
+            return Err(Error::PayloadTooLarge {
+              param: name,
+              limit: TEXT_LIMIT as u64,
+            });
+          }
+          text.extend_from_slice(&chunk);
+        }
+        let text = String::from_utf8(text).map_err(|_| invalid(&name, "is not UTF-8"))?;
+        form.texts.insert(name, text);
+      } else {
+        return Err(invalid(&name, "is not a field of this request"));
+      }
+    }
+    Ok(form)
+  }
+
+  /// A text field, empty fields count as missing.
+  pub fn text(&mut self, name: &str) -> Option<String> {
+    self.texts.remove(name).filter(|text| !text.is_empty())
+  }
+
+  pub fn required(&mut self, name: &str) -> Result<String> {
+    self.text(name).ok_or_else(|| invalid(name, "is required"))
+  }
+
+  /// A text field read like the same field of a JSON request, as a number or other JSON value
+  /// where it is one and as a string otherwise.
+  pub fn parse<T: DeserializeOwned>(&mut self, name: &str) -> Result<Option<T>> {
+    let Some(text) = self.text(name) else {
+      return Ok(None);
+    };
+    serde_json::from_str(&text)
+      .or_else(|_| serde_json::from_value(Value::String(text)))
+      .map(Some)
+      .map_err(|e| invalid(name, e))
+  }
+
+  pub fn file(&mut self, name: &str) -> Option<Upload> {
+    self.files.remove(name)
+  }
+
+  pub fn required_file(&mut self, name: &str) -> Result<Upload> {
+    self.file(name).ok_or_else(|| invalid(name, "is required"))
+  }
+}
+
+/// A request read from a multipart form.
+pub(crate) trait FromForm: Sized + Send {
+  /// The text fields of the form.
+  const FIELDS: &'static [&'static str];
+  /// The file fields of the form.
+  const FILES: &'static [&'static str];
+  /// The most bytes of a single file.
+  const FILE_LIMIT: u64;
+
+  fn from_form(form: Form) -> Result<Self>;
+}
+
+/// Extracts a [FromForm] request, rejecting unknown, malformed and oversized fields with an
+/// error naming the field, and models the API key of the request is not limited to. Routes
+/// using it need to lift the default body limit of axum.
+#[derive(Debug)]
+pub(crate) struct TypedMultipart<T>(pub T);
+
+#[async_trait]
+impl<T: FromForm, S: Send + Sync> FromRequest<S> for TypedMultipart<T> {
+  type Rejection = Error;
+
+  async fn from_request(request: Request, state: &S) -> Result<Self> {
+    let scope = request.extensions().get::<ModelScope>().cloned();
+    let multipart = Multipart::from_request(request, state)
+      .await
+      .map_err(|e| invalid("body", e.body_text()))?;
+    let form = Form::read::<T>(multipart).await?;
+    if let (Some(scope), Some(model)) = (scope, form.texts.get("model")) {
+      scope.check(model)?;
+    }
+    T::from_form(form).map(Self)
+  }
+}
+
+/// Fails unless `value` is between `min` and `max`, for numbers with limits the types do not express.
+pub(crate) fn check_range<T: PartialOrd + std::fmt::Display>(
+  name: &str,
+  value: Option<T>,
+  min: T,
+  max: T,
+) -> Result<Option<T>> {
+  match value {
+    Some(value) if value < min || value > max => Err(invalid(
+      name,
+      format!("{value} is not between {min} and {max}"),
+    )),
+    value => Ok(value),
+  }
+}
@@ -1 +1,3 @@
+pub(crate) mod tools;
+
 pub trait LocalAiProvider {}
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+
+use async_openai::types::{
+  ChatCompletionMessageToolCall, ChatCompletionMessageToolCallChunk, ChatCompletionNamedToolChoice,
+  ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageArgs,
+  ChatCompletionRequestUserMessageArgs, ChatCompletionResponseStream, ChatCompletionTool,
+  ChatCompletionToolChoiceOption, ChatCompletionToolType, CreateChatCompletionRequest,
+  CreateChatCompletionResponse, FinishReason, FunctionCall, FunctionCallStream,
+};
+use futures::{stream, StreamExt};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::Result;
+
+const TOOL_PROMPT: &str = "You can call the tools listed below. To call tools, reply with \
+nothing but a JSON block of the form
+
+```json
+{\"tool_calls\": [{\"name\": \"<tool name>\", \"arguments\": {<arguments>}}]}
+```
+
+and wait for their results. When no tool is needed, answer normally.
+
+Tools:";
+
+/// Function calling for models that only produce text. The tool schemas are described in the
+/// system prompt and the JSON the model answers with is turned back into `tool_calls`.
+#[derive(Debug, Clone)]
+pub(crate) struct ToolEmulation {
+  names: Vec<String>,
+}
+
+fn describe(tool: &ChatCompletionTool) -> String {
+  let function = &tool.function;
+  let parameters = function
+    .parameters
+    .clone()
+    .unwrap_or_else(|| json!({"type": "object", "properties": {}}));
+  format!(
+    "- {}: {}\n  parameters: {}",
+    function.name,
+    function.description.as_deref().unwrap_or_default(),
+    parameters
+  )
+}
+
+/// The tool calls of an earlier assistant message, written the way the model is asked to.
+fn call_block(tool_calls: &[ChatCompletionMessageToolCall]) -> String {
+  let calls: Vec<Value> = tool_calls
+    .iter()
+    .map(|call| {
+      let arguments = serde_json::from_str(&call.function.arguments)
+        .unwrap_or_else(|_| Value::String(call.function.arguments.clone()));
+      json!({"name": call.function.name, "arguments": arguments})
+    })
+    .collect();
+  format!("```json\n{}\n```", json!({ "tool_calls": calls }))
+}
+
+/// The JSON in a fenced block of `text`, or the whole text when it is JSON.
+fn json_candidate(text: &str) -> Option<&str> {
+  if let Some(start) = text.find("```") {
+    let block = &text[start + 3..];
+    let block = block.strip_prefix("json").unwrap_or(block);
+    let end = block.find("```")?;
+    return Some(block[..end].trim());
+  }
+  let text = text.trim();
+  (text.starts_with('{') || text.starts_with('[')).then_some(text)
+}
+
+impl ToolEmulation {
+  /// Rewrites a request with tools for a model without native support. The tools move into the
+  /// system prompt and earlier tool calls and results become plain messages. `None` when the
+  /// request has no tools or forbids calling them.
+  pub fn prepare(request: &mut CreateChatCompletionRequest) -> Result<Option<Self>> {
+    let tools = request.tools.take().unwrap_or_default();
+    let tool_choice = request.tool_choice.take();
+
+    let mut names = HashMap::new();
+    let mut messages = Vec::with_capacity(request.messages.len() + 1);
+    for message in request.messages.drain(..) {
+      match message {
+        ChatCompletionRequestMessage::Assistant(mut message) => {
+          if let Some(tool_calls) = message.tool_calls.take() {
+            for call in &tool_calls {
+              names.insert(call.id.clone(), call.function.name.clone());
+            }
+            let content = message.content.take().unwrap_or_default();
+            message.content = Some(format!("{content}\n{}", call_block(&tool_calls)));
+          }
+          messages.push(ChatCompletionRequestMessage::Assistant(message));
+        }
+        ChatCompletionRequestMessage::Tool(message) => {
+          let name = names
+            .get(&message.tool_call_id)
+            .map(String::as_str)
+            .unwrap_or("the tool");
+          let content = format!("Result of {name}:\n{}", message.content);
+          let message = ChatCompletionRequestUserMessageArgs::default()
+            .content(content)
+            .build()?;
+          messages.push(ChatCompletionRequestMessage::User(message));
+        }
+        message => messages.push(message),
+      }
+    }
+    request.messages = messages;
+
+    if tools.is_empty() || tool_choice == Some(ChatCompletionToolChoiceOption::None) {
+      return Ok(None);
+    }
+
+    let mut prompt = TOOL_PROMPT.to_string();
+    for tool in &tools {
+      prompt.push('\n');
+      prompt.push_str(&describe(tool));
+    }
+    if let Some(ChatCompletionToolChoiceOption::Named(ChatCompletionNamedToolChoice {
+      function,
+      ..
+    })) = &tool_choice
+    {
+      prompt.push_str(&format!(
+        "\n\nYou must call the tool {} now.",
+        function.name
+      ));
+    }
+    match request.messages.first_mut() {
+      Some(ChatCompletionRequestMessage::System(system)) => {
+        system.content = format!("{}\n\n{prompt}", system.content);
+      }
+      _ => {
+        let system = ChatCompletionRequestSystemMessageArgs::default()
+          .content(prompt)
+          .build()?;
+        request
+          .messages
+          .insert(0, ChatCompletionRequestMessage::System(system));
+      }
+    }
+
+    Ok(Some(Self {
+      names: tools.into_iter().map(|tool| tool.function.name).collect(),
+    }))
+  }
+
+  /// The tool calls in the text of the model, `None` when it did not call a known tool.
+  fn parse(&self, text: &str) -> Option<Vec<ChatCompletionMessageToolCall>> {
+    let value: Value = serde_json::from_str(json_candidate(text)?).ok()?;
+    let calls = match value {
+      Value::Object(mut object) => match object.remove("tool_calls") {
+        Some(Value::Array(calls)) => calls,
+        Some(_) => return None,
+        None => vec![Value::Object(object)],
+      },
+      Value::Array(calls) => calls,
+      _ => return None,
+    };
+
+    let mut tool_calls = vec![];
+    for call in calls {
+      let name = call["name"].as_str()?;
+      if !self.names.iter().any(|n| n == name) {
+        return None;
+      }
+      let arguments = match &call["arguments"] {
+        Value::String(arguments) => arguments.clone(),
+        Value::Null => "{}".to_string(),
+        arguments => arguments.to_string(),
+      };
+      tool_calls.push(ChatCompletionMessageToolCall {
+        id: format!("call_{}", Uuid::new_v4().simple()),
+        r#type: ChatCompletionToolType::Function,
+        function: FunctionCall {
+          name: name.to_string(),
+          arguments,
+        },
+      });
+    }
+    (!tool_calls.is_empty()).then_some(tool_calls)
+  }
+
+  pub fn restore_response(&self, response: &mut CreateChatCompletionResponse) {
+    for choice in &mut response.choices {
+      let Some(tool_calls) = choice
+        .message
+        .content
+        .as_deref()
+        .and_then(|c| self.parse(c))
+      else {
+        continue;
+      };
+      choice.message.content = None;
+      choice.message.tool_calls = Some(tool_calls);
+      choice.finish_reason = Some(FinishReason::ToolCalls);
+    }
+  }
+
+  /// Whether text that starts like this may still turn out to be a tool call.
+  fn may_be_call(text: &str) -> bool {
+    let text = text.trim_start();
+    text.is_empty() || text.starts_with('{') || text.starts_with('[') || text.starts_with('`')
+  }
+
+  /// The tool calls of a streamed choice, all in a single delta.
+  fn call_chunks(
+    tool_calls: Vec<ChatCompletionMessageToolCall>,
+  ) -> Vec<ChatCompletionMessageToolCallChunk> {
+    tool_calls
+      .into_iter()
+      .enumerate()
+      .map(|(index, call)| ChatCompletionMessageToolCallChunk {
+        index: index as i32,
+        id: Some(call.id),
+        r#type: Some(call.r#type),
+        function: Some(FunctionCallStream {
+          name: Some(call.function.name),
+          arguments: Some(call.function.arguments),
+        }),
+      })
+      .collect()
+  }
+
+  /// Restores tool calls in a streamed response. Content that may be a tool call is held back
+  /// until the choice finishes, anything else is passed through as it arrives.
+  pub fn restore_stream(
+    self,
+    upstream: ChatCompletionResponseStream,
+  ) -> ChatCompletionResponseStream {
+    // per choice, the held back content while it may still be a tool call
+    let state = (
+      upstream,
+      self,
+      HashMap::<u32, Option<String>>::new(),
+      None,
+      false,
+    );
+    Box::pin(stream::unfold(
+      state,
+      |(mut upstream, emulation, mut held, mut last, done)| async move {
+        if done {
+          return None;
+        }
+        match upstream.next().await {
+          Some(Ok(mut chunk)) => {
+            for choice in &mut chunk.choices {
+              let buffer = held
+                .entry(choice.index)
+                .or_insert_with(|| Some(String::new()));
+              // once flushed, content passes through untouched
+              if let Some(text) = buffer.as_mut() {
+                if let Some(content) = choice.delta.content.take() {
+                  text.push_str(&content);
+                  if !Self::may_be_call(text) {
+                    choice.delta.content = buffer.take();
+                  }
+                }
+              }
+              if choice.finish_reason.is_some() {
+                if let Some(text) = buffer.take() {
+                  match emulation.parse(&text) {
+                    Some(tool_calls) => {
+                      choice.delta.tool_calls = Some(Self::call_chunks(tool_calls));
+                      choice.finish_reason = Some(FinishReason::ToolCalls);
+                    }
+                    None if !text.is_empty() => choice.delta.content = Some(text),
+                    None => {}
+                  }
+                }
+              }
+            }
+            last = Some(chunk.clone());
+            Some((Ok(chunk), (upstream, emulation, held, last, false)))
+          }
+          Some(Err(e)) => Some((Err(e), (upstream, emulation, held, last, false))),
+          None => {
+            // choices that never finished get what was held back in one last chunk
+            let mut chunk = last.take()?;
+            let template = chunk.choices.first()?.clone();
+            chunk.choices = held
+              .drain()
+              .filter_map(|(index, text)| Some((index, text.filter(|t| !t.is_empty())?)))
+              .map(|(index, text)| {
+                let mut choice = template.clone();
+                choice.index = index;
+                choice.delta.role = None;
+                choice.delta.content = None;
+                match emulation.parse(&text) {
+                  Some(tool_calls) => {
+                    choice.delta.tool_calls = Some(Self::call_chunks(tool_calls));
+                    choice.finish_reason = Some(FinishReason::ToolCalls);
+                  }
+                  None => {
+                    choice.delta.tool_calls = None;
+                    choice.delta.content = Some(text);
+                  }
+                }
+                choice
+              })
+              .collect();
+            if chunk.choices.is_empty() {
+              return None;
+            }
+            Some((Ok(chunk), (upstream, emulation, held, None, true)))
+          }
+        }
+      },
+    ))
+  }
+}
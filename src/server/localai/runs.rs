@@ -0,0 +1,440 @@
+use async_openai::{
+  error::OpenAIError,
+  types::{ChatCompletionTool, ChatCompletionToolType, CreateChatCompletionRequest},
+};
+use uuid::Uuid;
+
+use super::{provider::tools::ToolEmulation, tools};
+use crate::{
+  app::state::AppState,
+  models::{
+    assistants::{AssistantTools, LastError, RequiredAction, RunStatus},
+    Chat, ChatCompletionMessageToolCall, ChatCompletionRequestSystemMessage, ChatMessage, Role,
+  },
+  pgdb::{NewThreadMessage, RequestKind, Run, ThreadMessage},
+  server::{
+    audit::AgentCall,
+    moderation::{self, Subject},
+    pii::Redactor,
+    pricing, quota,
+    usage::Tracker,
+  },
+  Error, Result,
+};
+
+/// The most rounds of the app's tools a run takes before it fails, so a model that keeps calling
+/// them does not spend forever.
+const MAX_TOOL_STEPS: i32 = 10;
+
+/// Picks up the runs that were interrupted when the server stopped, from their last step.
+pub(crate) async fn resume(app_state: &AppState) -> Result<()> {
+  for run in Run::requeue_unfinished(&app_state.pool).await? {
+    tracing::info!("resuming run {}", run.id);
+    spawn(app_state.clone(), run);
+  }
+  Ok(())
+}
+
+/// Executes a queued run in the background.
+pub(crate) fn spawn(app_state: AppState, run: Run) {
+  tokio::spawn(async move {
+    let run_id = run.id;
+    if let Err(e) = execute(&app_state, run).await {
+      tracing::error!("run {run_id} failed: {e}");
+      let last_error = LastError {
+        code: "server_error".to_string(),
+        message: e.to_string(),
+      };
+      _ = Run::transition(
+        run_id,
+        &[RunStatus::Queued, RunStatus::InProgress],
+        RunStatus::Failed,
+        None,
+        Some(last_error),
+        &app_state.pool,
+      )
+      .await;
+    }
+  });
+}
+
+/// Runs a single step: sends the thread to the model and either completes the run with the
+/// assistant's answer or parks it in `requires_action` until the client submits tool outputs.
+#[tracing::instrument(skip(app_state, run), fields(run_id = %run.id))]
+async fn execute(app_state: &AppState, run: Run) -> Result<()> {
+  let pool = &app_state.pool;
+  let Some(run) = Run::transition(
+    run.id,
+    &[RunStatus::Queued],
+    RunStatus::InProgress,
+    None,
+    None,
+    pool,
+  )
+  .await?
+  else {
+    return Ok(());
+  };
+
+  let mut messages = vec![];
+  if !run.instructions.is_empty() {
+    messages.push(ChatMessage::System(ChatCompletionRequestSystemMessage {
+      content: run.instructions.clone(),
+      role: Role::System,
+      name: None,
+    }));
+  }
+  messages.extend(ThreadMessage::history(run.thread_id, pool).await?);
+
+  let chat_id = workspace_chat(app_state, &run).await?;
+  let mut tools = function_tools(&run.tools.0);
+  // the app's tools run here, unless the assistant brings a function of the same name
+  let mut app_tools = vec![];
+  if chat_id.is_some() {
+    for tool in tools::definitions() {
+      if !tools.iter().any(|t| t.function.name == tool.function.name) {
+        app_tools.push(tool.function.name.clone());
+        tools.push(tool);
+      }
+    }
+  }
+  let mut request = CreateChatCompletionRequest {
+    model: run.model.clone(),
+    messages: messages.into_iter().map(Into::into).collect(),
+    tools: (!tools.is_empty()).then_some(tools),
+    ..Default::default()
+  };
+  // the thread is proxied user content, gated like chat completions
+  let redactor = match Redactor::for_chat(app_state, Some(run.user_id), chat_id).await? {
+    Some(mut redactor) => {
+      redactor.redact_messages(&mut request.messages);
+      redactor.record(app_state, "/v1/chat/completions");
+      Some(redactor)
+    }
+    None => None,
+  };
+  let subject = Subject {
+    source: moderation::SOURCE_CHAT,
+    user_id: Some(run.user_id),
+    chat_id,
+  };
+  let admitted = match moderation::check(
+    app_state,
+    subject,
+    moderation::user_content(&request.messages),
+  )
+  .await
+  {
+    // runs of a chat spend its budget like the chat itself
+    Ok(_) => match chat_id {
+      Some(chat_id) => pricing::check_budget(app_state, chat_id).await,
+      None => Ok(None),
+    },
+    Err(e) => Err(e),
+  };
+  let budget = match admitted {
+    Ok(budget) => budget,
+    Err(e @ (Error::ContentBlocked(_) | Error::BudgetExceeded(_))) => {
+      let code = match e {
+        Error::ContentBlocked(_) => "invalid_prompt",
+        _ => "rate_limit_exceeded",
+      };
+      let last_error = LastError {
+        code: code.to_string(),
+        message: e.to_string(),
+      };
+      Run::transition(
+        run.id,
+        &[RunStatus::InProgress],
+        RunStatus::Failed,
+        None,
+        Some(last_error),
+        pool,
+      )
+      .await?;
+      return Ok(());
+    }
+    Err(e) => return Err(e),
+  };
+
+  let emulation = match app_state.catalog().native_tools(&run.model) {
+    true => None,
+    false => ToolEmulation::prepare(&mut request)?,
+  };
+
+  let usage = Tracker::start("/v1/chat/completions", &run.model)
+    .user(Some(run.user_id))
+    .chat(chat_id)
+    .budget(budget);
+  let audit = AgentCall::start("/v1/chat/completions", &request).user(Some(run.user_id));
+  let result = app_state.openai_client().chat().create(request).await;
+  usage.finish(app_state, &result);
+  audit.finish(app_state, &result);
+  let response = match result {
+    Ok(mut response) => {
+      if let Some(emulation) = &emulation {
+        emulation.restore_response(&mut response);
+      }
+      if let Some(redactor) = &redactor {
+        redactor.restore_response(&mut response);
+      }
+      response
+    }
+    Err(e) => {
+      Run::transition(
+        run.id,
+        &[RunStatus::InProgress],
+        RunStatus::Failed,
+        None,
+        Some(last_error(&e)),
+        pool,
+      )
+      .await?;
+      return Ok(());
+    }
+  };
+
+  let Some(choice) = response.choices.into_iter().next() else {
+    Run::transition(
+      run.id,
+      &[RunStatus::InProgress],
+      RunStatus::Failed,
+      None,
+      Some(LastError {
+        code: "server_error".to_string(),
+        message: "the model returned no choices".to_string(),
+      }),
+      pool,
+    )
+    .await?;
+    return Ok(());
+  };
+
+  let tool_calls: Vec<ChatCompletionMessageToolCall> = choice
+    .message
+    .tool_calls
+    .unwrap_or_default()
+    .into_iter()
+    .map(Into::into)
+    .collect();
+
+  if let Some(chat_id) = chat_id.filter(|_| {
+    !tool_calls.is_empty()
+      && tool_calls
+        .iter()
+        .all(|call| app_tools.contains(&call.function.name))
+  }) {
+    return call_tools(app_state, run, chat_id, choice.message.content, tool_calls).await;
+  }
+
+  let mut tx = pool.begin().await?;
+  let (status, required_action, message) = if tool_calls.is_empty() {
+    let message = NewThreadMessage {
+      role: "assistant".to_string(),
+      content: choice.message.content,
+      assistant_id: run.assistant_id,
+      run_id: Some(run.id),
+      ..Default::default()
+    };
+    (RunStatus::Completed, None, message)
+  } else {
+    let message = NewThreadMessage {
+      role: "assistant".to_string(),
+      content: choice.message.content,
+      tool_calls: Some(tool_calls.clone()),
+      temporary: true,
+      assistant_id: run.assistant_id,
+      run_id: Some(run.id),
+      ..Default::default()
+    };
+    (
+      RunStatus::RequiresAction,
+      Some(RequiredAction::from(tool_calls)),
+      message,
+    )
+  };
+
+  let updated = Run::transition(
+    run.id,
+    &[RunStatus::InProgress],
+    status,
+    required_action,
+    None,
+    &mut *tx,
+  )
+  .await?;
+
+  if updated.is_none() {
+    // cancelled while the model was thinking, drop the answer
+    tx.rollback().await?;
+    Run::transition(
+      run.id,
+      &[RunStatus::Cancelling],
+      RunStatus::Cancelled,
+      None,
+      None,
+      pool,
+    )
+    .await?;
+    return Ok(());
+  }
+
+  ThreadMessage::create(run.thread_id, message, &mut *tx).await?;
+  tx.commit().await?;
+  Ok(())
+}
+
+/// Executes the calls of the app's tools and queues the run again with their outputs, as if the
+/// client had submitted them.
+async fn call_tools(
+  app_state: &AppState,
+  run: Run,
+  chat_id: Uuid,
+  content: Option<String>,
+  tool_calls: Vec<ChatCompletionMessageToolCall>,
+) -> Result<()> {
+  // every round sends the thread to the model again, which counts like any other request
+  let steps = Run::add_tool_step(run.id, &app_state.pool).await?;
+  let refused = if steps > MAX_TOOL_STEPS {
+    Some(LastError {
+      code: "server_error".to_string(),
+      message: format!("the run called tools more than {MAX_TOOL_STEPS} times"),
+    })
+  } else {
+    match quota::check(app_state, Some(run.user_id), RequestKind::Completion).await {
+      Ok(()) => None,
+      Err(Error::QuotaExceeded(exceeded)) => Some(LastError {
+        code: "rate_limit_exceeded".to_string(),
+        message: exceeded.to_string(),
+      }),
+      Err(e) => return Err(e),
+    }
+  };
+  if let Some(last_error) = refused {
+    Run::transition(
+      run.id,
+      &[RunStatus::InProgress],
+      RunStatus::Failed,
+      None,
+      Some(last_error),
+      &app_state.pool,
+    )
+    .await?;
+    return Ok(());
+  }
+
+  let mut outputs = Vec::with_capacity(tool_calls.len());
+  for call in &tool_calls {
+    let output = tools::dispatch(
+      app_state,
+      run.user_id,
+      chat_id,
+      &call.function.name,
+      &call.function.arguments,
+    )
+    .await;
+    outputs.push((call.id.clone(), output));
+  }
+
+  let mut tx = app_state.pool.begin().await?;
+  let Some(run) = Run::transition(
+    run.id,
+    &[RunStatus::InProgress],
+    RunStatus::Queued,
+    None,
+    None,
+    &mut *tx,
+  )
+  .await?
+  else {
+    // cancelled while the tools ran, the images stay in the workspace
+    tx.rollback().await?;
+    Run::transition(
+      run.id,
+      &[RunStatus::Cancelling],
+      RunStatus::Cancelled,
+      None,
+      None,
+      &app_state.pool,
+    )
+    .await?;
+    return Ok(());
+  };
+
+  let message = NewThreadMessage {
+    role: "assistant".to_string(),
+    content,
+    tool_calls: Some(tool_calls),
+    temporary: true,
+    assistant_id: run.assistant_id,
+    run_id: Some(run.id),
+    ..Default::default()
+  };
+  ThreadMessage::create(run.thread_id, message, &mut *tx).await?;
+  for (tool_call_id, output) in outputs {
+    let message = NewThreadMessage {
+      role: "tool".to_string(),
+      content: Some(output),
+      tool_call_id: Some(tool_call_id),
+      temporary: true,
+      run_id: Some(run.id),
+      ..Default::default()
+    };
+    ThreadMessage::create(run.thread_id, message, &mut *tx).await?;
+  }
+  tx.commit().await?;
+
+  spawn(app_state.clone(), run);
+  Ok(())
+}
+
+/// The chat named by the run's `chat_id` metadata, whose workspace the app's tools act on and
+/// whose budget the run spends. Chats of other users are ignored.
+async fn workspace_chat(app_state: &AppState, run: &Run) -> Result<Option<Uuid>> {
+  let chat_id = run
+    .metadata
+    .as_ref()
+    .and_then(|metadata| metadata.get(tools::CHAT_ID_METADATA))
+    .and_then(|chat_id| chat_id.as_str())
+    .and_then(|chat_id| chat_id.parse().ok());
+  match chat_id {
+    Some(chat_id) if Chat::is_owned_by(chat_id, run.user_id, &app_state.pool).await? => {
+      Ok(Some(chat_id))
+    }
+    _ => Ok(None),
+  }
+}
+
+/// Converts the assistant's tools into chat completion tools, only functions run locally.
+fn function_tools(tools: &[AssistantTools]) -> Vec<ChatCompletionTool> {
+  tools
+    .iter()
+    .filter_map(|tool| match tool {
+      AssistantTools::Function { function } => Some(ChatCompletionTool {
+        r#type: ChatCompletionToolType::Function,
+        function: function.clone().into(),
+      }),
+      _ => None,
+    })
+    .collect()
+}
+
+fn last_error(err: &OpenAIError) -> LastError {
+  let code = match err {
+    OpenAIError::ApiError(api_error)
+      if api_error.r#type.as_deref() == Some("rate_limit_exceeded")
+        || api_error
+          .code
+          .as_ref()
+          .and_then(|c| c.as_str())
+          .is_some_and(|c| c == "rate_limit_exceeded") =>
+    {
+      "rate_limit_exceeded"
+    }
+    _ => "server_error",
+  };
+  LastError {
+    code: code.to_string(),
+    message: err.to_string(),
+  }
+}
@@ -0,0 +1,110 @@
+use std::{convert::Infallible, time::Duration};
+
+use async_openai::error::OpenAIError;
+use axum::response::{
+  sse::{Event, KeepAlive},
+  Sse,
+};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use serde_json::json;
+use tokio_stream::wrappers::ReceiverStream;
+
+/// The number of events we buffer for a slow client before we stop pulling from upstream.
+const STREAM_BUFFER_SIZE: usize = 32;
+
+/// The sentinel OpenAI sends as the last `data:` line of a streamed response.
+const DONE_MARKER: &str = "[DONE]";
+
+/// Forwards an upstream OpenAI stream to the client as server-sent events.
+///
+/// Chunks are relayed through a bounded channel so a slow client applies backpressure to the
+/// upstream request. An upstream error is sent as an OpenAI-style `{"error": {...}}` event and
+/// ends the stream. Every stream is terminated with `data: [DONE]`. When the client disconnects
+/// the upstream stream is dropped, which aborts the request to the provider.
+pub(crate) fn stream_response<S, T>(
+  upstream: S,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>>
+where
+  S: Stream<Item = Result<T, OpenAIError>> + Send + 'static,
+  T: Serialize + Send + 'static,
+{
+  let (tx, rx) = tokio::sync::mpsc::channel(STREAM_BUFFER_SIZE);
+
+  tokio::spawn(async move {
+    let mut upstream = Box::pin(upstream);
+    loop {
+      let item = tokio::select! {
+        _ = tx.closed() => {
+          tracing::debug!("client disconnected, aborting upstream stream");
+          return;
+        }
+        item = upstream.next() => item,
+      };
+
+      let (event, is_error) = match item {
+        Some(Ok(chunk)) => match Event::default().json_data(chunk) {
+          Ok(event) => (event, false),
+          Err(e) => (
+            error_event(&e.to_string(), "server_error", None, None),
+            true,
+          ),
+        },
+        Some(Err(e)) => {
+          tracing::warn!("upstream stream failed: {e}");
+          (openai_error_event(&e), true)
+        }
+        None => break,
+      };
+
+      if tx.send(event).await.is_err() {
+        // the client went away while we were waiting for buffer space
+        return;
+      }
+      if is_error {
+        break;
+      }
+    }
+
+    _ = tx.send(Event::default().data(DONE_MARKER)).await;
+  });
+
+  Sse::new(ReceiverStream::new(rx).map(Ok)).keep_alive(
+    // comment lines are ignored by the OpenAI SDK parsers, unlike arbitrary keep-alive text
+    KeepAlive::new().interval(Duration::from_secs(15)),
+  )
+}
+
+fn openai_error_event(err: &OpenAIError) -> Event {
+  match err {
+    OpenAIError::ApiError(api_error) => error_event(
+      &api_error.message,
+      api_error.r#type.as_deref().unwrap_or("api_error"),
+      api_error.param.clone(),
+      api_error.code.clone(),
+    ),
+    OpenAIError::InvalidArgument(message) => {
+      error_event(message, "invalid_request_error", None, None)
+    }
+    e => error_event(&e.to_string(), "server_error", None, None),
+  }
+}
+
+fn error_event(
+  message: &str,
+  r#type: &str,
+  param: Option<serde_json::Value>,
+  code: Option<serde_json::Value>,
+) -> Event {
+  Event::default().data(
+    json!({
+      "error": {
+        "message": message,
+        "type": r#type,
+        "param": param,
+        "code": code,
+      }
+    })
+    .to_string(),
+  )
+}
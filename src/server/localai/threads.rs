@@ -0,0 +1,498 @@
+use axum::{
+  extract::{Extension, Path, Query, State},
+  Json,
+};
+use uuid::Uuid;
+
+use super::runs;
+use crate::{
+  app::state::AppState,
+  models::assistants::{
+    CreateMessageRequest, CreateRunRequest, CreateThreadAndRunRequest, CreateThreadRequest,
+    DeletedObject, ListQuery, ListResponse, MessageObject, MessageRole, ModifyMetadataRequest,
+    RunObject, RunStatus, SubmitToolOutputsRunRequest, ThreadObject,
+  },
+  pgdb::{parse_object_id, Assistant, NewRun, NewThreadMessage, Run, Thread, ThreadMessage},
+  server::{
+    api_key::{Caller, ModelScope},
+    openapi::{ApiRouter, Body, Operation, Schema},
+  },
+  Error, Result,
+};
+
+pub(super) fn api() -> ApiRouter {
+  ApiRouter::new()
+    .route(
+      Operation::post("/", "Create a thread")
+        .request(Body::Json(Schema::OpenAI("CreateThreadRequest")))
+        .response(Body::Json(Schema::OpenAI("ThreadObject"))),
+      create,
+    )
+    .route(
+      Operation::post("/runs", "Create a thread and run it")
+        .request(Body::Json(Schema::OpenAI("CreateThreadAndRunRequest")))
+        .response(Body::Json(Schema::OpenAI("RunObject"))),
+      create_thread_and_run,
+    )
+    .route(
+      Operation::get("/:thread_id", "Retrieve a thread")
+        .response(Body::Json(Schema::OpenAI("ThreadObject"))),
+      get_thread,
+    )
+    .route(
+      Operation::post("/:thread_id", "Modify a thread")
+        .request(Body::Json(Schema::OpenAI("ModifyThreadRequest")))
+        .response(Body::Json(Schema::OpenAI("ThreadObject"))),
+      modify,
+    )
+    .route(
+      Operation::delete("/:thread_id", "Delete a thread")
+        .response(Body::Json(Schema::OpenAI("DeleteThreadResponse"))),
+      delete_thread,
+    )
+    .route(
+      Operation::get("/:thread_id/messages", "List the messages of a thread")
+        .query(&["limit", "order", "after"])
+        .response(Body::Json(Schema::OpenAI("ListMessagesResponse"))),
+      list_messages,
+    )
+    .route(
+      Operation::post("/:thread_id/messages", "Add a message to a thread")
+        .request(Body::Json(Schema::OpenAI("CreateMessageRequest")))
+        .response(Body::Json(Schema::OpenAI("MessageObject"))),
+      create_message,
+    )
+    .route(
+      Operation::get("/:thread_id/messages/:message_id", "Retrieve a message")
+        .response(Body::Json(Schema::OpenAI("MessageObject"))),
+      get_message,
+    )
+    .route(
+      Operation::post("/:thread_id/messages/:message_id", "Modify a message")
+        .request(Body::Json(Schema::OpenAI("ModifyMessageRequest")))
+        .response(Body::Json(Schema::OpenAI("MessageObject"))),
+      modify_message,
+    )
+    .route(
+      Operation::get("/:thread_id/runs", "List the runs of a thread")
+        .query(&["limit", "order", "after"])
+        .response(Body::Json(Schema::OpenAI("ListRunsResponse"))),
+      list_runs,
+    )
+    .route(
+      Operation::post("/:thread_id/runs", "Run a thread")
+        .request(Body::Json(Schema::OpenAI("CreateRunRequest")))
+        .response(Body::Json(Schema::OpenAI("RunObject"))),
+      create_run,
+    )
+    .route(
+      Operation::get("/:thread_id/runs/:run_id", "Retrieve a run")
+        .response(Body::Json(Schema::OpenAI("RunObject"))),
+      get_run,
+    )
+    .route(
+      Operation::post("/:thread_id/runs/:run_id", "Modify a run")
+        .request(Body::Json(Schema::OpenAI("ModifyRunRequest")))
+        .response(Body::Json(Schema::OpenAI("RunObject"))),
+      modify_run,
+    )
+    .route(
+      Operation::post("/:thread_id/runs/:run_id/cancel", "Cancel a run")
+        .response(Body::Json(Schema::OpenAI("RunObject"))),
+      cancel_run,
+    )
+    .route(
+      Operation::post(
+        "/:thread_id/runs/:run_id/submit_tool_outputs",
+        "Submit the outputs of the tool calls a run waits for",
+      )
+      .request(Body::Json(Schema::OpenAI("SubmitToolOutputsRunRequest")))
+      .response(Body::Json(Schema::OpenAI("RunObject"))),
+      submit_tool_outputs,
+    )
+}
+
+fn new_user_message(request: CreateMessageRequest) -> Result<NewThreadMessage> {
+  if request.role != MessageRole::User {
+    return Err(Error::InvalidArgument(
+      "only user messages can be added to a thread".to_string(),
+    ));
+  }
+  Ok(NewThreadMessage {
+    role: "user".to_string(),
+    content: Some(request.content),
+    file_ids: request.file_ids.unwrap_or_default(),
+    metadata: request.metadata,
+    ..Default::default()
+  })
+}
+
+async fn create_thread(
+  app_state: &AppState,
+  user_id: Uuid,
+  request: CreateThreadRequest,
+) -> Result<Thread> {
+  let mut tx = app_state.pool.begin().await?;
+  let thread = Thread::create(user_id, request.metadata, &mut *tx).await?;
+  for message in request.messages.unwrap_or_default() {
+    ThreadMessage::create(thread.id, new_user_message(message)?, &mut *tx).await?;
+  }
+  tx.commit().await?;
+  Ok(thread)
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn create(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Json(request): Json<CreateThreadRequest>,
+) -> Result<Json<ThreadObject>> {
+  let user_id = super::user_id(caller)?;
+  let thread = create_thread(&app_state, user_id, request).await?;
+  Ok(Json(thread.into()))
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn get_thread(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path(thread_id): Path<Uuid>,
+) -> Result<Json<ThreadObject>> {
+  let user_id = super::user_id(caller)?;
+  let thread = Thread::get(user_id, thread_id, &app_state.pool).await?;
+  Ok(Json(thread.into()))
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn modify(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path(thread_id): Path<Uuid>,
+  Json(request): Json<ModifyMetadataRequest>,
+) -> Result<Json<ThreadObject>> {
+  let user_id = super::user_id(caller)?;
+  let thread =
+    Thread::update_metadata(user_id, thread_id, request.metadata, &app_state.pool).await?;
+  Ok(Json(thread.into()))
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn delete_thread(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path(thread_id): Path<Uuid>,
+) -> Result<Json<DeletedObject>> {
+  let user_id = super::user_id(caller)?;
+  if !Thread::delete(user_id, thread_id, &app_state.pool).await? {
+    return Err(Error::NotFound(format!("thread {thread_id}")));
+  }
+  Ok(Json(DeletedObject {
+    id: thread_id.to_string(),
+    object: "thread.deleted".to_string(),
+    deleted: true,
+  }))
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn create_message(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path(thread_id): Path<Uuid>,
+  Json(request): Json<CreateMessageRequest>,
+) -> Result<Json<MessageObject>> {
+  let user_id = super::user_id(caller)?;
+  Thread::get(user_id, thread_id, &app_state.pool).await?;
+  let message =
+    ThreadMessage::create(thread_id, new_user_message(request)?, &app_state.pool).await?;
+  Ok(Json(message.into()))
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn list_messages(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path(thread_id): Path<Uuid>,
+  Query(query): Query<ListQuery>,
+) -> Result<Json<ListResponse<MessageObject>>> {
+  let user_id = super::user_id(caller)?;
+  Thread::get(user_id, thread_id, &app_state.pool).await?;
+  ThreadMessage::list(thread_id, &query, &app_state.pool)
+    .await
+    .map(Json)
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn get_message(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path((thread_id, message_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<MessageObject>> {
+  let user_id = super::user_id(caller)?;
+  Thread::get(user_id, thread_id, &app_state.pool).await?;
+  let message = ThreadMessage::get(thread_id, message_id, &app_state.pool).await?;
+  Ok(Json(message.into()))
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn modify_message(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path((thread_id, message_id)): Path<(Uuid, Uuid)>,
+  Json(request): Json<ModifyMetadataRequest>,
+) -> Result<Json<MessageObject>> {
+  let user_id = super::user_id(caller)?;
+  Thread::get(user_id, thread_id, &app_state.pool).await?;
+  let message =
+    ThreadMessage::update_metadata(thread_id, message_id, request.metadata, &app_state.pool)
+      .await?;
+  Ok(Json(message.into()))
+}
+
+/// Resolves the assistant and the run level overrides into the settings a run executes with, the
+/// model must be one the API key may use.
+async fn new_run(
+  app_state: &AppState,
+  user_id: Uuid,
+  scope: Option<Extension<ModelScope>>,
+  request: CreateRunRequest,
+) -> Result<NewRun> {
+  let assistant_id = parse_object_id("assistant", &request.assistant_id)?;
+  let assistant = Assistant::get(user_id, assistant_id, &app_state.pool).await?;
+
+  let mut instructions = request
+    .instructions
+    .or(assistant.instructions)
+    .unwrap_or_default();
+  if let Some(additional) = request.additional_instructions {
+    if !instructions.is_empty() {
+      instructions.push_str("\n\n");
+    }
+    instructions.push_str(&additional);
+  }
+
+  let model = request.model.unwrap_or(assistant.model);
+  if let Some(Extension(scope)) = scope {
+    scope.check(&model)?;
+  }
+
+  Ok(NewRun {
+    assistant_id: Some(assistant.id),
+    model,
+    instructions,
+    tools: request.tools.unwrap_or(assistant.tools.0),
+    file_ids: assistant.file_ids,
+    metadata: request.metadata,
+  })
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn create_run(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  scope: Option<Extension<ModelScope>>,
+  Path(thread_id): Path<Uuid>,
+  Json(request): Json<CreateRunRequest>,
+) -> Result<Json<RunObject>> {
+  let user_id = super::user_id(caller)?;
+  Thread::get(user_id, thread_id, &app_state.pool).await?;
+  let run = Run::create(
+    user_id,
+    thread_id,
+    new_run(&app_state, user_id, scope, request).await?,
+    &app_state.pool,
+  )
+  .await?;
+  runs::spawn(app_state, run.clone());
+  Ok(Json(run.into()))
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn create_thread_and_run(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  scope: Option<Extension<ModelScope>>,
+  Json(request): Json<CreateThreadAndRunRequest>,
+) -> Result<Json<RunObject>> {
+  let user_id = super::user_id(caller)?;
+  let run_settings = new_run(
+    &app_state,
+    user_id,
+    scope,
+    CreateRunRequest {
+      assistant_id: request.assistant_id,
+      model: request.model,
+      instructions: request.instructions,
+      additional_instructions: None,
+      tools: request.tools,
+      metadata: request.metadata,
+    },
+  )
+  .await?;
+  let thread = create_thread(&app_state, user_id, request.thread.unwrap_or_default()).await?;
+  let run = Run::create(user_id, thread.id, run_settings, &app_state.pool).await?;
+  runs::spawn(app_state, run.clone());
+  Ok(Json(run.into()))
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn list_runs(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path(thread_id): Path<Uuid>,
+  Query(query): Query<ListQuery>,
+) -> Result<Json<ListResponse<RunObject>>> {
+  let user_id = super::user_id(caller)?;
+  Thread::get(user_id, thread_id, &app_state.pool).await?;
+  Run::list(user_id, thread_id, &query, &app_state.pool)
+    .await
+    .map(Json)
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn get_run(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path((thread_id, run_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<RunObject>> {
+  let user_id = super::user_id(caller)?;
+  let run = Run::get(user_id, thread_id, run_id, &app_state.pool).await?;
+  Ok(Json(run.into()))
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn modify_run(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path((thread_id, run_id)): Path<(Uuid, Uuid)>,
+  Json(request): Json<ModifyMetadataRequest>,
+) -> Result<Json<RunObject>> {
+  let user_id = super::user_id(caller)?;
+  let run = Run::update_metadata(
+    user_id,
+    thread_id,
+    run_id,
+    request.metadata,
+    &app_state.pool,
+  )
+  .await?;
+  Ok(Json(run.into()))
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn cancel_run(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path((thread_id, run_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<RunObject>> {
+  let user_id = super::user_id(caller)?;
+  let pool = &app_state.pool;
+  let run = Run::get(user_id, thread_id, run_id, pool).await?;
+
+  // a run that is waiting on nobody can be cancelled right away, an in progress run is marked
+  // and the executor cancels it once the model returns
+  let cancelled = match Run::transition(
+    run.id,
+    &[RunStatus::Queued, RunStatus::RequiresAction],
+    RunStatus::Cancelled,
+    None,
+    None,
+    pool,
+  )
+  .await?
+  {
+    Some(run) => Some(run),
+    None => {
+      Run::transition(
+        run.id,
+        &[RunStatus::InProgress],
+        RunStatus::Cancelling,
+        None,
+        None,
+        pool,
+      )
+      .await?
+    }
+  };
+
+  match cancelled {
+    Some(run) => Ok(Json(run.into())),
+    None => Err(Error::InvalidArgument(format!(
+      "cannot cancel run with status {}",
+      run.status
+    ))),
+  }
+}
+
+#[tracing::instrument(skip(app_state))]
+async fn submit_tool_outputs(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  Path((thread_id, run_id)): Path<(Uuid, Uuid)>,
+  Json(request): Json<SubmitToolOutputsRunRequest>,
+) -> Result<Json<RunObject>> {
+  let user_id = super::user_id(caller)?;
+  let run = Run::get(user_id, thread_id, run_id, &app_state.pool).await?;
+  let Some(required_action) = run
+    .required_action
+    .as_ref()
+    .filter(|_| run.status() == RunStatus::RequiresAction)
+  else {
+    return Err(Error::InvalidArgument(format!(
+      "run {run_id} is not waiting for tool outputs"
+    )));
+  };
+
+  let expected = &required_action.submit_tool_outputs.tool_calls;
+  for call in expected {
+    if !request
+      .tool_outputs
+      .iter()
+      .any(|output| output.tool_call_id.as_deref() == Some(call.id.as_str()))
+    {
+      return Err(Error::InvalidArgument(format!(
+        "missing output for tool call {}",
+        call.id
+      )));
+    }
+  }
+
+  let mut tx = app_state.pool.begin().await?;
+  let Some(run) = Run::transition(
+    run.id,
+    &[RunStatus::RequiresAction],
+    RunStatus::Queued,
+    None,
+    None,
+    &mut *tx,
+  )
+  .await?
+  else {
+    return Err(Error::InvalidArgument(format!(
+      "run {run_id} is not waiting for tool outputs"
+    )));
+  };
+
+  for output in request.tool_outputs {
+    let Some(tool_call_id) = output.tool_call_id else {
+      continue;
+    };
+    if !expected.iter().any(|call| call.id == tool_call_id) {
+      return Err(Error::InvalidArgument(format!(
+        "unknown tool call {tool_call_id}"
+      )));
+    }
+    let message = NewThreadMessage {
+      role: "tool".to_string(),
+      content: Some(output.output.unwrap_or_default()),
+      tool_call_id: Some(tool_call_id),
+      temporary: true,
+      run_id: Some(run.id),
+      ..Default::default()
+    };
+    ThreadMessage::create(thread_id, message, &mut *tx).await?;
+  }
+  tx.commit().await?;
+
+  runs::spawn(app_state, run.clone());
+  Ok(Json(run.into()))
+}
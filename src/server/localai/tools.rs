@@ -0,0 +1,458 @@
+use std::io::Cursor;
+
+use async_openai::types::{
+  ChatCompletionTool, ChatCompletionToolType, CreateImageEditRequest, FunctionObject, Image,
+  ImageInput, ImagesResponse, InputSource,
+};
+use axum::{
+  extract::{Extension, State},
+  http::HeaderMap,
+  Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::Utc;
+use image::ImageFormat;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+  app::state::AppState,
+  models::{
+    images::{
+      DallE2ImageSize, GenerateImage, GenerateImageOutput, ImageSize, ResponseFormat,
+      WorkspaceImage,
+    },
+    retrieval::{cited_excerpts, SearchWorkspace, SearchWorkspaceOutput},
+    Chat, ChatLog, WORKSPACE_SCHEME,
+  },
+  server::{
+    api_key::Caller,
+    audit::AgentCall,
+    openapi::{self, ApiRouter, Body, Operation, Schema},
+    pii::CHAT_ID_HEADER,
+    pricing::{self, BudgetHold},
+    retrieval,
+    usage::{model_name, Tracker},
+    workspace,
+  },
+  Error, Result,
+};
+
+pub(super) fn api() -> ApiRouter {
+  ApiRouter::new()
+    .route(
+      Operation::get("/", "List the tools the app executes for agents")
+        .response(Body::Json(Schema::Inline(tool_list))),
+      list,
+    )
+    .route(
+      Operation::post(
+        "/generate_image",
+        "Generate images into the workspace of a chat",
+      )
+      .request(Body::Json(Schema::Inline(generate_image_parameters)))
+      .response(Body::Json(Schema::Model(
+        openapi::model::<GenerateImageOutput>,
+      ))),
+      generate_image,
+    )
+    .route(
+      Operation::post(
+        "/search_workspace",
+        "Find the passages of the workspace files of a chat that match a query",
+      )
+      .request(Body::Json(Schema::Model(openapi::model::<SearchWorkspace>)))
+      .response(Body::Json(Schema::Model(
+        openapi::model::<SearchWorkspaceOutput>,
+      ))),
+      search_workspace,
+    )
+}
+
+/// The most chunks `search_workspace` returns at once.
+const MAX_SEARCH_CHUNKS: u32 = 20;
+
+/// The run metadata naming the chat whose workspace the tools act on, runs without it leave all
+/// tool calls to the client.
+pub(super) const CHAT_ID_METADATA: &str = "chat_id";
+
+/// The tools the app executes for agents, to be passed as `tools` of a chat completion.
+pub(super) fn definitions() -> Vec<ChatCompletionTool> {
+  vec![
+    ChatCompletionTool {
+      r#type: ChatCompletionToolType::Function,
+      function: FunctionObject {
+        name: "generate_image".into(),
+        description: Some(
+          "Generates an image from a prompt and saves it into the workspace of the chat. Pass a \
+           workspace PNG as source to edit it instead, optionally with a mask whose transparent \
+           areas mark what to change."
+            .into(),
+        ),
+        parameters: Some(generate_image_parameters()),
+      },
+    },
+    ChatCompletionTool {
+      r#type: ChatCompletionToolType::Function,
+      function: FunctionObject {
+        name: "search_workspace".into(),
+        description: Some(
+          "Searches the files in the workspace of the chat and returns the passages that match \
+           the query. Cite a passage with the markdown link after `Source:` when you use it."
+            .into(),
+        ),
+        parameters: Some(search_workspace_parameters()),
+      },
+    },
+  ]
+}
+
+/// The JSON schema of the arguments of `generate_image`.
+fn generate_image_parameters() -> serde_json::Value {
+  json!({
+    "type": "object",
+    "properties": {
+      "prompt": {"type": "string", "description": "What the image shows."},
+      "model": {"type": "string", "enum": ["dall-e-2", "dall-e-3"]},
+      "size": {
+        "type": "string",
+        "enum": ["256x256", "512x512", "1024x1024", "1792x1024", "1024x1792"]
+      },
+      "quality": {"type": "string", "enum": ["standard", "hd"]},
+      "style": {"type": "string", "enum": ["vivid", "natural"]},
+      "n": {"type": "integer", "minimum": 1, "maximum": 10},
+      "source": {"type": "string", "description": "A workspace file to edit."},
+      "mask": {"type": "string", "description": "A workspace PNG masking the edit."}
+    },
+    "required": ["prompt"]
+  })
+}
+
+/// The JSON schema of the arguments of `search_workspace`.
+fn search_workspace_parameters() -> serde_json::Value {
+  json!({
+    "type": "object",
+    "properties": {
+      "query": {"type": "string", "description": "What to look for in the files."},
+      "k": {"type": "integer", "minimum": 1, "maximum": MAX_SEARCH_CHUNKS}
+    },
+    "required": ["query"]
+  })
+}
+
+/// The schema of the tool list for the OpenAPI document.
+fn tool_list() -> serde_json::Value {
+  json!({"type": "array", "items": openapi::openai_schema("ChatCompletionTool")})
+}
+
+#[tracing::instrument]
+async fn list() -> Json<Vec<ChatCompletionTool>> {
+  Json(definitions())
+}
+
+/// Generates or edits images for the chat in the `x-miko-chat-id` header, which must belong to
+/// the caller.
+#[tracing::instrument(skip(app_state))]
+async fn generate_image(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  headers: HeaderMap,
+  Json(args): Json<GenerateImage>,
+) -> Result<Json<GenerateImageOutput>> {
+  let Some(Extension(caller)) = caller else {
+    return Err(Error::UserNotAuthenticated);
+  };
+  let chat_id = chat_id(&headers)?;
+  let output = generate(&app_state, caller.user_id, chat_id, args).await?;
+  Ok(Json(output))
+}
+
+/// Finds the chunks of the workspace files of the chat in the `x-miko-chat-id` header that match
+/// the query, the chat must belong to the caller.
+#[tracing::instrument(skip(app_state))]
+async fn search_workspace(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  headers: HeaderMap,
+  Json(args): Json<SearchWorkspace>,
+) -> Result<Json<SearchWorkspaceOutput>> {
+  let Some(Extension(caller)) = caller else {
+    return Err(Error::UserNotAuthenticated);
+  };
+  let chat_id = chat_id(&headers)?;
+  let output = search(&app_state, caller.user_id, chat_id, args).await?;
+  Ok(Json(output))
+}
+
+/// Executes a tool call of a run for the chat of the run. Failures, unknown tools included, are
+/// returned to the model as an error object rather than failing the run.
+pub(super) async fn dispatch(
+  app_state: &AppState,
+  user_id: Uuid,
+  chat_id: Uuid,
+  name: &str,
+  arguments: &str,
+) -> String {
+  let output = match name {
+    "generate_image" => match serde_json::from_str(arguments) {
+      Ok(args) => generate(app_state, user_id, chat_id, args)
+        .await
+        .and_then(|output| Ok(serde_json::to_string(&output)?)),
+      Err(e) => Err(Error::InvalidArgument(format!("arguments: {e}"))),
+    },
+    "search_workspace" => match serde_json::from_str(arguments) {
+      Ok(args) => search(app_state, user_id, chat_id, args)
+        .await
+        .and_then(|output| Ok(serde_json::to_string(&output)?)),
+      Err(e) => Err(Error::InvalidArgument(format!("arguments: {e}"))),
+    },
+    _ => Err(Error::InvalidArgument(format!("unknown tool {name}"))),
+  };
+  output.unwrap_or_else(|e| json!({"error": e.to_string()}).to_string())
+}
+
+/// Generates or edits images into the workspace of the chat, which must belong to the user, and
+/// logs them in the chat.
+async fn generate(
+  app_state: &AppState,
+  user_id: Uuid,
+  chat_id: Uuid,
+  args: GenerateImage,
+) -> Result<GenerateImageOutput> {
+  if !Chat::is_owned_by(chat_id, user_id, &app_state.pool).await? {
+    return Err(Error::NotFound(format!("chat {chat_id}")));
+  }
+  if args.request.prompt.trim().is_empty() {
+    return Err(Error::InvalidArgument("prompt is required".into()));
+  }
+  let budget = pricing::check_budget(app_state, chat_id).await?;
+
+  let images = create_images(app_state, user_id, chat_id, budget, args).await?;
+  let content = images
+    .iter()
+    .map(|image| {
+      // brackets and line breaks would end the alt text early
+      let alt: String = image
+        .prompt
+        .chars()
+        .map(|c| match c {
+          '[' | ']' | '\n' | '\r' => ' ',
+          c => c,
+        })
+        .collect();
+      format!("![{}]({WORKSPACE_SCHEME}{})", alt.trim(), image.file_name)
+    })
+    .collect::<Vec<_>>()
+    .join("\n\n");
+  ChatLog::create(
+    chat_id,
+    "miko".into(),
+    format!("### Generated image\n\n{content}"),
+    None,
+    &app_state.pool,
+  )
+  .await?;
+  Ok(GenerateImageOutput { content, images })
+}
+
+/// Finds the chunks of the workspace files of the chat that match the query, the chat must
+/// belong to the user.
+async fn search(
+  app_state: &AppState,
+  user_id: Uuid,
+  chat_id: Uuid,
+  args: SearchWorkspace,
+) -> Result<SearchWorkspaceOutput> {
+  let Some(redact_pii) = Chat::redacts_pii(chat_id, user_id, &app_state.pool).await? else {
+    return Err(Error::NotFound(format!("chat {chat_id}")));
+  };
+  let k = args
+    .k
+    .unwrap_or_else(|| app_state.retrieval().top_k())
+    .clamp(1, MAX_SEARCH_CHUNKS);
+
+  let chunks = retrieval::retrieve(app_state, user_id, chat_id, &args.query, k, redact_pii).await?;
+  Ok(SearchWorkspaceOutput {
+    content: cited_excerpts(&chunks),
+    chunks,
+  })
+}
+
+/// The chat named by the `x-miko-chat-id` header, which tools act on.
+fn chat_id(headers: &HeaderMap) -> Result<Uuid> {
+  headers
+    .get(CHAT_ID_HEADER)
+    .and_then(|v| v.to_str().ok())
+    .and_then(|v| v.parse().ok())
+    .ok_or_else(|| Error::InvalidArgument(format!("the {CHAT_ID_HEADER} header is required")))
+}
+
+/// A workspace image as a PNG, edits only take PNGs.
+async fn png_input(app_state: &AppState, chat_id: Uuid, file_name: &str) -> Result<ImageInput> {
+  if !workspace::path_is_valid(file_name) {
+    return Err(Error::InvalidArgument(format!("Invalid path: {file_name}")));
+  }
+  let path = app_state
+    .upload_store
+    .join(chat_id.to_string())
+    .join(file_name);
+  let bytes = tokio::fs::read(path)
+    .await
+    .map_err(|_| Error::NotFound(format!("workspace file {file_name}")))?;
+  let bytes = tokio::task::spawn_blocking(move || {
+    let invalid = |e: image::ImageError| Error::InvalidArgument(format!("image: {e}"));
+    let image = image::load_from_memory(&bytes).map_err(invalid)?;
+    let mut png = Cursor::new(vec![]);
+    image
+      .to_rgba8()
+      .write_to(&mut png, ImageFormat::Png)
+      .map_err(invalid)?;
+    Ok::<_, Error>(png.into_inner())
+  })
+  .await
+  .map_err(|e| Error::InvalidArgument(format!("image: {e}")))??;
+  Ok(ImageInput {
+    source: InputSource::Bytes {
+      filename: "image.png".into(),
+      bytes: bytes.into(),
+    },
+  })
+}
+
+/// Edits are limited to the square sizes of `dall-e-2`.
+fn edit_size(size: Option<ImageSize>) -> DallE2ImageSize {
+  match size {
+    Some(ImageSize::S256x256) => DallE2ImageSize::S256x256,
+    Some(ImageSize::S512x512) => DallE2ImageSize::S512x512,
+    _ => DallE2ImageSize::S1024x1024,
+  }
+}
+
+async fn create_images(
+  app_state: &AppState,
+  user_id: Uuid,
+  chat_id: Uuid,
+  budget: Option<BudgetHold>,
+  args: GenerateImage,
+) -> Result<Vec<WorkspaceImage>> {
+  let GenerateImage {
+    mut request,
+    source,
+    mask,
+  } = args;
+  request.response_format = Some(ResponseFormat::B64Json);
+  let model = model_name(&request.model.clone().unwrap_or_default());
+  let n = request.n.unwrap_or(1) as u32;
+  let client = app_state.openai_client();
+
+  let (size, result) = match &source {
+    None => {
+      let size = model_name(&request.size.unwrap_or_default());
+      let usage = Tracker::start("/v1/images/generations", &model)
+        .user(Some(user_id))
+        .chat(Some(chat_id))
+        .budget(budget)
+        .images(&size, n);
+      let request: async_openai::types::CreateImageRequest = request.clone().into();
+      let audit = AgentCall::start("/v1/images/generations", &request).user(Some(user_id));
+      let result = client.images().create(request).await;
+      usage.finish(app_state, &result);
+      audit.finish(app_state, &result);
+      (size, result)
+    }
+    Some(source) => {
+      let edit = CreateImageEditRequest {
+        image: png_input(app_state, chat_id, source).await?,
+        prompt: request.prompt.clone(),
+        mask: match &mask {
+          Some(mask) => Some(png_input(app_state, chat_id, mask).await?),
+          None => None,
+        },
+        model: request.model.clone().map(Into::into),
+        n: request.n,
+        size: Some(edit_size(request.size).into()),
+        response_format: Some(ResponseFormat::B64Json.into()),
+        user: None,
+      };
+      let size = model_name(&edit_size(request.size));
+      let usage = Tracker::start("/v1/images/edits", &model)
+        .user(Some(user_id))
+        .chat(Some(chat_id))
+        .budget(budget)
+        .images(&size, n);
+      // the images of an edit are multipart uploads, only their workspace names are audited
+      let audit = AgentCall::start(
+        "/v1/images/edits",
+        &json!({
+          "model": model,
+          "prompt": edit.prompt,
+          "image": source,
+          "mask": mask,
+          "size": size,
+          "n": n,
+        }),
+      )
+      .user(Some(user_id));
+      let result = client.images().create_edit(edit).await;
+      usage.finish(app_state, &result);
+      audit.finish(app_state, &result);
+      (size, result)
+    }
+  };
+  let ImagesResponse { data, .. } = result?;
+
+  let workspace_dir = app_state.upload_store.join(chat_id.to_string());
+  tokio::fs::create_dir_all(&workspace_dir).await?;
+  let mut images = Vec::with_capacity(data.len());
+  for image in data {
+    let (bytes, revised_prompt) = match image.as_ref() {
+      Image::B64Json {
+        b64_json,
+        revised_prompt,
+      } => (
+        STANDARD
+          .decode(b64_json.as_bytes())
+          .map_err(|e| Error::InvalidArgument(format!("image: {e}")))?,
+        revised_prompt.clone(),
+      ),
+      // providers that ignore `response_format`
+      Image::Url {
+        url,
+        revised_prompt,
+      } => (
+        reqwest::get(url)
+          .await
+          .and_then(|response| response.error_for_status())
+          .map_err(|e| Error::InvalidArgument(format!("image: {e}")))?
+          .bytes()
+          .await
+          .map_err(|e| Error::InvalidArgument(format!("image: {e}")))?
+          .to_vec(),
+        revised_prompt.clone(),
+      ),
+    };
+    // providers serving URLs do not all return PNGs, the bytes tell the format
+    let format =
+      image::guess_format(&bytes).map_err(|e| Error::InvalidArgument(format!("image: {e}")))?;
+    let extension = format.extensions_str().first().copied().unwrap_or("png");
+    let file_name = format!(
+      "image-{}.{extension}",
+      &Uuid::new_v4().simple().to_string()[..12]
+    );
+    tokio::fs::write(workspace_dir.join(&file_name), bytes).await?;
+
+    let image = WorkspaceImage {
+      chat_id,
+      file_name,
+      prompt: request.prompt.clone(),
+      revised_prompt,
+      model: model.clone(),
+      size: size.clone(),
+      source: source.clone(),
+      created_at: Utc::now(),
+    };
+    image.save(user_id, &app_state.pool).await?;
+    images.push(image);
+  }
+  Ok(images)
+}
@@ -1,11 +1,45 @@
-use crate::app::state::AppState;
+use axum::routing::get;
 
+use crate::{app::state::AppState, server::openapi::ApiRouter};
+
+pub(crate) mod api_key;
+pub mod audit;
+pub(crate) mod catalog;
+pub(crate) mod datasets;
+pub(crate) mod extract;
+pub(crate) mod file_store;
 pub mod localai;
+pub(crate) mod moderation;
+pub mod openapi;
+pub(crate) mod pii;
+pub(crate) mod pricing;
+pub(crate) mod quota;
+pub mod retrieval;
+pub mod search;
+pub(crate) mod transcription;
+pub(crate) mod usage;
+pub(crate) mod vision;
+pub(crate) mod voice;
 pub mod workspace;
 
 pub fn routes(app_state: AppState) -> axum::Router<AppState> {
-  axum::Router::new()
-    .nest("/workspace", workspace::routes(app_state.clone()))
+  apis()
+    .into_iter()
+    .fold(axum::Router::new(), |router, (prefix, api)| {
+      router.nest(prefix, api.into_router())
+    })
     .nest("/localai", localai::routes(app_state.clone()))
+    .route("/openapi.json", get(openapi::spec))
+    .route("/docs", get(openapi::docs))
     .with_state(app_state)
 }
+
+/// The nested routers of the session authenticated API by their prefix, served and documented
+/// from this one list.
+pub(crate) fn apis() -> Vec<(&'static str, ApiRouter)> {
+  vec![
+    ("/audit", audit::api()),
+    ("/voice", voice::api()),
+    ("/workspace", workspace::api()),
+  ]
+}
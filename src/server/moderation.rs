@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+
+use async_openai::types::{
+  ChatCompletionRequestMessage, ChatCompletionRequestMessageContentPart,
+  ChatCompletionRequestUserMessageContent, CreateModerationRequest, ModerationInput,
+};
+use regex::{Regex, RegexBuilder};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+  app::state::AppState,
+  models::moderation::{ContentBlocked, ModerationAction},
+  pgdb::NewModeration,
+  server::audit::AgentCall,
+  Error, Result,
+};
+
+pub(crate) const SOURCE_GOAL: &str = "goal";
+pub(crate) const SOURCE_CHAT: &str = "chat";
+
+/// Where the moderation scores come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Classifier {
+  /// The moderation endpoint of the configured provider.
+  Provider,
+  /// Keyword and regex rules, a match scores 1.0 in its category.
+  Local,
+}
+
+/// Decides which user content is blocked or flagged, disabled unless `MIKO_MODERATION` is set.
+#[derive(Debug, Default)]
+pub struct ModerationPolicy {
+  classifier: Option<Classifier>,
+  /// Category to minimum score, `*` applies to every category. When empty everything the
+  /// classifier flags is blocked.
+  block: HashMap<String, f64>,
+  flag: HashMap<String, f64>,
+  rules: Vec<(String, Regex)>,
+}
+
+/// Parses `hate=0.5,violence/graphic=0.8,*=0.9`.
+fn thresholds(name: &str) -> HashMap<String, f64> {
+  dotenvy::var(name)
+    .unwrap_or_default()
+    .split(',')
+    .filter_map(|entry| {
+      let (category, score) = entry.split_once('=')?;
+      Some((category.trim().to_string(), score.trim().parse().ok()?))
+    })
+    .collect()
+}
+
+/// Reads the local rules, a JSON object of category to a list of keywords or regexes.
+fn rules(path: &str) -> Result<Vec<(String, Regex)>> {
+  let content = std::fs::read_to_string(path)?;
+  let rules: HashMap<String, Vec<String>> = serde_json::from_str(&content)?;
+  let mut compiled = vec![];
+  for (category, patterns) in rules {
+    for pattern in patterns {
+      let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| Error::InvalidArgument(format!("moderation rule {pattern}: {e}")))?;
+      compiled.push((category.clone(), regex));
+    }
+  }
+  Ok(compiled)
+}
+
+impl ModerationPolicy {
+  /// Reads `MIKO_MODERATION` (`provider` or `local`), the `MIKO_MODERATION_BLOCK` and
+  /// `MIKO_MODERATION_FLAG` thresholds and, for the local classifier, the rules file at
+  /// `MIKO_MODERATION_RULES`.
+  pub fn from_env() -> Result<Self> {
+    let classifier = match dotenvy::var("MIKO_MODERATION").as_deref() {
+      Ok("provider") => Some(Classifier::Provider),
+      Ok("local") => Some(Classifier::Local),
+      Ok("") | Ok("off") | Err(_) => None,
+      Ok(other) => {
+        return Err(Error::InvalidArgument(format!(
+          "MIKO_MODERATION must be provider, local or off, not {other}"
+        )))
+      }
+    };
+    let rules = match (classifier, dotenvy::var("MIKO_MODERATION_RULES")) {
+      (Some(Classifier::Local), Ok(path)) => rules(&path)?,
+      (Some(Classifier::Local), Err(_)) => {
+        return Err(Error::InvalidArgument(
+          "MIKO_MODERATION_RULES is required for local moderation".into(),
+        ))
+      }
+      _ => vec![],
+    };
+    Ok(Self {
+      classifier,
+      block: thresholds("MIKO_MODERATION_BLOCK"),
+      flag: thresholds("MIKO_MODERATION_FLAG"),
+      rules,
+    })
+  }
+
+  /// Scores the input with the local rules, shaped like a `CreateModerationResponse`.
+  fn classify_locally(&self, input: &[String]) -> Value {
+    let results = input
+      .iter()
+      .map(|text| {
+        let mut categories = serde_json::Map::new();
+        let mut scores = serde_json::Map::new();
+        for (category, regex) in &self.rules {
+          let hit = regex.is_match(text);
+          let flagged = categories.get(category).and_then(Value::as_bool) == Some(true) || hit;
+          categories.insert(category.clone(), json!(flagged));
+          scores.insert(category.clone(), json!(if flagged { 1.0 } else { 0.0 }));
+        }
+        json!({
+          "flagged": categories.values().any(|v| v.as_bool() == Some(true)),
+          "categories": categories,
+          "category_scores": scores,
+        })
+      })
+      .collect::<Vec<_>>();
+    json!({
+      "id": format!("modr-{}", Uuid::new_v4().simple()),
+      "model": "local",
+      "results": results,
+    })
+  }
+
+  fn threshold<'a>(thresholds: &'a HashMap<String, f64>, category: &str) -> Option<&'a f64> {
+    thresholds.get(category).or_else(|| thresholds.get("*"))
+  }
+
+  /// The strictest action any category of any result calls for, with the categories behind it.
+  fn evaluate(&self, response: &Value) -> (ModerationAction, Vec<String>) {
+    let mut blocked = vec![];
+    let mut flagged = vec![];
+    for result in response["results"].as_array().into_iter().flatten() {
+      let Some(scores) = result["category_scores"].as_object() else {
+        continue;
+      };
+      for (category, score) in scores {
+        let score = score.as_f64().unwrap_or_default();
+        let block = match Self::threshold(&self.block, category) {
+          Some(min) => score >= *min,
+          None => self.block.is_empty() && result["categories"][category].as_bool() == Some(true),
+        };
+        if block {
+          blocked.push(category.clone());
+        } else if Self::threshold(&self.flag, category).is_some_and(|min| score >= *min) {
+          flagged.push(category.clone());
+        }
+      }
+    }
+    blocked.sort();
+    blocked.dedup();
+    flagged.sort();
+    flagged.dedup();
+
+    if !blocked.is_empty() {
+      (ModerationAction::Block, blocked)
+    } else if !flagged.is_empty() {
+      (ModerationAction::Flag, flagged)
+    } else {
+      (ModerationAction::Allow, vec![])
+    }
+  }
+}
+
+/// The text of the user messages of a chat completion request.
+pub(crate) fn user_content(messages: &[ChatCompletionRequestMessage]) -> Vec<String> {
+  messages
+    .iter()
+    .filter_map(|message| match message {
+      ChatCompletionRequestMessage::User(message) => Some(&message.content),
+      _ => None,
+    })
+    .flat_map(|content| match content {
+      ChatCompletionRequestUserMessageContent::Text(text) => vec![text.clone()],
+      ChatCompletionRequestUserMessageContent::Array(parts) => parts
+        .iter()
+        .filter_map(|part| match part {
+          ChatCompletionRequestMessageContentPart::Text(part) => Some(part.text.clone()),
+          _ => None,
+        })
+        .collect(),
+    })
+    .filter(|text| !text.trim().is_empty())
+    .collect()
+}
+
+/// Who the moderated content belongs to.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Subject {
+  pub source: &'static str,
+  pub user_id: Option<Uuid>,
+  pub chat_id: Option<Uuid>,
+}
+
+/// Runs the moderation policy on user content and fails with [Error::ContentBlocked] when it is
+/// blocked. Results that are flagged or blocked, or belong to a chat, are recorded. When the
+/// provider can not be reached the content is let through, so an outage does not stop all chats.
+pub(crate) async fn check(
+  app_state: &AppState,
+  subject: Subject,
+  input: Vec<String>,
+) -> Result<ModerationAction> {
+  let policy = app_state.moderation();
+  let Some(classifier) = policy.classifier else {
+    return Ok(ModerationAction::Allow);
+  };
+  if input.is_empty() {
+    return Ok(ModerationAction::Allow);
+  }
+
+  let response = match classifier {
+    Classifier::Local => policy.classify_locally(&input),
+    Classifier::Provider => {
+      let request = CreateModerationRequest {
+        input: ModerationInput::StringArray(input),
+        model: None,
+      };
+      let audit = AgentCall::start("/v1/moderations", &request).user(subject.user_id);
+      let result = app_state
+        .openai_client()
+        .moderations()
+        .create(request)
+        .await;
+      audit.finish(app_state, &result);
+      match result {
+        Ok(response) => serde_json::to_value(response)?,
+        Err(e) => {
+          tracing::warn!("moderation failed, letting the content through: {e}");
+          return Ok(ModerationAction::Allow);
+        }
+      }
+    }
+  };
+
+  let (action, categories) = policy.evaluate(&response);
+  if action != ModerationAction::Allow || subject.chat_id.is_some() {
+    let moderation = NewModeration {
+      chat_id: subject.chat_id,
+      user_id: subject.user_id,
+      source: subject.source,
+      action,
+      categories: categories.clone(),
+      response,
+    };
+    if let Err(e) = moderation.insert(&app_state.pool).await {
+      tracing::warn!("failed to record moderation: {e}");
+    }
+  }
+
+  match action {
+    ModerationAction::Block => Err(Error::ContentBlocked(ContentBlocked { categories })),
+    action => Ok(action),
+  }
+}
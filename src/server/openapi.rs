@@ -0,0 +1,384 @@
+use axum::{
+  extract::DefaultBodyLimit,
+  handler::Handler,
+  http::header,
+  response::{Html, IntoResponse},
+  routing::{on, MethodFilter, MethodRouter},
+  Json, Router,
+};
+use schemars::{
+  gen::{SchemaGenerator, SchemaSettings},
+  JsonSchema,
+};
+use serde_json::{json, Map, Value};
+
+use crate::{
+  app::state::AppState,
+  server::{self, localai},
+};
+
+/// The published specification of the OpenAI API, the proxy takes and returns its types. Pinned
+/// to a release, so the schemas it links to do not change under the document.
+const OPENAI_SPEC: &str =
+  "https://raw.githubusercontent.com/openai/openai-openapi/2.0.0/openapi.yaml";
+
+/// The docs UI, a single page that renders the document served next to it.
+const DOCS_PAGE: &str = include_str!("openapi.html");
+
+/// The schema of a request or response body.
+#[derive(Clone, Copy)]
+pub(crate) enum Schema {
+  /// A schema of the OpenAI specification, by its name there.
+  OpenAI(&'static str),
+  /// A type of `models`, generated from its serde shape.
+  Model(fn(&mut SchemaGenerator) -> schemars::schema::Schema),
+  /// A schema the app builds itself, like the parameters of its tools.
+  Inline(fn() -> Value),
+}
+
+impl Schema {
+  fn to_json(self, generator: &mut SchemaGenerator) -> Value {
+    match self {
+      Schema::OpenAI(name) => openai_schema(name),
+      Schema::Model(schema) => serde_json::to_value(schema(generator)).unwrap_or_default(),
+      Schema::Inline(schema) => schema(),
+    }
+  }
+}
+
+/// A reference to a schema of the OpenAI specification.
+pub(crate) fn openai_schema(name: &str) -> Value {
+  json!({ "$ref": format!("{OPENAI_SPEC}#/components/schemas/{name}") })
+}
+
+/// The schema of a type of `models`, for [Schema::Model].
+pub(crate) fn model<T: JsonSchema>(generator: &mut SchemaGenerator) -> schemars::schema::Schema {
+  generator.subschema_for::<T>()
+}
+
+/// What a request sends or a response returns.
+#[derive(Clone, Copy)]
+pub(crate) enum Body {
+  None,
+  Json(Schema),
+  /// A form with the file and text fields of the schema.
+  Multipart(Schema),
+  /// Raw bytes of any media type, like audio or the contents of files.
+  Binary,
+  /// Text of the media type, like the text extracted from documents.
+  Text(&'static str),
+  /// Server-sent events whose data is JSON of the schema.
+  Events(Schema),
+}
+
+impl Body {
+  fn content(self, generator: &mut SchemaGenerator) -> Option<Value> {
+    let (media_type, schema) = match self {
+      Body::None => return None,
+      Body::Json(schema) => ("application/json", schema.to_json(generator)),
+      Body::Multipart(schema) => ("multipart/form-data", schema.to_json(generator)),
+      Body::Binary => (
+        "application/octet-stream",
+        json!({"type": "string", "contentMediaType": "application/octet-stream"}),
+      ),
+      Body::Text(media_type) => (media_type, json!({"type": "string"})),
+      Body::Events(schema) => ("text/event-stream", schema.to_json(generator)),
+    };
+    let mut content = Map::new();
+    content.insert(media_type.to_string(), json!({ "schema": schema }));
+    Some(Value::Object(content))
+  }
+}
+
+/// A route and method of a router with what it takes and returns. Path parameters are read from
+/// the `:name` segments of the path.
+#[derive(Clone, Copy)]
+pub(crate) struct Operation {
+  pub method: &'static str,
+  pub path: &'static str,
+  pub summary: &'static str,
+  pub query: &'static [&'static str],
+  pub request: Body,
+  pub response: Body,
+}
+
+impl Operation {
+  const fn new(method: &'static str, path: &'static str, summary: &'static str) -> Self {
+    Self {
+      method,
+      path,
+      summary,
+      query: &[],
+      request: Body::None,
+      response: Body::None,
+    }
+  }
+
+  pub const fn get(path: &'static str, summary: &'static str) -> Self {
+    Self::new("get", path, summary)
+  }
+
+  pub const fn post(path: &'static str, summary: &'static str) -> Self {
+    Self::new("post", path, summary)
+  }
+
+  pub const fn delete(path: &'static str, summary: &'static str) -> Self {
+    Self::new("delete", path, summary)
+  }
+
+  fn method_filter(&self) -> MethodFilter {
+    match self.method {
+      "get" => MethodFilter::GET,
+      "post" => MethodFilter::POST,
+      "delete" => MethodFilter::DELETE,
+      method => unreachable!("operations are not made with {method}"),
+    }
+  }
+
+  /// The optional string parameters of the query.
+  pub const fn query(mut self, names: &'static [&'static str]) -> Self {
+    self.query = names;
+    self
+  }
+
+  pub const fn request(mut self, body: Body) -> Self {
+    self.request = body;
+    self
+  }
+
+  pub const fn response(mut self, body: Body) -> Self {
+    self.response = body;
+    self
+  }
+
+  fn to_json(self, tag: &str, generator: &mut SchemaGenerator) -> Value {
+    let path_params = self
+      .path
+      .split('/')
+      .filter_map(|segment| segment.strip_prefix(':'))
+      .map(
+        |name| json!({"name": name, "in": "path", "required": true, "schema": {"type": "string"}}),
+      );
+    let query_params = self
+      .query
+      .iter()
+      .map(|name| json!({"name": name, "in": "query", "schema": {"type": "string"}}));
+    let mut operation = json!({
+      "tags": [tag],
+      "summary": self.summary,
+      "parameters": path_params.chain(query_params).collect::<Vec<_>>(),
+      "responses": {
+        "200": {"description": "OK"},
+        "default": {"$ref": "#/components/responses/Error"},
+      },
+    });
+    if let Some(content) = self.request.content(generator) {
+      operation["requestBody"] = json!({"required": true, "content": content});
+    }
+    if let Some(content) = self.response.content(generator) {
+      operation["responses"]["200"]["content"] = content;
+    }
+    operation
+  }
+}
+
+/// A router that takes every route together with the operation documenting it, so the document
+/// is built from what is actually served.
+pub(crate) struct ApiRouter {
+  router: Router<AppState>,
+  operations: Vec<Operation>,
+}
+
+impl ApiRouter {
+  pub fn new() -> Self {
+    Self {
+      router: Router::new(),
+      operations: vec![],
+    }
+  }
+
+  /// Serves `handler` at the method and path of the operation.
+  pub fn route<H, T>(self, operation: Operation, handler: H) -> Self
+  where
+    H: Handler<T, AppState>,
+    T: 'static,
+  {
+    let method_router = on(operation.method_filter(), handler);
+    self.add(operation, method_router)
+  }
+
+  /// Like [ApiRouter::route] for uploads, which are limited by their forms instead of the default
+  /// body limit of axum.
+  pub fn upload<H, T>(self, operation: Operation, handler: H) -> Self
+  where
+    H: Handler<T, AppState>,
+    T: 'static,
+  {
+    let method_router = on(operation.method_filter(), handler).layer(DefaultBodyLimit::disable());
+    self.add(operation, method_router)
+  }
+
+  fn add(mut self, operation: Operation, method_router: MethodRouter<AppState>) -> Self {
+    self.router = self.router.route(operation.path, method_router);
+    self.operations.push(operation);
+    self
+  }
+
+  pub fn operations(&self) -> &[Operation] {
+    &self.operations
+  }
+
+  pub fn into_router(self) -> Router<AppState> {
+    self.router
+  }
+}
+
+/// The documented routers with the path they are served at and their tag.
+fn apis() -> Vec<(String, &'static str, ApiRouter)> {
+  let mut apis = vec![];
+  for (prefix, api) in server::apis() {
+    apis.push((
+      format!("/api/v1{prefix}"),
+      prefix.trim_start_matches('/'),
+      api,
+    ));
+  }
+  for (prefix, api) in localai::apis() {
+    apis.push((
+      format!("/openai/v1{prefix}"),
+      prefix.trim_start_matches('/'),
+      api,
+    ));
+  }
+  apis
+}
+
+/// `/:chat_id/files` as `{chat_id}/files`, without the slash of nested roots.
+fn openapi_path(prefix: &str, path: &str) -> String {
+  let path = path
+    .split('/')
+    .map(|segment| match segment.strip_prefix(':') {
+      Some(name) => format!("{{{name}}}"),
+      None => segment.to_string(),
+    })
+    .collect::<Vec<_>>()
+    .join("/");
+  format!("{prefix}{}", path.trim_end_matches('/'))
+}
+
+/// The OpenAPI 3.1 document of the workspace routes and the OpenAI compatible proxy.
+pub fn document() -> Value {
+  let mut generator = SchemaSettings::draft2019_09()
+    .with(|settings| {
+      settings.definitions_path = "#/components/schemas/".to_string();
+      settings.meta_schema = None;
+    })
+    .into_generator();
+
+  let mut paths = Map::new();
+  for (prefix, tag, api) in apis() {
+    for operation in api.operations() {
+      let item = paths
+        .entry(openapi_path(&prefix, operation.path))
+        .or_insert_with(|| json!({}));
+      item[operation.method] = operation.to_json(tag, &mut generator);
+    }
+  }
+
+  json!({
+    "openapi": "3.1.0",
+    "info": {
+      "title": "Miko",
+      "version": env!("CARGO_PKG_VERSION"),
+      "description": "The chat workspaces of Miko and its OpenAI compatible proxy. The proxy \
+        authenticates with API keys as bearer tokens and is also served at `/api/v1/localai`, \
+        the types it shares with the OpenAI API link to the OpenAI specification. The workspace, \
+        voice and audit routes use the session of a signed in user instead, the audit log is for \
+        admins only. Requests refused by the quotas of the proxy carry `Retry-After` and \
+        `x-ratelimit-*` headers, requests the provider rate limits only carry `Retry-After` as \
+        the `x-ratelimit-*` headers of the provider are not passed on.",
+    },
+    "paths": paths,
+    "components": {
+      "schemas": generator.take_definitions(),
+      "responses": {
+        "Error": {
+          "description": "An error, shaped like the errors of the OpenAI API on the proxy.",
+          "content": {"application/json": {"schema": {"oneOf": [
+            {
+              "type": "object",
+              "properties": {"error": openai_schema("Error")},
+              "required": ["error"],
+            },
+            {
+              "type": "object",
+              "properties": {"message": {"type": "string"}},
+              "required": ["message"],
+            },
+          ]}}},
+        },
+      },
+      "securitySchemes": {
+        "apiKey": {"type": "http", "scheme": "bearer"},
+      },
+    },
+    "security": [{"apiKey": []}],
+  })
+}
+
+#[tracing::instrument]
+pub(crate) async fn spec() -> impl IntoResponse {
+  Json(document())
+}
+
+#[tracing::instrument]
+pub(crate) async fn docs() -> impl IntoResponse {
+  ([(header::CACHE_CONTROL, "no-cache")], Html(DOCS_PAGE))
+}
+
+#[cfg(test)]
+mod tests {
+  use regex::Regex;
+
+  use super::*;
+
+  #[test]
+  fn document_resolves_local_references() {
+    let document = document();
+    let text = document.to_string();
+    let reference = Regex::new(r##""\$ref":"#/components/schemas/([^"]+)""##).unwrap();
+    for captures in reference.captures_iter(&text) {
+      assert!(
+        document["components"]["schemas"]
+          .get(&captures[1])
+          .is_some(),
+        "missing schema {}",
+        &captures[1]
+      );
+    }
+  }
+
+  /// Cancelling takes no body and deleting may return none, any other operation without them
+  /// was added without documenting what it takes and returns.
+  #[test]
+  fn operations_document_their_bodies() {
+    for (prefix, _, api) in apis() {
+      for operation in api.operations() {
+        let path = openapi_path(&prefix, operation.path);
+        if operation.method == "post" && !path.ends_with("/cancel") {
+          assert!(
+            !matches!(operation.request, Body::None),
+            "POST {path} documents no request body"
+          );
+        }
+        if operation.method != "delete" {
+          assert!(
+            !matches!(operation.response, Body::None),
+            "{} {path} documents no response body",
+            operation.method.to_uppercase()
+          );
+        }
+      }
+    }
+  }
+}
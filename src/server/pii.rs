@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+use async_openai::types::{
+  ChatCompletionRequestMessage, ChatCompletionRequestMessageContentPart,
+  ChatCompletionRequestUserMessageContent, ChatCompletionResponseStream,
+  CreateChatCompletionResponse,
+};
+use futures::{stream, StreamExt};
+use regex::{Captures, Regex};
+use uuid::Uuid;
+
+use crate::{app::state::AppState, models::pii::PiiRedaction, pgdb::Chat, Error, Result};
+
+/// Names the chat a request to the OpenAI compatible API belongs to, for chats that opted in.
+pub(crate) const CHAT_ID_HEADER: &str = "x-miko-chat-id";
+
+/// Longest placeholder that is held back while a streamed response is restored.
+const MAX_PLACEHOLDER: usize = 48;
+
+/// When prompts are redacted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Mode {
+  Off,
+  /// Only for chats that opted in.
+  #[default]
+  PerChat,
+  Always,
+}
+
+#[derive(Debug)]
+struct Detector {
+  kind: String,
+  regex: Regex,
+  validate: fn(&str) -> bool,
+}
+
+fn any(_: &str) -> bool {
+  true
+}
+
+fn digits(value: &str) -> Vec<u32> {
+  value.chars().filter_map(|c| c.to_digit(10)).collect()
+}
+
+/// ISO dates like `2024-01-15` have the shape of a phone number.
+fn is_date(value: &str) -> bool {
+  let mut parts = value.splitn(4, '-');
+  let (Some(year), Some(month), Some(day)) = (parts.next(), parts.next(), parts.next()) else {
+    return false;
+  };
+  let is_number = |part: &str, len: usize, range: std::ops::RangeInclusive<u32>| {
+    part.len() == len
+      && part.chars().all(|c| c.is_ascii_digit())
+      && part.parse().is_ok_and(|n| range.contains(&n))
+  };
+  is_number(year, 4, 1000..=9999)
+    && is_number(month, 2, 1..=12)
+    && is_number(&day[..day.len().min(2)], 2, 1..=31)
+}
+
+fn is_phone(value: &str) -> bool {
+  (7..=15).contains(&digits(value).len()) && !is_date(value)
+}
+
+/// Card numbers pass the Luhn check.
+fn is_card(value: &str) -> bool {
+  let digits = digits(value);
+  if !(13..=19).contains(&digits.len()) {
+    return false;
+  }
+  let sum: u32 = digits
+    .iter()
+    .rev()
+    .enumerate()
+    .map(|(i, d)| match (i % 2, d * 2) {
+      (1, doubled) if doubled > 9 => doubled - 9,
+      (1, doubled) => doubled,
+      _ => *d,
+    })
+    .sum();
+  sum % 10 == 0
+}
+
+/// IBANs pass the mod 97 check.
+fn is_iban(value: &str) -> bool {
+  let iban: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+  if iban.len() < 15 {
+    return false;
+  }
+  let (head, tail) = iban.split_at(4);
+  let remainder = tail.chars().chain(head.chars()).try_fold(0u32, |acc, c| {
+    let value = c.to_digit(36)?;
+    Some(if value > 9 {
+      (acc * 100 + value) % 97
+    } else {
+      (acc * 10 + value) % 97
+    })
+  });
+  remainder == Some(1)
+}
+
+fn builtin(kind: &str) -> Option<Detector> {
+  let (pattern, validate): (&str, fn(&str) -> bool) = match kind {
+    "email" => (r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}", any),
+    "iban" => (r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]){11,30}\b", is_iban),
+    "card" => (r"\b(?:\d[ -]?){12,18}\d\b", is_card),
+    "phone" => (
+      r"(?:\+\d{1,3}[ .-]?)?(?:\(\d{1,4}\)[ .-]?)?\d{2,4}(?:[ .-]?\d{2,4}){2,3}",
+      is_phone,
+    ),
+    _ => return None,
+  };
+  Some(Detector {
+    kind: kind.to_string(),
+    regex: Regex::new(pattern).expect("builtin pattern"),
+    validate,
+  })
+}
+
+/// Which personal data is replaced in prompts before they are sent to the provider.
+#[derive(Debug, Default)]
+pub struct PiiPolicy {
+  mode: Mode,
+  /// Applied in order, so IBANs and cards are found before their digits look like phones.
+  detectors: Vec<Detector>,
+}
+
+impl PiiPolicy {
+  /// Reads `MIKO_PII_REDACT` (`chat`, `always` or `off`), the builtin detectors to use from
+  /// `MIKO_PII_KINDS` and custom patterns from the JSON object of name to regex in the file at
+  /// `MIKO_PII_PATTERNS`.
+  pub fn from_env() -> Result<Self> {
+    let mode = match dotenvy::var("MIKO_PII_REDACT").as_deref() {
+      Ok("off") => Mode::Off,
+      Ok("always") => Mode::Always,
+      Ok("") | Ok("chat") | Err(_) => Mode::PerChat,
+      Ok(other) => {
+        return Err(Error::InvalidArgument(format!(
+          "MIKO_PII_REDACT must be chat, always or off, not {other}"
+        )))
+      }
+    };
+
+    let kinds = dotenvy::var("MIKO_PII_KINDS").unwrap_or_else(|_| "email,iban,card,phone".into());
+    let mut detectors = vec![];
+    for kind in kinds.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+      let detector =
+        builtin(kind).ok_or_else(|| Error::InvalidArgument(format!("unknown PII kind {kind}")))?;
+      detectors.push(detector);
+    }
+    if let Ok(path) = dotenvy::var("MIKO_PII_PATTERNS") {
+      let patterns: HashMap<String, String> =
+        serde_json::from_str(&std::fs::read_to_string(path)?)?;
+      for (kind, pattern) in patterns {
+        let regex = Regex::new(&pattern)
+          .map_err(|e| Error::InvalidArgument(format!("PII pattern {kind}: {e}")))?;
+        detectors.push(Detector {
+          kind,
+          regex,
+          validate: any,
+        });
+      }
+    }
+
+    Ok(Self { mode, detectors })
+  }
+
+  /// Whether the prompts of every chat are redacted, so no chat text may leave the server as is.
+  pub(crate) fn redacts_all(&self) -> bool {
+    self.mode == Mode::Always
+  }
+}
+
+/// Replaces personal data with placeholders like `[EMAIL_1]` and puts it back in the response.
+/// The same value gets the same placeholder for the whole request.
+#[derive(Debug)]
+pub(crate) struct Redactor {
+  policy: std::sync::Arc<PiiPolicy>,
+  chat_id: Option<Uuid>,
+  user_id: Option<Uuid>,
+  placeholders: HashMap<String, String>,
+  /// `(kind, placeholder, original)` in the order they were found.
+  redactions: Vec<(String, String, String)>,
+}
+
+impl Redactor {
+  /// The redactor for a request of `user_id`, when the policy or the chat asks for one.
+  pub async fn for_chat(
+    app_state: &AppState,
+    user_id: Option<Uuid>,
+    chat_id: Option<Uuid>,
+  ) -> Result<Option<Self>> {
+    let policy = app_state.pii();
+    if policy.mode == Mode::Off {
+      return Ok(None);
+    }
+    let redact_pii = match (user_id, chat_id) {
+      (Some(user_id), Some(chat_id)) => {
+        Chat::redacts_pii(chat_id, user_id, &app_state.pool).await?
+      }
+      _ => None,
+    };
+    // redactions are only recorded under chats of the caller
+    let chat_id = chat_id.filter(|_| redact_pii.is_some());
+    let enabled = match policy.mode {
+      Mode::Always => true,
+      _ => redact_pii.unwrap_or_default(),
+    };
+    Ok(enabled.then(|| Self {
+      policy,
+      chat_id,
+      user_id,
+      placeholders: HashMap::new(),
+      redactions: vec![],
+    }))
+  }
+
+  fn placeholder(&mut self, kind: &str, value: &str) -> String {
+    if let Some(placeholder) = self.placeholders.get(value) {
+      return placeholder.clone();
+    }
+    let count = self.redactions.iter().filter(|(k, ..)| k == kind).count() + 1;
+    let placeholder = format!("[{}_{count}]", kind.to_uppercase());
+    self
+      .placeholders
+      .insert(value.to_string(), placeholder.clone());
+    self
+      .redactions
+      .push((kind.to_string(), placeholder.clone(), value.to_string()));
+    placeholder
+  }
+
+  pub fn redact(&mut self, text: &str) -> String {
+    let policy = self.policy.clone();
+    let mut text = text.to_string();
+    for detector in &policy.detectors {
+      text = detector
+        .regex
+        .replace_all(&text, |caps: &Captures| {
+          let value = &caps[0];
+          if (detector.validate)(value) {
+            self.placeholder(&detector.kind, value)
+          } else {
+            value.to_string()
+          }
+        })
+        .into_owned();
+    }
+    text
+  }
+
+  pub fn redact_messages(&mut self, messages: &mut [ChatCompletionRequestMessage]) {
+    for message in messages {
+      match message {
+        ChatCompletionRequestMessage::System(message) => {
+          message.content = self.redact(&message.content)
+        }
+        ChatCompletionRequestMessage::User(message) => match &mut message.content {
+          ChatCompletionRequestUserMessageContent::Text(text) => *text = self.redact(text),
+          ChatCompletionRequestUserMessageContent::Array(parts) => {
+            for part in parts {
+              if let ChatCompletionRequestMessageContentPart::Text(part) = part {
+                part.text = self.redact(&part.text);
+              }
+            }
+          }
+        },
+        ChatCompletionRequestMessage::Assistant(message) => {
+          message.content = message.content.as_deref().map(|c| self.redact(c))
+        }
+        ChatCompletionRequestMessage::Tool(message) => {
+          message.content = self.redact(&message.content)
+        }
+        ChatCompletionRequestMessage::Function(message) => {
+          message.content = message.content.as_deref().map(|c| self.redact(c))
+        }
+      }
+    }
+  }
+
+  pub fn restore(&self, text: &str) -> String {
+    self
+      .redactions
+      .iter()
+      .fold(text.to_string(), |text, (_, placeholder, original)| {
+        text.replace(placeholder, original)
+      })
+  }
+
+  pub fn restore_response(&self, response: &mut CreateChatCompletionResponse) {
+    for choice in &mut response.choices {
+      choice.message.content = choice.message.content.as_deref().map(|c| self.restore(c));
+      for tool_call in choice.message.tool_calls.iter_mut().flatten() {
+        tool_call.function.arguments = self.restore(&tool_call.function.arguments);
+      }
+    }
+  }
+
+  /// Splits off the end of `text` that could be the start of a placeholder.
+  fn hold_back(text: &mut String) -> String {
+    match text.rfind('[') {
+      Some(start) if !text[start..].contains(']') && text.len() - start < MAX_PLACEHOLDER => {
+        text.split_off(start)
+      }
+      _ => String::new(),
+    }
+  }
+
+  /// Restores the deltas of a streamed response. Placeholders can be split over chunks, so
+  /// text that may start one is held back until it is complete.
+  pub fn restore_stream(
+    self,
+    upstream: ChatCompletionResponseStream,
+  ) -> ChatCompletionResponseStream {
+    let state = (upstream, self, HashMap::<u32, String>::new(), None, false);
+    Box::pin(stream::unfold(
+      state,
+      |(mut upstream, redactor, mut pending, mut last, done)| async move {
+        if done {
+          return None;
+        }
+        match upstream.next().await {
+          Some(Ok(mut chunk)) => {
+            for choice in &mut chunk.choices {
+              for tool_call in choice.delta.tool_calls.iter_mut().flatten() {
+                if let Some(function) = tool_call.function.as_mut() {
+                  function.arguments = function.arguments.as_deref().map(|a| redactor.restore(a));
+                }
+              }
+              let Some(content) = choice.delta.content.take() else {
+                continue;
+              };
+              let buffer = pending.entry(choice.index).or_default();
+              buffer.push_str(&content);
+              let rest = Self::hold_back(buffer);
+              choice.delta.content = Some(redactor.restore(buffer));
+              *buffer = rest;
+            }
+            last = Some(chunk.clone());
+            Some((Ok(chunk), (upstream, redactor, pending, last, false)))
+          }
+          Some(Err(e)) => Some((Err(e), (upstream, redactor, pending, last, false))),
+          None => {
+            // whatever was held back goes out in one last chunk
+            let mut chunk = last.take()?;
+            let template = chunk.choices.first()?.clone();
+            chunk.choices = pending
+              .drain()
+              .filter(|(_, text)| !text.is_empty())
+              .map(|(index, text)| {
+                let mut choice = template.clone();
+                choice.index = index;
+                choice.delta.role = None;
+                choice.delta.tool_calls = None;
+                choice.delta.function_call = None;
+                choice.delta.content = Some(redactor.restore(&text));
+                choice
+              })
+              .collect();
+            if chunk.choices.is_empty() {
+              return None;
+            }
+            Some((Ok(chunk), (upstream, redactor, pending, None, true)))
+          }
+        }
+      },
+    ))
+  }
+
+  /// Logs the kinds and placeholders of what was redacted, in the background.
+  pub fn record(&self, app_state: &AppState, endpoint: &str) {
+    if self.redactions.is_empty() {
+      return;
+    }
+    let redactions: Vec<(String, String)> = self
+      .redactions
+      .iter()
+      .map(|(kind, placeholder, _)| (kind.clone(), placeholder.clone()))
+      .collect();
+    let (chat_id, user_id, endpoint) = (self.chat_id, self.user_id, endpoint.to_string());
+    let pool = app_state.pool.clone();
+    tokio::spawn(async move {
+      if let Err(e) =
+        PiiRedaction::insert_all(chat_id, user_id, &endpoint, &redactions, &pool).await
+      {
+        tracing::warn!("failed to record PII redactions for {endpoint}: {e}");
+      }
+    });
+  }
+}
@@ -0,0 +1,178 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+};
+
+use tokio::sync::{Mutex as CallLock, OwnedMutexGuard};
+use uuid::Uuid;
+
+use crate::{
+  app::state::AppState,
+  models::pricing::{BudgetExceeded, GoalBudget, ModelPrice},
+  Error, Result,
+};
+
+/// What a single call used, in the units models are priced in.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Units {
+  pub prompt_tokens: i32,
+  pub completion_tokens: i32,
+  /// Characters turned into speech.
+  pub input_chars: usize,
+  /// The size and number of generated images.
+  pub images: Option<(String, u32)>,
+  pub audio_seconds: f64,
+}
+
+fn tokens(input_per_1k: f64, output_per_1k: f64) -> ModelPrice {
+  ModelPrice {
+    input_per_1k,
+    output_per_1k,
+    ..Default::default()
+  }
+}
+
+fn images(sizes: &[(&str, f64)]) -> ModelPrice {
+  ModelPrice {
+    per_image: sizes
+      .iter()
+      .map(|(size, price)| (size.to_string(), *price))
+      .collect(),
+    ..Default::default()
+  }
+}
+
+/// The price of every model, looked up by the longest name that prefixes the model, so
+/// `gpt-4-0613` is priced as `gpt-4`.
+#[derive(Debug)]
+pub struct Pricing {
+  models: HashMap<String, ModelPrice>,
+  /// One lock per chat with a budget, so its calls run one after the other.
+  budget_locks: Mutex<HashMap<Uuid, Arc<CallLock<()>>>>,
+}
+
+/// Held by a call of a chat with a budget until its usage is recorded, see [check_budget].
+pub(crate) type BudgetHold = OwnedMutexGuard<()>;
+
+impl Default for Pricing {
+  /// The list prices of OpenAI in USD.
+  fn default() -> Self {
+    let models = [
+      ("gpt-3.5-turbo", tokens(0.0005, 0.0015)),
+      ("gpt-3.5-turbo-instruct", tokens(0.0015, 0.002)),
+      ("gpt-4", tokens(0.03, 0.06)),
+      ("gpt-4-32k", tokens(0.06, 0.12)),
+      ("gpt-4-turbo", tokens(0.01, 0.03)),
+      ("gpt-4-1106", tokens(0.01, 0.03)),
+      ("gpt-4-0125", tokens(0.01, 0.03)),
+      ("gpt-4-vision", tokens(0.01, 0.03)),
+      ("text-embedding-ada-002", tokens(0.0001, 0.0)),
+      ("text-embedding-3-small", tokens(0.00002, 0.0)),
+      ("text-embedding-3-large", tokens(0.00013, 0.0)),
+      ("tts-1", tokens(0.015, 0.0)),
+      ("tts-1-hd", tokens(0.03, 0.0)),
+      (
+        "whisper-1",
+        ModelPrice {
+          per_audio_minute: 0.006,
+          ..Default::default()
+        },
+      ),
+      (
+        "dall-e-2",
+        images(&[("256x256", 0.016), ("512x512", 0.018), ("1024x1024", 0.02)]),
+      ),
+      (
+        "dall-e-3",
+        images(&[
+          ("1024x1024", 0.04),
+          ("1024x1792", 0.08),
+          ("1792x1024", 0.08),
+        ]),
+      ),
+    ];
+    Self {
+      models: models
+        .into_iter()
+        .map(|(model, price)| (model.to_string(), price))
+        .collect(),
+      budget_locks: Default::default(),
+    }
+  }
+}
+
+impl Pricing {
+  /// The default prices, overridden per model by the JSON object of model to [ModelPrice] in
+  /// the file at `MIKO_PRICING`.
+  pub fn from_env() -> Result<Self> {
+    let mut pricing = Self::default();
+    if let Ok(path) = dotenvy::var("MIKO_PRICING") {
+      let models: HashMap<String, ModelPrice> =
+        serde_json::from_str(&std::fs::read_to_string(path)?)?;
+      pricing.models.extend(models);
+    }
+    Ok(pricing)
+  }
+
+  pub fn price(&self, model: &str) -> Option<&ModelPrice> {
+    self
+      .models
+      .iter()
+      .filter(|(name, _)| model.starts_with(name.as_str()))
+      .max_by_key(|(name, _)| name.len())
+      .map(|(_, price)| price)
+  }
+
+  /// Waits until no other call of the chat holds its budget.
+  async fn hold(&self, chat_id: Uuid) -> BudgetHold {
+    let lock = {
+      let mut locks = self.budget_locks.lock().unwrap();
+      locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+      locks.entry(chat_id).or_default().clone()
+    };
+    lock.lock_owned().await
+  }
+
+  /// The cost in USD, 0 for models without a price.
+  pub fn cost(&self, model: &str, units: &Units) -> f64 {
+    let Some(price) = self.price(model) else {
+      return 0.0;
+    };
+    let tokens = (units.prompt_tokens as f64 + units.input_chars as f64) / 1000.0
+      * price.input_per_1k
+      + units.completion_tokens as f64 / 1000.0 * price.output_per_1k;
+    let images = units
+      .images
+      .as_ref()
+      .and_then(|(size, n)| price.per_image.get(size).map(|p| p * *n as f64))
+      .unwrap_or_default();
+    let audio = units.audio_seconds / 60.0 * price.per_audio_minute;
+    tokens + images + audio
+  }
+}
+
+/// Fails with [Error::BudgetExceeded] once the goal of the chat spent its budget. Calls of a
+/// chat with a budget run one at a time: the hold is passed to the [Tracker] of the call, which
+/// releases it once the cost is recorded, so no call starts before the previous one is paid.
+///
+/// [Tracker]: super::usage::Tracker
+pub(crate) async fn check_budget(
+  app_state: &AppState,
+  chat_id: Uuid,
+) -> Result<Option<BudgetHold>> {
+  let hold = app_state.pricing().hold(chat_id).await;
+  match GoalBudget::for_chat(chat_id, &app_state.pool).await? {
+    Some(GoalBudget {
+      budget_usd: Some(budget_usd),
+      spent_usd,
+    }) if spent_usd >= budget_usd => Err(Error::BudgetExceeded(BudgetExceeded {
+      budget_usd,
+      spent_usd,
+    })),
+    Some(GoalBudget {
+      budget_usd: Some(_),
+      ..
+    }) => Ok(Some(hold)),
+    _ => Ok(None),
+  }
+}
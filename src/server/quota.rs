@@ -0,0 +1,225 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  sync::Mutex,
+  time::{Duration, Instant},
+};
+
+use axum::{
+  extract::{Extension, Request, State},
+  http::Method,
+  middleware::Next,
+  response::Response,
+};
+use chrono::{Datelike, Days, Months, NaiveTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+  app::state::AppState,
+  models::quota::{QuotaExceeded, QuotaLimits, QuotaUsage},
+  pgdb::RequestKind,
+  server::api_key::Caller,
+  Error, Result,
+};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// The default limits and the in-memory requests per minute windows.
+#[derive(Debug, Default)]
+pub struct Quotas {
+  defaults: QuotaLimits,
+  windows: Mutex<HashMap<Option<Uuid>, VecDeque<Instant>>>,
+}
+
+fn env_limit(name: &str) -> Option<i64> {
+  dotenvy::var(name).ok().and_then(|v| v.parse().ok())
+}
+
+impl Quotas {
+  /// Reads the default limits from `MIKO_QUOTA_*` and `MIKO_RATE_LIMIT_RPM`, unset means unlimited.
+  pub fn from_env() -> Self {
+    Self {
+      defaults: QuotaLimits {
+        daily_requests: env_limit("MIKO_QUOTA_DAILY_REQUESTS"),
+        monthly_requests: env_limit("MIKO_QUOTA_MONTHLY_REQUESTS"),
+        daily_tokens: env_limit("MIKO_QUOTA_DAILY_TOKENS"),
+        monthly_tokens: env_limit("MIKO_QUOTA_MONTHLY_TOKENS"),
+        requests_per_minute: env_limit("MIKO_RATE_LIMIT_RPM"),
+      },
+      windows: Default::default(),
+    }
+  }
+
+  pub fn defaults(&self) -> &QuotaLimits {
+    &self.defaults
+  }
+
+  /// Takes a slot in the sliding one minute window of the user.
+  fn acquire(&self, user_id: Option<Uuid>, max: Option<i64>) -> Result<(), QuotaExceeded> {
+    let Some(max) = max else {
+      return Ok(());
+    };
+    let now = Instant::now();
+    let mut windows = self.windows.lock().unwrap();
+    let window = windows.entry(user_id).or_default();
+    while window
+      .front()
+      .is_some_and(|t| now.duration_since(*t) >= WINDOW)
+    {
+      window.pop_front();
+    }
+    if window.len() as i64 >= max {
+      let reset_in = window
+        .front()
+        .map(|t| WINDOW.saturating_sub(now.duration_since(*t)))
+        .unwrap_or(WINDOW);
+      return Err(QuotaExceeded {
+        limit: "requests_per_minute".to_string(),
+        max,
+        used: window.len() as i64,
+        reset_at: Utc::now() + chrono::Duration::from_std(reset_in).unwrap_or_default(),
+      });
+    }
+    window.push_back(now);
+    Ok(())
+  }
+
+  /// Gives back the slot of a request that was refused by another limit.
+  fn release(&self, user_id: Option<Uuid>) {
+    if let Some(window) = self.windows.lock().unwrap().get_mut(&user_id) {
+      window.pop_back();
+    }
+  }
+}
+
+/// Checks all limits of a user and counts the request, `None` is anonymous traffic which only
+/// shares a requests per minute window. A refused request uses up neither a slot of the window
+/// nor a subsidy.
+pub(crate) async fn check(
+  app_state: &AppState,
+  user_id: Option<Uuid>,
+  kind: RequestKind,
+) -> Result<()> {
+  let quotas = app_state.quotas();
+  let pool = &app_state.pool;
+  let Some(user_id) = user_id else {
+    return quotas
+      .acquire(None, quotas.defaults().requests_per_minute)
+      .map_err(Error::QuotaExceeded);
+  };
+
+  let limits = QuotaLimits::for_user(user_id, pool)
+    .await?
+    .or(quotas.defaults());
+  quotas
+    .acquire(Some(user_id), limits.requests_per_minute)
+    .map_err(Error::QuotaExceeded)?;
+
+  let admitted = admit(user_id, kind, &limits, pool).await;
+  if admitted.is_err() && limits.requests_per_minute.is_some() {
+    quotas.release(Some(user_id));
+  }
+  admitted
+}
+
+/// Checks the daily and monthly limits and counts the request, a subsidy is only used once the
+/// rate limit passed.
+async fn admit(
+  user_id: Uuid,
+  kind: RequestKind,
+  limits: &QuotaLimits,
+  pool: &PgPool,
+) -> Result<()> {
+  loop {
+    let usage = QuotaUsage::for_user(user_id, pool).await?;
+    if let Some(exceeded) = exceeded(limits, &usage) {
+      if !QuotaUsage::consume_subsidy(user_id, kind, pool).await? {
+        return Err(Error::QuotaExceeded(exceeded));
+      }
+      QuotaUsage::count_request(user_id, None, pool).await?;
+      return Ok(());
+    }
+    // a concurrent request took the last one since the usage was read, read it again
+    if QuotaUsage::count_request(user_id, Some(limits), pool).await? {
+      return Ok(());
+    }
+  }
+}
+
+/// The first limit the user has used up, if any.
+fn exceeded(limits: &QuotaLimits, usage: &QuotaUsage) -> Option<QuotaExceeded> {
+  let today = Utc::now().date_naive();
+  let tomorrow = (today + Days::new(1)).and_time(NaiveTime::MIN).and_utc();
+  let next_month = (today.with_day(1).unwrap() + Months::new(1))
+    .and_time(NaiveTime::MIN)
+    .and_utc();
+
+  [
+    (
+      "daily_requests",
+      limits.daily_requests,
+      usage.daily_requests,
+      tomorrow,
+    ),
+    (
+      "monthly_requests",
+      limits.monthly_requests,
+      usage.monthly_requests,
+      next_month,
+    ),
+    (
+      "daily_tokens",
+      limits.daily_tokens,
+      usage.daily_tokens,
+      tomorrow,
+    ),
+    (
+      "monthly_tokens",
+      limits.monthly_tokens,
+      usage.monthly_tokens,
+      next_month,
+    ),
+  ]
+  .into_iter()
+  .find_map(|(limit, max, used, reset_at)| {
+    max.filter(|max| used >= *max).map(|max| QuotaExceeded {
+      limit: limit.to_string(),
+      max,
+      used,
+      reset_at,
+    })
+  })
+}
+
+/// The capacity a request to the OpenAI compatible API uses, `None` for requests that do not
+/// reach the provider. Batches count each of their requests when the worker sends it.
+fn request_kind(method: &Method, path: &str) -> Option<RequestKind> {
+  if method != Method::POST {
+    return None;
+  }
+  if path.starts_with("/embeddings") {
+    return Some(RequestKind::Embedding);
+  }
+  let metered = path.starts_with("/chat/")
+    || path.starts_with("/audio/")
+    || path.starts_with("/images/")
+    || path.starts_with("/tools/")
+    || path == "/threads/runs"
+    || (path.starts_with("/threads/")
+      && (path.ends_with("/runs") || path.ends_with("/submit_tool_outputs")));
+  metered.then_some(RequestKind::Completion)
+}
+
+/// Middleware enforcing quotas and rate limits in front of the OpenAI compatible API.
+pub(crate) async fn enforce(
+  State(app_state): State<AppState>,
+  caller: Option<Extension<Caller>>,
+  request: Request,
+  next: Next,
+) -> Result<Response> {
+  if let Some(kind) = request_kind(request.method(), request.uri().path()) {
+    let user_id = caller.map(|Extension(caller)| caller.user_id);
+    check(&app_state, user_id, kind).await?;
+  }
+  Ok(next.run(request).await)
+}
@@ -0,0 +1,381 @@
+use std::{
+  collections::HashSet,
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+use async_openai::types::{ChatCompletionRequestMessage, ChatCompletionRequestSystemMessage, Role};
+use chrono::{DateTime, SubsecRound, Utc};
+use uuid::Uuid;
+
+use crate::{
+  app::state::AppState,
+  models::retrieval::{cited_excerpts, WorkspaceChunk},
+  pgdb::{NewChunk, RequestKind},
+  server::{
+    catalog::ModelCatalog,
+    extract, moderation, quota,
+    search::{embed, embedded_text},
+    workspace,
+  },
+  Error, Result,
+};
+
+/// The most characters of a chunk, chunks end on a line break unless a line is longer.
+const CHUNK_CHARS: usize = 1_500;
+
+/// The lines a chunk repeats from the one before, so passages that span both are found.
+const OVERLAP_LINES: usize = 2;
+
+/// The chunks embedded per request.
+const BATCH_SIZE: usize = 96;
+
+/// The length of the embeddings `workspace_chunks` stores, see its migration.
+const EMBEDDING_DIMENSIONS: u32 = 1536;
+
+/// How long the changes to files settle before they are indexed, uploads arrive in many writes.
+const SETTLE: Duration = Duration::from_secs(2);
+
+/// How workspace files are retrieved for prompts.
+#[derive(Debug)]
+pub struct RetrievalPolicy {
+  /// The embedding model, chunks of other models are only found by their words.
+  model: String,
+  /// The chunks put in a prompt.
+  top_k: u32,
+  /// The largest cosine distance of a chunk that is still relevant.
+  max_distance: f64,
+  /// Whether chat completions of chats get the chunks that match the last user message.
+  inject: bool,
+}
+
+impl Default for RetrievalPolicy {
+  fn default() -> Self {
+    Self {
+      model: "text-embedding-3-small".to_string(),
+      top_k: 4,
+      max_distance: 0.6,
+      inject: true,
+    }
+  }
+}
+
+impl RetrievalPolicy {
+  /// Reads `MIKO_RETRIEVAL_EMBEDDING_MODEL`, `MIKO_RETRIEVAL_TOP_K`,
+  /// `MIKO_RETRIEVAL_MAX_DISTANCE` and `MIKO_RETRIEVAL_INJECT` (`off` leaves retrieval to the
+  /// `search_workspace` tool), falling back to the defaults. Models the catalog knows to make
+  /// embeddings of another length than [EMBEDDING_DIMENSIONS] are refused.
+  pub fn from_env(catalog: &ModelCatalog) -> Result<Self> {
+    let defaults = Self::default();
+    let policy = Self {
+      model: dotenvy::var("MIKO_RETRIEVAL_EMBEDDING_MODEL").unwrap_or(defaults.model),
+      top_k: dotenvy::var("MIKO_RETRIEVAL_TOP_K")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|k| *k > 0)
+        .unwrap_or(defaults.top_k),
+      max_distance: dotenvy::var("MIKO_RETRIEVAL_MAX_DISTANCE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(defaults.max_distance),
+      inject: !matches!(
+        dotenvy::var("MIKO_RETRIEVAL_INJECT").as_deref(),
+        Ok("off") | Ok("false")
+      ),
+    };
+    match catalog.embedding_dimensions(&policy.model) {
+      Some(dimensions) if dimensions != EMBEDDING_DIMENSIONS => {
+        Err(Error::InvalidArgument(format!(
+          "MIKO_RETRIEVAL_EMBEDDING_MODEL {} makes {dimensions} dimensional embeddings, \
+           workspace chunks store {EMBEDDING_DIMENSIONS}",
+          policy.model
+        )))
+      }
+      _ => Ok(policy),
+    }
+  }
+
+  pub fn top_k(&self) -> u32 {
+    self.top_k
+  }
+}
+
+/// The lines of a text in chunks of at most [CHUNK_CHARS], overlapping by [OVERLAP_LINES].
+fn chunks(text: &str) -> Vec<NewChunk> {
+  let lines = text.lines().collect::<Vec<_>>();
+  let mut chunks = vec![];
+  let mut start = 0;
+  while start < lines.len() {
+    let mut end = start;
+    let mut len = 0;
+    while end < lines.len() && (end == start || len + lines[end].len() <= CHUNK_CHARS) {
+      len += lines[end].len() + 1;
+      end += 1;
+    }
+    let content = lines[start..end].join("\n");
+    if !content.trim().is_empty() {
+      chunks.push(NewChunk {
+        start_line: start as i32 + 1,
+        end_line: end as i32,
+        content: embedded_text(&content),
+      });
+    }
+    start = if end - start > OVERLAP_LINES {
+      end - OVERLAP_LINES
+    } else {
+      end
+    };
+  }
+  chunks
+}
+
+/// The chat and the name of a file directly in the workspace of a chat.
+fn workspace_file<'a>(root: &Path, path: &'a Path) -> Option<(Uuid, &'a str)> {
+  let dir = path.parent()?;
+  if dir.parent()? != root {
+    return None;
+  }
+  let chat_id = dir.file_name()?.to_str()?.parse().ok()?;
+  let file_name = path.file_name()?.to_str()?;
+  (!workspace::is_hidden(file_name)).then_some((chat_id, file_name))
+}
+
+/// Indexes the workspaces when the app starts and every file that changes after.
+pub fn spawn_indexer(app_state: AppState) {
+  tokio::spawn(async move {
+    // the watcher reports absolute paths
+    let root = match tokio::fs::create_dir_all(&app_state.upload_store).await {
+      Ok(()) => tokio::fs::canonicalize(&app_state.upload_store).await,
+      Err(e) => Err(e),
+    };
+    let root = match root {
+      Ok(root) => root,
+      Err(e) => {
+        tracing::warn!("failed to open the upload store: {e}");
+        return;
+      }
+    };
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<PathBuf>();
+    let _watcher = match workspace::watch(&root, notify::RecursiveMode::Recursive, move |paths| {
+      for path in paths {
+        _ = tx.send(path.clone());
+      }
+    }) {
+      Ok(watcher) => watcher,
+      Err(e) => {
+        tracing::warn!("failed to watch the workspaces: {e}");
+        return;
+      }
+    };
+
+    // catches up with the files that changed while the app was down
+    if let Err(e) = index_all(&app_state, &root).await {
+      tracing::warn!("failed to index the workspaces: {e}");
+    }
+
+    while let Some(path) = rx.recv().await {
+      tokio::time::sleep(SETTLE).await;
+      let mut changed = HashSet::from([path]);
+      while let Ok(path) = rx.try_recv() {
+        changed.insert(path);
+      }
+      for path in changed {
+        let Some((chat_id, file_name)) = workspace_file(&root, &path) else {
+          continue;
+        };
+        if let Err(e) = index_file(&app_state, chat_id, file_name, None).await {
+          tracing::warn!("failed to index {file_name} of chat {chat_id}: {e}");
+        }
+      }
+    }
+  });
+}
+
+/// Indexes the files of every workspace that changed since they were chunked and forgets the
+/// files that are gone.
+async fn index_all(app_state: &AppState, root: &Path) -> Result<()> {
+  let mut workspaces = tokio::fs::read_dir(root).await?;
+  while let Some(workspace) = workspaces.next_entry().await? {
+    let Some(chat_id) = workspace.file_name().to_str().and_then(|n| n.parse().ok()) else {
+      continue;
+    };
+    if !workspace.file_type().await?.is_dir() {
+      continue;
+    }
+    let mut indexed = WorkspaceChunk::indexed_files(chat_id, &app_state.pool).await?;
+    let mut files = tokio::fs::read_dir(workspace.path()).await?;
+    while let Some(file) = files.next_entry().await? {
+      let path = file.path();
+      let Some((_, file_name)) = workspace_file(root, &path) else {
+        continue;
+      };
+      let modified_at = indexed.remove(file_name);
+      if let Err(e) = index_file(app_state, chat_id, file_name, modified_at).await {
+        tracing::warn!("failed to index {file_name} of chat {chat_id}: {e}");
+      }
+    }
+    for file_name in indexed.keys() {
+      WorkspaceChunk::remove(chat_id, file_name, &app_state.pool).await?;
+    }
+  }
+  Ok(())
+}
+
+/// Chunks and embeds a workspace file again, unless it was not modified since `indexed_at`.
+/// Files that are gone or are not text lose their chunks.
+async fn index_file(
+  app_state: &AppState,
+  chat_id: Uuid,
+  file_name: &str,
+  indexed_at: Option<DateTime<Utc>>,
+) -> Result<()> {
+  let pool = &app_state.pool;
+  let path = app_state
+    .upload_store
+    .join(chat_id.to_string())
+    .join(file_name);
+  let Some(owner) = WorkspaceChunk::owner(chat_id, pool).await? else {
+    return Ok(());
+  };
+  // the database keeps microseconds
+  let modified_at = match tokio::fs::metadata(&path).await {
+    Ok(metadata) => DateTime::<Utc>::from(metadata.modified()?).trunc_subsecs(6),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+      extract::forget(&path).await?;
+      return WorkspaceChunk::remove(chat_id, file_name, pool).await;
+    }
+    Err(e) => return Err(e.into()),
+  };
+  if indexed_at == Some(modified_at) {
+    return Ok(());
+  }
+  let Some(text) = extract::text(&path).await? else {
+    return WorkspaceChunk::remove(chat_id, file_name, pool).await;
+  };
+
+  let chunks = chunks(&text);
+  let model = app_state.retrieval().model.clone();
+  // the files of chats that redact personal data never leave the server, chunks left without
+  // embeddings once the user runs out of quota are only found by their words
+  let mut embeddings = vec![];
+  if !owner.redact_pii && !app_state.pii().redacts_all() {
+    for batch in chunks.chunks(BATCH_SIZE) {
+      match quota::check(app_state, Some(owner.user_id), RequestKind::Embedding).await {
+        Ok(()) => {}
+        Err(Error::QuotaExceeded(_)) => break,
+        Err(e) => return Err(e),
+      }
+      let texts = batch.iter().map(|chunk| chunk.content.clone()).collect();
+      embeddings.extend(embed(app_state, &model, owner.user_id, Some(chat_id), texts).await?);
+    }
+  }
+  WorkspaceChunk::replace(
+    chat_id,
+    file_name,
+    modified_at,
+    &chunks,
+    &embeddings,
+    &model,
+    pool,
+  )
+  .await
+}
+
+/// The chunks of the workspace of a chat that match `query` best. The query is embedded unless
+/// the chat redacts personal data or the user ran out of quota, then chunks are matched by
+/// their words.
+pub(crate) async fn retrieve(
+  app_state: &AppState,
+  user_id: Uuid,
+  chat_id: Uuid,
+  query: &str,
+  k: u32,
+  redact_pii: bool,
+) -> Result<Vec<WorkspaceChunk>> {
+  let query = query.trim();
+  if query.is_empty() {
+    return Err(Error::InvalidArgument("the query is empty".into()));
+  }
+  let policy = app_state.retrieval();
+  let pool = &app_state.pool;
+  let limit = k as i64;
+
+  if !redact_pii && !app_state.pii().redacts_all() {
+    let embedding = match quota::check(app_state, Some(user_id), RequestKind::Embedding).await {
+      Ok(()) => embed(
+        app_state,
+        &policy.model,
+        user_id,
+        Some(chat_id),
+        vec![embedded_text(query)],
+      )
+      .await
+      .map_err(|e| tracing::warn!("retrieving without embeddings: {e}"))
+      .ok()
+      .and_then(|embeddings| embeddings.into_iter().next()),
+      Err(Error::QuotaExceeded(_)) => None,
+      Err(e) => return Err(e),
+    };
+    if let Some(embedding) = embedding {
+      return WorkspaceChunk::nearest(
+        chat_id,
+        &embedding,
+        &policy.model,
+        policy.max_distance,
+        limit,
+        pool,
+      )
+      .await;
+    }
+  }
+  WorkspaceChunk::matching(chat_id, query, limit, pool).await
+}
+
+/// Puts the workspace chunks that match the last user message in front of it, with the
+/// instruction to cite them. Does nothing when retrieval is off or nothing matches.
+pub(crate) async fn inject(
+  app_state: &AppState,
+  user_id: Uuid,
+  chat_id: Uuid,
+  redact_pii: bool,
+  messages: &mut Vec<ChatCompletionRequestMessage>,
+) -> Result<()> {
+  let policy = app_state.retrieval();
+  if !policy.inject {
+    return Ok(());
+  }
+  let Some(query) = moderation::user_content(messages).pop() else {
+    return Ok(());
+  };
+  let chunks = retrieve(
+    app_state,
+    user_id,
+    chat_id,
+    &query,
+    policy.top_k,
+    redact_pii,
+  )
+  .await?;
+  if chunks.is_empty() {
+    return Ok(());
+  }
+
+  let content = format!(
+    "Excerpts of the files in the workspace of this chat that may help with the next message. \
+     When you use one, cite it with the markdown link after `Source:`.\n\n{}",
+    cited_excerpts(&chunks)
+  );
+  let position = messages
+    .iter()
+    .rposition(|message| matches!(message, ChatCompletionRequestMessage::User(_)))
+    .unwrap_or(messages.len());
+  messages.insert(
+    position,
+    ChatCompletionRequestMessage::System(ChatCompletionRequestSystemMessage {
+      content,
+      role: Role::System,
+      name: None,
+    }),
+  );
+  Ok(())
+}
@@ -0,0 +1,203 @@
+use std::{collections::HashMap, time::Duration};
+
+use async_openai::types::{CreateEmbeddingRequest, EmbeddingInput};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::{
+  app::state::AppState,
+  models::search::SearchHit,
+  pgdb::{PendingEntry, RequestKind, SearchEntry},
+  server::{audit::AgentCall, quota, usage::Tracker},
+  Error, Result,
+};
+
+/// The entries embedded per round of the indexer.
+const BATCH_SIZE: i64 = 96;
+
+/// The most characters of an entry that are embedded, well below the input limit of the models.
+const MAX_EMBEDDED_CHARS: usize = 8_000;
+
+/// Clock differences between the app and the database that a sync still catches.
+const SYNC_OVERLAP: chrono::Duration = chrono::Duration::minutes(1);
+
+/// How chats are indexed for search.
+#[derive(Debug)]
+pub struct SearchPolicy {
+  /// The embedding model, entries of other models are embedded again.
+  model: String,
+  interval: Duration,
+}
+
+impl Default for SearchPolicy {
+  fn default() -> Self {
+    Self {
+      model: "text-embedding-3-small".to_string(),
+      interval: Duration::from_secs(30),
+    }
+  }
+}
+
+impl SearchPolicy {
+  /// Reads `MIKO_SEARCH_EMBEDDING_MODEL` and `MIKO_SEARCH_INDEX_INTERVAL_SECS`, falling back to
+  /// the defaults.
+  pub fn from_env() -> Self {
+    let defaults = Self::default();
+    Self {
+      model: dotenvy::var("MIKO_SEARCH_EMBEDDING_MODEL").unwrap_or(defaults.model),
+      interval: dotenvy::var("MIKO_SEARCH_INDEX_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(defaults.interval),
+    }
+  }
+
+  pub fn model(&self) -> &str {
+    &self.model
+  }
+}
+
+/// The text an entry is embedded from, cut on a char boundary.
+pub(crate) fn embedded_text(content: &str) -> String {
+  match content.char_indices().nth(MAX_EMBEDDED_CHARS) {
+    Some((end, _)) => content[..end].to_string(),
+    None => content.to_string(),
+  }
+}
+
+/// Embeds texts with `model` for a user and records the usage, in the order of the texts.
+pub(crate) async fn embed(
+  app_state: &AppState,
+  model: &str,
+  user_id: Uuid,
+  chat_id: Option<Uuid>,
+  texts: Vec<String>,
+) -> Result<Vec<Vec<f32>>> {
+  let request = CreateEmbeddingRequest {
+    model: model.to_string(),
+    input: EmbeddingInput::StringArray(texts),
+    encoding_format: None,
+    user: Some(user_id.to_string()),
+  };
+  let usage = Tracker::start("/v1/embeddings", model)
+    .user(Some(user_id))
+    .chat(chat_id);
+  let audit = AgentCall::start("/v1/embeddings", &request).user(Some(user_id));
+  let result = app_state.openai_client().embeddings().create(request).await;
+  usage.finish(app_state, &result);
+  audit.finish(app_state, &result);
+  let mut data = result?.data;
+  data.sort_by_key(|embedding| embedding.index);
+  Ok(
+    data
+      .into_iter()
+      .map(|embedding| embedding.embedding)
+      .collect(),
+  )
+}
+
+/// Syncs the searchable text of chats and embeds new entries at the interval of the policy.
+pub fn spawn_indexer(app_state: AppState) {
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(app_state.search().interval);
+    // the first round catches up with everything written while the app was down
+    let mut since = DateTime::<Utc>::default();
+    loop {
+      interval.tick().await;
+      let started = Utc::now() - SYNC_OVERLAP;
+      match SearchEntry::sync(since, &app_state.pool).await {
+        Ok(_) => since = started,
+        Err(e) => tracing::warn!("failed to sync the search index: {e}"),
+      }
+      if let Err(e) = embed_pending(&app_state).await {
+        tracing::warn!("failed to embed search entries: {e}");
+      }
+    }
+  });
+}
+
+/// Embeds the entries that wait for it, one request per user.
+async fn embed_pending(app_state: &AppState) -> Result<()> {
+  if app_state.pii().redacts_all() {
+    return Ok(());
+  }
+  let model = app_state.search().model().to_string();
+  let pending = SearchEntry::pending(&model, BATCH_SIZE, &app_state.pool).await?;
+  let mut by_user: HashMap<Uuid, Vec<PendingEntry>> = HashMap::new();
+  for entry in pending {
+    by_user.entry(entry.user_id).or_default().push(entry);
+  }
+
+  for (user_id, entries) in by_user {
+    let ids = entries.iter().map(|e| e.id).collect::<Vec<_>>();
+    // the index spends the quota of the owner, entries over it wait for the next period
+    match quota::check(app_state, Some(user_id), RequestKind::Embedding).await {
+      Ok(()) => {}
+      Err(Error::QuotaExceeded(_)) => {
+        SearchEntry::defer(&ids, &app_state.pool).await?;
+        continue;
+      }
+      Err(e) => return Err(e),
+    }
+    let texts = entries.iter().map(|e| embedded_text(&e.content)).collect();
+    let embeddings = match embed(app_state, &model, user_id, None, texts).await {
+      Ok(embeddings) => embeddings,
+      Err(e) => {
+        // retried after the entries of the other users had their turn
+        tracing::warn!("failed to embed search entries of {user_id}: {e}");
+        SearchEntry::defer(&ids, &app_state.pool).await?;
+        continue;
+      }
+    };
+    for (entry, embedding) in entries.iter().zip(embeddings) {
+      SearchEntry::set_embedding(entry.id, &model, &embedding, &app_state.pool).await?;
+    }
+  }
+  Ok(())
+}
+
+/// The chats of a user that match `query`. The query is embedded for the semantic half of the
+/// search, when that fails or is not allowed the chats are searched by their words only.
+pub(crate) async fn search(
+  app_state: &AppState,
+  user_id: Uuid,
+  query: &str,
+  limit: i64,
+) -> Result<Vec<SearchHit>> {
+  let query = query.trim();
+  if query.is_empty() {
+    return Err(Error::InvalidArgument("the search query is empty".into()));
+  }
+
+  let embedding = if app_state.pii().redacts_all() {
+    None
+  } else {
+    match quota::check(app_state, Some(user_id), RequestKind::Embedding).await {
+      Ok(()) => embed(
+        app_state,
+        app_state.search().model(),
+        user_id,
+        None,
+        vec![embedded_text(query)],
+      )
+      .await
+      .map_err(|e| tracing::warn!("searching without embeddings: {e}"))
+      .ok()
+      .and_then(|embeddings| embeddings.into_iter().next()),
+      Err(Error::QuotaExceeded(_)) => None,
+      Err(e) => return Err(e),
+    }
+  };
+
+  SearchHit::search(
+    user_id,
+    query,
+    embedding.as_deref(),
+    app_state.search().model(),
+    limit,
+    &app_state.pool,
+  )
+  .await
+}
@@ -0,0 +1,276 @@
+use std::{fmt::Write as _, path::Path, time::Duration};
+
+use async_openai::{config::Config, error::OpenAIError};
+use mime_guess::mime;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+  app::state::AppState,
+  pgdb::RequestKind,
+  server::{
+    audit::AgentCall,
+    pricing, quota,
+    usage::{TokenUsage, Tracker},
+    workspace,
+  },
+  Error, Result,
+};
+
+const TRANSCRIPTION_MODEL: &str = "whisper-1";
+
+/// The largest file the transcription endpoint accepts.
+const UPLOAD_LIMIT: u64 = 25 * 1024 * 1024;
+
+/// The length of the chunks longer recordings are split into, at 48 kbit/s well under the limit.
+const CHUNK_SECONDS: u32 = 600;
+
+/// The formats the transcription endpoint reads without converting them first.
+const SUPPORTED_EXTENSIONS: [&str; 9] = [
+  "flac", "m4a", "mp3", "mp4", "mpeg", "mpga", "oga", "ogg", "wav",
+];
+
+#[derive(Debug, Clone, Deserialize)]
+struct Segment {
+  start: f64,
+  end: f64,
+  text: String,
+}
+
+/// The `verbose_json` transcription, the only format with both the text and its timing.
+#[derive(Debug, Clone, Deserialize)]
+struct VerboseTranscription {
+  #[serde(default)]
+  duration: f64,
+  #[serde(default)]
+  segments: Vec<Segment>,
+}
+
+impl TokenUsage for VerboseTranscription {}
+
+/// Whether a workspace file is a recording that gets transcribed.
+pub(crate) fn is_media(file_name: &str) -> bool {
+  let media_type = workspace::mime_type(file_name);
+  media_type.type_() == mime::AUDIO || media_type.type_() == mime::VIDEO
+}
+
+/// Transcribes a recording dropped into the workspace of a chat in the background, writing the
+/// transcript next to it as `<file>.txt`, `<file>.srt` and `<file>.vtt`.
+pub(crate) fn spawn(app_state: AppState, user_id: Uuid, chat_id: Uuid, file_name: String) {
+  tokio::spawn(async move {
+    tracing::info!("transcribing {file_name} in workspace {chat_id}");
+    if let Err(e) = transcribe_file(&app_state, user_id, chat_id, &file_name).await {
+      tracing::warn!("failed to transcribe {file_name} in workspace {chat_id}: {e}");
+    }
+  });
+}
+
+async fn transcribe_file(
+  app_state: &AppState,
+  user_id: Uuid,
+  chat_id: Uuid,
+  file_name: &str,
+) -> Result<()> {
+  if !workspace::path_is_valid(file_name) {
+    return Err(Error::InvalidArgument(format!("Invalid path: {file_name}")));
+  }
+  quota::check(app_state, Some(user_id), RequestKind::Completion).await?;
+
+  let workspace_dir = app_state.upload_store.join(chat_id.to_string());
+  let path = workspace_dir.join(file_name);
+  let mut segments = vec![];
+  for (index, (chunk_name, bytes)) in chunks(&path).await?.into_iter().enumerate() {
+    let offset = (index as u32 * CHUNK_SECONDS) as f64;
+    let transcription = transcribe_chunk(app_state, user_id, chat_id, chunk_name, bytes).await?;
+    segments.extend(transcription.segments.into_iter().map(|segment| Segment {
+      start: segment.start + offset,
+      end: segment.end + offset,
+      text: segment.text.trim().to_string(),
+    }));
+  }
+
+  for (extension, contents) in [
+    ("txt", to_text(&segments)),
+    ("srt", to_srt(&segments)),
+    ("vtt", to_vtt(&segments)),
+  ] {
+    tokio::fs::write(
+      workspace_dir.join(format!("{file_name}.{extension}")),
+      contents,
+    )
+    .await?;
+  }
+  Ok(())
+}
+
+/// The recording as files under the upload limit, with their names. Supported audio that fits
+/// is sent as is, anything else is converted to mono MP3 chunks of [CHUNK_SECONDS] by ffmpeg,
+/// which also drops the video.
+async fn chunks(path: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+  let file_name = path
+    .file_name()
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_default();
+  let supported = path
+    .extension()
+    .and_then(|extension| extension.to_str())
+    .is_some_and(|extension| SUPPORTED_EXTENSIONS.contains(&extension.to_lowercase().as_str()));
+  if supported
+    && workspace::mime_type(&file_name).type_() == mime::AUDIO
+    && tokio::fs::metadata(path).await?.len() <= UPLOAD_LIMIT
+  {
+    return Ok(vec![(file_name, tokio::fs::read(path).await?)]);
+  }
+
+  let chunk_dir = std::env::temp_dir().join(format!("miko-transcription-{}", Uuid::new_v4()));
+  tokio::fs::create_dir_all(&chunk_dir).await?;
+  let result = split(path, &chunk_dir).await;
+  let _ = tokio::fs::remove_dir_all(&chunk_dir).await;
+  result
+}
+
+async fn split(path: &Path, chunk_dir: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+  let ffmpeg = dotenvy::var("MIKO_FFMPEG").unwrap_or_else(|_| "ffmpeg".to_string());
+  let output = tokio::process::Command::new(ffmpeg)
+    .arg("-nostdin")
+    .args(["-loglevel", "error", "-i"])
+    .arg(path)
+    .args(["-vn", "-ac", "1", "-ar", "16000", "-b:a", "48k"])
+    .args(["-f", "segment", "-reset_timestamps", "1", "-segment_time"])
+    .arg(CHUNK_SECONDS.to_string())
+    .arg(chunk_dir.join("chunk%04d.mp3"))
+    .output()
+    .await?;
+  if !output.status.success() {
+    return Err(Error::InvalidArgument(format!(
+      "ffmpeg: {}",
+      String::from_utf8_lossy(&output.stderr).trim()
+    )));
+  }
+
+  let mut names = vec![];
+  let mut reader = tokio::fs::read_dir(chunk_dir).await?;
+  while let Some(entry) = reader.next_entry().await? {
+    names.push(entry.file_name().to_string_lossy().to_string());
+  }
+  names.sort();
+  let mut chunks = Vec::with_capacity(names.len());
+  for name in names {
+    let bytes = tokio::fs::read(chunk_dir.join(&name)).await?;
+    chunks.push((name, bytes));
+  }
+  Ok(chunks)
+}
+
+/// Transcribes one chunk, spending the budget of the chat. `async-openai` only reads the text of
+/// a transcription, so the `verbose_json` with its segments is requested directly.
+async fn transcribe_chunk(
+  app_state: &AppState,
+  user_id: Uuid,
+  chat_id: Uuid,
+  file_name: String,
+  bytes: Vec<u8>,
+) -> Result<VerboseTranscription> {
+  let budget = pricing::check_budget(app_state, chat_id).await?;
+  let client = app_state.openai_client();
+  let config = client.config();
+  let usage = Tracker::start("/v1/audio/transcriptions", TRANSCRIPTION_MODEL)
+    .user(Some(user_id))
+    .chat(Some(chat_id))
+    .budget(budget);
+
+  let audit = AgentCall::start(
+    "/v1/audio/transcriptions",
+    &json!({
+      "model": TRANSCRIPTION_MODEL,
+      "response_format": "verbose_json",
+      "file": file_name,
+    }),
+  )
+  .user(Some(user_id));
+
+  let form = reqwest::multipart::Form::new()
+    .text("model", TRANSCRIPTION_MODEL)
+    .text("response_format", "verbose_json")
+    .part(
+      "file",
+      reqwest::multipart::Part::bytes(bytes).file_name(file_name),
+    );
+  let result: Result<VerboseTranscription, OpenAIError> = async {
+    reqwest::Client::new()
+      .post(config.url("/audio/transcriptions"))
+      .query(&config.query())
+      .headers(config.headers())
+      .timeout(Duration::from_secs(10 * 60))
+      .multipart(form)
+      .send()
+      .await?
+      .error_for_status()?
+      .json()
+      .await
+      .map_err(OpenAIError::from)
+  }
+  .await;
+
+  let seconds = result.as_ref().map(|t| t.duration).unwrap_or_default();
+  usage.audio_seconds(seconds).finish(app_state, &result);
+  audit.finish(
+    app_state,
+    &result
+      .as_ref()
+      .map(|transcription| json!({"duration": transcription.duration})),
+  );
+  Ok(result?)
+}
+
+fn to_text(segments: &[Segment]) -> String {
+  let mut text = segments
+    .iter()
+    .map(|segment| segment.text.as_str())
+    .collect::<Vec<_>>()
+    .join(" ");
+  text.push('\n');
+  text
+}
+
+/// `HH:MM:SS` followed by the milliseconds after `separator`.
+fn timestamp(seconds: f64, separator: char) -> String {
+  let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+  format!(
+    "{:02}:{:02}:{:02}{separator}{:03}",
+    millis / 3_600_000,
+    millis / 60_000 % 60,
+    millis / 1000 % 60,
+    millis % 1000
+  )
+}
+
+fn to_srt(segments: &[Segment]) -> String {
+  let mut srt = String::new();
+  for (index, segment) in segments.iter().enumerate() {
+    let _ = writeln!(
+      srt,
+      "{}\n{} --> {}\n{}\n",
+      index + 1,
+      timestamp(segment.start, ','),
+      timestamp(segment.end, ','),
+      segment.text
+    );
+  }
+  srt
+}
+
+fn to_vtt(segments: &[Segment]) -> String {
+  let mut vtt = String::from("WEBVTT\n\n");
+  for segment in segments {
+    let _ = writeln!(
+      vtt,
+      "{} --> {}\n{}\n",
+      timestamp(segment.start, '.'),
+      timestamp(segment.end, '.'),
+      segment.text
+    );
+  }
+  vtt
+}
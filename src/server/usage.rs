@@ -0,0 +1,254 @@
+use std::{fmt::Display, time::Instant};
+
+use async_openai::{
+  error::OpenAIError,
+  types::{
+    ChatCompletionResponseStream, CreateChatCompletionRequest, CreateChatCompletionResponse,
+    CreateEmbeddingResponse, CreateSpeechResponse, CreateTranscriptionResponse,
+    CreateTranslationResponse, ImagesResponse,
+  },
+};
+use axum::extract::Extension;
+use futures::{stream, StreamExt};
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::{
+  api_key::Caller,
+  pricing::{BudgetHold, Units},
+};
+use crate::{app::state::AppState, pgdb::UsageEvent};
+
+/// Responses that report how many tokens a call used, as `(prompt, completion)`.
+pub(crate) trait TokenUsage {
+  fn token_usage(&self) -> (i32, i32) {
+    (0, 0)
+  }
+}
+
+impl TokenUsage for CreateChatCompletionResponse {
+  fn token_usage(&self) -> (i32, i32) {
+    self
+      .usage
+      .as_ref()
+      .map(|u| (u.prompt_tokens as i32, u.completion_tokens as i32))
+      .unwrap_or_default()
+  }
+}
+
+impl TokenUsage for CreateEmbeddingResponse {
+  fn token_usage(&self) -> (i32, i32) {
+    (self.usage.prompt_tokens as i32, 0)
+  }
+}
+
+impl TokenUsage for ImagesResponse {}
+impl TokenUsage for CreateSpeechResponse {}
+impl TokenUsage for CreateTranscriptionResponse {}
+impl TokenUsage for CreateTranslationResponse {}
+
+/// About four characters make a token in English text.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// A rough token count of a text.
+pub(crate) fn estimate_tokens(text: &str) -> usize {
+  text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// The estimated prompt tokens of a chat completion, from its messages and tools as sent.
+pub(crate) fn estimate_prompt_tokens(request: &CreateChatCompletionRequest) -> i32 {
+  let messages = serde_json::to_string(&request.messages).unwrap_or_default();
+  let tools = serde_json::to_string(&request.tools).unwrap_or_default();
+  (estimate_tokens(&messages) + estimate_tokens(&tools)).min(i32::MAX as usize) as i32
+}
+
+/// The wire name of a model enum like `ImageModel` or `SpeechModel`.
+pub(crate) fn model_name<T: Serialize>(model: &T) -> String {
+  match serde_json::to_value(model) {
+    Ok(serde_json::Value::String(name)) => name,
+    _ => "unknown".to_string(),
+  }
+}
+
+/// The length of uploaded audio from its first bytes and size. WAV files carry their byte rate,
+/// for compressed formats it is estimated from the size at 128 kbit/s.
+pub(crate) fn audio_seconds(head: &[u8], len: u64) -> f64 {
+  if head.len() >= 32 && len > 44 && &head[0..4] == b"RIFF" && &head[8..12] == b"WAVE" {
+    let byte_rate = u32::from_le_bytes([head[28], head[29], head[30], head[31]]);
+    if byte_rate > 0 {
+      return (len - 44) as f64 / byte_rate as f64;
+    }
+  }
+  len as f64 * 8.0 / 128_000.0
+}
+
+/// Measures a single provider call, prices it and records it in the `usage_events` hypertable.
+#[derive(Debug)]
+pub(crate) struct Tracker {
+  event: UsageEvent,
+  units: Units,
+  started: Instant,
+  budget: Option<BudgetHold>,
+}
+
+impl Tracker {
+  pub fn start<S: Into<String>>(endpoint: &str, model: S) -> Self {
+    Self {
+      event: UsageEvent {
+        endpoint: endpoint.to_string(),
+        model: model.into(),
+        ..Default::default()
+      },
+      units: Units::default(),
+      started: Instant::now(),
+      budget: None,
+    }
+  }
+
+  pub fn user(mut self, user_id: Option<Uuid>) -> Self {
+    self.event.user_id = user_id;
+    self
+  }
+
+  /// Attributes the call to the user of the API key the request was made with.
+  pub fn caller(self, caller: Option<Extension<Caller>>) -> Self {
+    self.user(caller.map(|Extension(caller)| caller.user_id))
+  }
+
+  pub fn chat(mut self, chat_id: Option<Uuid>) -> Self {
+    self.event.chat_id = chat_id;
+    self
+  }
+
+  /// Keeps the budget of the chat held until the cost of the call is recorded.
+  pub fn budget(mut self, hold: Option<BudgetHold>) -> Self {
+    self.budget = hold;
+    self
+  }
+
+  /// The size and number of images requested, for image models.
+  pub fn images(mut self, size: impl Into<String>, n: u32) -> Self {
+    self.units.images = Some((size.into(), n));
+    self
+  }
+
+  /// The length of the audio, for transcriptions and translations.
+  pub fn audio_seconds(mut self, seconds: f64) -> Self {
+    self.units.audio_seconds = seconds;
+    self
+  }
+
+  /// The characters turned into speech, for text to speech models.
+  pub fn input_chars(mut self, chars: usize) -> Self {
+    self.units.input_chars = chars;
+    self
+  }
+
+  /// Records the outcome of the call in the background, so callers never wait on the insert.
+  pub fn finish<T: TokenUsage, E: Display>(self, app_state: &AppState, result: &Result<T, E>) {
+    let tokens = match result {
+      Ok(response) => Ok(response.token_usage()),
+      Err(e) => Err(e.to_string()),
+    };
+    self.record(app_state, tokens);
+  }
+
+  /// Records a streamed chat completion once its stream ends, or when the client goes away
+  /// before. Streams do not report usage, so the completion tokens are estimated from the
+  /// deltas and the prompt tokens from the request, see [estimate_prompt_tokens].
+  pub fn finish_stream(
+    self,
+    app_state: &AppState,
+    prompt_tokens: i32,
+    result: Result<ChatCompletionResponseStream, OpenAIError>,
+  ) -> Result<ChatCompletionResponseStream, OpenAIError> {
+    let upstream = match result {
+      Ok(upstream) => upstream,
+      Err(e) => {
+        self.record(app_state, Err(e.to_string()));
+        return Err(e);
+      }
+    };
+    let usage = StreamUsage {
+      tracker: Some(self),
+      app_state: app_state.clone(),
+      prompt_tokens,
+      completion_chars: 0,
+      error: None,
+    };
+    Ok(Box::pin(stream::unfold(
+      (upstream, usage),
+      |(mut upstream, mut usage)| async move {
+        let item = upstream.next().await?;
+        match &item {
+          Ok(chunk) => {
+            for choice in &chunk.choices {
+              let content = choice.delta.content.iter();
+              let arguments = choice
+                .delta
+                .tool_calls
+                .iter()
+                .flatten()
+                .filter_map(|call| call.function.as_ref()?.arguments.as_ref());
+              usage.completion_chars += content
+                .chain(arguments)
+                .map(|text| text.chars().count())
+                .sum::<usize>();
+            }
+          }
+          Err(e) => usage.error = Some(e.to_string()),
+        }
+        Some((item, (upstream, usage)))
+      },
+    )))
+  }
+
+  fn record(self, app_state: &AppState, tokens: Result<(i32, i32), String>) {
+    let mut event = self.event;
+    let mut units = self.units;
+    event.latency_ms = self.started.elapsed().as_millis().min(i32::MAX as u128) as i32;
+    match tokens {
+      Ok(tokens) => {
+        (event.prompt_tokens, event.completion_tokens) = tokens;
+        (units.prompt_tokens, units.completion_tokens) = tokens;
+        event.cost = app_state.pricing().cost(&event.model, &units);
+      }
+      Err(e) => event.error = Some(e),
+    }
+
+    let pool = app_state.pool.clone();
+    let budget = self.budget;
+    tokio::spawn(async move {
+      if let Err(e) = event.insert(&pool).await {
+        tracing::warn!("failed to record usage for {}: {e}", event.endpoint);
+      }
+      drop(budget);
+    });
+  }
+}
+
+/// What a streamed chat completion used so far, recorded when the stream is dropped.
+struct StreamUsage {
+  tracker: Option<Tracker>,
+  app_state: AppState,
+  prompt_tokens: i32,
+  completion_chars: usize,
+  error: Option<String>,
+}
+
+impl Drop for StreamUsage {
+  fn drop(&mut self) {
+    let Some(tracker) = self.tracker.take() else {
+      return;
+    };
+    let completion_tokens = self
+      .completion_chars
+      .div_ceil(CHARS_PER_TOKEN)
+      .min(i32::MAX as usize) as i32;
+    let tokens = match self.error.take() {
+      Some(e) => Err(e),
+      None => Ok((self.prompt_tokens, completion_tokens)),
+    };
+    tracker.record(&self.app_state, tokens);
+  }
+}
@@ -0,0 +1,128 @@
+use std::io::Cursor;
+
+use async_openai::types::{
+  ChatCompletionRequestMessage, ChatCompletionRequestMessageContentPart,
+  ChatCompletionRequestUserMessageContent, ImageUrlDetail,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use image::{imageops::FilterType, ImageFormat};
+use uuid::Uuid;
+
+use crate::{
+  app::state::AppState,
+  models::{self, workspace_images, WORKSPACE_SCHEME},
+  server::workspace,
+  Error, Result,
+};
+
+/// The longest side of images sent with low detail, the model looks at them at this size.
+const LOW_DETAIL_SIDE: u32 = 512;
+/// Other images are first fit into a square of this size...
+const MAX_SIDE: u32 = 2048;
+/// ...and then scaled down until their shortest side is at most this.
+const MAX_SHORT_SIDE: u32 = 768;
+
+/// The size the model scales an image to, larger images only cost upload time.
+fn target_size(width: u32, height: u32, detail: &ImageUrlDetail) -> (u32, u32) {
+  let fit = |width: u32, height: u32, max: u32| {
+    let scale = (max as f64 / width.max(height) as f64).min(1.0);
+    (
+      (width as f64 * scale).round() as u32,
+      (height as f64 * scale).round() as u32,
+    )
+  };
+  match detail {
+    ImageUrlDetail::Low => fit(width, height, LOW_DETAIL_SIDE),
+    ImageUrlDetail::Auto | ImageUrlDetail::High => {
+      let (width, height) = fit(width, height, MAX_SIDE);
+      let scale = (MAX_SHORT_SIDE as f64 / width.min(height) as f64).min(1.0);
+      (
+        (width as f64 * scale).round() as u32,
+        (height as f64 * scale).round() as u32,
+      )
+    }
+  }
+}
+
+/// Resizes an image to what the model uses, JPEGs stay JPEGs and everything else becomes PNG.
+fn encode(bytes: &[u8], detail: &ImageUrlDetail) -> Result<String> {
+  let invalid = |e: image::ImageError| Error::InvalidArgument(format!("image: {e}"));
+  let format = image::guess_format(bytes).map_err(invalid)?;
+  let mut image = image::load_from_memory_with_format(bytes, format).map_err(invalid)?;
+  let (width, height) = target_size(image.width(), image.height(), detail);
+  if (width, height) != (image.width(), image.height()) {
+    image = image.resize(width.max(1), height.max(1), FilterType::Lanczos3);
+  }
+
+  let format = match format {
+    ImageFormat::Jpeg => ImageFormat::Jpeg,
+    _ => ImageFormat::Png,
+  };
+  let mut encoded = Cursor::new(vec![]);
+  image.write_to(&mut encoded, format).map_err(invalid)?;
+  Ok(format!(
+    "data:{};base64,{}",
+    format.to_mime_type(),
+    STANDARD.encode(encoded.into_inner())
+  ))
+}
+
+/// A workspace image as a base64 data URL.
+async fn data_url(
+  app_state: &AppState,
+  chat_id: Uuid,
+  file_name: &str,
+  detail: ImageUrlDetail,
+) -> Result<String> {
+  if !workspace::path_is_valid(file_name) {
+    return Err(Error::InvalidArgument(format!("Invalid path: {file_name}")));
+  }
+  let path = app_state
+    .upload_store
+    .join(chat_id.to_string())
+    .join(file_name);
+  let bytes = tokio::fs::read(path)
+    .await
+    .map_err(|_| Error::NotFound(format!("workspace file {file_name}")))?;
+  tokio::task::spawn_blocking(move || encode(&bytes, &detail))
+    .await
+    .map_err(|e| Error::InvalidArgument(format!("image: {e}")))?
+}
+
+/// Replaces the workspace images of user messages, embedded in their text or as image parts,
+/// with data URLs the provider can read.
+pub(crate) async fn inline_images(
+  app_state: &AppState,
+  chat_id: Option<Uuid>,
+  messages: &mut [ChatCompletionRequestMessage],
+) -> Result<()> {
+  for message in messages {
+    let ChatCompletionRequestMessage::User(message) = message else {
+      continue;
+    };
+    if let ChatCompletionRequestUserMessageContent::Text(text) = &message.content {
+      if workspace_images(text).is_empty() {
+        continue;
+      }
+      message.content =
+        models::ChatCompletionRequestUserMessageContent::with_workspace_images(text.clone()).into();
+    }
+    let ChatCompletionRequestUserMessageContent::Array(parts) = &mut message.content else {
+      continue;
+    };
+    for part in parts {
+      let ChatCompletionRequestMessageContentPart::Image(part) = part else {
+        continue;
+      };
+      let Some(file_name) = part.image_url.url.strip_prefix(WORKSPACE_SCHEME) else {
+        continue;
+      };
+      let chat_id = chat_id.ok_or_else(|| {
+        Error::InvalidArgument("workspace images need the chat they belong to".into())
+      })?;
+      part.image_url.url =
+        data_url(app_state, chat_id, file_name, part.image_url.detail.clone()).await?;
+    }
+  }
+  Ok(())
+}
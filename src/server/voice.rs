@@ -0,0 +1,149 @@
+use async_openai::types::{
+  AudioInput, CreateSpeechRequest, CreateTranscriptionRequest, InputSource, SpeechModel,
+  SpeechResponseFormat,
+};
+use axum::{
+  extract::{Multipart, State},
+  http::header,
+  response::IntoResponse,
+  Json,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+  app::{handlers::AuthSession, state::AppState},
+  models::settings::UserSettings,
+  pgdb::RequestKind,
+  server::{
+    audit::AgentCall,
+    openapi::{self, ApiRouter, Body, Operation, Schema},
+    quota,
+    usage::{audio_seconds, Tracker},
+  },
+  Error, Result,
+};
+
+const TRANSCRIPTION_MODEL: &str = "whisper-1";
+
+pub(crate) fn api() -> ApiRouter {
+  ApiRouter::new()
+    .route(
+      Operation::post(
+        "/transcriptions",
+        "Transcribe a recording of the microphone",
+      )
+      .request(Body::Multipart(Schema::Inline(recording_form)))
+      .response(Body::Json(Schema::Model(openapi::model::<Transcription>))),
+      transcribe,
+    )
+    .route(
+      Operation::post("/speech", "Read a message aloud as MP3")
+        .request(Body::Json(Schema::Model(openapi::model::<Speech>)))
+        .response(Body::Binary),
+      speak,
+    )
+}
+
+fn recording_form() -> serde_json::Value {
+  json!({
+    "type": "object",
+    "properties": {"file": {"type": "string", "contentMediaType": "application/octet-stream"}},
+    "required": ["file"],
+  })
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct Transcription {
+  text: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+struct Speech {
+  input: String,
+}
+
+/// Transcribes a recording from the microphone in the chat input.
+#[tracing::instrument(skip(app_state, auth, multipart))]
+async fn transcribe(
+  State(app_state): State<AppState>,
+  auth: AuthSession,
+  mut multipart: Multipart,
+) -> Result<Json<Transcription>> {
+  let user = auth.current_user.ok_or(Error::UserNotAuthenticated)?;
+  let mut file = None;
+  let mut seconds = 0.0;
+  while let Ok(Some(field)) = multipart.next_field().await {
+    if field.name() == Some("file") {
+      let filename = field.file_name().unwrap_or("speech.webm").to_string();
+      let bytes = field
+        .bytes()
+        .await
+        .map_err(|e| Error::InvalidArgument(e.to_string()))?;
+      seconds = audio_seconds(&bytes, bytes.len() as u64);
+      file = Some(AudioInput {
+        source: InputSource::Bytes { filename, bytes },
+      });
+    }
+  }
+  let file = file.ok_or_else(|| Error::InvalidArgument("file is required".into()))?;
+
+  quota::check(&app_state, Some(user.id), RequestKind::Completion).await?;
+  let usage = Tracker::start("/v1/audio/transcriptions", TRANSCRIPTION_MODEL)
+    .user(Some(user.id))
+    .audio_seconds(seconds);
+  let request = CreateTranscriptionRequest {
+    file,
+    model: TRANSCRIPTION_MODEL.into(),
+    ..Default::default()
+  };
+  // the recording itself is not audited, like other binary bodies
+  let audit = AgentCall::start(
+    "/v1/audio/transcriptions",
+    &json!({"model": TRANSCRIPTION_MODEL, "audio_seconds": seconds}),
+  )
+  .user(Some(user.id));
+  let result = app_state.openai_client().audio().transcribe(request).await;
+  usage.finish(&app_state, &result);
+  audit.finish(&app_state, &result);
+  Ok(Json(Transcription { text: result?.text }))
+}
+
+/// Reads a message aloud with the voice and speed from the settings of the user.
+#[tracing::instrument(skip(app_state, auth, speech))]
+async fn speak(
+  State(app_state): State<AppState>,
+  auth: AuthSession,
+  Json(speech): Json<Speech>,
+) -> Result<impl IntoResponse> {
+  let user = auth.current_user.ok_or(Error::UserNotAuthenticated)?;
+  if speech.input.trim().is_empty() {
+    return Err(Error::InvalidArgument("input is required".into()));
+  }
+  // the speech endpoint reads at most 4096 characters
+  let input: String = speech.input.chars().take(4096).collect();
+
+  quota::check(&app_state, Some(user.id), RequestKind::Completion).await?;
+  let settings = UserSettings::for_user(user.id, &app_state.pool).await?;
+  let usage = Tracker::start("/v1/audio/speech", "tts-1")
+    .user(Some(user.id))
+    .input_chars(input.chars().count());
+  let request = CreateSpeechRequest {
+    input,
+    model: SpeechModel::Tts1,
+    voice: settings.voice.into(),
+    response_format: Some(SpeechResponseFormat::Mp3),
+    speed: Some(settings.speech_speed),
+  };
+  let audit = AgentCall::start("/v1/audio/speech", &request).user(Some(user.id));
+  let result = app_state.openai_client().audio().speech(request).await;
+  usage.finish(&app_state, &result);
+  audit.finish(
+    &app_state,
+    &result
+      .as_ref()
+      .map(|speech| json!({"audio_bytes": speech.bytes.len()})),
+  );
+  Ok(([(header::CONTENT_TYPE, "audio/mpeg")], result?.bytes))
+}
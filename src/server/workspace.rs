@@ -1,14 +1,12 @@
 use std::{convert::Infallible, path::PathBuf, str::FromStr, time::Duration};
 
 use axum::{
-  body::Body,
   extract::{Path, State},
-  http::Request,
+  http::{header::CONTENT_TYPE, Request},
   response::{
     sse::{Event, Sse},
     IntoResponse,
   },
-  routing::get,
   Json,
 };
 use bytes::Bytes;
@@ -17,6 +15,7 @@ use futures::{
   TryStreamExt,
 };
 use notify::Watcher;
+use serde_json::json;
 use tokio::{fs::File, io::BufWriter};
 use tokio_util::io::StreamReader;
 use tower::{BoxError, Service};
@@ -24,21 +23,68 @@ use tower_http::services::ServeFile;
 use tracing::info;
 use uuid::Uuid;
 
+pub(crate) use crate::models::workspace_mime_type as mime_type;
 use crate::{
   app::{handlers::AuthSession, state::AppState},
-  models::UploadedFile,
+  models::{Chat, UploadedFile},
+  server::{
+    extract,
+    openapi::{self, ApiRouter, Body, Operation, Schema},
+    transcription,
+  },
   Error, Result,
 };
 
-pub fn routes(app_state: AppState) -> axum::Router<AppState> {
-  axum::Router::new()
+pub(crate) fn api() -> ApiRouter {
+  ApiRouter::new()
+    .route(
+      Operation::get("/:chat_id", "List the files of a workspace")
+        .response(Body::Json(Schema::Inline(file_names))),
+      list_files,
+    )
+    .route(
+      Operation::post("/:chat_id", "Upload files, returns how many were saved")
+        .request(Body::Multipart(Schema::Inline(upload_form)))
+        .response(Body::Json(Schema::Inline(file_count))),
+      upload_files,
+    )
+    .route(
+      Operation::delete("/:chat_id", "Remove a workspace with its files"),
+      remove_workspace,
+    )
+    .route(
+      Operation::get("/:chat_id/watch", "Watch the files of a workspace change")
+        .response(Body::Events(Schema::Model(openapi::model::<UploadedFile>))),
+      watch_files,
+    )
     .route(
-      "/:chat_id",
-      get(list_files).post(upload_files).delete(remove_workspace),
+      Operation::get("/:chat_id/files/:file_name", "Download a file").response(Body::Binary),
+      serve_file,
+    )
+    .route(
+      Operation::get(
+        "/:chat_id/files/:file_name/text",
+        "The text of a file, as markdown for PDFs, Office documents, HTML and EPUBs",
+      )
+      .response(Body::Text("text/markdown")),
+      file_text,
     )
-    .route("/:chat_id/watch", get(watch_files))
-    .route("/:chat_id/files/:file_name", get(serve_file))
-    .with_state(app_state)
+}
+
+fn file_names() -> serde_json::Value {
+  json!({"type": "array", "items": {"type": "string"}})
+}
+
+/// Every file field of the form is saved under its file name.
+fn upload_form() -> serde_json::Value {
+  json!({
+    "type": "object",
+    "additionalProperties": {"type": "string", "contentMediaType": "application/octet-stream"},
+  })
+}
+
+fn file_count() -> serde_json::Value {
+  json!({"type": "integer", "minimum": 0})
 }
 
 #[tracing::instrument(skip(app_state, auth))]
@@ -46,7 +92,7 @@ async fn serve_file(
   State(app_state): State<AppState>,
   Path((chat_id, file_name)): Path<(Uuid, String)>,
   auth: AuthSession,
-  req: Request<Body>,
+  req: Request<axum::body::Body>,
 ) -> Result<impl IntoResponse> {
   if !auth.is_authenticated() {
     return Err(Error::UserNotAuthenticated);
@@ -59,6 +105,40 @@ async fn serve_file(
   Ok(ServeFile::new(file_path).call(req).await.unwrap())
 }
 
+/// The text of a file of one of the user's chats, extracted once for documents and cached
+/// beside them.
+#[tracing::instrument(skip(app_state, auth))]
+async fn file_text(
+  State(app_state): State<AppState>,
+  Path((chat_id, file_name)): Path<(Uuid, String)>,
+  auth: AuthSession,
+) -> Result<impl IntoResponse> {
+  let Some(user) = &auth.current_user else {
+    return Err(Error::UserNotAuthenticated);
+  };
+  if !Chat::is_owned_by(chat_id, user.id, &app_state.pool).await? {
+    return Err(Error::NotFound(format!("chat {chat_id}")));
+  }
+  if !path_is_valid(&file_name) || is_hidden(&file_name) {
+    return Err(Error::InvalidArgument(format!("Invalid path: {file_name}")));
+  }
+
+  let file_path = app_state
+    .upload_store
+    .join(chat_id.to_string())
+    .join(&file_name);
+  if !tokio::fs::try_exists(&file_path).await? {
+    return Err(Error::NotFound(format!("workspace file {file_name}")));
+  }
+  match extract::text(&file_path).await? {
+    Some(text) => Ok(([(CONTENT_TYPE, "text/markdown; charset=utf-8")], text)),
+    None => Err(Error::Extraction {
+      file: file_name,
+      message: "no extractor reads this kind of file".into(),
+    }),
+  }
+}
+
 #[tracing::instrument(skip(app_state, auth))]
 async fn watch_files(
   State(app_state): State<AppState>,
@@ -71,6 +151,46 @@ async fn watch_files(
 
   let (tx, rx) = futures::channel::mpsc::unbounded();
 
+  let watcher = watch(
+    &app_state.upload_store.join(chat_id.to_string()),
+    notify::RecursiveMode::NonRecursive,
+    move |paths| {
+      if let Some(path) = paths.last().filter(|path| !is_hidden_path(path)) {
+        let mime = mime_type(path);
+        let filename = path.file_name().unwrap().to_str().unwrap().to_string();
+        let data = serde_json::to_string(&UploadedFile {
+          file_name: filename,
+          mime_type: mime.to_string(),
+          workspace: chat_id.to_string(),
+        })
+        .unwrap();
+        info!("sending event for file {}", data);
+        _ = tx.unbounded_send(Event::default().data(data));
+      }
+    },
+  )?;
+
+  std::mem::forget(watcher);
+
+  Ok(
+    Sse::new(rx.map(Ok)).keep_alive(
+      axum::response::sse::KeepAlive::new()
+        .interval(Duration::from_secs(15))
+        .text("keep-alive-text"),
+    ),
+  )
+}
+
+/// Calls `on_change` with the paths of every file created, modified or removed in `dir`, for as
+/// long as the returned watcher lives. Renames pass the old and the new path.
+pub(crate) fn watch<F>(
+  dir: &std::path::Path,
+  mode: notify::RecursiveMode,
+  on_change: F,
+) -> Result<notify::RecommendedWatcher>
+where
+  F: Fn(&[PathBuf]) + Send + 'static,
+{
   let mut watcher =
     notify::recommended_watcher(move |res: Result<notify::Event, notify::Error>| {
       if let Ok(event) = res {
@@ -80,38 +200,13 @@ async fn watch_files(
         }
 
         info!("event: {:?}", event);
-        if let Some(path) = event.paths.last() {
-          let mime = mime_guess::from_path(path).first_or_octet_stream();
-          let filename = path.file_name().unwrap().to_str().unwrap().to_string();
-          let data = serde_json::to_string(&UploadedFile {
-            file_name: filename,
-            mime_type: mime.to_string(),
-            workspace: chat_id.to_string(),
-          })
-          .unwrap();
-          info!("sending event for file {}", data);
-          _ = tx.unbounded_send(Event::default().data(data));
-        }
+        on_change(&event.paths);
       }
     })
     .map_err(Error::Watcher)?;
 
-  watcher
-    .watch(
-      app_state.upload_store.join(chat_id.to_string()).as_path(),
-      notify::RecursiveMode::NonRecursive,
-    )
-    .map_err(Error::Watcher)?;
-
-  std::mem::forget(watcher);
-
-  Ok(
-    Sse::new(rx.map(Ok)).keep_alive(
-      axum::response::sse::KeepAlive::new()
-        .interval(Duration::from_secs(15))
-        .text("keep-alive-text"),
-    ),
-  )
+  watcher.watch(dir, mode).map_err(Error::Watcher)?;
+  Ok(watcher)
 }
 
 #[tracing::instrument(skip(app_state, auth))]
@@ -150,7 +245,10 @@ async fn list_files(
   let mut reader = tokio::fs::read_dir(workspace_dir).await?;
   let mut result = vec![];
   while let Some(file) = reader.next_entry().await? {
-    result.push(file.file_name().to_string_lossy().to_string());
+    let file_name = file.file_name().to_string_lossy().to_string();
+    if !is_hidden(&file_name) {
+      result.push(file_name);
+    }
   }
   Ok(Json(result))
 }
@@ -180,10 +278,28 @@ async fn upload_files(
 
     stream_to_file(&workspace_dir, &file_name, field).await?;
     count += 1;
+
+    if let Some(user) = &auth.current_user {
+      uploaded(&app_state, user.id, chat_id, &file_name);
+    }
   }
   Ok(Json(count))
 }
 
+/// Starts the background work for a file a user added to a workspace, recordings get a
+/// transcript next to them once it is ready.
+pub(crate) fn uploaded<P: AsRef<std::path::Path>>(
+  app_state: &AppState,
+  user_id: Uuid,
+  chat_id: Uuid,
+  file_name: P,
+) {
+  let file_name = file_name.as_ref().to_string_lossy().to_string();
+  if transcription::is_media(&file_name) {
+    transcription::spawn(app_state.clone(), user_id, chat_id, file_name);
+  }
+}
+
 // Save a `Stream` to a file
 pub async fn stream_to_file<P, S, E>(base_path: P, file_name: P, stream: S) -> Result<()>
 where
@@ -220,9 +336,22 @@ where
   Ok(())
 }
 
+/// Files the app derives from the files of users, like the text extracted from documents, are
+/// hidden in the workspace.
+pub(crate) fn is_hidden(file_name: &str) -> bool {
+  file_name.starts_with('.')
+}
+
+fn is_hidden_path(path: &std::path::Path) -> bool {
+  path
+    .file_name()
+    .and_then(|name| name.to_str())
+    .map_or(true, is_hidden)
+}
+
 // to prevent directory traversal attacks we ensure the path consists of exactly one normal
 // component
-fn path_is_valid<P: AsRef<std::path::Path>>(path: P) -> bool {
+pub(crate) fn path_is_valid<P: AsRef<std::path::Path>>(path: P) -> bool {
   let path = path.as_ref();
   let mut components = path.components().peekable();
 